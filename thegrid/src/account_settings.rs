@@ -1,7 +1,7 @@
 pub mod deactivate_account;
 mod devices_settings;
 mod ignored_users_settings;
-mod notifications_settings;
+pub mod notifications_settings;
 mod profile_settings;
 pub mod security_settings;
 
@@ -1,5 +1,8 @@
 use crate::account_settings::security_settings::recovery_key_reset_popover::RecoveryKeyResetPopover;
 use crate::auth::oauth_management_page_redirect_dialog::OAuthManagementPageRedirectDialog;
+use crate::auth::qr_login::{
+    QrLoginDirection, QrLoginState, QrLoginView, forward_qr_login_progress,
+};
 use crate::auth::verification_popover::VerificationPopover;
 use crate::uiaa_client::{SendAuthDataEvent, UiaaClient};
 use chrono::{DateTime, Local};
@@ -12,13 +15,14 @@ use contemporary::components::grandstand::grandstand;
 use contemporary::components::icon::icon;
 use contemporary::components::icon_text::icon_text;
 use contemporary::components::layer::{Layer, layer};
+use contemporary::components::popover::popover;
 use contemporary::components::scroll_area::scroll_area_cx;
 use contemporary::components::subtitle::subtitle;
 use contemporary::styling::theme::{Theme, ThemeStorage, VariableColor};
 use gpui::prelude::FluentBuilder;
 use gpui::{
     App, AppContext, AsyncApp, Context, ElementId, Entity, InteractiveElement, IntoElement,
-    ParentElement, Render, RenderOnce, Styled, WeakEntity, Window, div, px, rgba,
+    ParentElement, Render, RenderOnce, Styled, Task, WeakEntity, Window, div, px, rgba,
 };
 use matrix_sdk::encryption::VerificationState;
 use matrix_sdk::encryption::identities::Device;
@@ -42,6 +46,8 @@ pub struct DevicesSettings {
     log_out_confirm_dialog_visible: bool,
     uiaa_client: Entity<UiaaClient>,
     oauth_management_page_redirect_dialog: Entity<OAuthManagementPageRedirectDialog>,
+    link_device_view: Entity<QrLoginView>,
+    link_device_task: Option<Task<()>>,
 
     devices: Vec<CachedDevice>,
     inactive_devices: Vec<CachedDevice>,
@@ -64,6 +70,8 @@ impl DevicesSettings {
                 uiaa_client: cx.new(|cx| UiaaClient::new(send_auth_data, |_, _, _| {}, cx)),
                 oauth_management_page_redirect_dialog: cx
                     .new(|cx| OAuthManagementPageRedirectDialog::new(cx)),
+                link_device_view: cx.new(|cx| QrLoginView::new(QrLoginDirection::Grant, cx)),
+                link_device_task: None,
                 devices: Vec::new(),
                 inactive_devices: Vec::new(),
                 this_device: None,
@@ -132,6 +140,65 @@ impl DevicesSettings {
         .detach();
     }
 
+    pub fn link_new_device(&mut self, cx: &mut Context<Self>) {
+        let session_manager = cx.global::<SessionManager>();
+        let client = session_manager.client().unwrap().read(cx).clone();
+
+        let link_device_view = self.link_device_view.clone();
+        link_device_view.update(cx, |link_device_view, cx| {
+            link_device_view.set_state(QrLoginState::Starting, cx);
+        });
+
+        let (progress_tx, progress_rx) = async_channel::unbounded();
+        cx.spawn({
+            let link_device_view = link_device_view.clone();
+            async move |_, cx: &mut AsyncApp| {
+                while let Ok(progress) = progress_rx.recv().await {
+                    link_device_view.update(cx, |link_device_view, cx| {
+                        link_device_view
+                            .set_state(QrLoginState::from_grant_progress(progress), cx);
+                    });
+                }
+            }
+        })
+        .detach();
+
+        self.link_device_task = Some(cx.spawn(
+            async move |weak_this: WeakEntity<Self>, cx: &mut AsyncApp| {
+                let result = cx
+                    .spawn_tokio(async move {
+                        let oauth = client.oauth();
+                        let grant = oauth.grant_login_with_qr_code().generate();
+                        let progress = grant.subscribe_to_progress();
+
+                        // The new device receives the cross-signing secrets from this device,
+                        // so it is verified as soon as it has logged in
+                        forward_qr_login_progress(progress, grant.into_future(), progress_tx).await
+                    })
+                    .await;
+
+                link_device_view.update(cx, |link_device_view, cx| match result {
+                    Ok(_) => link_device_view.set_state(QrLoginState::Done, cx),
+                    Err(e) => {
+                        error!("Unable to link new device: {e:?}");
+                        link_device_view.set_state(QrLoginState::Error, cx)
+                    }
+                });
+
+                let _ = weak_this.update(cx, |this, cx| {
+                    this.update_devices(cx);
+                    cx.notify();
+                });
+            },
+        ));
+        cx.notify();
+    }
+
+    fn close_link_device(&mut self, cx: &mut Context<Self>) {
+        self.link_device_task = None;
+        cx.notify();
+    }
+
     fn update_devices(&mut self, cx: &mut App) {
         let session_manager = cx.global::<SessionManager>();
         let client = session_manager.client().unwrap().read(cx).clone();
@@ -323,6 +390,48 @@ impl Render for DevicesSettings {
                                         }),
                                 )
                             })
+                            .when(verified, |david| {
+                                david.child(
+                                    layer()
+                                        .flex()
+                                        .flex_col()
+                                        .p(px(8.))
+                                        .w_full()
+                                        .child(subtitle(tr!(
+                                            "DEVICES_LINK_NEW_DEVICE",
+                                            "Link New Device"
+                                        )))
+                                        .child(
+                                            div()
+                                                .flex()
+                                                .flex_col()
+                                                .gap(px(4.))
+                                                .child(tr!(
+                                                    "DEVICES_LINK_NEW_DEVICE_DESCRIPTION",
+                                                    "Log in on another device by scanning a QR \
+                                                    code. The new device will be verified \
+                                                    automatically."
+                                                ))
+                                                .child(
+                                                    div().flex().child(div().flex_grow(1.)).child(
+                                                        button("link-new-device")
+                                                            .child(icon_text(
+                                                                "list-add",
+                                                                tr!(
+                                                                    "DEVICES_LINK_SHOW_QR_CODE",
+                                                                    "Show QR Code"
+                                                                ),
+                                                            ))
+                                                            .on_click(cx.listener(
+                                                                |this, _, _, cx| {
+                                                                    this.link_new_device(cx)
+                                                                },
+                                                            )),
+                                                    ),
+                                                ),
+                                        ),
+                                )
+                            })
                             .when(!this.devices.is_empty(), |david| {
                                 david.child(
                                     this.device_layer(
@@ -413,6 +522,27 @@ impl Render for DevicesSettings {
                             })),
                     ),
             )
+            .child(
+                popover("link-device-popover")
+                    .visible(self.link_device_task.is_some())
+                    .size_neg(100.)
+                    .anchor_bottom()
+                    .content(
+                        div()
+                            .flex()
+                            .flex_col()
+                            .size_full()
+                            .gap(px(9.))
+                            .child(
+                                grandstand("link-device-grandstand")
+                                    .text(tr!("DEVICES_LINK_NEW_DEVICE"))
+                                    .on_back_click(cx.listener(|this, _, _, cx| {
+                                        this.close_link_device(cx)
+                                    })),
+                            )
+                            .child(self.link_device_view.clone()),
+                    ),
+            )
             .child(self.uiaa_client.clone())
             .child(self.oauth_management_page_redirect_dialog.clone())
             .child(self.recovery_key_reset_popover.clone())
@@ -1,18 +1,381 @@
-use cntp_i18n::tr;
+use cntp_i18n::{I18nString, Quote, tr};
+use contemporary::components::admonition::AdmonitionSeverity;
+use contemporary::components::button::button;
 use contemporary::components::constrainer::constrainer;
+use contemporary::components::context_menu::ContextMenuItem;
 use contemporary::components::grandstand::grandstand;
+use contemporary::components::icon::icon;
+use contemporary::components::icon_text::icon_text;
 use contemporary::components::layer::layer;
 use contemporary::components::subtitle::subtitle;
-use contemporary::styling::theme::Theme;
+use contemporary::components::switch::{SwitchChangeEvent, switch};
+use contemporary::components::text_field::TextField;
+use contemporary::components::toast::Toast;
+use contemporary::styling::theme::{Theme, VariableColor};
+use gpui::prelude::FluentBuilder;
 use gpui::{
-    App, AppContext, Context, Entity, IntoElement, ParentElement, Render, Styled, Window, div, px,
+    App, AppContext, AsyncWindowContext, Context, ElementId, Entity, InteractiveElement,
+    IntoElement, ListSizingBehavior, ParentElement, Render, Styled, WeakEntity, Window, div, px,
+    uniform_list,
 };
+use matrix_sdk::NotificationSettingsError;
+use matrix_sdk::notification_settings::{
+    IsEncrypted, IsOneToOne, NotificationSettings, RoomNotificationMode,
+};
+use matrix_sdk::ruma::OwnedRoomId;
+use matrix_sdk::ruma::push::{PredefinedOverrideRuleId, PredefinedUnderrideRuleId, RuleKind};
+use std::ops::Range;
+use thegrid_common::session::session_manager::SessionManager;
+use thegrid_common::tokio_helper::TokioHelper;
+
+pub struct NotificationsSettings {
+    keyword_field: Entity<TextField>,
+    processing: bool,
+}
+
+pub fn notification_mode_text(mode: &RoomNotificationMode) -> I18nString {
+    match mode {
+        RoomNotificationMode::AllMessages => {
+            tr!("NOTIFICATION_MODE_ALL_MESSAGES", "All Messages")
+        }
+        RoomNotificationMode::MentionsAndKeywordsOnly => {
+            tr!("NOTIFICATION_MODE_MENTIONS_KEYWORDS", "Mentions & Keywords")
+        }
+        RoomNotificationMode::Mute => tr!("NOTIFICATION_MODE_MUTE", "Mute"),
+    }
+}
+
+/// Menu items to change the notification mode of a room
+pub fn room_notification_mode_menu(
+    room_id: OwnedRoomId,
+    current_mode: Option<RoomNotificationMode>,
+    user_defined_mode: Option<RoomNotificationMode>,
+) -> Vec<ContextMenuItem> {
+    let mut menu: Vec<_> = [
+        RoomNotificationMode::AllMessages,
+        RoomNotificationMode::MentionsAndKeywordsOnly,
+        RoomNotificationMode::Mute,
+    ]
+    .into_iter()
+    .map(|mode| {
+        let room_id = room_id.clone();
+        ContextMenuItem::menu_item()
+            .label(notification_mode_text(&mode))
+            .when(current_mode.as_ref() == Some(&mode), |david| {
+                david.icon("dialog-ok")
+            })
+            .on_triggered(move |_, _, cx| {
+                let notification_settings = cx.global::<SessionManager>().notification_settings();
+                notification_settings.update(cx, |notification_settings, cx| {
+                    notification_settings
+                        .set_room_mode(room_id.clone(), Some(mode.clone()), cx)
+                        .detach();
+                });
+            })
+            .build()
+    })
+    .collect();
 
-pub struct NotificationsSettings {}
+    menu.push(ContextMenuItem::separator().build());
+    menu.push(
+        ContextMenuItem::menu_item()
+            .label(tr!("NOTIFICATIONS_ROOM_RESET", "Use Default"))
+            .icon("edit-undo")
+            .when(user_defined_mode.is_none(), |david| david.disabled())
+            .on_triggered(move |_, _, cx| {
+                let notification_settings = cx.global::<SessionManager>().notification_settings();
+                notification_settings.update(cx, |notification_settings, cx| {
+                    notification_settings
+                        .set_room_mode(room_id.clone(), None, cx)
+                        .detach();
+                });
+            })
+            .build(),
+    );
+
+    menu
+}
 
 impl NotificationsSettings {
     pub fn new(cx: &mut App) -> Entity<Self> {
-        cx.new(|cx| Self {})
+        cx.new(|cx| {
+            let add_keyword_enter_listener =
+                cx.listener(|this: &mut NotificationsSettings, _, window, cx| {
+                    cx.defer_in(window, |this, window, cx| this.add_keyword(window, cx));
+                });
+            let keyword_field = cx.new(|cx| {
+                let mut text_field = TextField::new("keyword", cx);
+                text_field.on_enter_press(add_keyword_enter_listener);
+                text_field.set_placeholder(
+                    tr!("NOTIFICATIONS_KEYWORD_PLACEHOLDER", "Keyword")
+                        .to_string()
+                        .as_str(),
+                );
+                text_field
+            });
+
+            Self {
+                keyword_field,
+                processing: false,
+            }
+        })
+    }
+
+    fn update_notification_settings<F, Fut>(
+        &mut self,
+        f: F,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) where
+        F: FnOnce(NotificationSettings) -> Fut + 'static,
+        Fut: Future<Output = Result<(), NotificationSettingsError>> + Send + 'static,
+    {
+        if self.processing {
+            return;
+        }
+
+        let session_manager = cx.global::<SessionManager>();
+        let Some(notification_settings) = session_manager
+            .notification_settings()
+            .read(cx)
+            .notification_settings()
+        else {
+            return;
+        };
+
+        self.processing = true;
+        cx.notify();
+
+        cx.spawn_in(
+            window,
+            async move |weak_this: WeakEntity<Self>, cx: &mut AsyncWindowContext| {
+                if cx.spawn_tokio(f(notification_settings)).await.is_err() {
+                    let _ = cx.update(|window, cx| {
+                        Toast::new()
+                            .title(&tr!(
+                                "NOTIFICATIONS_UPDATE_ERROR_TITLE",
+                                "Unable to update notification settings"
+                            ))
+                            .body(&tr!(
+                                "NOTIFICATIONS_UPDATE_ERROR_TEXT",
+                                "Your notification settings could not be saved to the server."
+                            ))
+                            .severity(AdmonitionSeverity::Error)
+                            .post(window, cx);
+                    });
+                }
+
+                let _ = weak_this.update(cx, |this, cx| {
+                    this.processing = false;
+                    cx.notify();
+                });
+            },
+        )
+        .detach();
+    }
+
+    pub fn set_push_rule_enabled(
+        &mut self,
+        kind: RuleKind,
+        rule_id: String,
+        enabled: bool,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        self.update_notification_settings(
+            move |notification_settings| async move {
+                notification_settings
+                    .set_push_rule_enabled(kind, rule_id, enabled)
+                    .await
+            },
+            window,
+            cx,
+        );
+    }
+
+    /// Turn notifications on or off for every device, using the master rule.
+    ///
+    /// The master rule silences everything while it is enabled, so it is the opposite of whether
+    /// notifications are enabled.
+    pub fn set_notifications_enabled(
+        &mut self,
+        enabled: bool,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        self.set_push_rule_enabled(
+            RuleKind::Override,
+            PredefinedOverrideRuleId::Master.as_str().to_string(),
+            !enabled,
+            window,
+            cx,
+        );
+    }
+
+    pub fn set_default_mode(
+        &mut self,
+        is_one_to_one: bool,
+        mode: RoomNotificationMode,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        self.update_notification_settings(
+            move |notification_settings| async move {
+                for is_encrypted in [IsEncrypted::No, IsEncrypted::Yes] {
+                    notification_settings
+                        .set_default_room_notification_mode(
+                            is_encrypted,
+                            if is_one_to_one {
+                                IsOneToOne::Yes
+                            } else {
+                                IsOneToOne::No
+                            },
+                            mode.clone(),
+                        )
+                        .await?;
+                }
+                Ok(())
+            },
+            window,
+            cx,
+        );
+    }
+
+    pub fn add_keyword(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let keyword = self.keyword_field.read(cx).text().trim().to_string();
+        if keyword.is_empty() {
+            self.keyword_field.update(cx, |field, cx| {
+                field.flash_error(window, cx);
+            });
+            return;
+        }
+
+        self.keyword_field.update(cx, |field, cx| {
+            field.set_text("");
+            cx.notify();
+        });
+        self.update_notification_settings(
+            move |notification_settings| async move {
+                notification_settings.add_keyword(keyword).await
+            },
+            window,
+            cx,
+        );
+    }
+
+    pub fn remove_keyword(&mut self, keyword: String, window: &mut Window, cx: &mut Context<Self>) {
+        self.update_notification_settings(
+            move |notification_settings| async move {
+                notification_settings.remove_keyword(&keyword).await
+            },
+            window,
+            cx,
+        );
+    }
+
+    /// Whether the settings can't be changed right now, because they are still loading or saving
+    fn is_busy(&self, cx: &App) -> bool {
+        self.processing
+            || cx
+                .global::<SessionManager>()
+                .notification_settings()
+                .read(cx)
+                .notification_settings()
+                .is_none()
+    }
+
+    fn render_notifications_enabled_switch(
+        &self,
+        notifications_enabled: bool,
+        cx: &Context<Self>,
+    ) -> impl IntoElement {
+        layer()
+            .p(px(4.))
+            .gap(px(4.))
+            .items_center()
+            .flex()
+            .child(tr!("NOTIFICATIONS_ENABLE", "Enable notifications"))
+            .child(div().flex_grow(1.))
+            .child(
+                switch("notifications-enabled")
+                    .when(notifications_enabled, |david| david.checked())
+                    .when(self.is_busy(cx), |david| david.disabled())
+                    .on_change(cx.listener(|this, event: &SwitchChangeEvent, window, cx| {
+                        this.set_notifications_enabled(event.checked, window, cx)
+                    })),
+            )
+    }
+
+    fn render_rule_switch(
+        &self,
+        id: &'static str,
+        label: I18nString,
+        checked: bool,
+        kind: RuleKind,
+        rule_id: &str,
+        cx: &Context<Self>,
+    ) -> impl IntoElement {
+        let rule_id = rule_id.to_string();
+        layer()
+            .p(px(4.))
+            .gap(px(4.))
+            .items_center()
+            .flex()
+            .child(label)
+            .child(div().flex_grow(1.))
+            .child(
+                switch(id)
+                    .when(checked, |david| david.checked())
+                    .when(self.is_busy(cx), |david| david.disabled())
+                    .on_change(cx.listener(move |this, event: &SwitchChangeEvent, window, cx| {
+                        this.set_push_rule_enabled(
+                            kind.clone(),
+                            rule_id.clone(),
+                            event.checked,
+                            window,
+                            cx,
+                        )
+                    })),
+            )
+    }
+
+    fn render_default_mode(
+        &self,
+        id: &'static str,
+        label: I18nString,
+        mode: &RoomNotificationMode,
+        is_one_to_one: bool,
+        cx: &Context<Self>,
+    ) -> impl IntoElement {
+        layer()
+            .p(px(4.))
+            .gap(px(4.))
+            .items_center()
+            .flex()
+            .child(label)
+            .child(div().flex_grow(1.))
+            .child(notification_mode_text(mode))
+            .child(
+                button(id)
+                    .child(icon("arrow-down"))
+                    .when(self.is_busy(cx), |david| david.disabled())
+                    .with_menu(
+                        [
+                            RoomNotificationMode::AllMessages,
+                            RoomNotificationMode::MentionsAndKeywordsOnly,
+                        ]
+                        .into_iter()
+                        .map(|mode| {
+                            ContextMenuItem::menu_item()
+                                .label(notification_mode_text(&mode))
+                                .on_triggered(cx.listener(move |this, _, window, cx| {
+                                    this.set_default_mode(is_one_to_one, mode.clone(), window, cx)
+                                }))
+                                .build()
+                        })
+                        .collect(),
+                    ),
+            )
     }
 }
 
@@ -20,6 +383,29 @@ impl Render for NotificationsSettings {
     fn render(&mut self, window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
         let theme = cx.global::<Theme>();
 
+        let session_manager = cx.global::<SessionManager>();
+        let notification_settings_cache = session_manager.notification_settings().read(cx);
+        let loaded = notification_settings_cache
+            .notification_settings()
+            .is_some();
+        let state = notification_settings_cache.state().clone();
+
+        let rooms = session_manager.rooms().read(cx);
+        let mut room_modes: Vec<_> = state
+            .room_modes
+            .iter()
+            .map(|(room_id, mode)| {
+                let room_name = rooms
+                    .room(room_id)
+                    .map(|room| room.read(cx).display_name())
+                    .unwrap_or_else(|| room_id.to_string());
+                (room_id.clone(), room_name, mode.clone())
+            })
+            .collect();
+        room_modes.sort_by(|(_, a, _), (_, b, _)| a.to_lowercase().cmp(&b.to_lowercase()));
+
+        let keywords = state.keywords.clone();
+
         div()
             .bg(theme.background)
             .w_full()
@@ -43,13 +429,335 @@ impl Render for NotificationsSettings {
                             .flex()
                             .flex_col()
                             .p(px(8.))
+                            .gap(px(4.))
                             .w_full()
                             .child(subtitle(tr!(
                                 "NOTIFICATIONS_SYSTEM",
                                 "System Notifications"
                             )))
-                            .child(div().child("Coming soon")),
-                    ),
+                            .child(div().child(tr!(
+                                "NOTIFICATIONS_SYSTEM_DESCRIPTION",
+                                "Notification settings are stored on your homeserver, and apply \
+                                to all of your devices."
+                            )))
+                            .when(!loaded, |david| {
+                                david.child(
+                                    div()
+                                        .text_color(theme.foreground.disabled())
+                                        .child(tr!(
+                                            "NOTIFICATIONS_LOADING",
+                                            "Loading notification settings..."
+                                        )),
+                                )
+                            })
+                            .child(
+                                self.render_notifications_enabled_switch(
+                                    state.notifications_enabled,
+                                    cx,
+                                ),
+                            )
+                            .when(!state.notifications_enabled, |david| {
+                                david.child(
+                                    div()
+                                        .text_color(theme.foreground.disabled())
+                                        .child(tr!(
+                                            "NOTIFICATIONS_DISABLED_DESCRIPTION",
+                                            "You won't receive any notifications on any of your \
+                                            devices until notifications are enabled again."
+                                        )),
+                                )
+                            }),
+                    )
+                    .when(state.notifications_enabled, |david| {
+                        david
+                            .child(
+                                layer()
+                                    .flex()
+                                    .flex_col()
+                                    .p(px(8.))
+                                    .gap(px(2.))
+                                    .w_full()
+                                    .child(subtitle(tr!(
+                                        "NOTIFICATIONS_DEFAULT",
+                                        "Default Notifications"
+                                    )))
+                                    .child(self.render_default_mode(
+                                        "default-group-mode",
+                                        tr!("NOTIFICATIONS_DEFAULT_GROUP", "Group chats"),
+                                        &state.group_mode,
+                                        false,
+                                        cx,
+                                    ))
+                                    .child(self.render_default_mode(
+                                        "default-direct-mode",
+                                        tr!("NOTIFICATIONS_DEFAULT_DIRECT", "Direct messages"),
+                                        &state.direct_mode,
+                                        true,
+                                        cx,
+                                    )),
+                            )
+                            .child(
+                                layer()
+                                    .flex()
+                                    .flex_col()
+                                    .p(px(8.))
+                                    .gap(px(2.))
+                                    .w_full()
+                                    .child(subtitle(tr!(
+                                        "NOTIFICATIONS_ALWAYS_NOTIFY",
+                                        "Always Notify"
+                                    )))
+                                    .child(self.render_rule_switch(
+                                        "user-mentions",
+                                        tr!(
+                                            "NOTIFICATIONS_USER_MENTIONS",
+                                            "When I am mentioned"
+                                        ),
+                                        state.user_mentions,
+                                        RuleKind::Override,
+                                        PredefinedOverrideRuleId::IsUserMention.as_str(),
+                                        cx,
+                                    ))
+                                    .child(self.render_rule_switch(
+                                        "room-mentions",
+                                        tr!(
+                                            "NOTIFICATIONS_ROOM_MENTIONS",
+                                            "When everyone in the room is mentioned"
+                                        ),
+                                        state.room_mentions,
+                                        RuleKind::Override,
+                                        PredefinedOverrideRuleId::IsRoomMention.as_str(),
+                                        cx,
+                                    ))
+                                    .child(self.render_rule_switch(
+                                        "invites",
+                                        tr!(
+                                            "NOTIFICATIONS_INVITES",
+                                            "When I am invited to a room"
+                                        ),
+                                        state.invites,
+                                        RuleKind::Override,
+                                        PredefinedOverrideRuleId::InviteForMe.as_str(),
+                                        cx,
+                                    ))
+                                    .child(self.render_rule_switch(
+                                        "calls",
+                                        tr!("NOTIFICATIONS_CALLS", "When a call is started"),
+                                        state.calls,
+                                        RuleKind::Underride,
+                                        PredefinedUnderrideRuleId::Call.as_str(),
+                                        cx,
+                                    )),
+                            )
+                            .child(
+                                layer()
+                                    .flex()
+                                    .flex_col()
+                                    .p(px(8.))
+                                    .gap(px(4.))
+                                    .w_full()
+                                    .child(subtitle(tr!("NOTIFICATIONS_KEYWORDS", "Keywords")))
+                                    .child(div().child(tr!(
+                                        "NOTIFICATIONS_KEYWORDS_DESCRIPTION",
+                                        "You'll be notified whenever a message contains one of \
+                                        these keywords, even in rooms that are set to only \
+                                        notify for mentions and keywords."
+                                    )))
+                                    .child(
+                                        div()
+                                            .flex()
+                                            .gap(px(4.))
+                                            .child(
+                                                div()
+                                                    .flex_grow(1.)
+                                                    .child(self.keyword_field.clone()),
+                                            )
+                                            .child(
+                                                button("add-keyword-button")
+                                                    .when(self.processing, |david| {
+                                                        david.disabled()
+                                                    })
+                                                    .child(icon_text(
+                                                        "list-add",
+                                                        tr!(
+                                                            "NOTIFICATIONS_KEYWORD_ADD",
+                                                            "Add Keyword"
+                                                        ),
+                                                    ))
+                                                    .on_click(cx.listener(
+                                                        |this, _, window, cx| {
+                                                            this.add_keyword(window, cx)
+                                                        },
+                                                    )),
+                                            ),
+                                    )
+                                    .child(
+                                        uniform_list(
+                                            "keywords-list",
+                                            keywords.len(),
+                                            cx.processor(
+                                                move |this, range: Range<usize>, _, cx| {
+                                                    range
+                                                        .map(|index| {
+                                                            let Some(keyword) =
+                                                                keywords.get(index).cloned()
+                                                            else {
+                                                                return div().into_any_element();
+                                                            };
+
+                                                            div()
+                                                                .id(ElementId::Name(
+                                                                    keyword.clone().into(),
+                                                                ))
+                                                                .py(px(2.))
+                                                                .child(
+                                                                    layer()
+                                                                        .p(px(2.))
+                                                                        .flex()
+                                                                        .items_center()
+                                                                        .child(
+                                                                            div()
+                                                                                .flex_grow(1.)
+                                                                                .child(
+                                                                                    keyword.clone(),
+                                                                                ),
+                                                                        )
+                                                                        .child(
+                                                                            button("delete")
+                                                                                .destructive()
+                                                                                .child(icon(
+                                                                                    "list-remove",
+                                                                                ))
+                                                                                .when(
+                                                                                    this.processing,
+                                                                                    |david| {
+                                                                                        david
+                                                                                            .disabled()
+                                                                                    },
+                                                                                )
+                                                                                .on_click(
+                                                                                    cx.listener(
+                                                                                        move |this, _, window, cx| {
+                                                                                            this.remove_keyword(
+                                                                                                keyword.clone(),
+                                                                                                window,
+                                                                                                cx,
+                                                                                            )
+                                                                                        },
+                                                                                    ),
+                                                                                ),
+                                                                        ),
+                                                                )
+                                                                .into_any_element()
+                                                        })
+                                                        .collect()
+                                                },
+                                            ),
+                                        )
+                                        .with_sizing_behavior(ListSizingBehavior::Infer),
+                                    ),
+                            )
+                            .child(
+                                layer()
+                                    .flex()
+                                    .flex_col()
+                                    .p(px(8.))
+                                    .w_full()
+                                    .child(subtitle(tr!(
+                                        "NOTIFICATIONS_ROOMS",
+                                        "Room Notifications"
+                                    )))
+                                    .child(div().child(tr!(
+                                        "NOTIFICATIONS_ROOMS_DESCRIPTION",
+                                        "These rooms have their own notification settings that \
+                                        override the defaults."
+                                    )))
+                                    .when(room_modes.is_empty(), |david| {
+                                        david.child(
+                                            div()
+                                                .text_color(theme.foreground.disabled())
+                                                .child(tr!(
+                                                    "NOTIFICATIONS_ROOMS_NONE",
+                                                    "No rooms have their own notification \
+                                                    settings."
+                                                )),
+                                        )
+                                    })
+                                    .child(
+                                        uniform_list(
+                                            "room-modes-list",
+                                            room_modes.len(),
+                                            cx.processor(
+                                                move |this, range: Range<usize>, _, cx| {
+                                                    range
+                                                        .map(|index| {
+                                                            let Some((room_id, room_name, mode)) =
+                                                                room_modes.get(index).cloned()
+                                                            else {
+                                                                return div().into_any_element();
+                                                            };
+
+                                                            let mut menu = vec![
+                                                                ContextMenuItem::separator()
+                                                                    .label(tr!(
+                                                                        "NOTIFICATIONS_ROOM_MENU_TITLE",
+                                                                        "For {{room}}",
+                                                                        room:Quote = room_name.clone()
+                                                                    ))
+                                                                    .build(),
+                                                            ];
+                                                            menu.extend(room_notification_mode_menu(
+                                                                room_id.clone(),
+                                                                Some(mode.clone()),
+                                                                Some(mode.clone()),
+                                                            ));
+
+                                                            div()
+                                                                .id(ElementId::Name(
+                                                                    room_id.to_string().into(),
+                                                                ))
+                                                                .py(px(2.))
+                                                                .child(
+                                                                    layer()
+                                                                        .p(px(2.))
+                                                                        .gap(px(4.))
+                                                                        .flex()
+                                                                        .items_center()
+                                                                        .child(
+                                                                            div()
+                                                                                .flex_grow(1.)
+                                                                                .child(room_name),
+                                                                        )
+                                                                        .child(
+                                                                            notification_mode_text(
+                                                                                &mode,
+                                                                            ),
+                                                                        )
+                                                                        .child(
+                                                                            button("change-mode")
+                                                                                .child(icon(
+                                                                                    "arrow-down",
+                                                                                ))
+                                                                                .when(
+                                                                                    this.processing,
+                                                                                    |david| {
+                                                                                        david
+                                                                                            .disabled()
+                                                                                    },
+                                                                                )
+                                                                                .with_menu(menu),
+                                                                        ),
+                                                                )
+                                                                .into_any_element()
+                                                        })
+                                                        .collect()
+                                                },
+                                            ),
+                                        )
+                                        .with_sizing_behavior(ListSizingBehavior::Infer),
+                                    ),
+                            )
+                    }),
             )
     }
 }
@@ -17,11 +17,13 @@ use contemporary::components::grandstand::grandstand;
 use contemporary::components::icon_text::icon_text;
 use contemporary::components::layer::layer;
 use contemporary::components::subtitle::subtitle;
+use contemporary::components::switch::{SwitchChangeEvent, switch};
+use contemporary::components::toast::Toast;
 use contemporary::styling::theme::Theme;
 use gpui::prelude::FluentBuilder;
 use gpui::{
-    App, AppContext, AsyncApp, ClickEvent, Context, Entity, IntoElement, ParentElement,
-    PathPromptOptions, Render, Styled, Window, div, px,
+    App, AppContext, AsyncApp, AsyncWindowContext, ClickEvent, Context, Entity, IntoElement,
+    ParentElement, PathPromptOptions, Render, Styled, Window, div, px,
 };
 use matrix_sdk::encryption::VerificationState;
 use matrix_sdk::encryption::recovery::RecoveryState;
@@ -30,6 +32,7 @@ use thegrid_common::session::session_manager::SessionManager;
 use thegrid_common::surfaces::{
     MainWindowSurface, SurfaceChange, SurfaceChangeEvent, SurfaceChangeHandler,
 };
+use tracing::error;
 
 pub struct SecuritySettings {
     recovery_key_reset_popover: Entity<RecoveryKeyResetPopover>,
@@ -106,6 +109,33 @@ impl SecuritySettings {
         })
         .detach();
     }
+
+    fn set_url_previews_enabled(
+        &mut self,
+        enabled: bool,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let task = cx
+            .global::<SessionManager>()
+            .url_previews()
+            .update(cx, |url_previews, cx| url_previews.set_globally_enabled(enabled, cx));
+        window
+            .spawn(cx, async move |cx: &mut AsyncWindowContext| {
+                if let Err(e) = task.await {
+                    error!("Unable to change link preview setting: {e:?}");
+                    let _ = cx.update(|window, cx| {
+                        Toast::new()
+                            .title(&tr!("URL_PREVIEWS_ERROR_TITLE"))
+                            .body(&tr!("URL_PREVIEWS_ERROR_TEXT"))
+                            .severity(AdmonitionSeverity::Error)
+                            .post(window, cx);
+                    });
+                }
+            })
+            .detach();
+        cx.notify();
+    }
 }
 
 impl Render for SecuritySettings {
@@ -127,6 +157,8 @@ impl Render for SecuritySettings {
             .read(cx)
             .can_change_password();
 
+        let url_previews_enabled = session_manager.url_previews().read(cx).globally_enabled();
+
         div()
             .bg(theme.background)
             .w_full()
@@ -356,6 +388,45 @@ impl Render for SecuritySettings {
                                     ),
                             ),
                     )
+                    .child(
+                        layer()
+                            .flex()
+                            .flex_col()
+                            .p(px(8.))
+                            .w_full()
+                            .child(subtitle(tr!("URL_PREVIEWS")))
+                            .child(div().child(tr!(
+                                "SECURITY_URL_PREVIEWS_DESCRIPTION",
+                                "To show a preview of a link, your homeserver fetches the page \
+                                for you. Link previews are off by default in encrypted rooms, and \
+                                can be turned on or off for each room in its settings."
+                            )))
+                            .child(
+                                layer()
+                                    .p(px(4.))
+                                    .gap(px(4.))
+                                    .items_center()
+                                    .flex()
+                                    .child(tr!(
+                                        "SECURITY_URL_PREVIEWS_SHOW",
+                                        "Show link previews"
+                                    ))
+                                    .child(div().flex_grow(1.))
+                                    .child(
+                                        switch("url-previews")
+                                            .when(url_previews_enabled, |david| david.checked())
+                                            .on_change(cx.listener(
+                                                |this, event: &SwitchChangeEvent, window, cx| {
+                                                    this.set_url_previews_enabled(
+                                                        event.checked,
+                                                        window,
+                                                        cx,
+                                                    )
+                                                },
+                                            )),
+                                    ),
+                            ),
+                    )
                     .child(
                         layer()
                             .flex()
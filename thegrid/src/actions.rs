@@ -1,4 +1,5 @@
 use gpui::{App, KeyBinding, actions};
+use thegrid_rtc_livekit::call_shortcuts::CallShortcuts;
 
 actions!(
     thegrid,
@@ -17,5 +18,8 @@ pub fn register_actions(cx: &mut App) {
         KeyBinding::new("secondary-n", CreateRoom, None),
         KeyBinding::new("secondary-s", CreateSpace, None),
         KeyBinding::new("secondary-j", DirectJoinRoom, None),
-    ])
+    ]);
+
+    let call_key_bindings = cx.global::<CallShortcuts>().key_bindings();
+    cx.bind_keys(call_key_bindings);
 }
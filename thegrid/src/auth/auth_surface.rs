@@ -1,3 +1,6 @@
+use crate::auth::qr_login::{
+    QrLoginDirection, QrLoginState, QrLoginView, forward_qr_login_progress,
+};
 use crate::utilities::default_device_name;
 use base64::alphabet::URL_SAFE;
 use base64::prelude::{BASE64_URL_SAFE, BASE64_URL_SAFE_NO_PAD};
@@ -26,7 +29,7 @@ use gpui::private::anyhow;
 use gpui::{
     App, AppContext, AsyncApp, BorrowAppContext, ClipboardItem, Context, ElementId, Entity,
     ImageSource, InteractiveElement, IntoElement, Menu, ParentElement, Render, Resource,
-    SharedString, Styled, WeakEntity, Window, div, img, px,
+    SharedString, Styled, Task, WeakEntity, Window, div, img, px,
 };
 use gpui_tokio::Tokio;
 use matrix_sdk::authentication::matrix::MatrixSession;
@@ -40,7 +43,7 @@ use matrix_sdk::encryption::CrossSigningStatus;
 use matrix_sdk::ruma::api::client::discovery::get_authorization_server_metadata::v1::AuthorizationServerMetadata;
 use matrix_sdk::ruma::api::client::session::get_login_types::v3::{IdentityProvider, LoginType};
 use matrix_sdk::ruma::serde::Raw;
-use matrix_sdk::ruma::{DeviceId, OwnedUserId, user_id};
+use matrix_sdk::ruma::{DeviceId, OwnedUserId, ServerName, UserId, user_id};
 use matrix_sdk::utils::UrlOrQuery;
 use matrix_sdk::{Client, ClientBuildError};
 use smol::future::FutureExt;
@@ -69,6 +72,7 @@ enum AuthState {
     AuthRequired,
     SsoTokenRequired(Option<IdentityProvider>, Entity<Option<SsoLogin>>),
     OAuthContinueInBrowserPrompt(Url, Entity<Option<SsoLogin>>),
+    QrLogin,
 }
 
 enum LoginMethod {
@@ -88,6 +92,8 @@ pub struct AuthSurface {
     user_id: Option<OwnedUserId>,
     session_uuid: Uuid,
     database_secret: DatabaseSecret,
+    qr_login_view: Entity<QrLoginView>,
+    qr_login_task: Option<Task<()>>,
 
     on_surface_change: Rc<Box<SurfaceChangeHandler>>,
     application_menu: Entity<ApplicationMenu>,
@@ -165,6 +171,8 @@ impl AuthSurface {
                 session_uuid: Uuid::new_v4(),
                 on_surface_change: Rc::new(Box::new(on_surface_change)),
                 database_secret: DatabaseSecret::new().unwrap(),
+                qr_login_view: cx.new(|cx| QrLoginView::new(QrLoginDirection::Login, cx)),
+                qr_login_task: None,
                 application_menu: ApplicationMenu::new(
                     cx,
                     Menu {
@@ -248,6 +256,126 @@ impl AuthSurface {
         cx.notify();
     }
 
+    fn qr_login_clicked(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        // Either a full Matrix ID or just the server name of the homeserver can be used
+        let matrix_id = self.matrix_id_field.read(cx).text();
+        let server_name = match UserId::parse(matrix_id.trim()) {
+            Ok(user_id) => Ok(user_id.server_name().to_owned()),
+            Err(_) => ServerName::parse(matrix_id.trim()),
+        };
+        let Ok(server_name) = server_name else {
+            self.matrix_id_field.update(cx, |matrix_id_field, cx| {
+                matrix_id_field.flash_error(window, cx);
+            });
+            return;
+        };
+        self.user_id = None;
+
+        let session_dir = self.session_dir(cx);
+        let store_dir = session_dir.join("store");
+
+        std::fs::create_dir_all(&store_dir).unwrap();
+
+        self.qr_login_view.update(cx, |qr_login_view, cx| {
+            qr_login_view.set_state(QrLoginState::Starting, cx);
+        });
+
+        let database_password = self.database_secret.database_password();
+        self.qr_login_task = Some(cx.spawn(
+            async move |this: WeakEntity<Self>, cx: &mut AsyncApp| {
+                let client = cx
+                    .spawn_tokio(async move {
+                        Client::builder()
+                            .server_name(&server_name)
+                            .sqlite_store(store_dir, Some(&database_password))
+                            .handle_refresh_tokens()
+                            .build()
+                            .await
+                    })
+                    .await;
+
+                let client = match client {
+                    Ok(client) => client,
+                    Err(e) => {
+                        this.update(cx, |this, cx| {
+                            if !matches!(this.state, AuthState::QrLogin) {
+                                return;
+                            }
+
+                            this.state = AuthState::ConnectionError;
+                            error!("Unable to create client: {e:?}");
+                            cx.notify();
+                        })
+                        .unwrap();
+                        return;
+                    }
+                };
+
+                Self::proceed_with_qr_login(client, this, cx).await;
+            },
+        ));
+
+        self.state = AuthState::QrLogin;
+        cx.notify();
+    }
+
+    async fn proceed_with_qr_login(client: Client, this: WeakEntity<Self>, cx: &mut AsyncApp) {
+        let client_metadata =
+            cx.read_global::<Details, _>(|details, _| oauth_client_metadata(details));
+        let registration_data = ClientRegistrationData::new(Raw::new(&client_metadata).unwrap());
+
+        let qr_login_view = this
+            .read_with(cx, |this, _| this.qr_login_view.clone())
+            .unwrap();
+
+        let (progress_tx, progress_rx) = async_channel::unbounded();
+        cx.spawn({
+            let qr_login_view = qr_login_view.clone();
+            async move |cx: &mut AsyncApp| {
+                while let Ok(progress) = progress_rx.recv().await {
+                    qr_login_view.update(cx, |qr_login_view, cx| {
+                        qr_login_view.set_state(QrLoginState::from_login_progress(progress), cx);
+                    });
+                }
+            }
+        })
+        .detach();
+
+        if let Err(e) = cx
+            .spawn_tokio({
+                let client = client.clone();
+                async move {
+                    let oauth = client.oauth();
+                    let login = oauth
+                        .login_with_qr_code(Some(&registration_data))
+                        .generate();
+                    let progress = login.subscribe_to_progress();
+
+                    // Once the secrets have been synced from the other device, this device
+                    // is signed by the cross-signing keys, so no manual verification is needed
+                    forward_qr_login_progress(progress, login.into_future(), progress_tx).await
+                }
+            })
+            .await
+        {
+            error!("Unable to log in with QR code: {e:?}");
+            qr_login_view.update(cx, |qr_login_view, cx| {
+                qr_login_view.set_state(QrLoginState::Error, cx);
+            });
+            return;
+        }
+
+        this.update(cx, |this, cx| {
+            this.client = Some(client.clone());
+            this.state = AuthState::Connecting;
+            cx.notify();
+        })
+        .unwrap();
+
+        let session = client.oauth().full_session().unwrap().into();
+        Self::complete_successful_login(client, SessionType::OAuth(session), this, cx).await;
+    }
+
     fn open_account_clicked(&mut self, window: &mut Window, cx: &mut Context<Self>) {
         (self.on_surface_change)(
             &SurfaceChangeEvent {
@@ -317,8 +445,8 @@ impl AuthSurface {
         this: WeakEntity<Self>,
         cx: &mut AsyncApp,
     ) {
-        let application_name = cx
-            .read_global::<Details, _>(|details, cx| details.generatable.application_name.clone());
+        let client_metadata =
+            cx.read_global::<Details, _>(|details, _| oauth_client_metadata(details));
 
         let should_use_legacy_auth = match cx
             .spawn_tokio({
@@ -328,36 +456,13 @@ impl AuthSurface {
             .await
         {
             Ok(_) => {
-                let redirect_uri = Url::parse("https://thegrid.vicr123.com/oauth-signin").unwrap();
-
                 // Continue with OAuth
-                let mut client_metadata = ClientMetadata::new(
-                    ApplicationType::Native,
-                    vec![OAuthGrantType::AuthorizationCode {
-                        redirect_uris: vec![redirect_uri.clone()],
-                    }],
-                    Localized::new(
-                        Url::parse("https://thegrid.vicr123.com").unwrap(),
-                        iter::empty(),
-                    ),
-                );
-
-                // TODO: Once support lands in Contemporary, read out all the localised values
-                client_metadata.client_name = Some(Localized::new(
-                    application_name.default_value(),
-                    iter::empty(),
-                ));
-                client_metadata.logo_uri = Some(Localized::new(
-                    Url::parse("https://thegrid.vicr123.com/thegrid.png").unwrap(),
-                    iter::empty(),
-                ));
-
                 match cx
                     .spawn_tokio({
                         let client = client.clone();
                         async move {
                             let mut builder = client.oauth().login(
-                                redirect_uri,
+                                oauth_redirect_uri(),
                                 None,
                                 Some(ClientRegistrationData::new(
                                     Raw::new(&client_metadata).unwrap(),
@@ -745,6 +850,7 @@ impl AuthSurface {
                 AuthState::AuthRequired => 5,
                 AuthState::SsoTokenRequired(_, _) => 6,
                 AuthState::OAuthContinueInBrowserPrompt(_, _) => 7,
+                AuthState::QrLogin => 8,
             },
         )
         .animation(FadeAnimation::new())
@@ -1136,6 +1242,7 @@ impl AuthSurface {
                 )
                 .into_any_element(),
         )
+        .page(self.qr_login_view.clone().into_any_element())
     }
 }
 
@@ -1232,6 +1339,17 @@ impl Render for AuthSurface {
                                         //         })),
                                         // )
                                         .child(div().flex_grow(1.))
+                                        .child(
+                                            button("qr_log_in")
+                                                .child(tr!(
+                                                    "AUTH_QR_LOG_IN",
+                                                    "Log In with QR Code..."
+                                                ))
+                                                .flat()
+                                                .on_click(cx.listener(|this, _, window, cx| {
+                                                    this.qr_login_clicked(window, cx)
+                                                })),
+                                        )
                                         .child(
                                             button("advanced_log_in")
                                                 .child(tr!(
@@ -1284,6 +1402,7 @@ impl Render for AuthSurface {
                                                 })
                                                 .on_back_click(cx.listener(|this, _, _, cx| {
                                                     this.client = None;
+                                                    this.qr_login_task = None;
                                                     this.state = AuthState::Idle;
                                                     cx.notify()
                                                 })),
@@ -1296,3 +1415,35 @@ impl Render for AuthSurface {
         )
     }
 }
+
+fn oauth_redirect_uri() -> Url {
+    Url::parse("https://thegrid.vicr123.com/oauth-signin").unwrap()
+}
+
+fn oauth_client_metadata(details: &Details) -> ClientMetadata {
+    let mut client_metadata = ClientMetadata::new(
+        ApplicationType::Native,
+        vec![
+            OAuthGrantType::AuthorizationCode {
+                redirect_uris: vec![oauth_redirect_uri()],
+            },
+            // Required to log in with a QR code
+            OAuthGrantType::DeviceCode,
+        ],
+        Localized::new(
+            Url::parse("https://thegrid.vicr123.com").unwrap(),
+            iter::empty(),
+        ),
+    );
+
+    // TODO: Once support lands in Contemporary, read out all the localised values
+    client_metadata.client_name = Some(Localized::new(
+        details.generatable.application_name.default_value(),
+        iter::empty(),
+    ));
+    client_metadata.logo_uri = Some(Localized::new(
+        Url::parse("https://thegrid.vicr123.com/thegrid.png").unwrap(),
+        iter::empty(),
+    ));
+    client_metadata
+}
@@ -1,26 +1,46 @@
 use cntp_i18n::tr;
 use contemporary::components::layer::layer;
+use contemporary::components::tooltip::simple_tooltip;
 use contemporary::components::{button::button, text_field::TextField};
-use contemporary::styling::theme::Theme;
+use contemporary::styling::theme::{Theme, VariableColor};
 use emojis::{Emoji, Group};
 use gpui::prelude::FluentBuilder;
 use gpui::{
-    App, AppContext, Context, Entity, InteractiveElement, IntoElement, ParentElement, Render,
-    StatefulInteractiveElement, Styled, Window, div, px,
+    AnyElement, App, AppContext, Context, Entity, InteractiveElement, IntoElement, ParentElement,
+    Render, StatefulInteractiveElement, Styled, Window, div, px,
 };
 use std::rc::Rc;
+use thegrid_common::mxc_image::{SizePolicy, mxc_image};
+use thegrid_common::session::image_packs_cache::{ImagePack, PackImage, PackUsage};
 
 pub type EmojiSelectedListener = dyn Fn(&EmojiSelectedEvent, &mut Window, &mut App) + 'static;
 
 #[derive(Clone)]
 pub struct EmojiSelectedEvent {
+    /// The emoji, or the shortcode surrounded by colons for images from an image pack
     pub emoji: String,
+    /// The image from an image pack that was selected, if any
+    pub image: Option<PackImage>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum EmojiCategory {
+    Group(Group),
+    Pack(usize),
+}
+
+#[derive(Clone)]
+enum VisibleEmoji {
+    Unicode(&'static Emoji),
+    Custom(String, PackImage),
 }
 
 pub struct EmojiFlyout {
     search_field: Entity<TextField>,
-    selected_group: Group,
-    visible_emoji: Vec<&'static Emoji>,
+    selected_category: EmojiCategory,
+    image_packs: Vec<ImagePack>,
+    usage: PackUsage,
+    visible_emoji: Vec<VisibleEmoji>,
 
     emoji_selected_listener: Option<Rc<Box<EmojiSelectedListener>>>,
 }
@@ -47,8 +67,13 @@ impl EmojiFlyout {
                 });
                 text_field
             }),
-            visible_emoji: Group::SmileysAndEmotion.emojis().collect(),
-            selected_group: Group::SmileysAndEmotion,
+            visible_emoji: Group::SmileysAndEmotion
+                .emojis()
+                .map(VisibleEmoji::Unicode)
+                .collect(),
+            selected_category: EmojiCategory::Group(Group::SmileysAndEmotion),
+            image_packs: Vec::new(),
+            usage: PackUsage::Emoticon,
             emoji_selected_listener: None,
         }
     }
@@ -60,26 +85,76 @@ impl EmojiFlyout {
         self.emoji_selected_listener = Some(Rc::new(Box::new(listener)));
     }
 
+    /// Offer images from image packs alongside the Unicode emoji.
+    ///
+    /// When picking stickers, only the images from the image packs are offered.
+    pub fn set_image_packs(
+        &mut self,
+        image_packs: Vec<ImagePack>,
+        usage: PackUsage,
+        cx: &mut Context<Self>,
+    ) {
+        self.image_packs = image_packs
+            .into_iter()
+            .filter(|pack| pack.images_for(usage).next().is_some())
+            .collect();
+        self.usage = usage;
+        if usage == PackUsage::Sticker {
+            self.selected_category = EmojiCategory::Pack(0);
+        }
+        self.update_visible_emoji(cx);
+    }
+
     pub fn update_visible_emoji(&mut self, cx: &mut Context<Self>) {
         let search_query = self.search_field.read(cx).text().to_lowercase();
         if search_query.is_empty() {
-            self.visible_emoji = self.selected_group.emojis().collect();
+            self.visible_emoji = match self.selected_category {
+                EmojiCategory::Group(group) => group.emojis().map(VisibleEmoji::Unicode).collect(),
+                EmojiCategory::Pack(index) => self
+                    .image_packs
+                    .get(index)
+                    .map(|pack| pack_emoji(pack, self.usage).collect())
+                    .unwrap_or_default(),
+            };
         } else {
-            self.visible_emoji = emojis::iter()
+            let custom_emoji = self
+                .image_packs
+                .iter()
+                .flat_map(|pack| pack_emoji(pack, self.usage))
+                .filter(|emoji| match emoji {
+                    VisibleEmoji::Custom(shortcode, image) => {
+                        shortcode.to_lowercase().contains(&search_query)
+                            || image
+                                .body
+                                .as_ref()
+                                .is_some_and(|body| body.to_lowercase().contains(&search_query))
+                    }
+                    VisibleEmoji::Unicode(_) => false,
+                });
+            let unicode_emoji = emojis::iter()
+                .filter(|_| self.usage == PackUsage::Emoticon)
                 .filter(|emoji| {
                     emoji
                         .shortcodes()
                         .any(|shortcode| shortcode.to_lowercase().contains(&search_query))
                         || emoji.name().to_lowercase().contains(&search_query)
                 })
-                .collect();
+                .map(VisibleEmoji::Unicode);
+            self.visible_emoji = custom_emoji.chain(unicode_emoji).collect();
         }
     }
 }
 
+fn pack_emoji(pack: &ImagePack, usage: PackUsage) -> impl Iterator<Item = VisibleEmoji> {
+    pack.images_for(usage)
+        .map(|(shortcode, image)| VisibleEmoji::Custom(shortcode.clone(), image.clone()))
+}
+
 impl Render for EmojiFlyout {
     fn render(&mut self, _: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
         let theme = cx.global::<Theme>();
+        let picking_stickers = self.usage == PackUsage::Sticker;
+        let image_size = if picking_stickers { px(56.) } else { px(20.) };
 
         div()
             .bg(theme.background)
@@ -92,50 +167,108 @@ impl Render for EmojiFlyout {
             .flex()
             .flex_col()
             .child(self.search_field.clone())
+            .when(picking_stickers && self.image_packs.is_empty(), |david| {
+                david.child(
+                    div()
+                        .flex_grow()
+                        .flex()
+                        .items_center()
+                        .justify_center()
+                        .p(px(8.))
+                        .text_color(theme.foreground.disabled())
+                        .child(tr!(
+                            "STICKERS_NONE_AVAILABLE",
+                            "There are no sticker packs available in this room."
+                        )),
+                )
+            })
             .child(
                 self.visible_emoji.iter().enumerate().fold(
                     div()
                         .id("emoji-selection-area")
+                        .flex_grow()
                         .overflow_y_scroll()
                         .grid()
-                        .grid_cols(10),
+                        .grid_cols(if picking_stickers { 4 } else { 10 }),
                     |david, (i, emoji)| {
-                        let emoji = *emoji;
-                        david.child(button(i).flat().child(emoji.as_str()).on_click(cx.listener(
-                            move |this, _, window, cx| {
-                                if let Some(emoji_selected_listener) = &this.emoji_selected_listener
-                                {
-                                    emoji_selected_listener(
-                                        &EmojiSelectedEvent {
-                                            emoji: emoji.as_str().to_string(),
-                                        },
-                                        window,
-                                        cx,
-                                    );
-                                }
-                                cx.notify()
-                            },
-                        )))
+                        let (content, event): (AnyElement, _) = match emoji {
+                            VisibleEmoji::Unicode(emoji) => (
+                                emoji.as_str().into_any_element(),
+                                EmojiSelectedEvent {
+                                    emoji: emoji.as_str().to_string(),
+                                    image: None,
+                                },
+                            ),
+                            VisibleEmoji::Custom(shortcode, image) => (
+                                mxc_image(image.url.clone())
+                                    .fixed_square(image_size)
+                                    .size_policy(SizePolicy::Fit)
+                                    .into_any_element(),
+                                EmojiSelectedEvent {
+                                    emoji: format!(":{shortcode}:"),
+                                    image: Some(image.clone()),
+                                },
+                            ),
+                        };
+                        david.child(
+                            button(i)
+                                .flat()
+                                .child(content)
+                                .when(event.image.is_some(), |david| {
+                                    david.tooltip(simple_tooltip(event.emoji.clone()))
+                                })
+                                .on_click(cx.listener(move |this, _, window, cx| {
+                                    if let Some(emoji_selected_listener) =
+                                        &this.emoji_selected_listener
+                                    {
+                                        emoji_selected_listener(&event, window, cx);
+                                    }
+                                    cx.notify()
+                                })),
+                        )
                     },
                 ),
             )
             .when(self.search_field.read(cx).text().is_empty(), |david| {
                 david.child(
-                    Group::iter()
-                        .enumerate()
-                        .fold(layer().flex(), |layer, (i, group)| {
-                            layer.child(
+                    layer()
+                        .flex()
+                        .when(!picking_stickers, |david| {
+                            david.children(Group::iter().enumerate().map(|(i, group)| {
                                 button(i)
                                     .flat()
                                     .child(group.emojis().next().unwrap().as_str())
-                                    .checked_when(self.selected_group == group)
+                                    .checked_when(
+                                        self.selected_category == EmojiCategory::Group(group),
+                                    )
                                     .on_click(cx.listener(move |this, _, _, cx| {
-                                        this.selected_group = group;
+                                        this.selected_category = EmojiCategory::Group(group);
                                         this.update_visible_emoji(cx);
                                         cx.notify()
-                                    })),
-                            )
-                        }),
+                                    }))
+                            }))
+                        })
+                        .children(self.image_packs.iter().enumerate().map(|(i, pack)| {
+                            let pack_icon = pack.info.avatar_url.clone().or_else(|| {
+                                pack.images_for(self.usage)
+                                    .next()
+                                    .map(|(_, image)| image.url.clone())
+                            });
+                            button(("image-pack", i))
+                                .flat()
+                                .child(
+                                    mxc_image(pack_icon)
+                                        .fixed_square(px(16.))
+                                        .size_policy(SizePolicy::Fit),
+                                )
+                                .tooltip(simple_tooltip(pack.display_name()))
+                                .checked_when(self.selected_category == EmojiCategory::Pack(i))
+                                .on_click(cx.listener(move |this, _, _, cx| {
+                                    this.selected_category = EmojiCategory::Pack(i);
+                                    this.update_visible_emoji(cx);
+                                    cx.notify()
+                                }))
+                        })),
                 )
             })
     }
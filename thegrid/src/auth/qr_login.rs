@@ -0,0 +1,463 @@
+use cntp_i18n::tr;
+use contemporary::components::button::button;
+use contemporary::components::constrainer::constrainer;
+use contemporary::components::icon_text::icon_text;
+use contemporary::components::layer::layer;
+use contemporary::components::pager::fade_animation::FadeAnimation;
+use contemporary::components::pager::pager;
+use contemporary::components::spinner::spinner;
+use contemporary::components::subtitle::subtitle;
+use contemporary::components::text_field::TextField;
+use contemporary::styling::theme::ThemeStorage;
+use gpui::prelude::FluentBuilder;
+use gpui::{
+    AppContext, AsyncApp, Context, Entity, IntoElement, ParentElement, Render, RenderImage, Styled,
+    WeakEntity, Window, div, img, px,
+};
+use image::{Frame, Rgba};
+use matrix_sdk::authentication::oauth::qrcode::{
+    CheckCodeSender, GeneratedQrProgress, GrantLoginProgress, LoginProgress, QrCodeData,
+};
+use qrcode::QrCode;
+use smallvec::smallvec;
+use smol::future::FutureExt;
+use smol::stream::{Stream, StreamExt};
+use std::future;
+use std::sync::Arc;
+use thegrid_common::tokio_helper::TokioHelper;
+use tracing::error;
+use url::Url;
+
+/// Which side of a QR code login this device is on
+#[derive(Clone, Copy, PartialEq)]
+pub enum QrLoginDirection {
+    /// This device is logging in, and the QR code is scanned by a device that is already logged in
+    Login,
+    /// This device is already logged in, and the QR code is scanned by the new device
+    Grant,
+}
+
+#[derive(Clone)]
+pub enum QrLoginState {
+    Starting,
+    ShowQrCode(Arc<RenderImage>),
+    EnterCheckCode(CheckCodeSender),
+    WaitingForToken(String),
+    WaitingForAuth(Url),
+    SyncingSecrets,
+    Done,
+    Error,
+}
+
+impl QrLoginState {
+    pub fn from_login_progress(progress: LoginProgress<GeneratedQrProgress>) -> Self {
+        match progress {
+            LoginProgress::Starting => QrLoginState::Starting,
+            LoginProgress::EstablishingSecureChannel(progress) => {
+                QrLoginState::from_generated_qr_progress(progress)
+            }
+            LoginProgress::WaitingForToken { user_code } => {
+                QrLoginState::WaitingForToken(user_code)
+            }
+            LoginProgress::SyncingSecrets => QrLoginState::SyncingSecrets,
+            LoginProgress::Done => QrLoginState::Done,
+        }
+    }
+
+    pub fn from_grant_progress(progress: GrantLoginProgress<GeneratedQrProgress>) -> Self {
+        match progress {
+            GrantLoginProgress::Starting => QrLoginState::Starting,
+            GrantLoginProgress::EstablishingSecureChannel(progress) => {
+                QrLoginState::from_generated_qr_progress(progress)
+            }
+            GrantLoginProgress::WaitingForAuth { verification_uri } => {
+                QrLoginState::WaitingForAuth(verification_uri)
+            }
+            GrantLoginProgress::SyncingSecrets => QrLoginState::SyncingSecrets,
+            GrantLoginProgress::Done => QrLoginState::Done,
+        }
+    }
+
+    fn from_generated_qr_progress(progress: GeneratedQrProgress) -> Self {
+        match progress {
+            GeneratedQrProgress::QrReady(qr_code_data) => qr_code_image(&qr_code_data)
+                .map(QrLoginState::ShowQrCode)
+                .unwrap_or(QrLoginState::Error),
+            GeneratedQrProgress::QrScanned(check_code_sender) => {
+                QrLoginState::EnterCheckCode(check_code_sender)
+            }
+        }
+    }
+}
+
+fn qr_code_image(qr_code_data: &QrCodeData) -> Option<Arc<RenderImage>> {
+    let qr_code = QrCode::new(qr_code_data.to_bytes())
+        .inspect_err(|e| error!("Unable to generate login QR code: {e:?}"))
+        .ok()?;
+    let image = qr_code
+        .render::<Rgba<u8>>()
+        .min_dimensions(250, 250)
+        .max_dimensions(300, 300)
+        .build();
+
+    Some(Arc::new(RenderImage::new(smallvec![Frame::new(image)])))
+}
+
+/// Drive a QR code login to completion, forwarding its progress to `progress_tx` as it happens
+pub async fn forward_qr_login_progress<P, T>(
+    progress: impl Stream<Item = P>,
+    login: impl Future<Output = T>,
+    progress_tx: async_channel::Sender<P>,
+) -> T {
+    login
+        .or(async move {
+            let mut progress = std::pin::pin!(progress);
+            while let Some(progress) = progress.next().await {
+                let _ = progress_tx.send(progress).await;
+            }
+            future::pending().await
+        })
+        .await
+}
+
+pub struct QrLoginView {
+    direction: QrLoginDirection,
+    state: QrLoginState,
+    check_code_field: Entity<TextField>,
+}
+
+impl QrLoginView {
+    pub fn new(direction: QrLoginDirection, cx: &mut Context<Self>) -> Self {
+        let check_code_enter_listener = cx.listener(|this: &mut Self, _, window, cx| {
+            this.submit_check_code(window, cx);
+        });
+
+        Self {
+            direction,
+            state: QrLoginState::Starting,
+            check_code_field: cx.new(|cx| {
+                let mut text_field = TextField::new("check-code", cx);
+                text_field.on_enter_press(check_code_enter_listener);
+                text_field.set_placeholder(
+                    tr!("QR_LOGIN_CHECK_CODE_PLACEHOLDER", "Check code")
+                        .to_string()
+                        .as_str(),
+                );
+                text_field
+            }),
+        }
+    }
+
+    pub fn set_state(&mut self, state: QrLoginState, cx: &mut Context<Self>) {
+        if matches!(state, QrLoginState::EnterCheckCode(_)) {
+            self.check_code_field
+                .update(cx, |check_code_field, _| check_code_field.set_text(""));
+        }
+
+        self.state = state;
+        cx.notify();
+    }
+
+    fn submit_check_code(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let QrLoginState::EnterCheckCode(check_code_sender) = &self.state else {
+            return;
+        };
+
+        // The check code is always shown as two digits on the other device
+        let check_code = self.check_code_field.read(cx).text();
+        let Some(check_code) = check_code
+            .trim()
+            .parse::<u8>()
+            .ok()
+            .filter(|check_code| *check_code < 100)
+        else {
+            self.check_code_field.update(cx, |check_code_field, cx| {
+                check_code_field.flash_error(window, cx);
+            });
+            return;
+        };
+
+        let check_code_sender = check_code_sender.clone();
+        cx.spawn(async move |this: WeakEntity<Self>, cx: &mut AsyncApp| {
+            if let Err(e) = cx
+                .spawn_tokio(async move { check_code_sender.send(check_code).await })
+                .await
+            {
+                error!("Unable to send check code: {e:?}");
+                let _ = this.update(cx, |this, cx| this.set_state(QrLoginState::Error, cx));
+            }
+        })
+        .detach();
+
+        self.state = QrLoginState::Starting;
+        cx.notify();
+    }
+}
+
+impl Render for QrLoginView {
+    fn render(&mut self, _: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let theme = cx.theme();
+
+        pager(
+            "qr-login-pager",
+            match &self.state {
+                QrLoginState::Starting => 0,
+                QrLoginState::ShowQrCode(_) => 1,
+                QrLoginState::EnterCheckCode(_) => 2,
+                QrLoginState::WaitingForToken(_) => 3,
+                QrLoginState::WaitingForAuth(_) => 4,
+                QrLoginState::SyncingSecrets => 5,
+                QrLoginState::Done => 6,
+                QrLoginState::Error => 7,
+            },
+        )
+        .animation(FadeAnimation::new())
+        .size_full()
+        .page(
+            div()
+                .flex()
+                .items_center()
+                .justify_center()
+                .size_full()
+                .child(spinner())
+                .into_any_element(),
+        )
+        .page(
+            constrainer("qr-login-show-constrainer")
+                .child(
+                    layer()
+                        .flex()
+                        .flex_col()
+                        .p(px(8.))
+                        .w_full()
+                        .child(subtitle(tr!("QR_LOGIN_SCAN", "Scan QR Code")))
+                        .child(
+                            div()
+                                .flex()
+                                .flex_col()
+                                .gap(px(8.))
+                                .child(match self.direction {
+                                    QrLoginDirection::Login => tr!(
+                                        "QR_LOGIN_SCAN_TEXT",
+                                        "On a device where you're already logged in, open the \
+                                        device settings and choose to link a new device. Then, \
+                                        scan this QR code."
+                                    ),
+                                    QrLoginDirection::Grant => tr!(
+                                        "QR_LOGIN_GRANT_SCAN_TEXT",
+                                        "On the new device, choose to log in with a QR code and \
+                                        scan this QR code."
+                                    ),
+                                })
+                                .when_some(
+                                    match &self.state {
+                                        QrLoginState::ShowQrCode(image) => Some(image.clone()),
+                                        _ => None,
+                                    },
+                                    |david, image| {
+                                        david.child(
+                                            div()
+                                                .flex()
+                                                .justify_center()
+                                                .child(img(image).rounded(theme.border_radius)),
+                                        )
+                                    },
+                                ),
+                        ),
+                )
+                .into_any_element(),
+        )
+        .page(
+            constrainer("qr-login-check-code-constrainer")
+                .child(
+                    layer()
+                        .flex()
+                        .flex_col()
+                        .p(px(8.))
+                        .w_full()
+                        .child(subtitle(tr!("QR_LOGIN_CHECK_CODE", "Enter Check Code")))
+                        .child(
+                            div()
+                                .flex()
+                                .flex_col()
+                                .gap(px(8.))
+                                .child(tr!(
+                                    "QR_LOGIN_CHECK_CODE_TEXT",
+                                    "The other device is now showing a two digit code. Enter \
+                                    it here to make sure that you're connecting to the right \
+                                    device."
+                                ))
+                                .child(self.check_code_field.clone())
+                                .child(
+                                    div().flex().child(div().flex_grow(1.)).child(
+                                        button("qr-login-check-code-ok")
+                                            .child(icon_text(
+                                                "dialog-ok",
+                                                tr!("QR_LOGIN_CHECK_CODE_OK", "Continue"),
+                                            ))
+                                            .on_click(cx.listener(|this, _, window, cx| {
+                                                this.submit_check_code(window, cx)
+                                            })),
+                                    ),
+                                ),
+                        ),
+                )
+                .into_any_element(),
+        )
+        .page(
+            constrainer("qr-login-waiting-for-token-constrainer")
+                .child(
+                    layer()
+                        .flex()
+                        .flex_col()
+                        .p(px(8.))
+                        .w_full()
+                        .child(subtitle(tr!("QR_LOGIN_CONFIRM", "Confirm Login")))
+                        .child(
+                            div()
+                                .flex()
+                                .flex_col()
+                                .gap(px(8.))
+                                .child(tr!(
+                                    "QR_LOGIN_CONFIRM_TEXT",
+                                    "Follow the instructions on the other device to allow this \
+                                    device to log in."
+                                ))
+                                .when_some(
+                                    match &self.state {
+                                        QrLoginState::WaitingForToken(user_code) => {
+                                            Some(user_code.clone())
+                                        }
+                                        _ => None,
+                                    },
+                                    |david, user_code| {
+                                        david.child(tr!(
+                                            "QR_LOGIN_CONFIRM_USER_CODE",
+                                            "If you're asked for a code, enter {{user_code}}.",
+                                            user_code = user_code
+                                        ))
+                                    },
+                                )
+                                .child(
+                                    div()
+                                        .flex()
+                                        .gap(px(8.))
+                                        .items_center()
+                                        .child(spinner().size(px(16.)))
+                                        .child(tr!(
+                                            "QR_LOGIN_WAITING",
+                                            "Waiting for the other device..."
+                                        )),
+                                ),
+                        ),
+                )
+                .into_any_element(),
+        )
+        .page(
+            constrainer("qr-login-waiting-for-auth-constrainer")
+                .child(
+                    layer()
+                        .flex()
+                        .flex_col()
+                        .p(px(8.))
+                        .w_full()
+                        .child(subtitle(tr!("QR_LOGIN_GRANT_CONFIRM", "Allow New Device")))
+                        .child(
+                            div()
+                                .flex()
+                                .flex_col()
+                                .gap(px(8.))
+                                .child(tr!(
+                                    "QR_LOGIN_GRANT_CONFIRM_TEXT",
+                                    "Your homeserver needs you to confirm that the new device \
+                                    is allowed to log in to your account. Continue in your \
+                                    browser, and then come back here once you're done."
+                                ))
+                                .child(
+                                    button("qr-login-continue-in-browser")
+                                        .child(icon_text("arrow-right", tr!("AUTH_OAUTH_BUTTON")))
+                                        .on_click(cx.listener(|this, _, _, cx| {
+                                            let QrLoginState::WaitingForAuth(url) = &this.state
+                                            else {
+                                                return;
+                                            };
+
+                                            cx.open_url(url.as_str())
+                                        })),
+                                ),
+                        ),
+                )
+                .into_any_element(),
+        )
+        .page(
+            div()
+                .flex()
+                .items_center()
+                .justify_center()
+                .size_full()
+                .child(
+                    div()
+                        .flex()
+                        .flex_col()
+                        .items_center()
+                        .gap(px(8.))
+                        .child(spinner())
+                        .child(tr!(
+                            "QR_LOGIN_SYNCING_SECRETS",
+                            "Setting up encryption. This device will be verified automatically."
+                        )),
+                )
+                .into_any_element(),
+        )
+        .page(
+            constrainer("qr-login-done-constrainer")
+                .child(
+                    layer()
+                        .flex()
+                        .flex_col()
+                        .p(px(8.))
+                        .w_full()
+                        .child(subtitle(tr!("QR_LOGIN_DONE", "All done!")))
+                        .child(match self.direction {
+                            QrLoginDirection::Login => tr!(
+                                "QR_LOGIN_DONE_TEXT",
+                                "You're now logged in, and this device has been verified."
+                            ),
+                            QrLoginDirection::Grant => tr!(
+                                "QR_LOGIN_GRANT_DONE_TEXT",
+                                "The new device is now logged in to your account, and has been \
+                                verified."
+                            ),
+                        }),
+                )
+                .into_any_element(),
+        )
+        .page(
+            constrainer("qr-login-error-constrainer")
+                .child(
+                    layer()
+                        .flex()
+                        .flex_col()
+                        .p(px(8.))
+                        .w_full()
+                        .child(subtitle(tr!(
+                            "QR_LOGIN_ERROR",
+                            "Unable to log in with QR code"
+                        )))
+                        .child(match self.direction {
+                            QrLoginDirection::Login => tr!(
+                                "QR_LOGIN_ERROR_TEXT",
+                                "The login was cancelled or couldn't be completed. Go back and \
+                                try again."
+                            ),
+                            QrLoginDirection::Grant => tr!(
+                                "QR_LOGIN_GRANT_ERROR_TEXT",
+                                "The new device couldn't be logged in. Make sure that this \
+                                device is verified, and then try again."
+                            ),
+                        }),
+                )
+                .into_any_element(),
+        )
+    }
+}
@@ -26,6 +26,7 @@ use matrix_sdk::encryption::identities::Device;
 use matrix_sdk::encryption::verification::VerificationRequestState;
 use matrix_sdk::encryption::VerificationState;
 use matrix_sdk::ruma::events::key::verification::cancel::CancelCode;
+use matrix_sdk::ruma::OwnedUserId;
 use matrix_sdk_crypto::{CancelInfo, QrVerificationState};
 use std::rc::Rc;
 use thegrid_common::sas_emoji::SasEmoji;
@@ -61,11 +62,27 @@ impl VerificationPopover {
     }
 
     pub fn trigger_outgoing_verification(&mut self, cx: &mut Context<VerificationPopover>) {
+        let session_manager = cx.global::<SessionManager>();
+        let user_id = session_manager
+            .client()
+            .unwrap()
+            .read(cx)
+            .user_id()
+            .unwrap()
+            .to_owned();
+
+        self.request_user_verification(user_id, cx)
+    }
+
+    pub fn request_user_verification(
+        &mut self,
+        user_id: OwnedUserId,
+        cx: &mut Context<VerificationPopover>,
+    ) {
         self.state = VerificationPopoverState::RequestingVerification;
 
         let session_manager = cx.global::<SessionManager>();
         let client = session_manager.client().unwrap().read(cx).clone();
-        let user_id = client.user_id().unwrap().to_owned();
         let verification_requests = session_manager.verification_requests();
 
         cx.spawn({
@@ -108,8 +125,15 @@ impl VerificationPopover {
                                 VerificationPopoverState::ActiveVerification(verification_request);
                             cx.notify()
                         });
+                        return;
                     }
                 };
+
+                // The user has no cross-signing identity, or the request couldn't be sent
+                let _ = weak_this.update(cx, |this, cx| {
+                    this.state = VerificationPopoverState::Idle;
+                    cx.notify()
+                });
             }
         })
         .detach();
@@ -360,7 +384,7 @@ impl Render for VerificationPopover {
                                             .flex()
                                             .flex_col()
                                             .gap(px(8.))
-                                            .child(if let Some((is_self, peer)) = peer && !is_self {
+                                            .child(if let Some((is_self, peer)) = peer.clone() && !is_self {
                                                 tr!(
                                                     "VERIFICATION_POPOVER_OK_PEER_MESSAGE",
                                                     "Your communication with {{peer}} is now \
@@ -488,25 +512,27 @@ impl Render for VerificationPopover {
                                             .flex()
                                             .flex_col()
                                             .gap(px(8.))
-                                            .when_else(
-                                                verified,
-                                                |david| {
-                                                    david.child(tr!(
-                                                        "VERIFICATION_POPOVER_AWAITING_OK_TEXT",
-                                                        "We sent a verification request to that \
-                                                        device. Go ahead and accept it there to \
-                                                        continue."
-                                                    ))
-                                                },
-                                                |david| {
-                                                    david.child(tr!(
-                                                        "VERIFICATION_POPOVER_AWAITING_OK_US_TEXT",
-                                                        "We sent a verification request to all \
-                                                        of your verified other devices. Go ahead \
-                                                        and accept it on one of them to continue."
-                                                    ))
-                                                },
-                                            )
+                                            .child(match peer {
+                                                Some((false, peer)) => tr!(
+                                                    "VERIFICATION_POPOVER_AWAITING_OK_PEER_TEXT",
+                                                    "We sent a verification request to \
+                                                    {{peer}}. Once they accept it, you'll be able \
+                                                    to continue.",
+                                                    peer:quote = peer
+                                                ),
+                                                _ if verified => tr!(
+                                                    "VERIFICATION_POPOVER_AWAITING_OK_TEXT",
+                                                    "We sent a verification request to that \
+                                                    device. Go ahead and accept it there to \
+                                                    continue."
+                                                ),
+                                                _ => tr!(
+                                                    "VERIFICATION_POPOVER_AWAITING_OK_US_TEXT",
+                                                    "We sent a verification request to all \
+                                                    of your verified other devices. Go ahead \
+                                                    and accept it on one of them to continue."
+                                                ),
+                                            })
                                             .child(
                                                 div()
                                                     .flex()
@@ -1,5 +1,5 @@
 pub mod chat_input;
-mod chat_room;
+pub mod chat_room;
 pub mod chat_surface;
 mod displayed_room;
 mod join_room;
@@ -4,21 +4,23 @@ use crate::chat::chat_input::autocomplete::{
     ApplyAutcompleteEvent, autocomplete_list, calculate_autocomplete,
 };
 use crate::chat::chat_room::open_room::OpenRoom;
+use crate::chat::chat_room::slash_commands::SlashCommand;
 use contemporary::components::anchorer::WithAnchorer;
 use contemporary::styling::theme::{Theme, ThemeStorage, VariableColor};
 use gpui::prelude::FluentBuilder;
 use gpui::{
     App, AppContext, Bounds, ClipboardItem, Context, Element, ElementId, ElementInputHandler,
-    Entity, EntityInputHandler, FocusHandle, Focusable, GlobalElementId, Hsla, InspectorElementId,
-    InteractiveElement, IntoElement, KeyBinding, LayoutId, MouseButton, MouseDownEvent,
-    MouseMoveEvent, MouseUpEvent, PaintQuad, ParentElement, Pixels, Point, Render, Rgba,
-    ShapedLine, Style, Styled, TextAlign, TextRun, UTF16Selection, UnderlineStyle, WeakEntity,
-    Window, actions, div, fill, point, px, relative, size,
+    Entity, EntityInputHandler, FocusHandle, Focusable, GlobalElementId, HighlightStyle, Hsla,
+    InspectorElementId, InteractiveElement, IntoElement, KeyBinding, LayoutId, MouseButton,
+    MouseDownEvent, MouseMoveEvent, MouseUpEvent, PaintQuad, ParentElement, Pixels, Point, Render,
+    Rgba, ShapedLine, Style, Styled, TextAlign, TextRun, UTF16Selection, UnderlineStyle,
+    WeakEntity, Window, actions, div, fill, point, px, relative, size,
 };
 use matrix_sdk::ruma::{OwnedMxcUri, OwnedUserId};
 use std::ops::{Add, Range};
 use std::panic::Location;
 use std::rc::Rc;
+use thegrid_text_rendering::markdown_source_highlights;
 use unicode_segmentation::UnicodeSegmentation;
 
 actions!(
@@ -43,6 +45,14 @@ actions!(
         Cut,
         Copy,
         Quit,
+        Bold,
+        Italic,
+        Strikethrough,
+        Code,
+        Link,
+        BulletList,
+        NumberedList,
+        Quote,
     ]
 );
 
@@ -66,6 +76,14 @@ pub fn bind_chat_input_keys(cx: &mut App) {
         KeyBinding::new("home", Home, None),
         KeyBinding::new("end", End, None),
         KeyBinding::new("ctrl-cmd-space", ShowCharacterPalette, None),
+        KeyBinding::new("secondary-b", Bold, Some("ChatInput")),
+        KeyBinding::new("secondary-i", Italic, Some("ChatInput")),
+        KeyBinding::new("secondary-shift-x", Strikethrough, Some("ChatInput")),
+        KeyBinding::new("secondary-e", Code, Some("ChatInput")),
+        KeyBinding::new("secondary-k", Link, Some("ChatInput")),
+        KeyBinding::new("secondary-shift-8", BulletList, Some("ChatInput")),
+        KeyBinding::new("secondary-shift-7", NumberedList, Some("ChatInput")),
+        KeyBinding::new("secondary-shift-9", Quote, Some("ChatInput")),
     ]);
 }
 
@@ -85,6 +103,19 @@ pub struct PasteRichEvent {
     pub clipboard_item: ClipboardItem,
 }
 
+/// Markdown formatting that can be applied to the text in a [`ChatInput`].
+#[derive(Clone, Copy, PartialEq)]
+pub enum TextFormat {
+    Bold,
+    Italic,
+    Strikethrough,
+    Code,
+    Link,
+    BulletList,
+    NumberedList,
+    Quote,
+}
+
 #[derive(Clone)]
 pub enum AutocompleteState {
     Idle,
@@ -102,11 +133,18 @@ pub enum AutocompleteOption {
         name: String,
         emoji: String,
     },
+    CustomEmoji {
+        name: String,
+        url: OwnedMxcUri,
+    },
     User {
         user_id: OwnedUserId,
         avatar_url: Option<OwnedMxcUri>,
         display_name: String,
     },
+    Command {
+        command: SlashCommand,
+    },
 }
 
 pub struct ChatInput {
@@ -477,6 +515,162 @@ impl ChatInput {
         }
     }
 
+    pub fn bold(&mut self, _: &Bold, window: &mut Window, cx: &mut Context<Self>) {
+        self.apply_format(TextFormat::Bold, window, cx);
+    }
+
+    pub fn italic(&mut self, _: &Italic, window: &mut Window, cx: &mut Context<Self>) {
+        self.apply_format(TextFormat::Italic, window, cx);
+    }
+
+    pub fn strikethrough(
+        &mut self,
+        _: &Strikethrough,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        self.apply_format(TextFormat::Strikethrough, window, cx);
+    }
+
+    pub fn code(&mut self, _: &Code, window: &mut Window, cx: &mut Context<Self>) {
+        self.apply_format(TextFormat::Code, window, cx);
+    }
+
+    pub fn link(&mut self, _: &Link, window: &mut Window, cx: &mut Context<Self>) {
+        self.apply_format(TextFormat::Link, window, cx);
+    }
+
+    pub fn bullet_list(&mut self, _: &BulletList, window: &mut Window, cx: &mut Context<Self>) {
+        self.apply_format(TextFormat::BulletList, window, cx);
+    }
+
+    pub fn numbered_list(&mut self, _: &NumberedList, window: &mut Window, cx: &mut Context<Self>) {
+        self.apply_format(TextFormat::NumberedList, window, cx);
+    }
+
+    pub fn quote(&mut self, _: &Quote, window: &mut Window, cx: &mut Context<Self>) {
+        self.apply_format(TextFormat::Quote, window, cx);
+    }
+
+    /// Apply Markdown formatting to the selected text, or remove it if it is already applied.
+    pub fn apply_format(
+        &mut self,
+        format: TextFormat,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        match format {
+            TextFormat::Bold => self.toggle_inline_format("**", window, cx),
+            TextFormat::Italic => self.toggle_inline_format("_", window, cx),
+            TextFormat::Strikethrough => self.toggle_inline_format("~~", window, cx),
+            TextFormat::Code => self.toggle_inline_format("`", window, cx),
+            TextFormat::Link => self.insert_link(window, cx),
+            TextFormat::BulletList => self.toggle_line_format(|_| "- ".into(), window, cx),
+            TextFormat::NumberedList => {
+                self.toggle_line_format(|ix| format!("{}. ", ix + 1), window, cx)
+            }
+            TextFormat::Quote => self.toggle_line_format(|_| "> ".into(), window, cx),
+        }
+        self.focus_handle.focus(window, cx);
+    }
+
+    /// Replace a range of the text, then select a range relative to the start of the new text.
+    fn replace_and_select(
+        &mut self,
+        range: Range<usize>,
+        new_text: &str,
+        selection: Range<usize>,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let start = range.start;
+        self.replace_text_in_range(Some(self.range_to_utf16(&range)), new_text, window, cx);
+        self.selected_range = start + selection.start..start + selection.end;
+        self.selection_reversed = false;
+        cx.notify();
+    }
+
+    fn toggle_inline_format(
+        &mut self,
+        delimiter: &str,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let range = self.selected_range.clone();
+        let selected = self.text[range.clone()].to_string();
+
+        if self.text[..range.start].ends_with(delimiter)
+            && self.text[range.end..].starts_with(delimiter)
+        {
+            let outer = range.start - delimiter.len()..range.end + delimiter.len();
+            self.replace_and_select(outer, &selected, 0..selected.len(), window, cx);
+        } else {
+            let new_text = format!("{delimiter}{selected}{delimiter}");
+            let inner = delimiter.len()..delimiter.len() + selected.len();
+            self.replace_and_select(range, &new_text, inner, window, cx);
+        }
+    }
+
+    /// Add a prefix to each line of the selection, or remove them if every line already has one.
+    ///
+    /// `prefix` is called with the index of each line within the selection.
+    fn toggle_line_format(
+        &mut self,
+        prefix: impl Fn(usize) -> String,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let line_start = self.text[..self.selected_range.start]
+            .rfind('\n')
+            .map_or(0, |ix| ix + 1);
+        let line_end = self.text[self.selected_range.end..]
+            .find('\n')
+            .map_or(self.text.len(), |ix| self.selected_range.end + ix);
+
+        let lines: Vec<(String, &str)> = self.text[line_start..line_end]
+            .split('\n')
+            .enumerate()
+            .map(|(ix, line)| (prefix(ix), line))
+            .collect();
+        let formatted = lines
+            .iter()
+            .all(|(prefix, line)| line.starts_with(prefix.as_str()));
+        let new_text = lines
+            .iter()
+            .map(|(prefix, line)| {
+                if formatted {
+                    line[prefix.len()..].to_string()
+                } else {
+                    format!("{prefix}{line}")
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let selection = if self.selected_range.is_empty() {
+            new_text.len()..new_text.len()
+        } else {
+            0..new_text.len()
+        };
+        self.replace_and_select(line_start..line_end, &new_text, selection, window, cx);
+    }
+
+    fn insert_link(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let range = self.selected_range.clone();
+        let selected = self.text[range.clone()].to_string();
+
+        if selected.starts_with("https://") || selected.starts_with("http://") {
+            // Select the text of the link so that it can be typed over
+            let new_text = format!("[{selected}]({selected})");
+            self.replace_and_select(range, &new_text, 1..1 + selected.len(), window, cx);
+        } else {
+            // Leave the cursor where the URL goes
+            let new_text = format!("[{selected}]()");
+            let url = new_text.len() - 1;
+            self.replace_and_select(range, &new_text, url..url, window, cx);
+        }
+    }
+
     pub fn update_autocomplete_state(&mut self, cx: &mut Context<Self>) {
         self.autocomplete_epoch += 1;
         let epoch = self.autocomplete_epoch;
@@ -496,6 +690,9 @@ impl ChatInput {
             AutocompleteOption::Emoji { emoji, .. } => {
                 self.replace_text_in_range(Some(replace_range), &format!("{emoji} "), window, cx);
             }
+            AutocompleteOption::CustomEmoji { name, .. } => {
+                self.replace_text_in_range(Some(replace_range), &format!("{name} "), window, cx);
+            }
             AutocompleteOption::User { user_id, .. } => {
                 self.replace_text_in_range(
                     Some(replace_range),
@@ -504,6 +701,14 @@ impl ChatInput {
                     cx,
                 );
             }
+            AutocompleteOption::Command { command } => {
+                self.replace_text_in_range(
+                    Some(replace_range),
+                    &format!("/{} ", command.name()),
+                    window,
+                    cx,
+                );
+            }
         }
     }
 }
@@ -549,6 +754,14 @@ impl Render for ChatInput {
             .on_action(cx.listener(Self::paste))
             .on_action(cx.listener(Self::cut))
             .on_action(cx.listener(Self::copy))
+            .on_action(cx.listener(Self::bold))
+            .on_action(cx.listener(Self::italic))
+            .on_action(cx.listener(Self::strikethrough))
+            .on_action(cx.listener(Self::code))
+            .on_action(cx.listener(Self::link))
+            .on_action(cx.listener(Self::bullet_list))
+            .on_action(cx.listener(Self::numbered_list))
+            .on_action(cx.listener(Self::quote))
             .on_mouse_down(MouseButton::Left, cx.listener(Self::on_mouse_down))
             .on_mouse_up(MouseButton::Left, cx.listener(Self::on_mouse_up))
             .on_mouse_up_out(MouseButton::Left, cx.listener(Self::on_mouse_up))
@@ -738,6 +951,7 @@ pub struct ChatElement {
 pub struct RequestLayoutState {
     lines: Vec<String>,
     text_color: Hsla,
+    highlights: Vec<(Range<usize>, HighlightStyle)>,
 }
 
 pub struct PrepaintState {
@@ -778,13 +992,18 @@ impl Element for ChatElement {
         let style = window.text_style();
         let theme = cx.global::<Theme>();
 
-        let (display_text, text_color) = if content.is_empty() {
+        let (display_text, text_color, highlights) = if content.is_empty() {
             (
                 input.placeholder.clone(),
                 Hsla::from(theme.foreground.disabled()),
+                vec![],
             )
         } else {
-            (content.clone(), style.color)
+            (
+                content.clone(),
+                style.color,
+                markdown_source_highlights(&content, cx),
+            )
         };
 
         let lines = split_string_lines(&display_text)
@@ -796,7 +1015,11 @@ impl Element for ChatElement {
         style.size.height = (window.line_height() * lines.len()).into();
         (
             window.request_layout(style, [], cx),
-            RequestLayoutState { lines, text_color },
+            RequestLayoutState {
+                lines,
+                text_color,
+                highlights,
+            },
         )
     }
 
@@ -816,6 +1039,7 @@ impl Element for ChatElement {
         let theme = cx.global::<Theme>();
         let line_height = window.line_height();
 
+        let mut line_start = 0;
         let lines = request_layout
             .lines
             .iter()
@@ -828,32 +1052,14 @@ impl Element for ChatElement {
                     underline: None,
                     strikethrough: None,
                 };
-                let runs = if let Some(marked_range) = input.marked_range.as_ref() {
-                    vec![
-                        TextRun {
-                            len: marked_range.start,
-                            ..run.clone()
-                        },
-                        TextRun {
-                            len: marked_range.end - marked_range.start,
-                            underline: Some(UnderlineStyle {
-                                color: Some(run.color),
-                                thickness: px(1.0),
-                                wavy: false,
-                            }),
-                            ..run.clone()
-                        },
-                        TextRun {
-                            len: text.len() - marked_range.end,
-                            ..run.clone()
-                        },
-                    ]
-                    .into_iter()
-                    .filter(|run| run.len > 0)
-                    .collect()
-                } else {
-                    vec![run]
-                };
+                let line_range = line_start..line_start + text.len();
+                line_start = line_range.end + 1;
+                let runs = line_runs(
+                    line_range,
+                    run,
+                    &request_layout.highlights,
+                    input.marked_range.as_ref(),
+                );
 
                 let font_size = style.font_size.to_pixels(window.rem_size());
                 window
@@ -995,6 +1201,79 @@ impl Focusable for ChatInput {
     }
 }
 
+/// Split a line of text into runs, styling the Markdown in it and underlining marked text.
+///
+/// `line_range` is the range of the line within the whole text, which is what the ranges of the
+/// highlights and marked text are relative to.
+fn line_runs(
+    line_range: Range<usize>,
+    base_run: TextRun,
+    highlights: &[(Range<usize>, HighlightStyle)],
+    marked_range: Option<&Range<usize>>,
+) -> Vec<TextRun> {
+    if line_range.is_empty() {
+        return vec![base_run];
+    }
+
+    let mut boundaries = vec![line_range.start, line_range.end];
+    for range in highlights
+        .iter()
+        .map(|(range, _)| range)
+        .chain(marked_range)
+    {
+        boundaries.extend(
+            [range.start, range.end]
+                .into_iter()
+                .filter(|offset| line_range.contains(offset)),
+        );
+    }
+    boundaries.sort_unstable();
+    boundaries.dedup();
+
+    boundaries
+        .windows(2)
+        .map(|segment| {
+            let segment = segment[0]..segment[1];
+            let mut run = TextRun {
+                len: segment.len(),
+                ..base_run.clone()
+            };
+            let covers =
+                |range: &Range<usize>| range.start <= segment.start && segment.end <= range.end;
+
+            for (_, highlight) in highlights.iter().filter(|(range, _)| covers(range)) {
+                if let Some(color) = highlight.color {
+                    run.color = color;
+                }
+                if let Some(font_weight) = highlight.font_weight {
+                    run.font.weight = font_weight;
+                }
+                if let Some(font_style) = highlight.font_style {
+                    run.font.style = font_style;
+                }
+                if let Some(background_color) = highlight.background_color {
+                    run.background_color = Some(background_color);
+                }
+                if let Some(underline) = highlight.underline {
+                    run.underline = Some(underline);
+                }
+                if let Some(strikethrough) = highlight.strikethrough {
+                    run.strikethrough = Some(strikethrough);
+                }
+            }
+
+            if marked_range.is_some_and(covers) {
+                run.underline = Some(UnderlineStyle {
+                    color: Some(run.color),
+                    thickness: px(1.0),
+                    wavy: false,
+                });
+            }
+            run
+        })
+        .collect()
+}
+
 fn split_string_lines(string: &str) -> impl Iterator<Item = &str> {
     string.split('\n').map(|line| {
         let Some(line) = line.strip_suffix('\r') else {
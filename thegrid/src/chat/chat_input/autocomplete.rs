@@ -1,5 +1,7 @@
 use crate::chat::chat_input::{AutocompleteOption, AutocompleteState, ChatInput};
 use crate::chat::chat_room::open_room::OpenRoom;
+use crate::chat::chat_room::slash_commands::SlashCommand;
+use contemporary::components::icon::icon;
 use contemporary::components::layer::layer;
 use contemporary::styling::theme::{ThemeStorage, VariableColor};
 use gpui::prelude::FluentBuilder;
@@ -9,10 +11,13 @@ use gpui::{
     Window, div, px, uniform_list,
 };
 use matrix_sdk::RoomMemberships;
+use matrix_sdk::ruma::OwnedMxcUri;
 use std::cmp::Ordering;
+use std::collections::BTreeMap;
 use std::ops::Range;
 use std::rc::Rc;
 use thegrid_common::mxc_image::{SizePolicy, mxc_image};
+use thegrid_common::session::session_manager::SessionManager;
 use thegrid_common::tokio_helper::TokioHelper;
 
 pub struct ApplyAutcompleteEvent {
@@ -94,6 +99,19 @@ impl RenderOnce for AutocompleteList {
                                                 .child(emoji),
                                         )
                                         .child(name),
+                                    AutocompleteOption::CustomEmoji { name, url } => div()
+                                        .id(i)
+                                        .flex()
+                                        .items_center()
+                                        .w_full()
+                                        .p(px(2.))
+                                        .gap(px(8.))
+                                        .child(
+                                            mxc_image(url)
+                                                .fixed_square(px(32.))
+                                                .size_policy(SizePolicy::Fit),
+                                        )
+                                        .child(name),
                                     AutocompleteOption::User {
                                         user_id,
                                         avatar_url,
@@ -119,6 +137,38 @@ impl RenderOnce for AutocompleteList {
                                                     .child(user_id.to_string()),
                                             ),
                                         ),
+                                    AutocompleteOption::Command { command } => div()
+                                        .id(i)
+                                        .flex()
+                                        .items_center()
+                                        .w_full()
+                                        .p(px(2.))
+                                        .gap(px(8.))
+                                        .child(
+                                            div()
+                                                .flex()
+                                                .items_center()
+                                                .justify_center()
+                                                .size(px(32.))
+                                                .child(icon("utilities-terminal")),
+                                        )
+                                        .child(
+                                            div()
+                                                .flex()
+                                                .flex_col()
+                                                .child(match command.arguments() {
+                                                    Some(arguments) => format!(
+                                                        "/{} {arguments}",
+                                                        command.name()
+                                                    ),
+                                                    None => format!("/{}", command.name()),
+                                                })
+                                                .child(
+                                                    div()
+                                                        .text_color(theme.foreground.disabled())
+                                                        .child(command.description()),
+                                                ),
+                                        ),
                                 }
                                 .when(i == current_option, |david| {
                                     david.bg(theme.layer_background)
@@ -158,10 +208,13 @@ pub fn calculate_autocomplete(
         return;
     }
 
-    if words.len() == 1 {
+    if words.len() == 1 && !typed.ends_with(char::is_whitespace) {
         // Could be a command
         let first_word = words.first().unwrap();
-        if first_word.starts_with('/') {
+        if first_word.starts_with('/') && typed.starts_with('/') {
+            let start = first_word.as_ptr() as usize - typed.as_ptr() as usize;
+            chat_input.autocomplete_state =
+                calculate_command_autocomplete(first_word, start..start + first_word.len());
             return;
         }
     }
@@ -174,9 +227,19 @@ pub fn calculate_autocomplete(
     if last_word.starts_with(':') && !last_word.ends_with(':') {
         chat_input.autocomplete_state = AutocompleteState::Loading;
 
+        let emoticons = room
+            .read_with(cx, |room, cx| {
+                cx.global::<SessionManager>()
+                    .image_packs()
+                    .read(cx)
+                    .emoticons_for_room(&room.room_id)
+            })
+            .unwrap_or_default();
+
         cx.spawn(
             async move |weak_chat_input: WeakEntity<ChatInput>, cx: &mut AsyncApp| {
-                let state = calculate_emoji_autocomplete(last_word, last_word_range).await;
+                let state =
+                    calculate_emoji_autocomplete(last_word, emoticons, last_word_range).await;
                 let _ = weak_chat_input.update(cx, |chat_input, cx| {
                     if chat_input.autocomplete_epoch == epoch {
                         chat_input.autocomplete_state = state;
@@ -209,10 +272,45 @@ pub fn calculate_autocomplete(
     chat_input.autocomplete_state = AutocompleteState::Idle;
 }
 
-pub async fn calculate_emoji_autocomplete(typed: String, range: Range<usize>) -> AutocompleteState {
+pub fn calculate_command_autocomplete(typed: &str, range: Range<usize>) -> AutocompleteState {
+    let command_name = typed.trim_start_matches('/').to_lowercase();
+    if typed.starts_with("//") {
+        return AutocompleteState::Idle;
+    }
+
+    let options: Vec<_> = SlashCommand::ALL
+        .into_iter()
+        .filter(|command| command.name().starts_with(&command_name))
+        .map(|command| AutocompleteOption::Command { command })
+        .collect();
+
+    if options.is_empty() {
+        AutocompleteState::Idle
+    } else {
+        AutocompleteState::Available {
+            options,
+            replace_range: range,
+            current_option: 0,
+        }
+    }
+}
+
+pub async fn calculate_emoji_autocomplete(
+    typed: String,
+    emoticons: BTreeMap<String, OwnedMxcUri>,
+    range: Range<usize>,
+) -> AutocompleteState {
     let emoji_name = typed.trim_start_matches(":").to_lowercase();
-    let options: Vec<_> = emojis::iter()
-        .flat_map(|emoji| {
+
+    // Custom emoticons from image packs come first, since they were chosen by the user or room
+    let options: Vec<_> = emoticons
+        .into_iter()
+        .filter(|(shortcode, _)| shortcode.to_lowercase().starts_with(&emoji_name))
+        .map(|(shortcode, url)| AutocompleteOption::CustomEmoji {
+            name: format!(":{}:", shortcode),
+            url,
+        })
+        .chain(emojis::iter().flat_map(|emoji| {
             emoji
                 .shortcodes()
                 .filter(|shortcode| shortcode.starts_with(&emoji_name))
@@ -220,7 +318,7 @@ pub async fn calculate_emoji_autocomplete(typed: String, range: Range<usize>) ->
                     name: format!(":{}:", shortcode),
                     emoji: emoji.to_string(),
                 })
-        })
+        }))
         .collect();
 
     if options.is_empty() {
@@ -1,44 +1,60 @@
 mod attachments_view;
 mod call_members_view;
 mod chat_bar;
+mod create_poll_popover;
+mod forward_popover;
 pub mod invite_popover;
+pub mod media_viewer;
 pub mod open_room;
+mod room_media;
 mod room_members;
 mod room_settings;
 mod room_timeline_content;
+pub mod search_flyout;
+pub mod slash_commands;
 mod space_lobby_content;
+mod thread_panel;
 mod timeline;
 mod timeline_view;
 mod user_action_dialogs;
 
-use crate::chat::chat_room::open_room::{OpenRoom, OpenRoomFocus, OpenRoomFocusReason};
+use crate::chat::chat_room::open_room::{OpenRoom, OpenRoomFocus, ThreadPanelContent};
+use crate::chat::chat_room::room_media::RoomMedia;
 use crate::chat::chat_room::room_members::RoomMembers;
 use crate::chat::chat_room::room_settings::RoomSettings;
 use crate::chat::chat_room::room_timeline_content::RoomTimelineContent;
+use crate::chat::chat_room::search_flyout::{
+    SearchFlyout, SearchResultSelectedEvent, SearchResultSelectedListener,
+};
+use crate::chat::chat_room::slash_commands::SlashCommandEvent;
 use crate::chat::chat_room::space_lobby_content::SpaceLobbyContent;
 use crate::chat::chat_room::user_action_dialogs::UserActionDialogs;
 use crate::chat::displayed_room::DisplayedRoom;
 use crate::chat::join_room::create_room_popover::CreateRoomPopover;
 use crate::chat::join_room::create_space_popover::CreateSpacePopover;
 use cntp_i18n::tr;
+use contemporary::components::anchorer::WithAnchorer;
 use contemporary::components::button::button;
 use contemporary::components::dialog_box::{StandardButton, dialog_box};
+use contemporary::components::flyout::flyout;
 use contemporary::components::grandstand::grandstand;
 use contemporary::components::icon::icon;
 use contemporary::components::icon_text::icon_text;
 use contemporary::components::pager::lift_animation::LiftAnimation;
 use contemporary::components::pager::pager;
 use contemporary::components::spinner::spinner;
+use contemporary::components::tooltip::simple_tooltip;
+use contemporary::styling::theme::{Theme, VariableColor};
 use gpui::prelude::FluentBuilder;
 use gpui::{
-    AnimationExt, App, AppContext, BorrowAppContext, Context, Entity, InteractiveElement,
-    IntoElement, ParentElement, Render, StatefulInteractiveElement, Styled, VisualContext, Window,
-    div, px,
+    AnimationExt, App, AppContext, BorrowAppContext, ClipboardItem, Context, Entity,
+    InteractiveElement, IntoElement, ParentElement, Render, StatefulInteractiveElement, Styled,
+    VisualContext, Window, div, px,
 };
-use matrix_sdk::ruma::OwnedRoomId;
-use matrix_sdk_ui::timeline::TimelineFocus;
+use matrix_sdk::ruma::{OwnedEventId, OwnedRoomId};
 use smol::stream::StreamExt;
 use std::rc::Rc;
+use thegrid_common::session::identities_cache::IdentityState;
 use thegrid_common::session::session_manager::SessionManager;
 use thegrid_common::surfaces::{
     MainWindowSurface, SurfaceChange, SurfaceChangeEvent, SurfaceChangeHandler,
@@ -50,19 +66,25 @@ pub struct ChatRoom {
     open_room: Entity<OpenRoom>,
     room_settings: Entity<RoomSettings>,
     room_members: Entity<RoomMembers>,
+    room_media: Entity<RoomMedia>,
     user_action_dialogs: Entity<UserActionDialogs>,
+    search_flyout: Entity<SearchFlyout>,
+    search_visible: bool,
     current_page: ChatRoomPage,
     view: ChatRoomView,
 
     on_surface_change: Rc<Box<SurfaceChangeHandler>>,
+    on_other_room_search_result: Option<Rc<Box<SearchResultSelectedListener>>>,
 
     microphone_access_dialog: bool,
+    devtools_dialog: bool,
 }
 
 enum ChatRoomPage {
     Chat,
     Settings,
     Members,
+    Media,
 }
 
 #[derive(Clone)]
@@ -73,8 +95,13 @@ enum ChatRoomView {
 }
 
 impl ChatRoom {
+    /// Create the view for a room.
+    ///
+    /// When `search_result` is set, the room opens at that message rather than at the latest
+    /// messages.
     pub fn new(
         room_id: OwnedRoomId,
+        search_result: Option<OwnedEventId>,
         displayed_room: Entity<DisplayedRoom>,
         create_room_popover: Entity<CreateRoomPopover>,
         create_space_popover: Entity<CreateSpacePopover>,
@@ -82,9 +109,29 @@ impl ChatRoom {
         cx: &mut App,
     ) -> Entity<Self> {
         cx.new(|cx| {
-            let open_room = cx.new(|cx| OpenRoom::new(room_id.clone(), displayed_room.clone(), cx));
+            let slash_command_listener = cx.listener(Self::slash_command);
+            let open_room = cx.new(|cx| {
+                let mut open_room = match search_result {
+                    Some(event_id) => OpenRoom::new_at_search_result(
+                        room_id.clone(),
+                        displayed_room.clone(),
+                        event_id,
+                        cx,
+                    ),
+                    None => OpenRoom::new(room_id.clone(), displayed_room.clone(), cx),
+                };
+                open_room.on_slash_command(slash_command_listener);
+                open_room
+            });
             let user_action_dialogs = cx.new(|cx| UserActionDialogs::new(room_id.clone(), cx));
 
+            let search_result_selected_listener = cx.listener(Self::search_result_selected);
+            let search_flyout = cx.new(|cx| {
+                let mut search_flyout = SearchFlyout::new(open_room.clone(), cx);
+                search_flyout.set_result_selected_listener(search_result_selected_listener);
+                search_flyout
+            });
+
             let settings_back_click = cx.listener(|this: &mut ChatRoom, _, _, cx| {
                 this.current_page = ChatRoomPage::Chat;
                 cx.notify();
@@ -93,8 +140,20 @@ impl ChatRoom {
                 this.current_page = ChatRoomPage::Members;
                 cx.notify();
             });
+            let media_click = cx.listener(|this: &mut ChatRoom, _, _, cx| {
+                this.current_page = ChatRoomPage::Media;
+                this.room_media
+                    .update(cx, |room_media, cx| room_media.load(cx));
+                cx.notify();
+            });
             let room_settings = cx.new(|cx| {
-                RoomSettings::new(open_room.clone(), settings_back_click, members_click, cx)
+                RoomSettings::new(
+                    open_room.clone(),
+                    settings_back_click,
+                    members_click,
+                    media_click,
+                    cx,
+                )
             });
 
             let members_back_click = cx.listener(|this: &mut ChatRoom, _, _, cx| {
@@ -113,6 +172,12 @@ impl ChatRoom {
                 )
             });
 
+            let media_back_click = cx.listener(|this: &mut ChatRoom, _, _, cx| {
+                this.current_page = ChatRoomPage::Settings;
+                cx.notify();
+            });
+            let room_media = cx.new(|cx| RoomMedia::new(open_room.clone(), media_back_click, cx));
+
             cx.observe(&open_room, {
                 let displayed_room = displayed_room.clone();
                 let on_surface_change = on_surface_change.clone();
@@ -160,13 +225,18 @@ impl ChatRoom {
             Self {
                 open_room,
                 user_action_dialogs,
+                search_flyout,
+                search_visible: false,
                 room_settings,
                 room_members,
+                room_media,
                 current_page: ChatRoomPage::Chat,
                 on_surface_change,
+                on_other_room_search_result: None,
                 view: ChatRoomView::Loading,
 
                 microphone_access_dialog: false,
+                devtools_dialog: false,
             }
         })
     }
@@ -192,12 +262,65 @@ impl ChatRoom {
                     UserAction::Unban => {
                         user_action_dialogs.open_unban_dialog(user_action.user.clone());
                     }
+                    UserAction::Verify => {
+                        let user_id = user_action.user.user_id().to_owned();
+                        let verification_popover =
+                            self.open_room.read(cx).verification_popover.clone();
+                        verification_popover.update(cx, |verification_popover, cx| {
+                            verification_popover.request_user_verification(user_id, cx)
+                        });
+                    }
                 }
 
                 cx.notify()
             })
     }
 
+    fn slash_command(
+        &mut self,
+        event: &SlashCommandEvent,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        match event {
+            SlashCommandEvent::UserAction(user_action) => {
+                self.trigger_user_action(user_action, window, cx)
+            }
+            SlashCommandEvent::OpenDevtools => {
+                self.devtools_dialog = true;
+                cx.notify()
+            }
+        }
+    }
+
+    /// Set the listener for search results picked from other rooms, which this room can't show
+    pub fn on_other_room_search_result(
+        &mut self,
+        listener: impl Fn(&SearchResultSelectedEvent, &mut Window, &mut App) + 'static,
+    ) {
+        self.on_other_room_search_result = Some(Rc::new(Box::new(listener)));
+    }
+
+    fn search_result_selected(
+        &mut self,
+        event: &SearchResultSelectedEvent,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        self.search_visible = false;
+        if event.room_id != self.open_room.read(cx).room_id {
+            if let Some(on_other_room_search_result) = &self.on_other_room_search_result {
+                on_other_room_search_result(event, window, cx);
+            }
+            return;
+        }
+
+        self.open_room.update(cx, |open_room, cx| {
+            open_room.focus_timeline(OpenRoomFocus::search_result(event.event_id.clone()), cx);
+        });
+        cx.notify()
+    }
+
     fn start_call(&mut self, window: &mut Window, cx: &mut Context<Self>) {
         let room_id = self.open_room.read(cx).room_id.clone();
         (self.on_surface_change)(
@@ -216,6 +339,32 @@ impl Render for ChatRoom {
         if session_manager.client().is_none() {
             return div();
         };
+        let identities = session_manager.identities();
+
+        // Offer to verify the other user from their DM room
+        let dm_verification_target = self
+            .open_room
+            .read(cx)
+            .room
+            .as_ref()
+            .map(|room| room.direct_targets())
+            .filter(|direct_targets| direct_targets.len() == 1)
+            .and_then(|direct_targets| {
+                direct_targets
+                    .iter()
+                    .next()
+                    .and_then(|direct_target| direct_target.as_user_id())
+                    .map(|user_id| user_id.to_owned())
+            })
+            .filter(|user_id| {
+                let identity_state = identities.update(cx, |identities, cx| {
+                    identities.identity_state(user_id, cx)
+                });
+                matches!(
+                    identity_state,
+                    Some(IdentityState::Unverified | IdentityState::VerificationViolation)
+                )
+            });
 
         let open_room = self.open_room.read(cx);
 
@@ -227,6 +376,31 @@ impl Render for ChatRoom {
             );
         };
 
+        let theme = cx.global::<Theme>();
+        let room_id = room.room_id().to_string();
+        let devtools_rows = [
+            (tr!("DEVTOOLS_ROOM_ID", "Room ID"), room_id.clone()),
+            (
+                tr!("DEVTOOLS_ROOM_VERSION", "Room Version"),
+                room.version()
+                    .map(|version| version.to_string())
+                    .unwrap_or_else(|| tr!("DEVTOOLS_UNKNOWN", "Unknown").to_string()),
+            ),
+            (
+                tr!("DEVTOOLS_ENCRYPTED", "Encrypted"),
+                if room.encryption_state().is_encrypted() {
+                    tr!("DEVTOOLS_YES", "Yes")
+                } else {
+                    tr!("DEVTOOLS_NO", "No")
+                }
+                .to_string(),
+            ),
+            (
+                tr!("DEVTOOLS_JOINED_MEMBERS", "Joined Members"),
+                room.joined_members_count().to_string(),
+            ),
+        ];
+
         div()
             .size_full()
             .child(
@@ -236,6 +410,7 @@ impl Render for ChatRoom {
                         ChatRoomPage::Chat => 0,
                         ChatRoomPage::Settings => 1,
                         ChatRoomPage::Members => 2,
+                        ChatRoomPage::Media => 3,
                     },
                 )
                 .animation(LiftAnimation::new())
@@ -254,6 +429,85 @@ impl Render for ChatRoom {
                                         .unwrap_or_default(),
                                 )
                                 .pt(px(36.))
+                                .when(!room.is_space(), |david| {
+                                    david
+                                        .child(
+                                            button("search-button")
+                                                .flat()
+                                                .child(icon("edit-find"))
+                                                .checked_when(self.search_visible)
+                                                .on_click(cx.listener(|this, _, _, cx| {
+                                                    this.search_visible = !this.search_visible;
+                                                    cx.notify()
+                                                }))
+                                                .with_anchorer({
+                                                    let search_visible = self.search_visible;
+                                                    let search_flyout = self.search_flyout.clone();
+                                                    let close_listener =
+                                                        cx.listener(|this, _, _, cx| {
+                                                            this.search_visible = false;
+                                                            cx.notify()
+                                                        });
+                                                    move |david, bounds, _, _| {
+                                                        david.child(
+                                                            flyout(bounds)
+                                                                .visible(search_visible)
+                                                                .anchor_bottom_right()
+                                                                .on_close(close_listener)
+                                                                .child(search_flyout),
+                                                        )
+                                                    }
+                                                }),
+                                        )
+                                        .child(
+                                            button("threads-button")
+                                                .flat()
+                                                .child(icon("mail-reply-all"))
+                                                .checked_when(!matches!(
+                                                    open_room.thread_panel,
+                                                    ThreadPanelContent::Closed
+                                                ))
+                                                .on_click(cx.listener(|this, _, _, cx| {
+                                                    this.open_room.update(cx, |open_room, cx| {
+                                                        if matches!(
+                                                            open_room.thread_panel,
+                                                            ThreadPanelContent::Closed
+                                                        ) {
+                                                            open_room.show_thread_list(cx);
+                                                        } else {
+                                                            open_room.close_thread_panel(cx);
+                                                        }
+                                                    })
+                                                })),
+                                        )
+                                })
+                                .when_some(dm_verification_target, |david, user_id| {
+                                    david.child(
+                                        button("verify-user-button")
+                                            .flat()
+                                            .child(icon("security-high"))
+                                            .tooltip(simple_tooltip(tr!(
+                                                "VERIFY_DM_USER",
+                                                "Verify {{user}}",
+                                                user:quote = user_id.to_string()
+                                            )))
+                                            .on_click(cx.listener(move |this, _, _, cx| {
+                                                let user_id = user_id.clone();
+                                                let verification_popover = this
+                                                    .open_room
+                                                    .read(cx)
+                                                    .verification_popover
+                                                    .clone();
+                                                verification_popover.update(
+                                                    cx,
+                                                    |verification_popover, cx| {
+                                                        verification_popover
+                                                            .request_user_verification(user_id, cx)
+                                                    },
+                                                );
+                                            })),
+                                    )
+                                })
                                 .child(
                                     button("call-start")
                                         .flat()
@@ -288,9 +542,11 @@ impl Render for ChatRoom {
                         .into_any_element(),
                 )
                 .page(self.room_settings.clone().into_any_element())
-                .page(self.room_members.clone().into_any_element()),
+                .page(self.room_members.clone().into_any_element())
+                .page(self.room_media.clone().into_any_element()),
             )
             .child(self.user_action_dialogs.clone())
+            .child(open_room.verification_popover.clone())
             .child(
                 dialog_box("microphone-access")
                     .visible(self.microphone_access_dialog)
@@ -311,5 +567,43 @@ impl Render for ChatRoom {
                         }),
                     ),
             )
+            .child(
+                dialog_box("devtools")
+                    .visible(self.devtools_dialog)
+                    .title(tr!("DEVTOOLS_TITLE", "Developer Tools"))
+                    .content(div().flex().flex_col().gap(px(4.)).w(px(500.)).children(
+                        devtools_rows.into_iter().map(|(label, value)| {
+                            div()
+                                .flex()
+                                .gap(px(8.))
+                                .child(
+                                    div()
+                                        .w(px(150.))
+                                        .flex_none()
+                                        .text_color(theme.foreground.disabled())
+                                        .child(label),
+                                )
+                                .child(value)
+                        }),
+                    ))
+                    .button(
+                        button("devtools-copy-room-id")
+                            .child(icon_text(
+                                "edit-copy",
+                                tr!("DEVTOOLS_COPY_ROOM_ID", "Copy Room ID"),
+                            ))
+                            .on_click(move |_, _, cx| {
+                                cx.write_to_clipboard(ClipboardItem::new_string(room_id.clone()))
+                            }),
+                    )
+                    .button(
+                        button("devtools-done")
+                            .child(icon_text("dialog-ok", tr!("DEVTOOLS_DONE", "Done")))
+                            .on_click(cx.listener(|this, _, _, cx| {
+                                this.devtools_dialog = false;
+                                cx.notify();
+                            })),
+                    ),
+            )
     }
 }
@@ -1,36 +1,55 @@
 use crate::auth::emoji_flyout::EmojiFlyout;
+use crate::chat::chat_input::TextFormat;
+use crate::chat::chat_room::create_poll_popover::CreatePollPopover;
 use crate::chat::chat_room::open_room::OpenRoom;
+use crate::chat::chat_room::timeline_view::audio_message_item::format_playback_time;
 use crate::chat::chat_room::timeline_view::author_flyout::AuthorFlyoutUserActionListener;
 use crate::chat::chat_room::timeline_view::reply_fragment::reply_fragment;
 use crate::chat::displayed_room::DisplayedRoom;
 use cntp_i18n::{tr, trn};
 use contemporary::components::admonition::{AdmonitionSeverity, admonition};
 use contemporary::components::anchorer::WithAnchorer;
-use contemporary::components::button::button;
+use contemporary::components::button::{ButtonMenuOpenPolicy, button};
+use contemporary::components::context_menu::ContextMenuItem;
 use contemporary::components::flyout::flyout;
 use contemporary::components::icon::icon;
 use contemporary::components::icon_text::icon_text;
 use contemporary::components::layer::layer;
 use contemporary::components::toast::Toast;
+use contemporary::components::tooltip::simple_tooltip;
 use contemporary::styling::theme::{ThemeStorage, VariableColor};
 use gpui::prelude::FluentBuilder;
 use gpui::{
     AppContext, AsyncApp, AsyncWindowContext, Context, Entity, InteractiveElement, IntoElement,
-    ParentElement, Point, Render, Styled, WeakEntity, Window, anchored, deferred, div, px,
+    MouseButton, ParentElement, Point, Render, Styled, Subscription, WeakEntity, Window, anchored,
+    deferred, div, px,
 };
 use matrix_sdk::RoomState;
 use matrix_sdk::ruma::events::MessageLikeEventType;
 use matrix_sdk::ruma::events::room::tombstone::RoomTombstoneEventContent;
 use matrix_sdk_ui::timeline::TimelineItemContent;
 use std::rc::Rc;
+use std::time::Duration;
+use thegrid_common::session::image_packs_cache::PackUsage;
 use thegrid_common::session::room_cache::RoomJoinEvent;
 use thegrid_common::session::session_manager::SessionManager;
 use thegrid_common::tokio_helper::TokioHelper;
+use thegrid_rtc_livekit::voice_recorder::VoiceRecorder;
+
+/// Recordings shorter than this are assumed to be accidental presses of the record button
+const MIN_VOICE_MESSAGE_DURATION: Duration = Duration::from_millis(500);
+
+/// The number of waveform points shown while recording a voice message
+const RECORDING_WAVEFORM_POINTS: usize = 50;
 
 pub struct ChatBar {
     open_room: Entity<OpenRoom>,
     displayed_room: Entity<DisplayedRoom>,
     emoji_flyout: Option<Entity<EmojiFlyout>>,
+    sticker_flyout: Option<Entity<EmojiFlyout>>,
+    create_poll_popover: Entity<CreatePollPopover>,
+    voice_recorder: Option<(Entity<VoiceRecorder>, Subscription)>,
+    show_formatting_toolbar: bool,
 }
 
 impl ChatBar {
@@ -39,11 +58,81 @@ impl ChatBar {
         displayed_room: Entity<DisplayedRoom>,
         cx: &mut Context<Self>,
     ) -> Self {
+        let create_poll_popover = cx.new(|cx| CreatePollPopover::new(open_room.clone(), cx));
+
         Self {
             open_room,
             displayed_room,
             emoji_flyout: None,
+            sticker_flyout: None,
+            create_poll_popover,
+            voice_recorder: None,
+            show_formatting_toolbar: false,
+        }
+    }
+
+    fn start_voice_recording(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        if self.voice_recorder.is_some() {
+            return;
+        }
+
+        match VoiceRecorder::start(cx) {
+            Ok(voice_recorder) => {
+                let subscription = cx.observe(&voice_recorder, |_, _, cx| cx.notify());
+                self.voice_recorder = Some((voice_recorder, subscription));
+            }
+            Err(e) => {
+                Toast::new()
+                    .title(tr!("VOICE_MESSAGE_ERROR_TITLE", "Unable to record").as_ref())
+                    .body(e.to_string().as_str())
+                    .severity(AdmonitionSeverity::Error)
+                    .post(window, cx);
+            }
         }
+        cx.notify();
+    }
+
+    fn finish_voice_recording(&mut self, send: bool, window: &mut Window, cx: &mut Context<Self>) {
+        let Some((voice_recorder, _)) = self.voice_recorder.take() else {
+            return;
+        };
+        cx.notify();
+
+        let too_short = voice_recorder.read(cx).duration() < MIN_VOICE_MESSAGE_DURATION;
+        let encode = voice_recorder.update(cx, |voice_recorder, cx| voice_recorder.finish(cx));
+        if !send {
+            return;
+        }
+        if too_short {
+            Toast::new()
+                .title(tr!("VOICE_MESSAGE_TOO_SHORT_TITLE", "Voice Messages").as_ref())
+                .body(
+                    tr!(
+                        "VOICE_MESSAGE_TOO_SHORT_TEXT",
+                        "Hold the microphone button to record a voice message"
+                    )
+                    .as_ref(),
+                )
+                .post(window, cx);
+            return;
+        }
+
+        cx.spawn(
+            async move |weak_this: WeakEntity<Self>, cx: &mut AsyncApp| match cx
+                .background_spawn(async move { encode() })
+                .await
+            {
+                Ok(recording) => {
+                    let _ = weak_this.update(cx, |this, cx| {
+                        this.open_room.update(cx, |open_room, cx| {
+                            open_room.send_voice_message(recording, cx);
+                        })
+                    });
+                }
+                Err(e) => log::error!("Failed to encode voice message: {:?}", e),
+            },
+        )
+        .detach();
     }
 
     pub fn render_tombstone_content(&mut self, cx: &mut Context<Self>) -> impl IntoElement {
@@ -234,6 +323,16 @@ impl Render for ChatBar {
             current_user.can_send_message(MessageLikeEventType::RoomMessage)
         });
 
+        // Only the end of the waveform is shown while recording
+        let recording = self.voice_recorder.as_ref().map(|(voice_recorder, _)| {
+            let voice_recorder = voice_recorder.read(cx);
+            let waveform = voice_recorder.waveform();
+            (
+                voice_recorder.duration(),
+                waveform[waveform.len().saturating_sub(RECORDING_WAVEFORM_POINTS)..].to_vec(),
+            )
+        });
+
         let window_size = window.viewport_size();
         let inset = window.client_inset().unwrap_or_else(|| px(0.));
 
@@ -241,6 +340,45 @@ impl Render for ChatBar {
 
         let theme = cx.theme();
 
+        let formats = [
+            (
+                TextFormat::Bold,
+                "format-text-bold",
+                tr!("FORMAT_BOLD", "Bold"),
+            ),
+            (
+                TextFormat::Italic,
+                "format-text-italic",
+                tr!("FORMAT_ITALIC", "Italic"),
+            ),
+            (
+                TextFormat::Strikethrough,
+                "format-text-strikethrough",
+                tr!("FORMAT_STRIKETHROUGH", "Strikethrough"),
+            ),
+            (
+                TextFormat::Code,
+                "format-text-code",
+                tr!("FORMAT_CODE", "Code"),
+            ),
+            (TextFormat::Link, "insert-link", tr!("FORMAT_LINK", "Link")),
+            (
+                TextFormat::BulletList,
+                "format-list-unordered",
+                tr!("FORMAT_BULLET_LIST", "Bulleted List"),
+            ),
+            (
+                TextFormat::NumberedList,
+                "format-list-ordered",
+                tr!("FORMAT_NUMBERED_LIST", "Numbered List"),
+            ),
+            (
+                TextFormat::Quote,
+                "format-text-blockquote",
+                tr!("FORMAT_QUOTE", "Quote"),
+            ),
+        ];
+
         div()
             .when_some(open_room.pending_reply.as_ref(), |david, pending_reply| {
                 let TimelineItemContent::MsgLike(content) = pending_reply.content() else {
@@ -256,6 +394,29 @@ impl Render for ChatBar {
                     Rc::new(Box::new(|_, _, _| {})),
                 )))
             })
+            .when(
+                self.show_formatting_toolbar && can_send_message && recording.is_none(),
+                |david| {
+                    david.child(
+                        div().flex().gap(px(2.)).mx(px(2.)).children(
+                            formats.into_iter().enumerate().map(
+                                |(ix, (format, icon_name, label))| {
+                                    let chat_input = open_room.chat_input.clone();
+                                    button(("format_button", ix))
+                                        .child(icon(icon_name))
+                                        .flat()
+                                        .tooltip(simple_tooltip(label))
+                                        .on_click(move |_, window, cx| {
+                                            chat_input.update(cx, |chat_input, cx| {
+                                                chat_input.apply_format(format, window, cx);
+                                            });
+                                        })
+                                },
+                            ),
+                        ),
+                    )
+                },
+            )
             .child(
                 layer()
                     .m(px(2.))
@@ -267,15 +428,72 @@ impl Render for ChatBar {
                             .when(!can_send_message, |david| david.disabled())
                             .child(icon("mail-attachment"))
                             .flat()
-                            .on_click(cx.listener(move |this, _, window, cx| {
-                                this.open_room.update(cx, |open_room, cx| {
-                                    open_room.show_attach_dialog(window, cx)
-                                });
-                            })),
+                            .with_menu_open_policy(ButtonMenuOpenPolicy::AnyClick)
+                            .with_menu(vec![
+                                ContextMenuItem::menu_item()
+                                    .label(tr!("CHAT_BAR_ATTACH_FILE", "Attach File..."))
+                                    .icon("mail-attachment")
+                                    .on_triggered(cx.listener(move |this, _, window, cx| {
+                                        this.open_room.update(cx, |open_room, cx| {
+                                            open_room.show_attach_dialog(window, cx)
+                                        });
+                                    }))
+                                    .build(),
+                                ContextMenuItem::menu_item()
+                                    .label(tr!("CHAT_BAR_CREATE_POLL", "Create Poll..."))
+                                    .icon("view-statistics")
+                                    .on_triggered(cx.listener(move |this, _, _, cx| {
+                                        this.create_poll_popover.update(
+                                            cx,
+                                            |create_poll_popover, cx| {
+                                                create_poll_popover.open(cx);
+                                            },
+                                        );
+                                    }))
+                                    .build(),
+                            ]),
                     )
                     .when_else(
                         can_send_message,
-                        |david| david.child(open_room.chat_input.clone()),
+                        |david| match recording {
+                            Some((duration, waveform)) => david.child(
+                                div()
+                                    .flex()
+                                    .flex_grow(1.)
+                                    .items_center()
+                                    .gap(px(4.))
+                                    .child(
+                                        div()
+                                            .size(px(8.))
+                                            .rounded(px(4.))
+                                            .bg(theme.error_accent_color),
+                                    )
+                                    .child(format_playback_time(duration))
+                                    .child(
+                                        div()
+                                            .flex()
+                                            .flex_grow(1.)
+                                            .items_center()
+                                            .justify_end()
+                                            .gap(px(1.))
+                                            .h(px(24.))
+                                            .children(waveform.into_iter().map(|amplitude| {
+                                                div()
+                                                    .w(px(2.))
+                                                    .h(px((amplitude * 24.).max(2.)))
+                                                    .rounded(px(1.))
+                                                    .bg(theme.foreground.disabled())
+                                            })),
+                                    )
+                                    .child(div().text_color(theme.foreground.disabled()).child(
+                                        tr!(
+                                            "VOICE_MESSAGE_RECORDING_HINT",
+                                            "Release to send, or move away to cancel"
+                                        ),
+                                    )),
+                            ),
+                            None => david.child(open_room.chat_input.clone()),
+                        },
                         |david| {
                             david.child(
                                 div()
@@ -289,19 +507,48 @@ impl Render for ChatBar {
                             )
                         },
                     )
+                    .child(
+                        button("formatting_button")
+                            .child(icon("format-text-rich"))
+                            .flat()
+                            .when(!can_send_message, |david| david.disabled())
+                            .when(self.show_formatting_toolbar, |david| david.checked())
+                            .tooltip(simple_tooltip(tr!("CHAT_BAR_FORMATTING", "Formatting")))
+                            .on_click(cx.listener(|this, _, _, cx| {
+                                this.show_formatting_toolbar = !this.show_formatting_toolbar;
+                                cx.notify()
+                            })),
+                    )
                     .child(
                         button("emoji")
                             .child(icon("face-smile-big"))
                             .flat()
                             .when(!can_send_message, |david| david.disabled())
                             .on_click(cx.listener(|this, _, _, cx| {
-                                let chat_input = this.open_room.read(cx).chat_input.clone();
+                                let open_room = this.open_room.read(cx);
+                                let chat_input = open_room.chat_input.clone();
+                                let image_packs = cx
+                                    .global::<SessionManager>()
+                                    .image_packs()
+                                    .read(cx)
+                                    .packs_for_room(&open_room.room_id);
                                 this.emoji_flyout = Some(cx.new(|cx| {
                                     let mut emoji_flyout = EmojiFlyout::new(cx);
+                                    emoji_flyout.set_image_packs(
+                                        image_packs,
+                                        PackUsage::Emoticon,
+                                        cx,
+                                    );
                                     emoji_flyout.set_emoji_selected_listener(
                                         move |event, window, cx| {
+                                            // Shortcodes are only replaced when they're a word
+                                            // on their own
+                                            let emoji = match event.image {
+                                                Some(_) => format!("{} ", event.emoji),
+                                                None => event.emoji.clone(),
+                                            };
                                             chat_input.update(cx, |chat_input, cx| {
-                                                chat_input.type_string(&event.emoji, window, cx);
+                                                chat_input.type_string(&emoji, window, cx);
                                             });
                                         },
                                     );
@@ -327,6 +574,99 @@ impl Render for ChatBar {
                                 }
                             }),
                     )
+                    .child(
+                        button("sticker")
+                            .child(icon("image-x-generic"))
+                            .flat()
+                            .when(!can_send_message, |david| david.disabled())
+                            .tooltip(simple_tooltip(tr!("CHAT_BAR_STICKERS", "Stickers")))
+                            .on_click(cx.listener(|this, _, _, cx| {
+                                let open_room = this.open_room.clone();
+                                let image_packs = cx
+                                    .global::<SessionManager>()
+                                    .image_packs()
+                                    .read(cx)
+                                    .packs_for_room(&open_room.read(cx).room_id);
+                                let weak_this = cx.weak_entity();
+                                this.sticker_flyout = Some(cx.new(|cx| {
+                                    let mut sticker_flyout = EmojiFlyout::new(cx);
+                                    sticker_flyout.set_image_packs(
+                                        image_packs,
+                                        PackUsage::Sticker,
+                                        cx,
+                                    );
+                                    sticker_flyout.set_emoji_selected_listener(
+                                        move |event, _, cx| {
+                                            let Some(image) = &event.image else {
+                                                return;
+                                            };
+                                            open_room.update(cx, |open_room, cx| {
+                                                open_room.send_sticker(&event.emoji, image, cx)
+                                            });
+                                            let _ = weak_this.update(cx, |this, cx| {
+                                                this.sticker_flyout = None;
+                                                cx.notify()
+                                            });
+                                        },
+                                    );
+                                    sticker_flyout
+                                }));
+                                cx.notify()
+                            }))
+                            .when_some(self.sticker_flyout.clone(), {
+                                let close_listener = cx.listener(move |this, _, _, cx| {
+                                    this.sticker_flyout = None;
+                                    cx.notify()
+                                });
+                                move |david, sticker_flyout| {
+                                    david.with_anchorer(|david, bounds, _, _| {
+                                        david.child(
+                                            flyout(bounds)
+                                                .visible(true)
+                                                .anchor_bottom_right()
+                                                .on_close(close_listener)
+                                                .child(sticker_flyout),
+                                        )
+                                    })
+                                }
+                            }),
+                    )
+                    .when(can_send_message, |david| {
+                        david.child(
+                            div()
+                                .id("voice_message_button")
+                                .flex()
+                                .items_center()
+                                .justify_center()
+                                .size(px(32.))
+                                .rounded(theme.border_radius)
+                                .cursor_pointer()
+                                .when_else(
+                                    self.voice_recorder.is_some(),
+                                    |david| david.bg(theme.error_accent_color),
+                                    |david| david.hover(|david| david.bg(theme.button_background)),
+                                )
+                                .child(icon("audio-input-microphone"))
+                                .on_mouse_down(
+                                    MouseButton::Left,
+                                    cx.listener(|this, _, window, cx| {
+                                        this.start_voice_recording(window, cx)
+                                    }),
+                                )
+                                .on_mouse_up(
+                                    MouseButton::Left,
+                                    cx.listener(|this, _, window, cx| {
+                                        this.finish_voice_recording(true, window, cx)
+                                    }),
+                                )
+                                .on_mouse_up_out(
+                                    MouseButton::Left,
+                                    cx.listener(|this, _, window, cx| {
+                                        this.finish_voice_recording(false, window, cx)
+                                    }),
+                                ),
+                        )
+                    })
                     .child(
                         button("send_button")
                             .child(icon("mail-send"))
@@ -389,6 +729,7 @@ impl Render for ChatBar {
                     .into(),
                 }),
             )
+            .child(self.create_poll_popover.clone())
             .into_any_element()
     }
 }
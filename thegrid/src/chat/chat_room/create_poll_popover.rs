@@ -0,0 +1,387 @@
+use crate::chat::chat_room::open_room::OpenRoom;
+use cntp_i18n::tr;
+use contemporary::components::admonition::AdmonitionSeverity;
+use contemporary::components::button::button;
+use contemporary::components::constrainer::constrainer;
+use contemporary::components::grandstand::grandstand;
+use contemporary::components::icon::icon;
+use contemporary::components::icon_text::icon_text;
+use contemporary::components::layer::layer;
+use contemporary::components::pager::pager;
+use contemporary::components::pager::slide_horizontal_animation::SlideHorizontalAnimation;
+use contemporary::components::popover::popover;
+use contemporary::components::spinner::spinner;
+use contemporary::components::subtitle::subtitle;
+use contemporary::components::switch::{SwitchChangeEvent, switch};
+use contemporary::components::text_field::TextField;
+use contemporary::components::toast::Toast;
+use gpui::prelude::FluentBuilder;
+use gpui::{
+    AppContext, AsyncWindowContext, Context, Entity, IntoElement, ParentElement, Render,
+    SharedString, Styled, WeakEntity, Window, div, px,
+};
+use matrix_sdk::ruma::UInt;
+use matrix_sdk::ruma::events::poll::start::PollKind;
+use matrix_sdk::ruma::events::poll::unstable_start::{
+    NewUnstablePollStartEventContent, UnstablePollAnswer, UnstablePollAnswers,
+    UnstablePollStartContentBlock, UnstablePollStartEventContent,
+};
+use thegrid_common::tokio_helper::TokioHelper;
+
+/// The minimum number of answers a poll can have
+const MIN_ANSWERS: usize = 2;
+
+/// The maximum number of answers a poll can have
+const MAX_ANSWERS: usize = 20;
+
+pub struct CreatePollPopover {
+    visible: bool,
+    processing: bool,
+
+    question_field: Entity<TextField>,
+    answer_fields: Vec<Entity<TextField>>,
+    show_results: bool,
+    multiple_choice: bool,
+    next_answer_id: usize,
+
+    open_room: Entity<OpenRoom>,
+}
+
+impl CreatePollPopover {
+    pub fn new(open_room: Entity<OpenRoom>, cx: &mut Context<Self>) -> Self {
+        Self {
+            visible: false,
+            processing: false,
+            question_field: cx.new(|cx| {
+                let mut text_field = TextField::new("poll-question", cx);
+                text_field.set_placeholder(
+                    tr!("CREATE_POLL_QUESTION", "Question").to_string().as_str(),
+                );
+                text_field
+            }),
+            answer_fields: Vec::new(),
+            show_results: true,
+            multiple_choice: false,
+            next_answer_id: 0,
+            open_room,
+        }
+    }
+
+    pub fn open(&mut self, cx: &mut Context<Self>) {
+        self.visible = true;
+        self.processing = false;
+        self.show_results = true;
+        self.multiple_choice = false;
+
+        self.question_field.update(cx, |question_field, _| {
+            question_field.set_text("");
+        });
+        self.answer_fields = Vec::new();
+        for _ in 0..MIN_ANSWERS {
+            self.add_answer(cx);
+        }
+
+        cx.notify()
+    }
+
+    fn add_answer(&mut self, cx: &mut Context<Self>) {
+        if self.answer_fields.len() >= MAX_ANSWERS {
+            return;
+        }
+
+        let answer_id = self.next_answer_id;
+        self.next_answer_id += 1;
+
+        let answer_number = self.answer_fields.len() + 1;
+        self.answer_fields.push(cx.new(|cx| {
+            let mut text_field =
+                TextField::new(SharedString::from(format!("poll-answer-{answer_id}")), cx);
+            text_field.set_placeholder(
+                tr!(
+                    "CREATE_POLL_ANSWER",
+                    "Answer {{number}}",
+                    number = answer_number
+                )
+                .to_string()
+                .as_str(),
+            );
+            text_field
+        }));
+        cx.notify()
+    }
+
+    fn remove_answer(&mut self, index: usize, cx: &mut Context<Self>) {
+        if self.answer_fields.len() <= MIN_ANSWERS {
+            return;
+        }
+
+        self.answer_fields.remove(index);
+        cx.notify()
+    }
+
+    pub fn create_poll(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let question = self.question_field.read(cx).text().trim().to_string();
+        if question.is_empty() {
+            self.question_field.update(cx, |question_field, cx| {
+                question_field.flash_error(window, cx);
+            });
+            return;
+        }
+
+        let mut answers = Vec::new();
+        for answer_field in &self.answer_fields {
+            let answer = answer_field.read(cx).text().trim().to_string();
+            if answer.is_empty() {
+                answer_field.update(cx, |answer_field, cx| {
+                    answer_field.flash_error(window, cx);
+                });
+                return;
+            }
+            answers.push(answer);
+        }
+
+        let Some(timeline) = self.open_room.read(cx).timeline.clone() else {
+            return;
+        };
+        let timeline = timeline.read(cx).inner.clone();
+
+        // Clients that don't understand polls show the fallback text instead
+        let fallback = answers
+            .iter()
+            .enumerate()
+            .fold(question.clone(), |fallback, (i, answer)| {
+                format!("{fallback}\n{}. {answer}", i + 1)
+            });
+
+        let answer_count = answers.len();
+        let Ok(poll_answers) = UnstablePollAnswers::try_from(
+            answers
+                .into_iter()
+                .enumerate()
+                .map(|(i, answer)| UnstablePollAnswer::new(i.to_string(), answer))
+                .collect::<Vec<_>>(),
+        ) else {
+            return;
+        };
+
+        let mut poll_start = UnstablePollStartContentBlock::new(question, poll_answers);
+        poll_start.kind = if self.show_results {
+            PollKind::Disclosed
+        } else {
+            PollKind::Undisclosed
+        };
+        poll_start.max_selections = if self.multiple_choice {
+            UInt::from(answer_count as u32)
+        } else {
+            UInt::from(1u32)
+        };
+
+        let content = UnstablePollStartEventContent::New(
+            NewUnstablePollStartEventContent::plain_text(fallback, poll_start),
+        );
+
+        self.processing = true;
+        cx.notify();
+
+        cx.spawn_in(
+            window,
+            async move |weak_this: WeakEntity<Self>, cx: &mut AsyncWindowContext| {
+                let result = cx
+                    .spawn_tokio(async move { timeline.send(content.into()).await })
+                    .await;
+
+                let _ = cx.update(|window, cx| {
+                    weak_this.update(cx, |this, cx| {
+                        this.processing = false;
+                        match result {
+                            Ok(_) => {
+                                this.visible = false;
+                            }
+                            Err(_) => {
+                                Toast::new()
+                                    .title(
+                                        tr!("CREATE_POLL_ERROR_TITLE", "Unable to create the poll")
+                                            .as_ref(),
+                                    )
+                                    .body(
+                                        tr!(
+                                            "CREATE_POLL_ERROR_TEXT",
+                                            "The poll could not be sent to the room"
+                                        )
+                                        .as_ref(),
+                                    )
+                                    .severity(AdmonitionSeverity::Error)
+                                    .post(window, cx);
+                            }
+                        }
+                        cx.notify();
+                    })
+                });
+            },
+        )
+        .detach();
+    }
+
+    fn create_poll_page_contents(&mut self, cx: &mut Context<Self>) -> impl IntoElement {
+        let can_remove_answers = self.answer_fields.len() > MIN_ANSWERS;
+        let can_add_answers = self.answer_fields.len() < MAX_ANSWERS;
+
+        constrainer("create-poll-constrainer").child(
+            layer()
+                .flex()
+                .flex_col()
+                .p(px(8.))
+                .w_full()
+                .child(subtitle(tr!("CREATE_POLL_OPTIONS", "Poll Options")))
+                .child(
+                    div()
+                        .flex()
+                        .flex_col()
+                        .gap(px(8.))
+                        .child(tr!(
+                            "CREATE_POLL_DESCRIPTION",
+                            "Ask a question and let room members vote on the answer"
+                        ))
+                        .child(self.question_field.clone())
+                        .child(
+                            layer()
+                                .p(px(8.))
+                                .flex()
+                                .flex_col()
+                                .gap(px(4.))
+                                .children(self.answer_fields.iter().enumerate().map(
+                                    |(i, answer_field)| {
+                                        div()
+                                            .flex()
+                                            .items_center()
+                                            .gap(px(4.))
+                                            .child(div().flex_grow(1.).child(answer_field.clone()))
+                                            .child(
+                                                button(("remove-answer", i))
+                                                    .flat()
+                                                    .child(icon("list-remove"))
+                                                    .when(!can_remove_answers, |david| {
+                                                        david.disabled()
+                                                    })
+                                                    .on_click(cx.listener(
+                                                        move |this, _, _, cx| {
+                                                            this.remove_answer(i, cx)
+                                                        },
+                                                    )),
+                                            )
+                                    },
+                                ))
+                                .child(
+                                    div().flex().child(
+                                        button("add-answer")
+                                            .child(icon_text(
+                                                "list-add",
+                                                tr!("CREATE_POLL_ADD_ANSWER", "Add Answer"),
+                                            ))
+                                            .when(!can_add_answers, |david| david.disabled())
+                                            .on_click(cx.listener(|this, _, _, cx| {
+                                                this.add_answer(cx)
+                                            })),
+                                    ),
+                                ),
+                        )
+                        .child(
+                            layer()
+                                .p(px(8.))
+                                .flex()
+                                .flex_col()
+                                .gap(px(4.))
+                                .child(
+                                    div()
+                                        .flex()
+                                        .child(tr!(
+                                            "CREATE_POLL_SHOW_RESULTS",
+                                            "Show results while voting"
+                                        ))
+                                        .child(div().flex_grow(1.))
+                                        .child(
+                                            switch("show-results-switch")
+                                                .when(self.show_results, |david| david.checked())
+                                                .on_change(cx.listener(
+                                                    |this, event: &SwitchChangeEvent, _, cx| {
+                                                        this.show_results = event.checked;
+                                                        cx.notify()
+                                                    },
+                                                )),
+                                        ),
+                                )
+                                .child(
+                                    div()
+                                        .flex()
+                                        .child(tr!(
+                                            "CREATE_POLL_MULTIPLE_CHOICE",
+                                            "Allow multiple answers"
+                                        ))
+                                        .child(div().flex_grow(1.))
+                                        .child(
+                                            switch("multiple-choice-switch")
+                                                .when(self.multiple_choice, |david| {
+                                                    david.checked()
+                                                })
+                                                .on_change(cx.listener(
+                                                    |this, event: &SwitchChangeEvent, _, cx| {
+                                                        this.multiple_choice = event.checked;
+                                                        cx.notify()
+                                                    },
+                                                )),
+                                        ),
+                                ),
+                        )
+                        .child(
+                            button("do-create")
+                                .child(icon_text(
+                                    "view-statistics",
+                                    tr!("CREATE_POLL", "Create Poll"),
+                                ))
+                                .on_click(cx.listener(move |this, _, window, cx| {
+                                    this.create_poll(window, cx)
+                                })),
+                        ),
+                ),
+        )
+    }
+}
+
+impl Render for CreatePollPopover {
+    fn render(&mut self, window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        popover("create-poll-popover")
+            .visible(self.visible)
+            .size_neg(100.)
+            .anchor_bottom()
+            .content(
+                pager("create-poll-pager", if self.processing { 1 } else { 0 })
+                    .animation(SlideHorizontalAnimation::new())
+                    .size_full()
+                    .page(
+                        div()
+                            .flex()
+                            .flex_col()
+                            .gap(px(9.))
+                            .child(
+                                grandstand("create-poll-grandstand")
+                                    .text(tr!("CREATE_POLL_TITLE", "Create Poll"))
+                                    .on_back_click(cx.listener(move |this, _, _, cx| {
+                                        this.visible = false;
+                                        cx.notify()
+                                    })),
+                            )
+                            .child(self.create_poll_page_contents(cx))
+                            .into_any_element(),
+                    )
+                    .page(
+                        div()
+                            .size_full()
+                            .flex()
+                            .items_center()
+                            .justify_center()
+                            .child(spinner())
+                            .into_any_element(),
+                    ),
+            )
+    }
+}
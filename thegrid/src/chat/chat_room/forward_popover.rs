@@ -0,0 +1,252 @@
+use cntp_i18n::tr;
+use contemporary::components::admonition::AdmonitionSeverity;
+use contemporary::components::constrainer::constrainer;
+use contemporary::components::grandstand::grandstand;
+use contemporary::components::layer::layer;
+use contemporary::components::pager::pager;
+use contemporary::components::pager::slide_horizontal_animation::SlideHorizontalAnimation;
+use contemporary::components::popover::popover;
+use contemporary::components::spinner::spinner;
+use contemporary::components::subtitle::subtitle;
+use contemporary::components::text_field::TextField;
+use contemporary::components::toast::Toast;
+use contemporary::styling::theme::{Theme, VariableColor};
+use gpui::prelude::FluentBuilder;
+use gpui::{
+    AppContext, AsyncWindowContext, Context, ElementId, Entity, InteractiveElement, IntoElement,
+    ParentElement, Render, StatefulInteractiveElement, Styled, WeakEntity, Window, div, px,
+};
+use matrix_sdk::RoomState;
+use matrix_sdk::ruma::OwnedRoomId;
+use matrix_sdk::ruma::events::room::message::{MessageType, RoomMessageEventContent};
+use std::rc::Rc;
+use thegrid_common::mxc_image::{SizePolicy, mxc_image};
+use thegrid_common::session::session_manager::SessionManager;
+use thegrid_common::tokio_helper::TokioHelper;
+use tracing::error;
+
+pub struct ForwardPopover {
+    message: Option<MessageType>,
+    room_search: Entity<TextField>,
+
+    busy: bool,
+}
+
+impl ForwardPopover {
+    pub fn new(cx: &mut Context<Self>) -> Self {
+        let text_changed_listener = Rc::new(cx.listener(|_, _, _, cx| cx.notify()));
+
+        Self {
+            message: None,
+            room_search: cx.new(|cx| {
+                let mut text_field = TextField::new("forward-search", cx);
+                text_field.set_placeholder(
+                    tr!("FORWARD_SEARCH_PLACEHOLDER", "Search for rooms...")
+                        .to_string()
+                        .as_str(),
+                );
+                text_field.on_text_changed({
+                    let text_changed_listener = text_changed_listener.clone();
+                    move |event, window, cx| {
+                        let event = event.clone();
+                        let text_changed_listener = text_changed_listener.clone();
+                        window.defer(cx, move |window, cx| {
+                            text_changed_listener(&event, window, cx)
+                        });
+                    }
+                });
+                text_field
+            }),
+
+            busy: false,
+        }
+    }
+
+    pub fn open_forward_popover(&mut self, message: MessageType, cx: &mut Context<Self>) {
+        self.message = Some(message);
+        self.busy = false;
+        cx.notify();
+    }
+
+    pub fn perform_forward(
+        &mut self,
+        room_id: OwnedRoomId,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let Some(message) = self.message.clone() else {
+            return;
+        };
+
+        let session_manager = cx.global::<SessionManager>();
+        let Some(cached_room) = session_manager.rooms().read(cx).room(&room_id) else {
+            return;
+        };
+        let room = cached_room.read(cx).inner.clone();
+        let room_name = cached_room.read(cx).display_name();
+
+        cx.spawn_in(
+            window,
+            async move |weak_this: WeakEntity<Self>, cx: &mut AsyncWindowContext| {
+                let content = RoomMessageEventContent::new(message);
+                if let Err(e) = cx
+                    .spawn_tokio(async move { room.send(content).await })
+                    .await
+                {
+                    error!("Unable to forward message: {e:?}");
+                    let _ = cx.update(|window, cx| {
+                        Toast::new()
+                            .title(&tr!("FORWARD_ERROR_TITLE", "Unable to forward message"))
+                            .body(&tr!(
+                                "FORWARD_ERROR_TEXT",
+                                "Unable to forward the message to {{room}}",
+                                room = room_name
+                            ))
+                            .severity(AdmonitionSeverity::Error)
+                            .post(window, cx);
+                    });
+
+                    let _ = weak_this.update(cx, |this, cx| {
+                        this.busy = false;
+                        cx.notify();
+                    });
+
+                    return;
+                }
+
+                let _ = weak_this.update(cx, |this, cx| {
+                    this.busy = false;
+                    this.message = None;
+                    cx.notify();
+                });
+            },
+        )
+        .detach();
+
+        self.busy = true;
+        cx.notify();
+    }
+}
+
+impl Render for ForwardPopover {
+    fn render(&mut self, _: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let theme = cx.global::<Theme>().clone();
+
+        let session_manager = cx.global::<SessionManager>();
+        let query = self.room_search.read(cx).text().trim().to_string();
+        let rooms = session_manager
+            .rooms()
+            .read(cx)
+            .filtered_rooms(None, &query, cx)
+            .into_iter()
+            .filter(|room| {
+                let room = &room.read(cx).inner;
+                room.state() == RoomState::Joined && !room.is_space()
+            })
+            .collect::<Vec<_>>();
+
+        popover("forward-popover")
+            .visible(self.message.is_some())
+            .size_neg(100.)
+            .anchor_bottom()
+            .content(
+                pager("forward-pager", if self.busy { 1 } else { 0 })
+                    .animation(SlideHorizontalAnimation::new())
+                    .size_full()
+                    .page(
+                        div()
+                            .flex()
+                            .flex_col()
+                            .gap(px(9.))
+                            .child(
+                                grandstand("forward-grandstand")
+                                    .text(tr!("FORWARD_TITLE", "Forward"))
+                                    .on_back_click(cx.listener(move |this, _, _, cx| {
+                                        this.message = None;
+                                        cx.notify()
+                                    })),
+                            )
+                            .child(
+                                constrainer("forward-constrainer").child(
+                                    layer()
+                                        .flex()
+                                        .flex_col()
+                                        .p(px(8.))
+                                        .gap(px(8.))
+                                        .w_full()
+                                        .child(subtitle(tr!("FORWARD_SUBTITLE", "Forward to room")))
+                                        .child(tr!(
+                                            "FORWARD_DESCRIPTION",
+                                            "Which room do you want to forward this to?"
+                                        ))
+                                        .child(self.room_search.clone())
+                                        .child(
+                                            div()
+                                                .id("forward-rooms")
+                                                .flex()
+                                                .flex_col()
+                                                .max_h(px(300.))
+                                                .overflow_y_scroll()
+                                                .when(rooms.is_empty(), |david| {
+                                                    david.child(
+                                                        div()
+                                                            .text_color(
+                                                                theme.foreground.disabled(),
+                                                            )
+                                                            .child(tr!("ROOT_SIDEBAR_NO_MATCHES")),
+                                                    )
+                                                })
+                                                .children(rooms.into_iter().map(|room| {
+                                                    let room = room.read(cx);
+                                                    let room_id = room.inner.room_id().to_owned();
+
+                                                    div()
+                                                        .id(ElementId::Name(
+                                                            room_id.to_string().into(),
+                                                        ))
+                                                        .flex()
+                                                        .items_center()
+                                                        .p(px(2.))
+                                                        .gap(px(4.))
+                                                        .rounded(theme.border_radius)
+                                                        .cursor_pointer()
+                                                        .hover(|david| {
+                                                            david.bg(theme.button_background)
+                                                        })
+                                                        .child(
+                                                            mxc_image(room.inner.avatar_url())
+                                                                .fallback_image(
+                                                                    room.inner.room_id(),
+                                                                )
+                                                                .fixed_square(px(24.))
+                                                                .size_policy(SizePolicy::Fit)
+                                                                .rounded(theme.border_radius),
+                                                        )
+                                                        .child(room.display_name())
+                                                        .on_click(cx.listener(
+                                                            move |this, _, window, cx| {
+                                                                this.perform_forward(
+                                                                    room_id.clone(),
+                                                                    window,
+                                                                    cx,
+                                                                )
+                                                            },
+                                                        ))
+                                                })),
+                                        ),
+                                ),
+                            )
+                            .into_any_element(),
+                    )
+                    .page(
+                        div()
+                            .size_full()
+                            .flex()
+                            .items_center()
+                            .justify_center()
+                            .child(spinner())
+                            .into_any_element(),
+                    ),
+            )
+    }
+}
@@ -0,0 +1,624 @@
+use crate::chat::chat_room::forward_popover::ForwardPopover;
+use crate::chat::chat_room::open_room::OpenRoom;
+use crate::chat::chat_room::timeline_view::timeline_message_item::save_file;
+use crate::chat::chat_room::timeline_view::video_message_item::video_message_item;
+use cntp_i18n::{I18N_MANAGER, tr};
+use contemporary::components::admonition::AdmonitionSeverity;
+use contemporary::components::button::button;
+use contemporary::components::icon::icon;
+use contemporary::components::spinner::spinner;
+use contemporary::components::toast::Toast;
+use contemporary::components::tooltip::simple_tooltip;
+use contemporary::styling::theme::{Theme, VariableColor};
+use gpui::prelude::FluentBuilder;
+use gpui::{
+    AnyElement, App, AppContext, BorrowAppContext, ClipboardItem, Context, Entity, FocusHandle,
+    FontWeight, Image, ImageFormat, InteractiveElement, IntoElement, KeyBinding, MouseButton,
+    MouseDownEvent, MouseMoveEvent, MouseUpEvent, ParentElement, Pixels, Point, Render,
+    RenderImage, ScrollWheelEvent, Styled, Window, actions, anchored, deferred, div, img, point,
+    px, rgba,
+};
+use matrix_sdk::ruma::OwnedEventId;
+use matrix_sdk::ruma::events::room::MediaSource;
+use matrix_sdk::ruma::events::room::message::MessageType;
+use matrix_sdk_ui::timeline::{MsgLikeContent, MsgLikeKind, TimelineDetails, TimelineItemContent};
+use std::fs::read;
+use std::path::PathBuf;
+use std::sync::Arc;
+use thegrid_common::session::media_cache::{MediaCacheEntry, MediaState};
+use thegrid_common::session::session_manager::SessionManager;
+
+actions!(
+    media_viewer,
+    [
+        PreviousMedia,
+        NextMedia,
+        CloseMediaViewer,
+        ZoomIn,
+        ZoomOut,
+        ResetZoom
+    ]
+);
+
+pub fn bind_media_viewer_keys(cx: &mut App) {
+    cx.bind_keys([
+        KeyBinding::new("left", PreviousMedia, Some("MediaViewer")),
+        KeyBinding::new("right", NextMedia, Some("MediaViewer")),
+        KeyBinding::new("escape", CloseMediaViewer, Some("MediaViewer")),
+        KeyBinding::new("=", ZoomIn, Some("MediaViewer")),
+        KeyBinding::new("-", ZoomOut, Some("MediaViewer")),
+        KeyBinding::new("0", ResetZoom, Some("MediaViewer")),
+    ]);
+}
+
+/// How much each step of zooming scales the image by
+const ZOOM_STEP: f32 = 1.25;
+const MIN_ZOOM: f32 = 0.25;
+const MAX_ZOOM: f32 = 16.;
+
+/// The height of the toolbar along the top of the media viewer
+const TOOLBAR_HEIGHT: f32 = 48.;
+
+pub struct MediaViewer {
+    open_room: Entity<OpenRoom>,
+    forward_popover: Entity<ForwardPopover>,
+    focus_handle: FocusHandle,
+    shown_event: Option<OwnedEventId>,
+    needs_focus: bool,
+
+    zoom: f32,
+    pan: Point<Pixels>,
+    drag_origin: Option<Point<Pixels>>,
+
+    // Held so that the decoded image stays alive while it is being shown
+    image: Option<Arc<RenderImage>>,
+}
+
+struct MediaItem {
+    event_id: OwnedEventId,
+    sender: String,
+    msgtype: MessageType,
+}
+
+impl MediaItem {
+    fn source(&self) -> MediaSource {
+        match &self.msgtype {
+            MessageType::Image(image) => image.source.clone(),
+            MessageType::Video(video) => video.source.clone(),
+            _ => unreachable!(),
+        }
+    }
+
+    fn filename(&self) -> String {
+        match &self.msgtype {
+            MessageType::Image(image) => {
+                image.filename.clone().unwrap_or_else(|| image.body.clone())
+            }
+            MessageType::Video(video) => {
+                video.filename.clone().unwrap_or_else(|| video.body.clone())
+            }
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl MediaViewer {
+    pub fn new(open_room: Entity<OpenRoom>, cx: &mut Context<Self>) -> Self {
+        cx.observe(&open_room, |this, open_room, cx| {
+            let viewed_media = open_room.read(cx).viewed_media.clone();
+            if viewed_media != this.shown_event {
+                this.shown_event = viewed_media;
+                this.needs_focus = true;
+                this.image = None;
+                this.reset_view();
+                cx.notify();
+            }
+        })
+        .detach();
+
+        Self {
+            open_room,
+            forward_popover: cx.new(|cx| ForwardPopover::new(cx)),
+            focus_handle: cx.focus_handle(),
+            shown_event: None,
+            needs_focus: false,
+            zoom: 1.,
+            pan: Point::default(),
+            drag_origin: None,
+            image: None,
+        }
+    }
+
+    /// The images and videos in the loaded part of the timeline, oldest first
+    fn media_items(&self, cx: &App) -> Vec<MediaItem> {
+        let open_room = self.open_room.read(cx);
+        let Some(timeline) = open_room.timeline.as_ref() else {
+            return Vec::new();
+        };
+
+        timeline
+            .read(cx)
+            .timeline_items()
+            .iter()
+            .filter_map(|item| {
+                let event = item.as_event()?;
+                let event_id = event.event_id()?.to_owned();
+                let TimelineItemContent::MsgLike(MsgLikeContent {
+                    kind: MsgLikeKind::Message(message),
+                    ..
+                }) = event.content()
+                else {
+                    return None;
+                };
+                if !matches!(
+                    message.msgtype(),
+                    MessageType::Image(_) | MessageType::Video(_)
+                ) {
+                    return None;
+                }
+
+                let sender = match event.sender_profile() {
+                    TimelineDetails::Ready(profile) => profile.display_name.clone(),
+                    _ => None,
+                }
+                .unwrap_or_else(|| event.sender().to_string());
+
+                Some(MediaItem {
+                    event_id,
+                    sender,
+                    msgtype: message.msgtype().clone(),
+                })
+            })
+            .collect()
+    }
+
+    fn reset_view(&mut self) {
+        self.zoom = 1.;
+        self.pan = Point::default();
+        self.drag_origin = None;
+    }
+
+    fn step(&mut self, offset: isize, cx: &mut Context<Self>) {
+        let items = self.media_items(cx);
+        let Some(index) = items
+            .iter()
+            .position(|item| Some(&item.event_id) == self.shown_event.as_ref())
+        else {
+            return;
+        };
+
+        match index.checked_add_signed(offset) {
+            Some(new_index) if new_index < items.len() => {
+                let event_id = items[new_index].event_id.clone();
+                self.open_room
+                    .update(cx, |open_room, cx| open_room.view_media(event_id, cx));
+            }
+            None => {
+                // Load older messages so that there is something to step back to
+                self.open_room
+                    .update(cx, |open_room, cx| open_room.paginate_backwards(cx));
+            }
+            _ => {}
+        }
+    }
+
+    fn set_zoom(&mut self, zoom: f32, cx: &mut Context<Self>) {
+        self.zoom = zoom.clamp(MIN_ZOOM, MAX_ZOOM);
+        cx.notify();
+    }
+
+    fn previous_media(&mut self, _: &PreviousMedia, _: &mut Window, cx: &mut Context<Self>) {
+        self.step(-1, cx);
+    }
+
+    fn next_media(&mut self, _: &NextMedia, _: &mut Window, cx: &mut Context<Self>) {
+        self.step(1, cx);
+    }
+
+    fn close(&mut self, _: &CloseMediaViewer, _: &mut Window, cx: &mut Context<Self>) {
+        self.open_room
+            .update(cx, |open_room, cx| open_room.close_media_viewer(cx));
+    }
+
+    fn zoom_in(&mut self, _: &ZoomIn, _: &mut Window, cx: &mut Context<Self>) {
+        self.set_zoom(self.zoom * ZOOM_STEP, cx);
+    }
+
+    fn zoom_out(&mut self, _: &ZoomOut, _: &mut Window, cx: &mut Context<Self>) {
+        self.set_zoom(self.zoom / ZOOM_STEP, cx);
+    }
+
+    fn reset_zoom(&mut self, _: &ResetZoom, _: &mut Window, cx: &mut Context<Self>) {
+        self.reset_view();
+        cx.notify();
+    }
+
+    fn forward(&mut self, msgtype: MessageType, cx: &mut Context<Self>) {
+        self.forward_popover.update(cx, |forward_popover, cx| {
+            forward_popover.open_forward_popover(msgtype, cx)
+        });
+        self.open_room
+            .update(cx, |open_room, cx| open_room.close_media_viewer(cx));
+    }
+
+    fn render_image(&mut self, is_failed: bool, window: &mut Window) -> AnyElement {
+        let Some(image) = self.image.clone() else {
+            return div()
+                .size_full()
+                .flex()
+                .items_center()
+                .justify_center()
+                .child(if is_failed {
+                    icon("exception").size(48.).into_any_element()
+                } else {
+                    spinner().into_any_element()
+                })
+                .into_any_element();
+        };
+
+        // Start out with the whole image on screen, without scaling it past its real size
+        let viewport = window.viewport_size();
+        let available_width = viewport.width.as_f32();
+        let available_height = viewport.height.as_f32() - TOOLBAR_HEIGHT;
+        let image_size = image.size(0);
+        let image_width = image_size.width.0 as f32;
+        let image_height = image_size.height.0 as f32;
+        let scale = (available_width / image_width)
+            .min(available_height / image_height)
+            .min(1.)
+            * self.zoom;
+        let width = image_width * scale;
+        let height = image_height * scale;
+
+        img(image)
+            .absolute()
+            .w(px(width))
+            .h(px(height))
+            .left(px((available_width - width) / 2.) + self.pan.x)
+            .top(px((available_height - height) / 2.) + self.pan.y)
+            .into_any_element()
+    }
+
+    fn render_viewer(&mut self, window: &mut Window, cx: &mut Context<Self>) -> Option<AnyElement> {
+        let items = self.media_items(cx);
+        let index = items
+            .iter()
+            .position(|item| Some(&item.event_id) == self.shown_event.as_ref())?;
+        let item = &items[index];
+
+        if self.needs_focus {
+            self.needs_focus = false;
+            self.focus_handle.focus(window, cx);
+        }
+
+        let media_file = cx.update_global::<SessionManager, _>(|session_manager, cx| {
+            let media_cache = session_manager.media();
+            let media_cache_entry = MediaCacheEntry::from(item.source());
+            match item.msgtype {
+                MessageType::Image(_) => media_cache.media_file(media_cache_entry, cx),
+                _ => media_cache.media_file_lazy(media_cache_entry, cx),
+            }
+        });
+        let loaded_path = match &media_file.read(cx).media_state {
+            MediaState::Loaded(media_file_handle) => Some(media_file_handle.path().to_path_buf()),
+            _ => None,
+        };
+        let is_failed = matches!(media_file.read(cx).media_state, MediaState::Failed);
+        if self.image.is_none() && matches!(item.msgtype, MessageType::Image(_)) {
+            self.image = media_file.read(cx).read_image().ok();
+        }
+
+        let theme = cx.global::<Theme>().clone();
+        let is_image = matches!(item.msgtype, MessageType::Image(_));
+        let has_previous = index > 0;
+        let has_next = index + 1 < items.len();
+        let locale = I18N_MANAGER.locale();
+
+        let stage = match &item.msgtype {
+            MessageType::Video(video) => div()
+                .size_full()
+                .flex()
+                .items_center()
+                .justify_center()
+                .child(video_message_item(video.clone()))
+                .into_any_element(),
+            _ => self.render_image(is_failed, window),
+        };
+
+        Some(
+            deferred(
+                anchored().position(point(px(0.), px(0.))).child(
+                    div()
+                        .id("media-viewer")
+                        .track_focus(&self.focus_handle)
+                        .key_context("MediaViewer")
+                        .on_action(cx.listener(Self::previous_media))
+                        .on_action(cx.listener(Self::next_media))
+                        .on_action(cx.listener(Self::close))
+                        .on_action(cx.listener(Self::zoom_in))
+                        .on_action(cx.listener(Self::zoom_out))
+                        .on_action(cx.listener(Self::reset_zoom))
+                        .occlude()
+                        .w(window.viewport_size().width)
+                        .h(window.viewport_size().height)
+                        .flex()
+                        .flex_col()
+                        .bg(rgba(0x000000E6))
+                        .child(
+                            div()
+                                .flex()
+                                .items_center()
+                                .h(px(TOOLBAR_HEIGHT))
+                                .px(px(8.))
+                                .gap(px(4.))
+                                .child(
+                                    div()
+                                        .flex()
+                                        .flex_col()
+                                        .flex_grow(1.)
+                                        .min_w_0()
+                                        .child(
+                                            div()
+                                                .font_weight(FontWeight::BOLD)
+                                                .child(item.sender.clone()),
+                                        )
+                                        .child(
+                                            div()
+                                                .text_color(theme.foreground.disabled())
+                                                .child(item.filename()),
+                                        ),
+                                )
+                                .when(is_image, |david| {
+                                    david
+                                        .child(
+                                            button("media-viewer-zoom-out")
+                                                .flat()
+                                                .child(icon("zoom-out"))
+                                                .tooltip(simple_tooltip(tr!(
+                                                    "MEDIA_VIEWER_ZOOM_OUT",
+                                                    "Zoom Out"
+                                                )))
+                                                .on_click(cx.listener(|this, _, window, cx| {
+                                                    this.zoom_out(&ZoomOut, window, cx)
+                                                })),
+                                        )
+                                        .child(
+                                            button("media-viewer-reset-zoom")
+                                                .flat()
+                                                .child(tr!(
+                                                    "MEDIA_VIEWER_ZOOM_LEVEL",
+                                                    "{{zoom}}%",
+                                                    zoom = locale.format_decimal(
+                                                        (self.zoom * 100.).round() as isize
+                                                    )
+                                                ))
+                                                .tooltip(simple_tooltip(tr!(
+                                                    "MEDIA_VIEWER_RESET_ZOOM",
+                                                    "Reset Zoom"
+                                                )))
+                                                .on_click(cx.listener(|this, _, window, cx| {
+                                                    this.reset_zoom(&ResetZoom, window, cx)
+                                                })),
+                                        )
+                                        .child(
+                                            button("media-viewer-zoom-in")
+                                                .flat()
+                                                .child(icon("zoom-in"))
+                                                .tooltip(simple_tooltip(tr!(
+                                                    "MEDIA_VIEWER_ZOOM_IN",
+                                                    "Zoom In"
+                                                )))
+                                                .on_click(cx.listener(|this, _, window, cx| {
+                                                    this.zoom_in(&ZoomIn, window, cx)
+                                                })),
+                                        )
+                                })
+                                .child(
+                                    button("media-viewer-save")
+                                        .flat()
+                                        .child(icon("document-save-as"))
+                                        .tooltip(simple_tooltip(tr!("FILE_SAVE_AS")))
+                                        .when_else(
+                                            loaded_path.is_some(),
+                                            |david| {
+                                                let media_file = media_file.clone();
+                                                let filename = item.filename();
+                                                david.on_click(move |_, _, cx| {
+                                                    save_file(&filename, media_file.clone(), cx)
+                                                })
+                                            },
+                                            |david| david.disabled(),
+                                        ),
+                                )
+                                .when(is_image, |david| {
+                                    david.child(
+                                        button("media-viewer-copy")
+                                            .flat()
+                                            .child(icon("edit-copy"))
+                                            .tooltip(simple_tooltip(tr!(
+                                                "MEDIA_VIEWER_COPY",
+                                                "Copy Image"
+                                            )))
+                                            .when_else(
+                                                loaded_path.is_some(),
+                                                |david| {
+                                                    let path = loaded_path.clone().unwrap();
+                                                    david.on_click(cx.listener(
+                                                        move |_, _, window, cx| {
+                                                            copy_image(path.clone(), window, cx)
+                                                        },
+                                                    ))
+                                                },
+                                                |david| david.disabled(),
+                                            ),
+                                    )
+                                })
+                                .child(
+                                    button("media-viewer-forward")
+                                        .flat()
+                                        .child(icon("mail-forward"))
+                                        .tooltip(simple_tooltip(tr!("FORWARD_TITLE")))
+                                        .on_click(cx.listener({
+                                            let msgtype = item.msgtype.clone();
+                                            move |this, _, _, cx| this.forward(msgtype.clone(), cx)
+                                        })),
+                                )
+                                .child(
+                                    button("media-viewer-close")
+                                        .flat()
+                                        .child(icon("window-close"))
+                                        .tooltip(simple_tooltip(tr!("MEDIA_VIEWER_CLOSE", "Close")))
+                                        .on_click(cx.listener(|this, _, window, cx| {
+                                            this.close(&CloseMediaViewer, window, cx)
+                                        })),
+                                ),
+                        )
+                        .child(
+                            div()
+                                .id("media-viewer-stage")
+                                .relative()
+                                .flex_grow(1.)
+                                .overflow_hidden()
+                                .when(is_image, |david| david.cursor_grab())
+                                .on_mouse_down(
+                                    MouseButton::Left,
+                                    cx.listener(|this, event: &MouseDownEvent, _, cx| {
+                                        this.drag_origin = Some(event.position - this.pan);
+                                        cx.notify()
+                                    }),
+                                )
+                                .on_mouse_move(cx.listener(
+                                    |this, event: &MouseMoveEvent, _, cx| {
+                                        if let Some(drag_origin) = this.drag_origin {
+                                            this.pan = event.position - drag_origin;
+                                            cx.notify()
+                                        }
+                                    },
+                                ))
+                                .on_mouse_up(
+                                    MouseButton::Left,
+                                    cx.listener(|this, _: &MouseUpEvent, _, _| {
+                                        this.drag_origin = None;
+                                    }),
+                                )
+                                .on_mouse_up_out(
+                                    MouseButton::Left,
+                                    cx.listener(|this, _: &MouseUpEvent, _, _| {
+                                        this.drag_origin = None;
+                                    }),
+                                )
+                                .on_scroll_wheel(cx.listener(
+                                    |this, event: &ScrollWheelEvent, _, cx| {
+                                        let delta = event.delta.pixel_delta(px(20.)).y;
+                                        if delta > px(0.) {
+                                            this.set_zoom(this.zoom * ZOOM_STEP, cx);
+                                        } else if delta < px(0.) {
+                                            this.set_zoom(this.zoom / ZOOM_STEP, cx);
+                                        }
+                                    },
+                                ))
+                                .child(stage)
+                                .when(has_previous, |david| {
+                                    david.child(
+                                        div()
+                                            .absolute()
+                                            .left(px(8.))
+                                            .top_0()
+                                            .bottom_0()
+                                            .flex()
+                                            .items_center()
+                                            .child(
+                                                button("media-viewer-previous")
+                                                    .child(icon("go-previous"))
+                                                    .on_click(cx.listener(
+                                                        |this, _, window, cx| {
+                                                            this.previous_media(
+                                                                &PreviousMedia,
+                                                                window,
+                                                                cx,
+                                                            )
+                                                        },
+                                                    )),
+                                            ),
+                                    )
+                                })
+                                .when(has_next, |david| {
+                                    david.child(
+                                        div()
+                                            .absolute()
+                                            .right(px(8.))
+                                            .top_0()
+                                            .bottom_0()
+                                            .flex()
+                                            .items_center()
+                                            .child(
+                                                button("media-viewer-next")
+                                                    .child(icon("go-next"))
+                                                    .on_click(cx.listener(
+                                                        |this, _, window, cx| {
+                                                            this.next_media(&NextMedia, window, cx)
+                                                        },
+                                                    )),
+                                            ),
+                                    )
+                                })
+                                .child(
+                                    div()
+                                        .absolute()
+                                        .bottom(px(8.))
+                                        .left_0()
+                                        .right_0()
+                                        .flex()
+                                        .justify_center()
+                                        .child(
+                                            div()
+                                                .px(px(4.))
+                                                .rounded(theme.border_radius)
+                                                .bg(rgba(0x000000C8))
+                                                .child(tr!(
+                                                    "MEDIA_VIEWER_POSITION",
+                                                    "{{position}} of {{count}}",
+                                                    position = locale.format_decimal(index + 1),
+                                                    count = locale.format_decimal(items.len())
+                                                )),
+                                        ),
+                                ),
+                        ),
+                ),
+            )
+            .with_priority(1)
+            .into_any_element(),
+        )
+    }
+}
+
+impl Render for MediaViewer {
+    fn render(&mut self, window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        div()
+            .children(self.render_viewer(window, cx))
+            .child(self.forward_popover.clone())
+    }
+}
+
+fn copy_image(path: PathBuf, window: &mut Window, cx: &mut App) {
+    let image = read(path).ok().and_then(|bytes| {
+        let format = image::guess_format(&bytes).ok()?;
+        let format = ImageFormat::from_mime_type(format.to_mime_type())?;
+        Some(Image::from_bytes(format, bytes))
+    });
+
+    match image {
+        Some(image) => cx.write_to_clipboard(ClipboardItem::new_image(&image)),
+        None => Toast::new()
+            .title(&tr!(
+                "MEDIA_VIEWER_COPY_ERROR_TITLE",
+                "Unable to copy image"
+            ))
+            .body(&tr!(
+                "MEDIA_VIEWER_COPY_ERROR_TEXT",
+                "This image can't be copied to the clipboard."
+            ))
+            .severity(AdmonitionSeverity::Error)
+            .post(window, cx),
+    }
+}
@@ -1,36 +1,58 @@
+use crate::auth::verification_popover::VerificationPopover;
 use crate::chat::chat_input::{ChatInput, PasteRichEvent};
 use crate::chat::chat_room::chat_bar::ChatBar;
+use crate::chat::chat_room::slash_commands::{
+    ParsedMessage, SlashCommandEvent, SlashCommandListener, parse_message,
+};
 use crate::chat::chat_room::timeline::Timeline;
 use crate::chat::chat_room::timeline_view::event_filter::event_filter;
 use crate::chat::displayed_room::DisplayedRoom;
 use cntp_i18n::tr;
+use contemporary::components::admonition::AdmonitionSeverity;
+use contemporary::components::toast::Toast;
 use gpui::http_client::anyhow;
 use gpui::private::anyhow;
 use gpui::{
     App, AppContext, AsyncApp, AsyncWindowContext, ClipboardEntry, Context, Entity,
-    PathPromptOptions, WeakEntity, Window,
+    PathPromptOptions, Task, WeakEntity, Window,
 };
 use log::error;
-use matrix_sdk::attachment::{AttachmentInfo, BaseFileInfo};
+use matrix_sdk::attachment::{AttachmentInfo, BaseAudioInfo, BaseFileInfo};
 use matrix_sdk::room::edit::EditedContent;
 use matrix_sdk::room::RoomMember;
 use matrix_sdk::ruma::api::client::room::aliases::v3::Response;
+use matrix_sdk::ruma::events::poll::unstable_end::UnstablePollEndEventContent;
+use matrix_sdk::ruma::events::poll::unstable_response::UnstablePollResponseEventContent;
 use matrix_sdk::ruma::events::room::canonical_alias::RoomCanonicalAliasEventContent;
 use matrix_sdk::ruma::events::room::message::RoomMessageEventContent;
+use matrix_sdk::ruma::events::sticker::StickerEventContent;
 use matrix_sdk::ruma::events::tag::Tags;
 use matrix_sdk::ruma::events::{room, Mentions, MessageLikeEventType};
-use matrix_sdk::ruma::{api, OwnedRoomAliasId, OwnedRoomId, UInt, UserId};
+use matrix_sdk::ruma::{
+    api, OwnedEventId, OwnedMxcUri, OwnedRoomAliasId, OwnedRoomId, UInt, UserId,
+};
+use matrix_sdk::store::{
+    ComposerDraft, ComposerDraftType, DraftAttachment, DraftAttachmentContent,
+};
 use matrix_sdk::{Error, HttpError, Room};
 use matrix_sdk_ui::timeline::{
-    AttachmentConfig, AttachmentSource, EventTimelineItem, RoomExt, TimelineFocus,
+    AttachmentConfig, AttachmentSource, EventTimelineItem, RoomExt, TimelineEventFocusThreadMode,
+    TimelineFocus,
 };
 use mime2ext::mime2ext;
+use std::collections::BTreeMap;
 use std::fs::read;
 use std::mem;
 use std::path::PathBuf;
+use std::rc::Rc;
+use std::time::Duration;
 use thegrid_common::room::active_call_participants::track_active_call_participants;
+use thegrid_common::session::drafts_cache::{write_draft, write_draft_attachments};
+use thegrid_common::session::image_packs_cache::PackImage;
 use thegrid_common::session::session_manager::SessionManager;
 use thegrid_common::tokio_helper::TokioHelper;
+use thegrid_rtc_livekit::voice_recorder::VoiceRecording;
+use thegrid_text_rendering::{escape_html, markdown_to_html};
 
 pub struct OpenRoom {
     pub room: Option<Room>,
@@ -42,11 +64,18 @@ pub struct OpenRoom {
     pub chat_input: Entity<ChatInput>,
     pub room_id: OwnedRoomId,
     pub chat_bar: Entity<ChatBar>,
+    pub verification_popover: Entity<VerificationPopover>,
     pub timeline: Option<Entity<Timeline>>,
     pub tags: Tags,
     pub pending_reply: Option<EventTimelineItem>,
     pub current_focus: OpenRoomFocus,
+    pub thread_panel: ThreadPanelContent,
+    pub viewed_media: Option<OwnedEventId>,
+    restored_reply: Option<OwnedEventId>,
+    draft_save_task: Option<Task<()>>,
+    draft_attachments_changed: bool,
     local_aliases: Vec<OwnedRoomAliasId>,
+    pub(super) slash_command_listener: Option<Rc<Box<SlashCommandListener>>>,
 }
 
 #[derive(Clone)]
@@ -55,12 +84,47 @@ pub struct OpenRoomFocus {
     pub reason: OpenRoomFocusReason,
 }
 
+impl OpenRoomFocus {
+    /// Focus on a message picked from the search results
+    pub fn search_result(event_id: OwnedEventId) -> Self {
+        Self {
+            timeline_focus: TimelineFocus::Event {
+                target: event_id,
+                num_context_events: 0,
+                thread_mode: TimelineEventFocusThreadMode::Automatic {
+                    hide_threaded_events: true,
+                },
+            },
+            reason: OpenRoomFocusReason::Search,
+        }
+    }
+
+    /// The root event of the thread this focus is showing, if it is showing a thread
+    pub fn thread_root(&self) -> Option<OwnedEventId> {
+        match &self.timeline_focus {
+            TimelineFocus::Thread { root_event_id } => Some(root_event_id.clone()),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Clone, PartialEq)]
 pub enum OpenRoomFocusReason {
     None,
     Reply,
+    Search,
+}
+
+#[derive(Clone)]
+pub enum ThreadPanelContent {
+    Closed,
+    ThreadList,
+    Thread(Entity<OpenRoom>),
 }
 
+/// How long to wait after the user stops typing before saving the draft
+const DRAFT_SAVE_DELAY: Duration = Duration::from_secs(1);
+
 pub struct PendingAttachment {
     pub filename: String,
     pub mime_type: String,
@@ -72,6 +136,65 @@ impl OpenRoom {
         room_id: OwnedRoomId,
         displayed_room: Entity<DisplayedRoom>,
         cx: &mut Context<Self>,
+    ) -> Self {
+        Self::new_with_focus(
+            room_id,
+            displayed_room,
+            OpenRoomFocus {
+                timeline_focus: TimelineFocus::Live {
+                    hide_threaded_events: true,
+                },
+                reason: OpenRoomFocusReason::None,
+            },
+            cx.new(VerificationPopover::new),
+            cx,
+        )
+    }
+
+    /// Open a room focused on a message picked from the search results
+    pub fn new_at_search_result(
+        room_id: OwnedRoomId,
+        displayed_room: Entity<DisplayedRoom>,
+        event_id: OwnedEventId,
+        cx: &mut Context<Self>,
+    ) -> Self {
+        Self::new_with_focus(
+            room_id,
+            displayed_room,
+            OpenRoomFocus::search_result(event_id),
+            cx.new(VerificationPopover::new),
+            cx,
+        )
+    }
+
+    /// Open a room showing only the thread rooted at `root_event_id`.
+    ///
+    /// Messages sent from a thread are sent as thread replies to the root event.
+    pub fn new_thread(
+        room_id: OwnedRoomId,
+        displayed_room: Entity<DisplayedRoom>,
+        root_event_id: OwnedEventId,
+        verification_popover: Entity<VerificationPopover>,
+        cx: &mut Context<Self>,
+    ) -> Self {
+        Self::new_with_focus(
+            room_id,
+            displayed_room,
+            OpenRoomFocus {
+                timeline_focus: TimelineFocus::Thread { root_event_id },
+                reason: OpenRoomFocusReason::None,
+            },
+            verification_popover,
+            cx,
+        )
+    }
+
+    fn new_with_focus(
+        room_id: OwnedRoomId,
+        displayed_room: Entity<DisplayedRoom>,
+        initial_focus: OpenRoomFocus,
+        verification_popover: Entity<VerificationPopover>,
+        cx: &mut Context<Self>,
     ) -> Self {
         let this_entity = cx.entity();
         let weak_this = cx.weak_entity();
@@ -102,19 +225,13 @@ impl OpenRoom {
         let client = session_manager.client().unwrap();
         let client = client.read(cx);
 
-        let initial_focus = OpenRoomFocus {
-            timeline_focus: TimelineFocus::Live {
-                hide_threaded_events: false,
-            },
-            reason: OpenRoomFocusReason::None,
-        };
-
         let mut self_return = Self {
             room: None,
             room_id: room_id.clone(),
             displayed_room,
             pending_attachments: Vec::new(),
             chat_bar,
+            verification_popover,
             current_user: None,
             typing_users: Vec::new(),
             active_call_users,
@@ -124,6 +241,12 @@ impl OpenRoom {
             pending_reply: None,
             local_aliases: Vec::new(),
             current_focus: initial_focus.clone(),
+            thread_panel: ThreadPanelContent::Closed,
+            viewed_media: None,
+            restored_reply: None,
+            draft_save_task: None,
+            draft_attachments_changed: false,
+            slash_command_listener: None,
         };
 
         let Some(room) = client.get_room(&room_id) else {
@@ -131,9 +254,22 @@ impl OpenRoom {
         };
         self_return.room = Some(room.clone());
 
+        cx.global::<SessionManager>()
+            .rooms()
+            .read(cx)
+            .subscribe_to_room(&room_id, cx);
+
         self_return.setup_acquire_own_user(cx);
         self_return.focus_timeline(initial_focus, cx);
 
+        let image_packs = cx.global::<SessionManager>().image_packs();
+        image_packs.update(cx, |image_packs, cx| image_packs.load_room_packs(&room_id, cx));
+
+        let url_previews = cx.global::<SessionManager>().url_previews();
+        url_previews.update(cx, |url_previews, cx| {
+            url_previews.load_room_setting(&room_id, cx)
+        });
+
         cx.spawn(
             async move |weak_this: WeakEntity<Self>, cx: &mut AsyncApp| {
                 let tags = cx.spawn_tokio(async move { room.tags().await }).await;
@@ -148,6 +284,10 @@ impl OpenRoom {
         .detach();
 
         self_return.update_local_aliases(cx);
+        self_return.restore_draft(cx);
+
+        // Closing the room or switching accounts shouldn't lose what the user was writing
+        cx.on_release(|this, cx| this.save_draft(cx)).detach();
 
         self_return
     }
@@ -179,6 +319,7 @@ impl OpenRoom {
 
                         this.paginate_backwards(cx);
                         this.paginate_forwards(cx);
+                        this.restore_pending_reply(cx);
                     })
                 } else {
                     weak_this.update(cx, |this, cx| {
@@ -217,6 +358,7 @@ impl OpenRoom {
                             cx.notify();
                         });
                         let _ = weak_this.update(cx, |this, cx| {
+                            this.restore_pending_reply(cx);
                             cx.notify();
                         });
                     },
@@ -321,6 +463,8 @@ impl OpenRoom {
     }
 
     pub fn text_changed(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        self.schedule_draft_save(cx);
+
         let room = self.room.clone().unwrap();
         cx.on_next_frame(window, move |_, window, cx| {
             cx.spawn(async move |_, cx: &mut AsyncApp| {
@@ -383,6 +527,8 @@ impl OpenRoom {
             }
         }
 
+        self.draft_attachments_changed = true;
+        self.schedule_draft_save(cx);
         cx.notify();
     }
 
@@ -401,25 +547,112 @@ impl OpenRoom {
             mime_type: "application/octet-stream".into(),
             data: file_contents.map_err(|e| anyhow!(e)),
         });
+        self.draft_attachments_changed = true;
+        self.schedule_draft_save(cx);
+    }
+
+    pub fn send_voice_message(&mut self, recording: VoiceRecording, cx: &mut Context<Self>) {
+        let timeline = self.timeline.clone().unwrap().read(cx).inner.clone();
+        cx.spawn(async move |_: WeakEntity<Self>, cx: &mut AsyncApp| {
+            if let Err(e) = cx
+                .spawn_tokio(async move {
+                    let attachment_config = AttachmentConfig {
+                        info: Some(AttachmentInfo::Voice(BaseAudioInfo {
+                            duration: Some(recording.duration),
+                            size: UInt::new(recording.data.len() as u64),
+                            waveform: Some(recording.waveform),
+                        })),
+                        ..Default::default()
+                    };
+
+                    timeline
+                        .send_attachment(
+                            AttachmentSource::Data {
+                                filename: "voice-message.ogg".into(),
+                                bytes: recording.data,
+                            },
+                            "audio/ogg".parse().unwrap(),
+                            attachment_config,
+                        )
+                        .await
+                })
+                .await
+            {
+                error!("Failed to send voice message: {:?}", e);
+            }
+        })
+        .detach();
     }
 
     pub fn send_pending_message(&mut self, window: &mut Window, cx: &mut Context<Self>) {
         let chat_input = self.chat_input.clone();
         let attachments = mem::take(&mut self.pending_attachments);
+        self.draft_attachments_changed = true;
 
+        // When this room is focused on a thread, the timeline adds the m.thread relation to
+        // anything sent through it, so replies and new messages both land in the thread.
         let timeline = self.timeline.clone().unwrap().read(cx).inner.clone();
         let pending_reply = self.pending_reply.take();
+        self.restored_reply = None;
 
-        cx.on_next_frame(window, move |_, _, cx| {
-            let message = chat_input.read(cx).text();
+        cx.on_next_frame(window, move |this, window, cx| {
+            let message = chat_input.read(cx).text().to_string();
             if message.is_empty() && attachments.is_empty() {
                 return;
             }
 
-            let content = if message.is_empty() {
-                None
-            } else {
-                Some(enrich_message(message))
+            let content = match parse_message(&message) {
+                ParsedMessage::Message("") => None,
+                ParsedMessage::Message(message) => {
+                    let emoticons = cx
+                        .global::<SessionManager>()
+                        .image_packs()
+                        .read(cx)
+                        .emoticons_for_room(&this.room_id);
+                    Some(enrich_message(message, &emoticons))
+                }
+                ParsedMessage::Command(command, args) => {
+                    match this.run_slash_command(command, args, window, cx) {
+                        Ok(Some(content)) => Some(content),
+                        Ok(None) => {
+                            // The command didn't send anything, so keep the attachments and
+                            // reply for the next message
+                            this.pending_attachments = attachments;
+                            this.pending_reply = pending_reply;
+                            chat_input.update(cx, |message_field, _| message_field.reset());
+                            this.save_draft(cx);
+                            cx.notify();
+                            return;
+                        }
+                        Err(error) => {
+                            this.pending_attachments = attachments;
+                            this.pending_reply = pending_reply;
+                            Toast::new()
+                                .title(&tr!("SLASH_COMMAND_ERROR_TITLE"))
+                                .body(&error)
+                                .severity(AdmonitionSeverity::Error)
+                                .post(window, cx);
+                            cx.notify();
+                            return;
+                        }
+                    }
+                }
+                ParsedMessage::UnknownCommand(name) => {
+                    this.pending_attachments = attachments;
+                    this.pending_reply = pending_reply;
+                    Toast::new()
+                        .title(&tr!("SLASH_COMMAND_ERROR_TITLE"))
+                        .body(&tr!(
+                            "SLASH_COMMAND_UNKNOWN",
+                            "/{{command}} is not a command. To send a message starting with a \
+                            slash, start it with two slashes.",
+                            command = name
+                        ))
+                        .severity(AdmonitionSeverity::Error)
+                        .post(window, cx);
+                    cx.notify();
+                    return;
+                }
             };
 
             cx.spawn(async move |_, cx: &mut AsyncApp| {
@@ -471,7 +704,8 @@ impl OpenRoom {
             })
             .detach();
 
-            chat_input.update(cx, |message_field, _| message_field.reset())
+            chat_input.update(cx, |message_field, _| message_field.reset());
+            this.save_draft(cx);
         });
 
         cx.notify();
@@ -520,6 +754,76 @@ impl OpenRoom {
         .detach();
     }
 
+    pub fn respond_to_poll(
+        &mut self,
+        event: &EventTimelineItem,
+        answers: Vec<String>,
+        cx: &mut Context<Self>,
+    ) {
+        let Some(poll_start_id) = event.event_id().map(|event_id| event_id.to_owned()) else {
+            return;
+        };
+
+        let timeline = self.timeline.clone().unwrap().read(cx).inner.clone();
+        let content = UnstablePollResponseEventContent::new(answers, poll_start_id);
+        cx.spawn(async move |_: WeakEntity<Self>, cx: &mut AsyncApp| {
+            if let Err(e) = cx
+                .spawn_tokio(async move { timeline.send(content.into()).await })
+                .await
+            {
+                error!("Failed to respond to poll: {:?}", e);
+            }
+        })
+        .detach();
+    }
+
+    pub fn end_poll(&mut self, event: &EventTimelineItem, cx: &mut Context<Self>) {
+        let Some(poll_start_id) = event.event_id().map(|event_id| event_id.to_owned()) else {
+            return;
+        };
+
+        let timeline = self.timeline.clone().unwrap().read(cx).inner.clone();
+        let content = UnstablePollEndEventContent::new(
+            tr!("POLL_END_FALLBACK", "The poll has ended").to_string(),
+            poll_start_id,
+        );
+        cx.spawn(async move |_: WeakEntity<Self>, cx: &mut AsyncApp| {
+            if let Err(e) = cx
+                .spawn_tokio(async move { timeline.send(content.into()).await })
+                .await
+            {
+                error!("Failed to end poll: {:?}", e);
+            }
+        })
+        .detach();
+    }
+
+    /// Send an image from an image pack as a sticker
+    pub fn send_sticker(&mut self, shortcode: &str, image: &PackImage, cx: &mut Context<Self>) {
+        let timeline = self.timeline.clone().unwrap().read(cx).inner.clone();
+        let content = StickerEventContent::new(
+            image.body.clone().unwrap_or_else(|| shortcode.to_string()),
+            image.info.clone().unwrap_or_default(),
+            image.url.clone(),
+        );
+        cx.spawn(async move |_: WeakEntity<Self>, cx: &mut AsyncApp| {
+            if let Err(e) = cx
+                .spawn_tokio(async move { timeline.send(content.into()).await })
+                .await
+            {
+                error!("Failed to send sticker: {:?}", e);
+            }
+        })
+        .detach();
+    }
+
+    pub fn on_slash_command(
+        &mut self,
+        listener: impl Fn(&SlashCommandEvent, &mut Window, &mut App) + 'static,
+    ) {
+        self.slash_command_listener = Some(Rc::new(Box::new(listener)));
+    }
+
     pub fn escape_press(&mut self, _: &mut Window, cx: &mut Context<Self>) {
         self.set_pending_reply(None, cx);
     }
@@ -530,12 +834,225 @@ impl OpenRoom {
             .is_none_or(|event| event.event_id().is_some())
         {
             self.pending_reply = event;
+            self.restored_reply = None;
+            self.schedule_draft_save(cx);
             cx.notify();
         }
     }
 
+    /// The message the user has started writing, or `None` if there is nothing to keep
+    fn draft(&self, cx: &App) -> Option<ComposerDraft> {
+        let plain_text = self.chat_input.read(cx).text().to_string();
+        let reply_event_id = self
+            .pending_reply
+            .as_ref()
+            .and_then(|pending_reply| pending_reply.event_id().map(|event_id| event_id.to_owned()))
+            .or_else(|| self.restored_reply.clone());
+
+        if plain_text.is_empty() && reply_event_id.is_none() && self.pending_attachments.is_empty()
+        {
+            return None;
+        }
+
+        Some(ComposerDraft {
+            plain_text,
+            html_text: None,
+            draft_type: match reply_event_id {
+                Some(event_id) => ComposerDraftType::Reply { event_id },
+                None => ComposerDraftType::NewMessage,
+            },
+            // Attachments are saved separately, only when they change
+            attachments: Vec::new(),
+        })
+    }
+
+    fn draft_attachments(&self) -> Vec<DraftAttachment> {
+        self.pending_attachments
+            .iter()
+            .filter_map(|attachment| {
+                let data = attachment.data.as_ref().ok()?;
+                Some(DraftAttachment {
+                    filename: attachment.filename.clone(),
+                    content: DraftAttachmentContent::File {
+                        data: data.clone(),
+                        mimetype: Some(attachment.mime_type.clone()),
+                        size: Some(data.len() as u64),
+                    },
+                })
+            })
+            .collect()
+    }
+
+    /// Save the draft once the user has stopped typing for a moment
+    fn schedule_draft_save(&mut self, cx: &mut Context<Self>) {
+        self.draft_save_task = Some(cx.spawn(
+            async move |weak_this: WeakEntity<Self>, cx: &mut AsyncApp| {
+                cx.background_executor().timer(DRAFT_SAVE_DELAY).await;
+                let _ = weak_this.update(cx, |this, cx| this.save_draft(cx));
+            },
+        ));
+    }
+
+    fn save_draft(&mut self, cx: &mut App) {
+        self.draft_save_task = None;
+
+        let Some(room) = self.room.clone() else {
+            return;
+        };
+        let thread_root = self.current_focus.thread_root();
+        let draft = self.draft(cx);
+
+        if mem::take(&mut self.draft_attachments_changed) {
+            write_draft_attachments(
+                room.clone(),
+                thread_root.clone(),
+                self.draft_attachments(),
+                cx,
+            );
+        }
+
+        let session_manager = cx.global::<SessionManager>();
+        let is_current_account = session_manager
+            .client()
+            .is_some_and(|client| client.read(cx).user_id() == Some(room.own_user_id()));
+        if is_current_account {
+            session_manager.drafts().update(cx, |drafts, cx| {
+                drafts.save_draft(room, thread_root, draft, cx)
+            });
+        } else {
+            write_draft(room, thread_root, draft, cx);
+        }
+    }
+
+    /// Put back the message the user was writing the last time this room was open
+    fn restore_draft(&mut self, cx: &mut Context<Self>) {
+        let Some(room) = self.room.clone() else {
+            return;
+        };
+
+        let thread_root = self.current_focus.thread_root();
+        let draft = cx
+            .global::<SessionManager>()
+            .drafts()
+            .update(cx, |drafts, cx| drafts.load_draft(room, thread_root, cx));
+        cx.spawn(
+            async move |weak_this: WeakEntity<Self>, cx: &mut AsyncApp| {
+                let Some(draft) = draft.await else {
+                    return;
+                };
+
+                let _ = weak_this.update(cx, |this, cx| {
+                    // Don't overwrite anything the user has started writing in the meantime
+                    if this.draft(cx).is_some() {
+                        return;
+                    }
+
+                    this.chat_input.update(cx, |chat_input, cx| {
+                        chat_input.set_text(&draft.plain_text);
+                        cx.notify();
+                    });
+                    this.pending_attachments = draft
+                        .attachments
+                        .into_iter()
+                        .filter_map(|attachment| match attachment.content {
+                            DraftAttachmentContent::File { data, mimetype, .. } => {
+                                Some(PendingAttachment {
+                                    filename: attachment.filename,
+                                    mime_type: mimetype
+                                        .unwrap_or_else(|| "application/octet-stream".into()),
+                                    data: Ok(data),
+                                })
+                            }
+                            _ => None,
+                        })
+                        .collect();
+                    if let ComposerDraftType::Reply { event_id } = draft.draft_type {
+                        this.restored_reply = Some(event_id);
+                        this.restore_pending_reply(cx);
+                    }
+                    cx.notify();
+                });
+            },
+        )
+        .detach();
+    }
+
+    /// Show the reply from a restored draft, once the event it replies to is in the timeline
+    fn restore_pending_reply(&mut self, cx: &mut Context<Self>) {
+        let (Some(event_id), Some(timeline)) = (self.restored_reply.clone(), &self.timeline) else {
+            return;
+        };
+
+        let timeline = timeline.read(cx).inner.clone();
+        cx.spawn(
+            async move |weak_this: WeakEntity<Self>, cx: &mut AsyncApp| {
+                let Some(event) = timeline.item_by_event_id(&event_id).await else {
+                    return;
+                };
+
+                let _ = weak_this.update(cx, |this, cx| {
+                    if this.restored_reply.as_ref() == Some(&event_id) {
+                        this.restored_reply = None;
+                        this.pending_reply = Some(event);
+                        cx.notify();
+                    }
+                });
+            },
+        )
+        .detach();
+    }
+
+    pub fn open_thread(&mut self, root_event_id: OwnedEventId, cx: &mut Context<Self>) {
+        if let ThreadPanelContent::Thread(thread) = &self.thread_panel
+            && thread.read(cx).current_focus.thread_root() == Some(root_event_id.clone())
+        {
+            return;
+        }
+
+        let room_id = self.room_id.clone();
+        let displayed_room = self.displayed_room.clone();
+        let verification_popover = self.verification_popover.clone();
+        let slash_command_listener = self.slash_command_listener.clone();
+        let thread = cx.new(|cx| {
+            let mut thread = OpenRoom::new_thread(
+                room_id,
+                displayed_room,
+                root_event_id,
+                verification_popover,
+                cx,
+            );
+            thread.slash_command_listener = slash_command_listener;
+            thread
+        });
+        self.thread_panel = ThreadPanelContent::Thread(thread);
+        cx.notify();
+    }
+
+    pub fn show_thread_list(&mut self, cx: &mut Context<Self>) {
+        self.thread_panel = ThreadPanelContent::ThreadList;
+        cx.notify();
+    }
+
+    pub fn close_thread_panel(&mut self, cx: &mut Context<Self>) {
+        self.thread_panel = ThreadPanelContent::Closed;
+        cx.notify();
+    }
+
+    /// Open the media viewer on the image or video sent in an event
+    pub fn view_media(&mut self, event_id: OwnedEventId, cx: &mut Context<Self>) {
+        self.viewed_media = Some(event_id);
+        cx.notify();
+    }
+
+    pub fn close_media_viewer(&mut self, cx: &mut Context<Self>) {
+        self.viewed_media = None;
+        cx.notify();
+    }
+
     pub fn remove_pending_attachment(&mut self, index: usize, cx: &mut Context<Self>) {
         self.pending_attachments.remove(index);
+        self.draft_attachments_changed = true;
+        self.schedule_draft_save(cx);
         cx.notify()
     }
 
@@ -729,9 +1246,15 @@ impl OpenRoom {
     }
 }
 
-pub fn enrich_message(message: &str) -> RoomMessageEventContent {
+/// Turn the text typed into the composer into message content, adding mentions and replacing the
+/// shortcodes of custom emoticons with the images they refer to.
+pub fn enrich_message(
+    message: &str,
+    emoticons: &BTreeMap<String, OwnedMxcUri>,
+) -> RoomMessageEventContent {
     let original_message = message.to_string();
     let mut sent_message = String::new();
+    let mut formatted_message = String::new();
     let mut mentions = Mentions::new();
 
     let mut last_end = 0;
@@ -740,19 +1263,43 @@ pub fn enrich_message(message: &str) -> RoomMessageEventContent {
         let previous_whitespace = &original_message[last_end..start];
         if !previous_whitespace.is_empty() {
             sent_message.push_str(previous_whitespace);
+            formatted_message.push_str(previous_whitespace);
         }
         last_end = start + part.len();
 
         if part == "@room" {
             mentions.room = true;
             sent_message.push_str(part);
+            formatted_message.push_str(part);
         } else if let Ok(user_id) = UserId::parse(part) {
-            sent_message.push_str(&format!("[{}]({})", part, user_id.matrix_to_uri()));
+            let link = format!("[{}]({})", part, user_id.matrix_to_uri());
+            sent_message.push_str(&link);
+            formatted_message.push_str(&link);
             mentions.user_ids.extend([user_id]);
+        } else if let Some(url) = part
+            .strip_prefix(':')
+            .and_then(|part| part.strip_suffix(':'))
+            .and_then(|shortcode| emoticons.get(shortcode))
+        {
+            // The plain body keeps the shortcode, which is what clients without support for
+            // custom emoticons show instead
+            sent_message.push_str(part);
+            let part = escape_html(part);
+            formatted_message.push_str(&format!(
+                r#"<img data-mx-emoticon src="{}" alt="{part}" title="{part}" height="32">"#,
+                escape_html(url.as_str())
+            ));
         } else {
             sent_message.push_str(part);
+            formatted_message.push_str(part);
         }
     }
 
-    RoomMessageEventContent::text_markdown(sent_message).add_mentions(mentions)
+    // Convert the Markdown with the same parser that renders the timeline, so messages look the
+    // same in the composer as they do once they're sent
+    match markdown_to_html(&formatted_message) {
+        Some(html) => RoomMessageEventContent::text_html(sent_message, html),
+        None => RoomMessageEventContent::text_plain(sent_message),
+    }
+    .add_mentions(mentions)
 }
@@ -0,0 +1,683 @@
+use crate::chat::chat_room::open_room::OpenRoom;
+use crate::chat::chat_room::timeline::Timeline;
+use crate::chat::chat_room::timeline_view::timeline_message_item::save_file;
+use chrono::{DateTime, Local};
+use cntp_i18n::{Quote, tr};
+use contemporary::components::button::button;
+use contemporary::components::context_menu::ContextMenuItem;
+use contemporary::components::grandstand::grandstand;
+use contemporary::components::icon::icon;
+use contemporary::components::layer::layer;
+use contemporary::components::spinner::spinner;
+use contemporary::components::subtitle::subtitle;
+use contemporary::styling::theme::{Theme, VariableColor};
+use gpui::prelude::FluentBuilder;
+use gpui::{
+    AnyElement, App, AppContext, AsyncApp, BorrowAppContext, ClickEvent, Context, ElementId,
+    Entity, InteractiveElement, IntoElement, ListAlignment, ListScrollEvent, ListState,
+    ParentElement, Render, StatefulInteractiveElement, Styled, WeakEntity, Window, div, list, px,
+    rgba,
+};
+use matrix_sdk::ruma::events::room::MediaSource;
+use matrix_sdk::ruma::events::room::message::MessageType;
+use matrix_sdk::ruma::events::{
+    AnySyncMessageLikeEvent, AnySyncTimelineEvent, SyncMessageLikeEvent,
+};
+use matrix_sdk::ruma::room_version_rules::RoomVersionRules;
+use matrix_sdk::ruma::{MilliSecondsSinceUnixEpoch, OwnedEventId};
+use matrix_sdk_ui::timeline::{
+    MsgLikeContent, MsgLikeKind, RoomExt, TimelineDetails, TimelineFocus, TimelineItemContent,
+};
+use std::rc::Rc;
+use thegrid_common::mxc_image::{SizePolicy, mxc_image};
+use thegrid_common::session::media_cache::{MediaCacheEntry, MediaFile, MediaState};
+use thegrid_common::session::session_manager::SessionManager;
+use thegrid_common::tokio_helper::TokioHelper;
+use tracing::error;
+
+/// The number of thumbnails in each row of the grid
+const GRID_COLUMNS: usize = 4;
+
+/// The size of each thumbnail in the grid
+const THUMBNAIL_SIZE: f32 = 128.;
+
+/// Keep loading older events until there are at least this many rows to show
+const MIN_ROWS: usize = 20;
+
+pub struct RoomMedia {
+    open_room: Entity<OpenRoom>,
+    on_back_click: Rc<Box<dyn Fn(&ClickEvent, &mut Window, &mut App)>>,
+
+    timeline: Option<Entity<Timeline>>,
+    entries: Vec<MediaEntry>,
+    filter: RoomMediaFilter,
+    view_mode: RoomMediaViewMode,
+    list_state: ListState,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum RoomMediaFilter {
+    All,
+    Images,
+    Videos,
+    Audio,
+    Files,
+}
+
+impl RoomMediaFilter {
+    fn matches(&self, msgtype: &MessageType) -> bool {
+        matches!(
+            (self, msgtype),
+            (RoomMediaFilter::All, _)
+                | (RoomMediaFilter::Images, MessageType::Image(_))
+                | (RoomMediaFilter::Videos, MessageType::Video(_))
+                | (RoomMediaFilter::Audio, MessageType::Audio(_))
+                | (RoomMediaFilter::Files, MessageType::File(_))
+        )
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum RoomMediaViewMode {
+    Grid,
+    List,
+}
+
+#[derive(Clone)]
+struct MediaEntry {
+    event_id: OwnedEventId,
+    sender: String,
+    timestamp: MilliSecondsSinceUnixEpoch,
+    msgtype: MessageType,
+}
+
+impl MediaEntry {
+    fn source(&self) -> MediaSource {
+        match &self.msgtype {
+            MessageType::Image(image) => image.source.clone(),
+            MessageType::Video(video) => video.source.clone(),
+            MessageType::Audio(audio) => audio.source.clone(),
+            MessageType::File(file) => file.source.clone(),
+            _ => unreachable!(),
+        }
+    }
+
+    fn thumbnail(&self) -> Option<MediaSource> {
+        match &self.msgtype {
+            MessageType::Image(image) => Some(
+                image
+                    .info
+                    .as_ref()
+                    .and_then(|info| info.thumbnail_source.clone())
+                    .unwrap_or_else(|| image.source.clone()),
+            ),
+            MessageType::Video(video) => video
+                .info
+                .as_ref()
+                .and_then(|info| info.thumbnail_source.clone()),
+            MessageType::File(file) => file
+                .info
+                .as_ref()
+                .and_then(|info| info.thumbnail_source.clone()),
+            _ => None,
+        }
+    }
+
+    fn filename(&self) -> String {
+        match &self.msgtype {
+            MessageType::Image(image) => image.filename.clone().unwrap_or(image.body.clone()),
+            MessageType::Video(video) => video.filename.clone().unwrap_or(video.body.clone()),
+            MessageType::Audio(audio) => audio.filename.clone().unwrap_or(audio.body.clone()),
+            MessageType::File(file) => file.filename().to_string(),
+            _ => unreachable!(),
+        }
+    }
+
+    fn mimetype(&self) -> Option<String> {
+        match &self.msgtype {
+            MessageType::Image(image) => image.info.as_ref()?.mimetype.clone(),
+            MessageType::Video(video) => video.info.as_ref()?.mimetype.clone(),
+            MessageType::Audio(audio) => audio.info.as_ref()?.mimetype.clone(),
+            MessageType::File(file) => file.info.as_ref()?.mimetype.clone(),
+            _ => None,
+        }
+    }
+
+    fn icon_name(&self) -> String {
+        self.mimetype()
+            .map(|mimetype| mimetype.replace("/", "-"))
+            .unwrap_or_else(|| {
+                match &self.msgtype {
+                    MessageType::Image(_) => "image-x-generic",
+                    MessageType::Video(_) => "video-x-generic",
+                    MessageType::Audio(_) => "audio-x-generic",
+                    _ => "application-octet-stream",
+                }
+                .to_string()
+            })
+    }
+}
+
+/// Only keep the events that have an attachment
+fn media_event_filter(event: &AnySyncTimelineEvent, _: &RoomVersionRules) -> bool {
+    let AnySyncTimelineEvent::MessageLike(AnySyncMessageLikeEvent::RoomMessage(
+        SyncMessageLikeEvent::Original(message),
+    )) = event
+    else {
+        return false;
+    };
+
+    matches!(
+        message.content.msgtype,
+        MessageType::Image(_)
+            | MessageType::Video(_)
+            | MessageType::Audio(_)
+            | MessageType::File(_)
+    )
+}
+
+impl RoomMedia {
+    pub fn new(
+        open_room: Entity<OpenRoom>,
+        on_back_click: impl Fn(&ClickEvent, &mut Window, &mut App) + 'static,
+        cx: &mut Context<Self>,
+    ) -> Self {
+        let list_state = ListState::new(0, ListAlignment::Top, px(200.));
+        list_state.set_scroll_handler(cx.listener(
+            |this: &mut Self, event: &ListScrollEvent, _, cx| {
+                if event.visible_range.end + 5 > this.row_count() {
+                    this.paginate(cx);
+                }
+            },
+        ));
+
+        Self {
+            open_room,
+            on_back_click: Rc::new(Box::new(on_back_click)),
+            timeline: None,
+            entries: Vec::new(),
+            filter: RoomMediaFilter::All,
+            view_mode: RoomMediaViewMode::Grid,
+            list_state,
+        }
+    }
+
+    /// Start loading the media in the room, if it hasn't been loaded already
+    pub fn load(&mut self, cx: &mut Context<Self>) {
+        if self.timeline.is_some() {
+            return;
+        }
+        let Some(room) = self.open_room.read(cx).room.clone() else {
+            return;
+        };
+
+        cx.spawn(
+            async move |weak_this: WeakEntity<Self>, cx: &mut AsyncApp| {
+                let timeline = cx
+                    .spawn_tokio(async move {
+                        room.timeline_builder()
+                            .event_filter(media_event_filter)
+                            .with_focus(TimelineFocus::Live {
+                                hide_threaded_events: false,
+                            })
+                            .build()
+                            .await
+                    })
+                    .await;
+
+                let timeline = match timeline {
+                    Ok(timeline) => timeline,
+                    Err(e) => {
+                        error!("Unable to load room media: {e:?}");
+                        return;
+                    }
+                };
+
+                let _ = weak_this.update(cx, |this, cx| {
+                    let timeline_entity = cx.new(|cx| Timeline::new(timeline, cx));
+                    cx.observe(&timeline_entity, |this, _, cx| {
+                        this.update_entries(cx);
+                    })
+                    .detach();
+                    this.timeline = Some(timeline_entity);
+                    this.paginate(cx);
+                    cx.notify();
+                });
+            },
+        )
+        .detach();
+    }
+
+    fn paginate(&mut self, cx: &mut Context<Self>) {
+        let Some(timeline) = self.timeline.as_ref() else {
+            return;
+        };
+
+        timeline.update(cx, |timeline, cx| {
+            if timeline.back_pagination_pending || timeline.pagination_at_top {
+                return;
+            }
+
+            timeline.back_pagination_pending = true;
+
+            let timeline = timeline.inner.clone();
+            cx.spawn(
+                async move |weak_timeline: WeakEntity<Timeline>, cx: &mut AsyncApp| {
+                    let pagination_at_top = cx
+                        .spawn_tokio(async move { timeline.paginate_backwards(50).await })
+                        .await
+                        .unwrap_or_else(|e| {
+                            error!("Failed to paginate room media: {}", e);
+                            false
+                        });
+                    let _ = weak_timeline.update(cx, |timeline, cx| {
+                        timeline.pagination_at_top = pagination_at_top;
+                        timeline.back_pagination_pending = false;
+                        cx.notify();
+                    });
+                },
+            )
+            .detach();
+        });
+    }
+
+    fn update_entries(&mut self, cx: &mut Context<Self>) {
+        let Some(timeline) = self.timeline.as_ref() else {
+            return;
+        };
+
+        // Show the newest media first
+        self.entries = timeline
+            .read(cx)
+            .timeline_items()
+            .iter()
+            .rev()
+            .filter_map(|item| {
+                let event = item.as_event()?;
+                let event_id = event.event_id()?.to_owned();
+                let TimelineItemContent::MsgLike(MsgLikeContent {
+                    kind: MsgLikeKind::Message(message),
+                    ..
+                }) = event.content()
+                else {
+                    return None;
+                };
+                if !self.filter.matches(message.msgtype()) {
+                    return None;
+                }
+
+                let sender = match event.sender_profile() {
+                    TimelineDetails::Ready(profile) => profile.display_name.clone(),
+                    _ => None,
+                }
+                .unwrap_or_else(|| event.sender().to_string());
+
+                Some(MediaEntry {
+                    event_id,
+                    sender,
+                    timestamp: event.timestamp(),
+                    msgtype: message.msgtype().clone(),
+                })
+            })
+            .collect();
+
+        let row_count = self.row_count();
+        if self.list_state.item_count() != row_count {
+            self.list_state.reset(row_count);
+        }
+
+        if row_count < MIN_ROWS {
+            self.paginate(cx);
+        }
+        cx.notify();
+    }
+
+    fn row_count(&self) -> usize {
+        match self.view_mode {
+            RoomMediaViewMode::Grid => self.entries.len().div_ceil(GRID_COLUMNS),
+            RoomMediaViewMode::List => self.entries.len(),
+        }
+    }
+
+    fn set_filter(&mut self, filter: RoomMediaFilter, cx: &mut Context<Self>) {
+        self.filter = filter;
+        self.update_entries(cx);
+    }
+
+    fn set_view_mode(&mut self, view_mode: RoomMediaViewMode, cx: &mut Context<Self>) {
+        self.view_mode = view_mode;
+        self.list_state.reset(self.row_count());
+        cx.notify();
+    }
+
+    fn render_row(&mut self, i: usize, _: &mut Window, cx: &mut Context<Self>) -> AnyElement {
+        match self.view_mode {
+            RoomMediaViewMode::Grid => {
+                let start = i * GRID_COLUMNS;
+                let end = (start + GRID_COLUMNS).min(self.entries.len());
+                div()
+                    .flex()
+                    .gap(px(4.))
+                    .py(px(2.))
+                    .children(
+                        self.entries[start..end]
+                            .iter()
+                            .map(|entry| render_tile(entry, cx)),
+                    )
+                    .into_any_element()
+            }
+            RoomMediaViewMode::List => render_list_item(&self.entries[i], cx),
+        }
+    }
+}
+
+fn render_tile(entry: &MediaEntry, cx: &mut App) -> AnyElement {
+    let theme = cx.global::<Theme>().clone();
+
+    div()
+        .id(ElementId::Name(entry.event_id.to_string().into()))
+        .relative()
+        .flex_none()
+        .size(px(THUMBNAIL_SIZE))
+        .rounded(theme.border_radius)
+        .bg(theme.layer_background)
+        .overflow_hidden()
+        .child(match entry.thumbnail() {
+            Some(thumbnail) => mxc_image(thumbnail)
+                .fixed_square(px(THUMBNAIL_SIZE))
+                .size_policy(SizePolicy::Fit)
+                .into_any_element(),
+            None => div()
+                .size_full()
+                .flex()
+                .flex_col()
+                .items_center()
+                .justify_center()
+                .p(px(4.))
+                .gap(px(4.))
+                .child(icon(entry.icon_name()).size(48.))
+                .child(
+                    div()
+                        .w_full()
+                        .text_center()
+                        .text_size(theme.system_font_size * 0.8)
+                        .truncate()
+                        .child(entry.filename()),
+                )
+                .into_any_element(),
+        })
+        .child(
+            div()
+                .absolute()
+                .bottom(px(4.))
+                .right(px(4.))
+                .rounded(theme.border_radius)
+                .bg(rgba(0x000000C8))
+                .child(media_actions(entry, cx)),
+        )
+        .into_any_element()
+}
+
+fn render_list_item(entry: &MediaEntry, cx: &mut App) -> AnyElement {
+    let theme = cx.global::<Theme>().clone();
+    let timestamp = DateTime::from_timestamp_millis(entry.timestamp.get().into())
+        .unwrap_or_default()
+        .with_timezone(&Local);
+
+    div()
+        .id(ElementId::Name(entry.event_id.to_string().into()))
+        .flex()
+        .items_center()
+        .w_full()
+        .my(px(2.))
+        .p(px(2.))
+        .gap(px(4.))
+        .rounded(theme.border_radius)
+        .hover(|david| david.bg(theme.background.hover()))
+        .child(match entry.thumbnail() {
+            Some(thumbnail) => mxc_image(thumbnail)
+                .fixed_square(px(40.))
+                .size_policy(SizePolicy::Fit)
+                .rounded(theme.border_radius)
+                .into_any_element(),
+            None => div()
+                .flex()
+                .flex_none()
+                .items_center()
+                .justify_center()
+                .size(px(40.))
+                .child(icon(entry.icon_name()).size(24.))
+                .into_any_element(),
+        })
+        .child(
+            div()
+                .flex()
+                .flex_col()
+                .flex_grow(1.)
+                .min_w_0()
+                .child(div().truncate().child(entry.filename()))
+                .child(
+                    div()
+                        .text_color(theme.foreground.disabled())
+                        .text_size(theme.system_font_size * 0.8)
+                        .child(tr!(
+                            "ROOM_MEDIA_SENT_BY",
+                            "{{sender}} · {{timestamp}}",
+                            sender = entry.sender.clone(),
+                            timestamp:date("YMDT", length="medium")=timestamp
+                        )),
+                ),
+        )
+        .child(media_actions(entry, cx))
+        .into_any_element()
+}
+
+/// The buttons to download, open and save an attachment
+fn media_actions(entry: &MediaEntry, cx: &mut App) -> AnyElement {
+    let media_file = cx.update_global::<SessionManager, _>(|session_manager, cx| {
+        let media_cache = session_manager.media();
+        media_cache.media_file_lazy(MediaCacheEntry::from(entry.source()), cx)
+    });
+    let filename = entry.filename();
+
+    match media_file.read(cx).media_state {
+        MediaState::Idle | MediaState::Failed => button("download-button")
+            .flat()
+            .child(icon("cloud-download"))
+            .on_click({
+                let mimetype = entry.mimetype();
+                move |_, _, cx| download_media(filename.clone(), mimetype.clone(), &media_file, cx)
+            })
+            .into_any_element(),
+        MediaState::Loading => div()
+            .p(px(4.))
+            .child(spinner().size(px(16.)))
+            .into_any_element(),
+        MediaState::Loaded(_) => button("open-button")
+            .flat()
+            .child(icon("document-open"))
+            .with_menu(vec![
+                ContextMenuItem::separator()
+                    .label(tr!(
+                        "FILE_OPEN_MENU_HEADER",
+                        filename:Quote = filename.clone()
+                    ))
+                    .build(),
+                ContextMenuItem::menu_item()
+                    .label(tr!("FILE_OPEN"))
+                    .icon("document-open")
+                    .on_triggered({
+                        let media_file = media_file.clone();
+                        move |_, _, cx| {
+                            let MediaState::Loaded(media_file) = &media_file.read(cx).media_state
+                            else {
+                                return;
+                            };
+
+                            cx.open_with_system(media_file.path())
+                        }
+                    })
+                    .build(),
+                ContextMenuItem::menu_item()
+                    .label(tr!("FILE_SAVE_AS"))
+                    .icon("document-save-as")
+                    .on_triggered(move |_, _, cx| save_file(&filename, media_file.clone(), cx))
+                    .build(),
+            ])
+            .into_any_element(),
+    }
+}
+
+fn download_media(
+    filename: String,
+    mimetype: Option<String>,
+    media_file: &Entity<MediaFile>,
+    cx: &mut App,
+) {
+    media_file.update(cx, |media_file, cx| {
+        media_file.request_media(Some(filename), mimetype, true, cx);
+    })
+}
+
+impl Render for RoomMedia {
+    fn render(&mut self, _: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let on_back_click = self.on_back_click.clone();
+        let theme = cx.global::<Theme>();
+
+        let (is_paginating, at_top) = self
+            .timeline
+            .as_ref()
+            .map(|timeline| {
+                let timeline = timeline.read(cx);
+                (timeline.back_pagination_pending, timeline.pagination_at_top)
+            })
+            .unwrap_or((true, false));
+
+        let filter_button = |id: &'static str, label, filter: RoomMediaFilter| {
+            button(id)
+                .child(label)
+                .checked_when(self.filter == filter)
+                .on_click(cx.listener(move |this, _, _, cx| this.set_filter(filter, cx)))
+        };
+        let view_mode_button = |id: &'static str, label, view_mode: RoomMediaViewMode| {
+            button(id)
+                .child(label)
+                .checked_when(self.view_mode == view_mode)
+                .on_click(cx.listener(move |this, _, _, cx| this.set_view_mode(view_mode, cx)))
+        };
+
+        div()
+            .flex()
+            .flex_col()
+            .bg(theme.background)
+            .size_full()
+            .child(
+                grandstand("room-media-grandstand")
+                    .text(tr!("ROOM_MEDIA", "Media & Files"))
+                    .pt(px(36.))
+                    .on_back_click(move |event, window, cx| {
+                        on_back_click.clone()(event, window, cx);
+                    }),
+            )
+            .child(
+                div()
+                    .flex()
+                    .justify_center()
+                    .size_full()
+                    .gap(px(8.))
+                    .child(
+                        div()
+                            .flex()
+                            .flex_col()
+                            .p(px(4.))
+                            .gap(px(4.))
+                            .child(
+                                layer()
+                                    .p(px(8.))
+                                    .gap(px(8.))
+                                    .child(subtitle(tr!("FILTERS")))
+                                    .child(
+                                        div()
+                                            .flex()
+                                            .flex_col()
+                                            .bg(theme.button_background)
+                                            .rounded(theme.border_radius)
+                                            .child(filter_button(
+                                                "filter-all",
+                                                tr!("ROOM_MEDIA_FILTER_ALL", "All"),
+                                                RoomMediaFilter::All,
+                                            ))
+                                            .child(filter_button(
+                                                "filter-images",
+                                                tr!("ROOM_MEDIA_FILTER_IMAGES", "Images"),
+                                                RoomMediaFilter::Images,
+                                            ))
+                                            .child(filter_button(
+                                                "filter-videos",
+                                                tr!("ROOM_MEDIA_FILTER_VIDEOS", "Videos"),
+                                                RoomMediaFilter::Videos,
+                                            ))
+                                            .child(filter_button(
+                                                "filter-audio",
+                                                tr!("ROOM_MEDIA_FILTER_AUDIO", "Audio"),
+                                                RoomMediaFilter::Audio,
+                                            ))
+                                            .child(filter_button(
+                                                "filter-files",
+                                                tr!("ROOM_MEDIA_FILTER_FILES", "Files"),
+                                                RoomMediaFilter::Files,
+                                            )),
+                                    ),
+                            )
+                            .child(
+                                layer()
+                                    .p(px(8.))
+                                    .gap(px(8.))
+                                    .child(subtitle(tr!("ROOM_MEDIA_VIEW", "View")))
+                                    .child(
+                                        div()
+                                            .flex()
+                                            .flex_col()
+                                            .bg(theme.button_background)
+                                            .rounded(theme.border_radius)
+                                            .child(view_mode_button(
+                                                "view-grid",
+                                                tr!("ROOM_MEDIA_VIEW_GRID", "Grid"),
+                                                RoomMediaViewMode::Grid,
+                                            ))
+                                            .child(view_mode_button(
+                                                "view-list",
+                                                tr!("ROOM_MEDIA_VIEW_LIST", "List"),
+                                                RoomMediaViewMode::List,
+                                            )),
+                                    ),
+                            ),
+                    )
+                    .child(
+                        div()
+                            .flex()
+                            .flex_col()
+                            .max_w(px(600.))
+                            .size_full()
+                            .px(px(8.))
+                            .gap(px(8.))
+                            .when(self.entries.is_empty() && at_top, |david| {
+                                david.child(div().text_color(theme.foreground.disabled()).child(
+                                    tr!(
+                                        "ROOM_MEDIA_EMPTY",
+                                        "Nothing has been shared in this room yet."
+                                    ),
+                                ))
+                            })
+                            .child(
+                                list(self.list_state.clone(), cx.processor(Self::render_row))
+                                    .size_full(),
+                            )
+                            .when(is_paginating, |david| {
+                                david
+                                    .child(div().flex().justify_center().p(px(4.)).child(spinner()))
+                            }),
+                    ),
+            )
+    }
+}
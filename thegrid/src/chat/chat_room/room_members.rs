@@ -3,6 +3,7 @@ use crate::chat::chat_room::open_room::OpenRoom;
 use crate::chat::chat_room::timeline_view::author_flyout::{
     AuthorFlyoutUserActionEvent, AuthorFlyoutUserActionListener, author_flyout,
 };
+use crate::chat::chat_room::timeline_view::identity_shield::identity_shield;
 use crate::chat::displayed_room::DisplayedRoom;
 use cntp_i18n::tr;
 use contemporary::components::anchorer::WithAnchorer;
@@ -184,6 +185,7 @@ impl RoomMembers {
                                     .map(|name| name.to_string())
                                     .unwrap_or_else(|| member.user_id().to_string()),
                             )
+                            .child(identity_shield(member.user_id().to_owned()))
                             .when(
                                 is_founder && *member.membership() == MembershipState::Join,
                                 |david| {
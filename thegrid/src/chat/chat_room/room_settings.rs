@@ -1,12 +1,19 @@
+mod image_pack_editor;
 mod room_replace_popover;
 
+use crate::account_settings::notifications_settings::{
+    notification_mode_text, room_notification_mode_menu,
+};
 use crate::chat::chat_room::open_room::OpenRoom;
+use crate::chat::chat_room::room_settings::image_pack_editor::ImagePackEditor;
 use crate::chat::chat_room::room_settings::room_replace_popover::{
     RoomReplaceEvent, RoomReplacePopover,
 };
 use crate::chat::displayed_room::DisplayedRoom;
+use crate::chat::sidebar::sidebar_sections::{SectionDialog, report_section_errors};
 use crate::upload_mxc_dialog::{UploadMxcAcceptEvent, upload_mxc_dialog};
 use cntp_i18n::{I18nString, tr};
+use contemporary::components::admonition::AdmonitionSeverity;
 use contemporary::components::button::{ButtonMenuOpenPolicy, button};
 use contemporary::components::constrainer::constrainer;
 use contemporary::components::context_menu::ContextMenuItem;
@@ -16,14 +23,14 @@ use contemporary::components::icon::icon;
 use contemporary::components::icon_text::icon_text;
 use contemporary::components::layer::layer;
 use contemporary::components::subtitle::subtitle;
-use contemporary::components::switch::switch;
+use contemporary::components::switch::{SwitchChangeEvent, switch};
 use contemporary::components::text_field::TextField;
 use contemporary::components::toast::Toast;
 use contemporary::styling::theme::{Theme, VariableColor};
 use gpui::prelude::FluentBuilder;
 use gpui::{
-    App, AppContext, AsyncApp, ClickEvent, Context, ElementId, Entity, InteractiveElement,
-    IntoElement, ParentElement, Render, Styled, WeakEntity, Window, div, px,
+    App, AppContext, AsyncApp, AsyncWindowContext, ClickEvent, Context, ElementId, Entity,
+    InteractiveElement, IntoElement, ParentElement, Render, Styled, WeakEntity, Window, div, px,
 };
 use matrix_sdk::ruma::api::client::room::Visibility;
 use matrix_sdk::ruma::events::room::avatar::ImageInfo;
@@ -33,12 +40,15 @@ use matrix_sdk::ruma::{OwnedRoomAliasId, RoomAliasId, UInt};
 use std::rc::Rc;
 use thegrid_common::mxc_image::{SizePolicy, mxc_image};
 use thegrid_common::session::session_manager::SessionManager;
+use thegrid_common::session::sidebar_sections_cache::section_name;
 use thegrid_common::tokio_helper::TokioHelper;
+use tracing::error;
 
 pub struct RoomSettings {
     open_room: Entity<OpenRoom>,
     on_back_click: Rc<Box<dyn Fn(&ClickEvent, &mut Window, &mut App)>>,
     on_members_click: Rc<Box<dyn Fn(&ClickEvent, &mut Window, &mut App)>>,
+    on_media_click: Rc<Box<dyn Fn(&ClickEvent, &mut Window, &mut App)>>,
     edit_room_name_open: bool,
     edit_room_topic_open: bool,
     edit_room_image_open: bool,
@@ -50,6 +60,9 @@ pub struct RoomSettings {
 
     add_alias_open: bool,
     add_alias_text_field: Entity<TextField>,
+
+    section_dialog: Entity<SectionDialog>,
+    image_pack_editor: Entity<ImagePackEditor>,
 }
 
 impl RoomSettings {
@@ -57,6 +70,7 @@ impl RoomSettings {
         open_room: Entity<OpenRoom>,
         on_back_click: impl Fn(&ClickEvent, &mut Window, &mut App) + 'static,
         on_members_click: impl Fn(&ClickEvent, &mut Window, &mut App) + 'static,
+        on_media_click: impl Fn(&ClickEvent, &mut Window, &mut App) + 'static,
         cx: &mut Context<RoomSettings>,
     ) -> Self {
         cx.observe(&open_room, |_, open_room, cx| {
@@ -80,10 +94,24 @@ impl RoomSettings {
         })
         .detach();
 
+        let notification_settings = cx.global::<SessionManager>().notification_settings();
+        cx.observe(&notification_settings, |_, _, cx| cx.notify())
+            .detach();
+
+        let sidebar_sections = cx.global::<SessionManager>().sidebar_sections();
+        cx.observe(&sidebar_sections, |_, _, cx| cx.notify())
+            .detach();
+
+        let url_previews = cx.global::<SessionManager>().url_previews();
+        cx.observe(&url_previews, |_, _, cx| cx.notify()).detach();
+
+        let image_pack_editor = cx.new(|cx| ImagePackEditor::new(open_room.clone(), cx));
+
         Self {
             open_room,
             on_back_click: Rc::new(Box::new(on_back_click)),
             on_members_click: Rc::new(Box::new(on_members_click)),
+            on_media_click: Rc::new(Box::new(on_media_click)),
 
             new_name_text_field: cx.new(|cx| {
                 let mut text_field = TextField::new("new-name", cx);
@@ -118,6 +146,9 @@ impl RoomSettings {
                 text_field.set_placeholder(tr!("ALIAS_PLACEHOLDER", "alias").to_string().as_str());
                 text_field
             }),
+
+            section_dialog: cx.new(|cx| SectionDialog::new(cx)),
+            image_pack_editor,
         }
     }
 
@@ -641,6 +672,221 @@ impl RoomSettings {
             )
     }
 
+    fn render_room_notifications(&mut self, cx: &mut Context<Self>) -> impl IntoElement {
+        let session_manager = cx.global::<SessionManager>();
+        let room = self.open_room.read(cx).room.as_ref().unwrap();
+        let room_id = room.room_id().to_owned();
+
+        let current_mode = session_manager
+            .rooms()
+            .read(cx)
+            .room(&room_id)
+            .and_then(|room| room.read(cx).notification_mode());
+        let user_defined_mode = session_manager
+            .notification_settings()
+            .read(cx)
+            .room_mode(&room_id);
+
+        layer()
+            .flex()
+            .flex_col()
+            .p(px(8.))
+            .w_full()
+            .child(subtitle(tr!("ROOM_NOTIFICATIONS")))
+            .child(
+                layer()
+                    .p(px(4.))
+                    .gap(px(4.))
+                    .items_center()
+                    .flex()
+                    .child(tr!("ROOM_NOTIFICATIONS_MODE", "Notify me for"))
+                    .child(div().flex_grow(1.))
+                    .child(match (&user_defined_mode, &current_mode) {
+                        (Some(mode), _) => notification_mode_text(mode),
+                        (None, Some(mode)) => tr!(
+                            "ROOM_NOTIFICATIONS_MODE_DEFAULT",
+                            "Default ({{mode}})",
+                            mode = notification_mode_text(mode).to_string()
+                        ),
+                        (None, None) => tr!("ROOM_NOTIFICATIONS_MODE_UNKNOWN", "Default"),
+                    })
+                    .child(
+                        button("change-room-notification-mode")
+                            .child(icon("arrow-down"))
+                            .with_menu(room_notification_mode_menu(
+                                room_id,
+                                user_defined_mode.clone().or(current_mode),
+                                user_defined_mode,
+                            )),
+                    ),
+            )
+    }
+
+    fn render_room_sections(&mut self, cx: &mut Context<Self>) -> impl IntoElement {
+        let session_manager = cx.global::<SessionManager>();
+        let room = self.open_room.read(cx).room.as_ref().unwrap();
+        let room_id = room.room_id().to_owned();
+
+        let room_tags = session_manager
+            .rooms()
+            .read(cx)
+            .room(&room_id)
+            .map(|room| room.read(cx).user_tags())
+            .unwrap_or_default();
+        let sections = session_manager.sidebar_sections().read(cx).sections(cx);
+
+        layer()
+            .flex()
+            .flex_col()
+            .p(px(8.))
+            .w_full()
+            .child(subtitle(tr!("SIDEBAR_SECTIONS")))
+            .child(tr!(
+                "ROOM_SECTIONS_DESCRIPTION",
+                "Sections group rooms together in the sidebar. A room can be in more than one \
+                section."
+            ))
+            .child(
+                div()
+                    .flex()
+                    .flex_col()
+                    .gap(px(4.))
+                    .children(sections.into_iter().enumerate().map(|(i, section)| {
+                        let in_section = room_tags.contains(&section);
+                        layer()
+                            .p(px(4.))
+                            .gap(px(4.))
+                            .items_center()
+                            .flex()
+                            .child(section_name(&section).to_string())
+                            .child(div().flex_grow(1.))
+                            .child(
+                                switch(ElementId::Name(format!("room-section-{i}").into()))
+                                    .when(in_section, |david| david.checked())
+                                    .on_change(cx.listener(
+                                        move |this, event: &SwitchChangeEvent, window, cx| {
+                                            let room =
+                                                this.open_room.read(cx).room.clone().unwrap();
+                                            let sidebar_sections = cx
+                                                .global::<SessionManager>()
+                                                .sidebar_sections();
+                                            let task = sidebar_sections.update(
+                                                cx,
+                                                |sidebar_sections, cx| {
+                                                    if event.checked {
+                                                        sidebar_sections
+                                                            .add_room(&section, room, cx)
+                                                    } else {
+                                                        sidebar_sections
+                                                            .remove_room(&section, room, cx)
+                                                    }
+                                                },
+                                            );
+                                            report_section_errors(task, window, cx);
+                                        },
+                                    )),
+                            )
+                    }))
+                    .child(
+                        button("new-room-section")
+                            .child(icon_text("list-add", tr!("SIDEBAR_SECTION_NEW")))
+                            .on_click(cx.listener(move |this, _, _, cx| {
+                                this.section_dialog.update(cx, |section_dialog, cx| {
+                                    section_dialog.open_create(&room_id, cx)
+                                })
+                            })),
+                    ),
+            )
+    }
+
+    fn set_url_previews_enabled(
+        &mut self,
+        enabled: bool,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let room = self.open_room.read(cx).room.clone().unwrap();
+        let url_previews = cx.global::<SessionManager>().url_previews();
+        let task = url_previews.update(cx, |url_previews, cx| {
+            url_previews.set_room_enabled(room, enabled, cx)
+        });
+
+        window
+            .spawn(cx, async move |cx: &mut AsyncWindowContext| {
+                if let Err(e) = task.await {
+                    error!("Unable to change link preview setting: {e:?}");
+                    let _ = cx.update(|window, cx| {
+                        Toast::new()
+                            .title(&tr!(
+                                "URL_PREVIEWS_ERROR_TITLE",
+                                "Unable to change link preview setting"
+                            ))
+                            .body(&tr!(
+                                "URL_PREVIEWS_ERROR_TEXT",
+                                "The link preview setting could not be saved."
+                            ))
+                            .severity(AdmonitionSeverity::Error)
+                            .post(window, cx);
+                    });
+                }
+            })
+            .detach();
+    }
+
+    fn render_url_previews(&mut self, cx: &mut Context<Self>) -> impl IntoElement {
+        let session_manager = cx.global::<SessionManager>();
+        let room = self.open_room.read(cx).room.as_ref().unwrap();
+        let url_previews = session_manager.url_previews().read(cx);
+        let enabled = url_previews.previews_enabled(room);
+        let globally_enabled = url_previews.globally_enabled();
+        let is_encrypted = room.encryption_state().is_encrypted();
+        let theme = cx.global::<Theme>();
+
+        layer()
+            .flex()
+            .flex_col()
+            .p(px(8.))
+            .w_full()
+            .child(subtitle(tr!("URL_PREVIEWS", "Link Previews")))
+            .child(if is_encrypted {
+                tr!(
+                    "ROOM_URL_PREVIEWS_ENCRYPTED_DESCRIPTION",
+                    "Link previews are off by default in encrypted rooms, because your homeserver \
+                    needs to see the links to generate previews for them."
+                )
+            } else {
+                tr!(
+                    "ROOM_URL_PREVIEWS_DESCRIPTION",
+                    "Show a preview of the first link in each message in this room."
+                )
+            })
+            .when(!globally_enabled, |david| {
+                david.child(div().text_color(theme.foreground.disabled()).child(tr!(
+                    "ROOM_URL_PREVIEWS_GLOBALLY_DISABLED",
+                    "Link previews are turned off for all rooms in the Security settings."
+                )))
+            })
+            .child(
+                layer()
+                    .p(px(4.))
+                    .gap(px(4.))
+                    .items_center()
+                    .flex()
+                    .child(tr!("ROOM_URL_PREVIEWS_SHOW", "Show link previews in this room"))
+                    .child(div().flex_grow(1.))
+                    .child(
+                        switch("room-url-previews")
+                            .when(enabled, |david| david.checked())
+                            .when(!globally_enabled, |david| david.disabled())
+                            .on_change(cx.listener(
+                                |this, event: &SwitchChangeEvent, window, cx| {
+                                    this.set_url_previews_enabled(event.checked, window, cx)
+                                },
+                            )),
+                    ),
+            )
+    }
+
     fn render_room_replace(
         &mut self,
         window: &mut Window,
@@ -873,6 +1119,16 @@ impl Render for RoomSettings {
                                                 (this.on_members_click)(event, window, cx);
                                             })),
                                     )
+                                    .child(
+                                        button("room-view-media")
+                                            .child(icon_text(
+                                                "folder-images",
+                                                tr!("ROOM_VIEW_MEDIA", "Media & Files"),
+                                            ))
+                                            .on_click(cx.listener(|this, event, window, cx| {
+                                                (this.on_media_click)(event, window, cx);
+                                            })),
+                                    )
                                     .when(
                                         !room.encryption_state().is_encrypted() && !is_space,
                                         |david| {
@@ -1013,6 +1269,13 @@ impl Render for RoomSettings {
                                     ),
                             ),
                     )
+                    .when(!is_space, |david| {
+                        david
+                            .child(self.render_room_notifications(cx))
+                            .child(self.render_room_sections(cx))
+                            .child(self.image_pack_editor.clone())
+                            .child(self.render_url_previews(cx))
+                    })
                     .child(self.render_room_aliases(window, cx))
                     .child(self.render_room_replace(window, cx)),
             )
@@ -1264,5 +1527,6 @@ impl Render for RoomSettings {
                             })),
                     ),
             )
+            .child(self.section_dialog.clone())
     }
 }
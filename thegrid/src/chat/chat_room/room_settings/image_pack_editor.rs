@@ -0,0 +1,308 @@
+use crate::chat::chat_room::open_room::OpenRoom;
+use crate::upload_mxc_dialog::{UploadMxcAcceptEvent, upload_mxc_dialog};
+use cntp_i18n::{Quote, tr};
+use contemporary::components::admonition::AdmonitionSeverity;
+use contemporary::components::button::button;
+use contemporary::components::context_menu::ContextMenuItem;
+use contemporary::components::icon::icon;
+use contemporary::components::icon_text::icon_text;
+use contemporary::components::layer::layer;
+use contemporary::components::subtitle::subtitle;
+use contemporary::components::text_field::TextField;
+use contemporary::components::toast::Toast;
+use gpui::prelude::FluentBuilder;
+use gpui::private::anyhow;
+use gpui::{
+    App, AppContext, AsyncWindowContext, Context, Entity, IntoElement, ParentElement, Render,
+    Styled, Task, Window, div, px,
+};
+use matrix_sdk::ruma::UInt;
+use matrix_sdk::ruma::events::StateEventType;
+use matrix_sdk::ruma::events::room::ImageInfo;
+use thegrid_common::mxc_image::{SizePolicy, mxc_image};
+use thegrid_common::session::image_packs_cache::{PackImage, PackUsage, RoomImagePackEventContent};
+use thegrid_common::session::session_manager::SessionManager;
+use tracing::error;
+
+/// The state key of the image pack that is edited from the room settings
+const ROOM_PACK_STATE_KEY: &str = "";
+
+/// Editor for the custom emoji and stickers that are shared with everyone in a room
+pub struct ImagePackEditor {
+    open_room: Entity<OpenRoom>,
+    shortcode_field: Entity<TextField>,
+    add_image_open: bool,
+}
+
+impl ImagePackEditor {
+    pub fn new(open_room: Entity<OpenRoom>, cx: &mut Context<Self>) -> Self {
+        let image_packs = cx.global::<SessionManager>().image_packs();
+        cx.observe(&image_packs, |_, _, cx| cx.notify()).detach();
+
+        Self {
+            open_room,
+            shortcode_field: cx.new(|cx| {
+                let mut text_field = TextField::new("image-pack-shortcode", cx);
+                text_field.set_placeholder(
+                    tr!("IMAGE_PACK_SHORTCODE_PLACEHOLDER", "Shortcode")
+                        .to_string()
+                        .as_str(),
+                );
+                text_field
+            }),
+            add_image_open: false,
+        }
+    }
+
+    fn pack_content(&self, cx: &App) -> RoomImagePackEventContent {
+        let room_id = self.open_room.read(cx).room_id.clone();
+        cx.global::<SessionManager>()
+            .image_packs()
+            .read(cx)
+            .room_pack_content(&room_id, ROOM_PACK_STATE_KEY)
+    }
+
+    fn save_pack(
+        &mut self,
+        content: RoomImagePackEventContent,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let Some(room) = self.open_room.read(cx).room.clone() else {
+            return;
+        };
+
+        let image_packs = cx.global::<SessionManager>().image_packs();
+        let task = image_packs.update(cx, |image_packs, cx| {
+            image_packs.save_room_pack(room, ROOM_PACK_STATE_KEY.to_string(), content, cx)
+        });
+        report_image_pack_errors(task, window, cx);
+    }
+
+    fn add_image(
+        &mut self,
+        event: &UploadMxcAcceptEvent,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let shortcode = self
+            .shortcode_field
+            .read(cx)
+            .text()
+            .trim()
+            .trim_matches(':')
+            .to_string();
+        if shortcode.is_empty() {
+            return;
+        }
+
+        let mut info = ImageInfo::new();
+        info.height = UInt::new(event.height);
+        info.width = UInt::new(event.width);
+        info.mimetype = Some(event.mime_type.clone());
+        info.size = UInt::new(event.file_size);
+
+        let mut content = self.pack_content(cx);
+        content.images.insert(
+            shortcode,
+            PackImage {
+                url: event.mxc_url.clone(),
+                body: None,
+                info: Some(info),
+                usage: Vec::new(),
+            },
+        );
+
+        self.add_image_open = false;
+        self.shortcode_field
+            .update(cx, |shortcode_field, _| shortcode_field.set_text(""));
+        self.save_pack(content, window, cx);
+        cx.notify();
+    }
+
+    fn set_image_usage(
+        &mut self,
+        shortcode: &str,
+        usage: Vec<PackUsage>,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let mut content = self.pack_content(cx);
+        let Some(image) = content.images.get_mut(shortcode) else {
+            return;
+        };
+        image.usage = usage;
+        self.save_pack(content, window, cx);
+    }
+
+    fn remove_image(&mut self, shortcode: &str, window: &mut Window, cx: &mut Context<Self>) {
+        let mut content = self.pack_content(cx);
+        if content.images.remove(shortcode).is_none() {
+            return;
+        }
+        self.save_pack(content, window, cx);
+    }
+}
+
+fn usage_text(usage: &[PackUsage]) -> String {
+    match usage {
+        [PackUsage::Emoticon] => tr!("IMAGE_PACK_USAGE_EMOTICON", "Emoji").to_string(),
+        [PackUsage::Sticker] => tr!("IMAGE_PACK_USAGE_STICKER", "Sticker").to_string(),
+        _ => tr!("IMAGE_PACK_USAGE_ANY", "Emoji and Sticker").to_string(),
+    }
+}
+
+/// Show a toast if a change to an image pack fails
+fn report_image_pack_errors(task: Task<anyhow::Result<()>>, window: &mut Window, cx: &mut App) {
+    window
+        .spawn(cx, async move |cx: &mut AsyncWindowContext| {
+            if let Err(e) = task.await {
+                error!("Unable to update image pack: {e:?}");
+                let _ = cx.update(|window, cx| {
+                    Toast::new()
+                        .title(&tr!(
+                            "IMAGE_PACK_ERROR_TITLE",
+                            "Unable to update custom emoji"
+                        ))
+                        .body(&tr!(
+                            "IMAGE_PACK_ERROR_TEXT",
+                            "The change to the custom emoji and stickers in this room could not \
+                            be saved."
+                        ))
+                        .severity(AdmonitionSeverity::Error)
+                        .post(window, cx);
+                });
+            }
+        })
+        .detach();
+}
+
+impl Render for ImagePackEditor {
+    fn render(&mut self, _: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let open_room = self.open_room.read(cx);
+        let can_edit = open_room
+            .current_user
+            .as_ref()
+            .is_some_and(|user| user.can_send_state(StateEventType::from("im.ponies.room_emotes")));
+        let content = self.pack_content(cx);
+
+        layer()
+            .flex()
+            .flex_col()
+            .p(px(8.))
+            .w_full()
+            .child(subtitle(tr!(
+                "IMAGE_PACK_ROOM_TITLE",
+                "Custom Emoji and Stickers"
+            )))
+            .child(tr!(
+                "IMAGE_PACK_ROOM_DESCRIPTION",
+                "Custom emoji and stickers added here can be used by everyone in this room."
+            ))
+            .child(
+                div()
+                    .flex()
+                    .flex_col()
+                    .gap(px(4.))
+                    .children(content.images.into_iter().enumerate().map(
+                        |(i, (shortcode, image))| {
+                            let usage_menu: Vec<_> = [
+                                Vec::new(),
+                                vec![PackUsage::Emoticon],
+                                vec![PackUsage::Sticker],
+                            ]
+                            .into_iter()
+                            .map(|usage| {
+                                let shortcode = shortcode.clone();
+                                ContextMenuItem::menu_item()
+                                    .label(usage_text(&usage))
+                                    .when(usage == image.usage, |david| david.icon("dialog-ok"))
+                                    .on_triggered(cx.listener(move |this, _, window, cx| {
+                                        this.set_image_usage(&shortcode, usage.clone(), window, cx)
+                                    }))
+                                    .build()
+                            })
+                            .collect();
+
+                            layer()
+                                .p(px(4.))
+                                .gap(px(4.))
+                                .items_center()
+                                .flex()
+                                .child(
+                                    mxc_image(image.url.clone())
+                                        .fixed_square(px(32.))
+                                        .size_policy(SizePolicy::Fit),
+                                )
+                                .child(format!(":{shortcode}:"))
+                                .child(div().flex_grow(1.))
+                                .child(usage_text(&image.usage))
+                                .when(can_edit, |david| {
+                                    david
+                                        .child(
+                                            button(("image-pack-usage", i))
+                                                .child(icon("arrow-down"))
+                                                .with_menu(usage_menu),
+                                        )
+                                        .child(
+                                            button(("image-pack-remove", i))
+                                                .destructive()
+                                                .child(icon("edit-delete"))
+                                                .on_click(cx.listener(
+                                                    move |this, _, window, cx| {
+                                                        this.remove_image(&shortcode, window, cx)
+                                                    },
+                                                )),
+                                        )
+                                })
+                        },
+                    ))
+                    .when(can_edit, |david| {
+                        david.child(
+                            div()
+                                .flex()
+                                .gap(px(4.))
+                                .child(div().flex_grow(1.).child(self.shortcode_field.clone()))
+                                .child(
+                                    button("image-pack-add")
+                                        .child(icon_text(
+                                            "list-add",
+                                            tr!("IMAGE_PACK_ADD", "Add Image..."),
+                                        ))
+                                        .on_click(cx.listener(|this, _, _, cx| {
+                                            // The shortcode is needed to add the image
+                                            // once it has been uploaded
+                                            let shortcode = this.shortcode_field.read(cx).text();
+                                            if shortcode.trim().trim_matches(':').is_empty() {
+                                                return;
+                                            }
+
+                                            this.add_image_open = true;
+                                            cx.notify()
+                                        })),
+                                ),
+                        )
+                    }),
+            )
+            .child(upload_mxc_dialog(
+                tr!(
+                    "IMAGE_PACK_ADD_TITLE",
+                    "Add {{shortcode}}",
+                    shortcode:Quote=format!(
+                        ":{}:",
+                        self.shortcode_field.read(cx).text().trim().trim_matches(':')
+                    )
+                ),
+                self.add_image_open,
+                "list-add".into(),
+                tr!("IMAGE_PACK_ADD_ACCEPT", "Add").into(),
+                cx.listener(|this, _, _, cx| {
+                    this.add_image_open = false;
+                    cx.notify()
+                }),
+                cx.listener(|this, event: &UploadMxcAcceptEvent, window, cx| {
+                    this.add_image(event, window, cx)
+                }),
+            ))
+    }
+}
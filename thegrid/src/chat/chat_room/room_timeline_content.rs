@@ -1,8 +1,13 @@
 use crate::chat::chat_room::attachments_view::AttachmentsView;
 use crate::chat::chat_room::call_members_view::CallMembersView;
-use crate::chat::chat_room::open_room::{OpenRoom, OpenRoomFocus, OpenRoomFocusReason};
+use crate::chat::chat_room::open_room::{
+    OpenRoom, OpenRoomFocus, OpenRoomFocusReason, ThreadPanelContent,
+};
+use crate::chat::chat_room::thread_panel::ThreadPanel;
 use crate::chat::chat_room::timeline_view::TimelineView;
-use crate::chat::chat_room::timeline_view::author_flyout::AuthorFlyoutUserActionEvent;
+use crate::chat::chat_room::timeline_view::author_flyout::{
+    AuthorFlyoutUserActionEvent, AuthorFlyoutUserActionListener,
+};
 use crate::chat::displayed_room::DisplayedRoom;
 use cntp_i18n::tr;
 use contemporary::components::admonition::admonition;
@@ -23,6 +28,7 @@ use thegrid_common::surfaces::{
 pub struct RoomTimelineContent {
     open_room: Entity<OpenRoom>,
     timeline_view: Entity<TimelineView>,
+    thread_panel: Entity<ThreadPanel>,
 
     on_surface_change: Rc<Box<SurfaceChangeHandler>>,
 }
@@ -35,18 +41,24 @@ impl RoomTimelineContent {
         on_user_action: impl Fn(&AuthorFlyoutUserActionEvent, &mut Window, &mut App) + 'static,
         cx: &mut Context<Self>,
     ) -> Self {
+        let on_user_action: Rc<Box<AuthorFlyoutUserActionListener>> =
+            Rc::new(Box::new(on_user_action));
         let timeline_view = cx.new(|cx| {
+            let on_user_action = on_user_action.clone();
             TimelineView::new(
                 open_room.clone(),
                 displayed_room.clone(),
-                on_user_action,
+                move |event, window, cx| on_user_action(event, window, cx),
                 cx,
             )
         });
+        let thread_panel =
+            cx.new(|cx| ThreadPanel::new(open_room.clone(), displayed_room, on_user_action, cx));
 
         Self {
             open_room,
             timeline_view,
+            thread_panel,
             on_surface_change,
         }
     }
@@ -70,8 +82,9 @@ impl Render for RoomTimelineContent {
         let call_members = open_room.active_call_users.read(cx).clone();
         let pending_attachments = &open_room.pending_attachments;
         let chat_bar = open_room.chat_bar.clone();
+        let thread_panel_open = !matches!(open_room.thread_panel, ThreadPanelContent::Closed);
 
-        div()
+        let main_area = div()
             .flex()
             .flex_col()
             .flex_grow(1.)
@@ -110,16 +123,17 @@ impl Render for RoomTimelineContent {
                     )
                 },
             )
-            .when(focus.reason == OpenRoomFocusReason::Reply, |david| {
+            .when(focus.reason != OpenRoomFocusReason::None, |david| {
                 david.child(
                     div()
                         .flex()
                         .items_center()
-                        .child(
-                            div()
-                                .flex_grow(1.)
-                                .child(tr!("FOCUS_REASON_REPLY", "Viewing a reply")),
-                        )
+                        .child(div().flex_grow(1.).child(match focus.reason {
+                            OpenRoomFocusReason::Search => {
+                                tr!("FOCUS_REASON_SEARCH", "Viewing a search result")
+                            }
+                            _ => tr!("FOCUS_REASON_REPLY", "Viewing a reply"),
+                        }))
                         .child(
                             button("to-present-button")
                                 .child(icon_text("arrow-down", tr!("TO_PRESENT", "To Present")))
@@ -130,7 +144,7 @@ impl Render for RoomTimelineContent {
                                             open_room.focus_timeline(
                                                 OpenRoomFocus {
                                                     timeline_focus: TimelineFocus::Live {
-                                                        hide_threaded_events: false,
+                                                        hide_threaded_events: true,
                                                     },
                                                     reason: OpenRoomFocusReason::None,
                                                 },
@@ -156,6 +170,12 @@ impl Render for RoomTimelineContent {
                             }
                         });
                     })),
-            )
+            );
+
+        div()
+            .flex()
+            .flex_grow(1.)
+            .child(main_area)
+            .when(thread_panel_open, |david| david.child(self.thread_panel.clone()))
     }
 }
@@ -0,0 +1,380 @@
+use crate::chat::chat_room::open_room::OpenRoom;
+use chrono::{DateTime, Local};
+use cntp_i18n::tr;
+use contemporary::components::button::button;
+use contemporary::components::spinner::spinner;
+use contemporary::components::text_field::TextField;
+use contemporary::styling::theme::{Theme, VariableColor};
+use gpui::prelude::FluentBuilder;
+use gpui::{
+    App, AppContext, AsyncApp, Context, Entity, InteractiveElement, IntoElement, ParentElement,
+    Render, StatefulInteractiveElement, Styled, WeakEntity, Window, div, px,
+};
+use log::error;
+use matrix_sdk::ruma::{OwnedEventId, OwnedMxcUri, OwnedRoomId, OwnedUserId};
+use std::collections::HashMap;
+use std::rc::Rc;
+use thegrid_common::mxc_image::{SizePolicy, mxc_image};
+use thegrid_common::session::search_index::{IndexedMessage, search_server};
+use thegrid_common::session::session_manager::SessionManager;
+use thegrid_common::tokio_helper::TokioHelper;
+
+pub type SearchResultSelectedListener =
+    dyn Fn(&SearchResultSelectedEvent, &mut Window, &mut App) + 'static;
+
+#[derive(Clone)]
+pub struct SearchResultSelectedEvent {
+    pub room_id: OwnedRoomId,
+    pub event_id: OwnedEventId,
+}
+
+pub struct SearchFlyout {
+    open_room: Entity<OpenRoom>,
+    search_field: Entity<TextField>,
+    scope: SearchScope,
+    results: Vec<SearchResult>,
+    searching: bool,
+    search_generation: usize,
+
+    result_selected_listener: Option<Rc<Box<SearchResultSelectedListener>>>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum SearchScope {
+    CurrentRoom,
+    AllRooms,
+}
+
+struct SearchResult {
+    message: IndexedMessage,
+    room_name: String,
+    sender_display_name: Option<String>,
+    sender_avatar: Option<OwnedMxcUri>,
+}
+
+impl SearchFlyout {
+    pub fn new(open_room: Entity<OpenRoom>, cx: &mut Context<Self>) -> Self {
+        let search_enter_listener = cx.listener(|this: &mut Self, _, window, cx| {
+            cx.defer_in(window, |this, _, cx| this.search(cx));
+        });
+
+        Self {
+            open_room,
+            search_field: cx.new(|cx| {
+                let mut text_field = TextField::new("search-field", cx);
+                text_field.set_placeholder(&tr!("SEARCH").to_string().as_str());
+                text_field.on_enter_press(search_enter_listener);
+                text_field
+            }),
+            scope: SearchScope::CurrentRoom,
+            results: Vec::new(),
+            searching: false,
+            search_generation: 0,
+            result_selected_listener: None,
+        }
+    }
+
+    pub fn set_result_selected_listener(
+        &mut self,
+        listener: impl Fn(&SearchResultSelectedEvent, &mut Window, &mut App) + 'static,
+    ) {
+        self.result_selected_listener = Some(Rc::new(Box::new(listener)));
+    }
+
+    fn set_scope(&mut self, scope: SearchScope, cx: &mut Context<Self>) {
+        if self.scope != scope {
+            self.scope = scope;
+            self.search(cx);
+        }
+    }
+
+    fn search(&mut self, cx: &mut Context<Self>) {
+        let query = self.search_field.read(cx).text().trim().to_string();
+        let Some(room) = self.open_room.read(cx).room.clone() else {
+            return;
+        };
+
+        self.search_generation += 1;
+        let search_generation = self.search_generation;
+
+        if query.is_empty() {
+            self.results = Vec::new();
+            self.searching = false;
+            cx.notify();
+            return;
+        }
+
+        let session_manager = cx.global::<SessionManager>();
+        let client = session_manager.client().unwrap().read(cx).clone();
+        let (local_results, server_rooms) = match self.scope {
+            SearchScope::CurrentRoom => (
+                session_manager
+                    .search_index()
+                    .read(cx)
+                    .search(Some(room.room_id()), &query),
+                vec![room],
+            ),
+            SearchScope::AllRooms => (
+                session_manager.search_index().read(cx).search(None, &query),
+                client.joined_rooms(),
+            ),
+        };
+
+        self.searching = true;
+        cx.notify();
+
+        cx.spawn(
+            async move |weak_this: WeakEntity<Self>, cx: &mut AsyncApp| {
+                let results = cx
+                    .spawn_tokio(async move {
+                        let mut results = local_results;
+
+                        // The homeserver can't read encrypted rooms, so only the local index is
+                        // able to search them
+                        let server_room_ids = server_rooms
+                            .iter()
+                            .filter(|room| !room.encryption_state().is_encrypted())
+                            .map(|room| room.room_id().to_owned())
+                            .collect::<Vec<_>>();
+                        if !server_room_ids.is_empty() {
+                            match search_server(&client, server_room_ids, query).await {
+                                Ok(server_results) => {
+                                    for server_result in server_results {
+                                        if !results.iter().any(|result| {
+                                            result.event_id == server_result.event_id
+                                        }) {
+                                            results.push(server_result);
+                                        }
+                                    }
+                                }
+                                Err(e) => {
+                                    error!("Server side search failed: {:?}", e);
+                                }
+                            }
+                        }
+                        results.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+
+                        let mut members = HashMap::<(OwnedRoomId, OwnedUserId), _>::new();
+                        let mut search_results = Vec::new();
+                        for message in results {
+                            let Some(room) = client.get_room(&message.room_id) else {
+                                continue;
+                            };
+
+                            let member_key = (message.room_id.clone(), message.sender.clone());
+                            if !members.contains_key(&member_key) {
+                                let member =
+                                    room.get_member_no_sync(&message.sender).await.ok().flatten();
+                                members.insert(member_key.clone(), member);
+                            }
+                            let member = members.get(&member_key).cloned().flatten();
+
+                            search_results.push(SearchResult {
+                                room_name: room
+                                    .cached_display_name()
+                                    .map(|name| name.to_string())
+                                    .or_else(|| room.name())
+                                    .unwrap_or_default(),
+                                sender_display_name: member.as_ref().and_then(|member| {
+                                    member.display_name().map(|name| name.to_string())
+                                }),
+                                sender_avatar: member.as_ref().and_then(|member| {
+                                    member.avatar_url().map(|url| url.to_owned())
+                                }),
+                                message,
+                            });
+                        }
+
+                        Ok::<_, matrix_sdk::Error>(search_results)
+                    })
+                    .await;
+
+                let _ = weak_this.update(cx, |this, cx| {
+                    if this.search_generation != search_generation {
+                        return;
+                    }
+
+                    this.searching = false;
+                    match results {
+                        Ok(results) => this.results = results,
+                        Err(e) => error!("Search failed: {:?}", e),
+                    }
+                    cx.notify();
+                });
+            },
+        )
+        .detach();
+    }
+}
+
+impl Render for SearchFlyout {
+    fn render(&mut self, _: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let theme = cx.global::<Theme>();
+        let is_encrypted = self
+            .open_room
+            .read(cx)
+            .room
+            .as_ref()
+            .is_some_and(|room| room.encryption_state().is_encrypted());
+        let all_rooms = self.scope == SearchScope::AllRooms;
+
+        div()
+            .bg(theme.background)
+            .rounded(theme.border_radius)
+            .w(px(400.))
+            .h(px(500.))
+            .border(px(1.))
+            .border_color(theme.border_color)
+            .occlude()
+            .flex()
+            .flex_col()
+            .child(self.search_field.clone())
+            .child(
+                div().flex().justify_center().p(px(4.)).child(
+                    div()
+                        .flex()
+                        .bg(theme.button_background)
+                        .rounded(theme.border_radius)
+                        .child(
+                            button("search-scope-current-room")
+                                .child(tr!("SEARCH_SCOPE_CURRENT_ROOM", "This Room"))
+                                .checked_when(!all_rooms)
+                                .on_click(cx.listener(|this, _, _, cx| {
+                                    this.set_scope(SearchScope::CurrentRoom, cx);
+                                })),
+                        )
+                        .child(
+                            button("search-scope-all-rooms")
+                                .child(tr!("SEARCH_SCOPE_ALL_ROOMS", "All Rooms"))
+                                .checked_when(all_rooms)
+                                .on_click(cx.listener(|this, _, _, cx| {
+                                    this.set_scope(SearchScope::AllRooms, cx);
+                                })),
+                        ),
+                ),
+            )
+            .when(is_encrypted || all_rooms, |david| {
+                david.child(
+                    div()
+                        .p(px(4.))
+                        .text_color(theme.foreground.disabled())
+                        .text_size(theme.system_font_size * 0.8)
+                        .child(tr!(
+                            "SEARCH_ENCRYPTED_ROOM",
+                            "Only messages that have been loaded on this device can be searched \
+                            in encrypted rooms."
+                        )),
+                )
+            })
+            .when(self.searching, |david| {
+                david.child(div().flex().justify_center().p(px(4.)).child(spinner()))
+            })
+            .when(
+                !self.searching
+                    && self.results.is_empty()
+                    && !self.search_field.read(cx).text().is_empty(),
+                |david| {
+                    david.child(
+                        div()
+                            .flex()
+                            .justify_center()
+                            .p(px(4.))
+                            .text_color(theme.foreground.disabled())
+                            .child(tr!("SEARCH_NO_RESULTS", "No messages found")),
+                    )
+                },
+            )
+            .child(
+                div()
+                    .id("search-results")
+                    .flex()
+                    .flex_col()
+                    .flex_grow(1.)
+                    .overflow_y_scroll()
+                    .p(px(4.))
+                    .gap(px(4.))
+                    .children(self.results.iter().enumerate().map(|(i, result)| {
+                        let timestamp = DateTime::from_timestamp_millis(
+                            result.message.timestamp.get().into(),
+                        )
+                        .unwrap_or_default()
+                        .with_timezone(&Local);
+
+                        div()
+                            .id(i)
+                            .flex()
+                            .gap(px(4.))
+                            .p(px(4.))
+                            .rounded(theme.border_radius)
+                            .bg(theme.layer_background)
+                            .cursor_pointer()
+                            .child(
+                                mxc_image(result.sender_avatar.clone())
+                                    .fallback_image(&result.message.sender)
+                                    .fixed_square(px(24.))
+                                    .size_policy(SizePolicy::Fit)
+                                    .rounded(theme.border_radius),
+                            )
+                            .child(
+                                div()
+                                    .flex()
+                                    .flex_col()
+                                    .flex_grow(1.)
+                                    .overflow_hidden()
+                                    .child(
+                                        div()
+                                            .flex()
+                                            .gap(px(4.))
+                                            .child(
+                                                div().flex_grow(1.).child(
+                                                    result
+                                                        .sender_display_name
+                                                        .clone()
+                                                        .unwrap_or_else(|| {
+                                                            result.message.sender.to_string()
+                                                        }),
+                                                ),
+                                            )
+                                            .when(all_rooms, |david| {
+                                                david.child(
+                                                    div()
+                                                        .text_color(theme.foreground.disabled())
+                                                        .text_size(theme.system_font_size * 0.8)
+                                                        .child(result.room_name.clone()),
+                                                )
+                                            })
+                                            .child(
+                                                div()
+                                                    .text_color(theme.foreground.disabled())
+                                                    .text_size(theme.system_font_size * 0.8)
+                                                    .child(tr!(
+                                                        "SEARCH_RESULT_TIMESTAMP",
+                                                        "{{timestamp}}",
+                                                        timestamp:date("YMDT", length="medium")=timestamp
+                                                    )),
+                                            ),
+                                    )
+                                    .child(result.message.body.clone()),
+                            )
+                            .on_click(cx.listener({
+                                let room_id = result.message.room_id.clone();
+                                let event_id = result.message.event_id.clone();
+                                move |this, _, window, cx| {
+                                    if let Some(result_selected_listener) =
+                                        &this.result_selected_listener
+                                    {
+                                        result_selected_listener(
+                                            &SearchResultSelectedEvent {
+                                                room_id: room_id.clone(),
+                                                event_id: event_id.clone(),
+                                            },
+                                            window,
+                                            cx,
+                                        );
+                                    }
+                                }
+                            }))
+                    })),
+            )
+    }
+}
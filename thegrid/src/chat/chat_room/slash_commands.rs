@@ -0,0 +1,609 @@
+use crate::chat::chat_room::open_room::{OpenRoom, enrich_message};
+use crate::chat::chat_room::timeline_view::author_flyout::{
+    AuthorFlyoutUserActionEvent, UserAction,
+};
+use crate::chat::displayed_room::DisplayedRoom;
+use cntp_i18n::tr;
+use contemporary::components::admonition::AdmonitionSeverity;
+use contemporary::components::toast::Toast;
+use gpui::private::anyhow;
+use gpui::{App, AsyncWindowContext, Context, Rgba, WeakEntity, Window, hsla};
+use log::error;
+use matrix_sdk::ruma::events::room::message::{
+    EmoteMessageEventContent, MessageType, RoomMessageEventContent,
+};
+use matrix_sdk::ruma::{OwnedRoomOrAliasId, OwnedUserId, UserId};
+use thegrid_common::session::session_manager::SessionManager;
+use thegrid_common::tokio_helper::TokioHelper;
+use thegrid_text_rendering::{escape_html, html_to_plain_text};
+
+pub type SlashCommandListener = dyn Fn(&SlashCommandEvent, &mut Window, &mut App) + 'static;
+
+/// Something a slash command needs the chat room to do on its behalf
+pub enum SlashCommandEvent {
+    UserAction(AuthorFlyoutUserActionEvent),
+    OpenDevtools,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SlashCommand {
+    Me,
+    Shrug,
+    Plain,
+    Html,
+    Rainbow,
+    Spoiler,
+    Topic,
+    Nick,
+    MyRoomNick,
+    Invite,
+    Kick,
+    Ban,
+    Op,
+    Deop,
+    Ignore,
+    Join,
+    Part,
+    ConvertToDm,
+    Devtools,
+}
+
+/// A message typed into the chat input, split into a command and its arguments if it is one
+#[derive(PartialEq, Eq, Debug)]
+pub enum ParsedMessage<'a> {
+    Message(&'a str),
+    Command(SlashCommand, &'a str),
+    UnknownCommand(&'a str),
+}
+
+impl SlashCommand {
+    pub const ALL: [SlashCommand; 19] = [
+        SlashCommand::Me,
+        SlashCommand::Shrug,
+        SlashCommand::Plain,
+        SlashCommand::Html,
+        SlashCommand::Rainbow,
+        SlashCommand::Spoiler,
+        SlashCommand::Topic,
+        SlashCommand::Nick,
+        SlashCommand::MyRoomNick,
+        SlashCommand::Invite,
+        SlashCommand::Kick,
+        SlashCommand::Ban,
+        SlashCommand::Op,
+        SlashCommand::Deop,
+        SlashCommand::Ignore,
+        SlashCommand::Join,
+        SlashCommand::Part,
+        SlashCommand::ConvertToDm,
+        SlashCommand::Devtools,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SlashCommand::Me => "me",
+            SlashCommand::Shrug => "shrug",
+            SlashCommand::Plain => "plain",
+            SlashCommand::Html => "html",
+            SlashCommand::Rainbow => "rainbow",
+            SlashCommand::Spoiler => "spoiler",
+            SlashCommand::Topic => "topic",
+            SlashCommand::Nick => "nick",
+            SlashCommand::MyRoomNick => "myroomnick",
+            SlashCommand::Invite => "invite",
+            SlashCommand::Kick => "kick",
+            SlashCommand::Ban => "ban",
+            SlashCommand::Op => "op",
+            SlashCommand::Deop => "deop",
+            SlashCommand::Ignore => "ignore",
+            SlashCommand::Join => "join",
+            SlashCommand::Part => "part",
+            SlashCommand::ConvertToDm => "converttodm",
+            SlashCommand::Devtools => "devtools",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<SlashCommand> {
+        Self::ALL
+            .into_iter()
+            .find(|command| command.name().eq_ignore_ascii_case(name))
+    }
+
+    /// The arguments this command takes, for showing in the autocomplete list
+    pub fn arguments(&self) -> Option<String> {
+        match self {
+            SlashCommand::Me
+            | SlashCommand::Plain
+            | SlashCommand::Html
+            | SlashCommand::Rainbow
+            | SlashCommand::Spoiler => {
+                Some(tr!("SLASH_COMMAND_ARGUMENT_MESSAGE", "<message>").to_string())
+            }
+            SlashCommand::Shrug => {
+                Some(tr!("SLASH_COMMAND_ARGUMENT_OPTIONAL_MESSAGE", "[message]").to_string())
+            }
+            SlashCommand::Topic => Some(tr!("SLASH_COMMAND_ARGUMENT_TOPIC", "<topic>").to_string()),
+            SlashCommand::Nick | SlashCommand::MyRoomNick => {
+                Some(tr!("SLASH_COMMAND_ARGUMENT_DISPLAY_NAME", "<display name>").to_string())
+            }
+            SlashCommand::Invite
+            | SlashCommand::Kick
+            | SlashCommand::Ban
+            | SlashCommand::Op
+            | SlashCommand::Deop
+            | SlashCommand::Ignore => {
+                Some(tr!("SLASH_COMMAND_ARGUMENT_USER", "<user>").to_string())
+            }
+            SlashCommand::Join => {
+                Some(tr!("SLASH_COMMAND_ARGUMENT_ROOM", "<room address>").to_string())
+            }
+            SlashCommand::Part | SlashCommand::ConvertToDm | SlashCommand::Devtools => None,
+        }
+    }
+
+    pub fn requires_arguments(&self) -> bool {
+        self.arguments().is_some() && *self != SlashCommand::Shrug
+    }
+
+    pub fn description(&self) -> String {
+        match self {
+            SlashCommand::Me => tr!("SLASH_COMMAND_ME", "Send an action"),
+            SlashCommand::Shrug => tr!("SLASH_COMMAND_SHRUG", "Prepend ¯\\_(ツ)_/¯ to a message"),
+            SlashCommand::Plain => tr!(
+                "SLASH_COMMAND_PLAIN",
+                "Send a message as plain text, without interpreting Markdown"
+            ),
+            SlashCommand::Html => tr!("SLASH_COMMAND_HTML", "Send a message as HTML"),
+            SlashCommand::Rainbow => {
+                tr!(
+                    "SLASH_COMMAND_RAINBOW",
+                    "Send a message coloured like a rainbow"
+                )
+            }
+            SlashCommand::Spoiler => tr!("SLASH_COMMAND_SPOILER", "Send a message as a spoiler"),
+            SlashCommand::Topic => tr!("SLASH_COMMAND_TOPIC", "Change the topic of this room"),
+            SlashCommand::Nick => tr!("SLASH_COMMAND_NICK", "Change your display name"),
+            SlashCommand::MyRoomNick => tr!(
+                "SLASH_COMMAND_MYROOMNICK",
+                "Change your display name in this room only"
+            ),
+            SlashCommand::Invite => tr!("SLASH_COMMAND_INVITE", "Invite a user to this room"),
+            SlashCommand::Kick => tr!("SLASH_COMMAND_KICK", "Kick a user from this room"),
+            SlashCommand::Ban => tr!("SLASH_COMMAND_BAN", "Ban a user from this room"),
+            SlashCommand::Op => tr!("SLASH_COMMAND_OP", "Change the power level of a user"),
+            SlashCommand::Deop => tr!(
+                "SLASH_COMMAND_DEOP",
+                "Reset the power level of a user to the default"
+            ),
+            SlashCommand::Ignore => tr!(
+                "SLASH_COMMAND_IGNORE",
+                "Ignore a user, hiding their messages from you"
+            ),
+            SlashCommand::Join => tr!("SLASH_COMMAND_JOIN", "Join a room"),
+            SlashCommand::Part => tr!("SLASH_COMMAND_PART", "Leave this room"),
+            SlashCommand::ConvertToDm => tr!(
+                "SLASH_COMMAND_CONVERTTODM",
+                "Mark this room as a direct message"
+            ),
+            SlashCommand::Devtools => tr!(
+                "SLASH_COMMAND_DEVTOOLS",
+                "Show developer information about this room"
+            ),
+        }
+        .to_string()
+    }
+}
+
+/// Work out whether a message is a slash command.
+///
+/// Messages starting with `//` are sent as messages with the first slash removed, so that
+/// messages starting with a slash can still be sent.
+pub fn parse_message(message: &str) -> ParsedMessage<'_> {
+    if message.starts_with("//") {
+        return ParsedMessage::Message(&message[1..]);
+    }
+
+    let Some(command_line) = message.strip_prefix('/') else {
+        return ParsedMessage::Message(message);
+    };
+
+    let (name, args) = command_line
+        .split_once(char::is_whitespace)
+        .unwrap_or((command_line, ""));
+    match SlashCommand::from_name(name) {
+        Some(command) => ParsedMessage::Command(command, args.trim()),
+        None => ParsedMessage::UnknownCommand(name),
+    }
+}
+
+impl OpenRoom {
+    /// Run a slash command typed into the chat input.
+    ///
+    /// Returns the message to send for commands that send one, or `None` if the command has been
+    /// carried out some other way. If the arguments are invalid, returns an error describing the
+    /// problem.
+    pub fn run_slash_command(
+        &mut self,
+        command: SlashCommand,
+        args: &str,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) -> Result<Option<RoomMessageEventContent>, String> {
+        let room = self.room.clone().unwrap();
+        let client = cx
+            .global::<SessionManager>()
+            .client()
+            .unwrap()
+            .read(cx)
+            .clone();
+
+        if args.is_empty() && command.requires_arguments() {
+            return Err(tr!(
+                "SLASH_COMMAND_USAGE",
+                "Usage: /{{command}} {{arguments}}",
+                command = command.name(),
+                arguments = command.arguments().unwrap_or_default()
+            )
+            .to_string());
+        }
+
+        match command {
+            SlashCommand::Me => {
+                let emoticons = cx
+                    .global::<SessionManager>()
+                    .image_packs()
+                    .read(cx)
+                    .emoticons_for_room(&self.room_id);
+                let mut content = enrich_message(args, &emoticons);
+                content.msgtype = match content.msgtype {
+                    MessageType::Text(text) => {
+                        let mut emote = EmoteMessageEventContent::plain(text.body);
+                        emote.formatted = text.formatted;
+                        MessageType::Emote(emote)
+                    }
+                    msgtype => msgtype,
+                };
+                Ok(Some(content))
+            }
+            SlashCommand::Shrug => {
+                let shrug = "¯\\_(ツ)_/¯";
+                Ok(Some(RoomMessageEventContent::text_plain(
+                    if args.is_empty() {
+                        shrug.to_string()
+                    } else {
+                        format!("{shrug} {args}")
+                    },
+                )))
+            }
+            SlashCommand::Plain => Ok(Some(RoomMessageEventContent::text_plain(args))),
+            SlashCommand::Html => Ok(Some(RoomMessageEventContent::text_html(
+                html_to_plain_text(args),
+                args,
+            ))),
+            SlashCommand::Rainbow => Ok(Some(RoomMessageEventContent::text_html(
+                args,
+                rainbow_html(args),
+            ))),
+            SlashCommand::Spoiler => Ok(Some(RoomMessageEventContent::text_html(
+                args,
+                format!("<span data-mx-spoiler>{}</span>", escape_html(args)),
+            ))),
+            SlashCommand::Topic => {
+                let topic = args.to_string();
+                spawn_command(
+                    command,
+                    async move {
+                        room.set_room_topic(&topic).await?;
+                        Ok(())
+                    },
+                    window,
+                    cx,
+                );
+                Ok(None)
+            }
+            SlashCommand::Nick => {
+                let display_name = args.to_string();
+                spawn_command(
+                    command,
+                    async move {
+                        client
+                            .account()
+                            .set_display_name(Some(display_name.as_str()))
+                            .await?;
+                        Ok(())
+                    },
+                    window,
+                    cx,
+                );
+                Ok(None)
+            }
+            SlashCommand::MyRoomNick => {
+                let display_name = args.to_string();
+                spawn_command(
+                    command,
+                    async move {
+                        let own_user_id = room.own_user_id().to_owned();
+                        let Some(us) = room.get_member(&own_user_id).await? else {
+                            return Err(anyhow::anyhow!("not a member of the room"));
+                        };
+                        let Some(mut content) = us.event().original_content().cloned() else {
+                            return Err(anyhow::anyhow!("membership event was redacted"));
+                        };
+                        content.displayname = Some(display_name);
+                        room.send_state_event_for_key(&own_user_id, content).await?;
+                        Ok(())
+                    },
+                    window,
+                    cx,
+                );
+                Ok(None)
+            }
+            SlashCommand::Invite => {
+                let user_id = parse_user_id(args)?;
+                spawn_command(
+                    command,
+                    async move {
+                        room.invite_user_by_id(&user_id).await?;
+                        Ok(())
+                    },
+                    window,
+                    cx,
+                );
+                Ok(None)
+            }
+            SlashCommand::Kick | SlashCommand::Ban | SlashCommand::Op => {
+                let user_id = parse_user_id(args)?;
+                let action = match command {
+                    SlashCommand::Kick => UserAction::Kick,
+                    SlashCommand::Ban => UserAction::Ban,
+                    _ => UserAction::ChangePowerLevel,
+                };
+                let Some(listener) = self.slash_command_listener.clone() else {
+                    return Err(tr!(
+                        "SLASH_COMMAND_UNAVAILABLE",
+                        "/{{command}} can't be used here.",
+                        command = command.name()
+                    )
+                    .to_string());
+                };
+
+                cx.spawn_in(
+                    window,
+                    async move |_: WeakEntity<Self>, cx: &mut AsyncWindowContext| {
+                        let room_clone = room.clone();
+                        let user_id_clone = user_id.clone();
+                        let member = cx
+                            .spawn_tokio(async move { room_clone.get_member(&user_id_clone).await })
+                            .await;
+
+                        let _ = cx.update(|window, cx| match member {
+                            Ok(Some(member)) => listener(
+                                &SlashCommandEvent::UserAction(AuthorFlyoutUserActionEvent {
+                                    action,
+                                    user: member,
+                                    room,
+                                }),
+                                window,
+                                cx,
+                            ),
+                            _ => Toast::new()
+                                .title(&tr!("SLASH_COMMAND_ERROR_TITLE"))
+                                .body(&tr!(
+                                    "SLASH_COMMAND_NOT_A_MEMBER",
+                                    "{{user}} is not a member of this room.",
+                                    user = user_id.to_string()
+                                ))
+                                .severity(AdmonitionSeverity::Error)
+                                .post(window, cx),
+                        });
+                    },
+                )
+                .detach();
+                Ok(None)
+            }
+            SlashCommand::Deop => {
+                let user_id = parse_user_id(args)?;
+                spawn_command(
+                    command,
+                    async move {
+                        let users_default = room.power_levels().await?.users_default;
+                        room.update_power_levels(vec![(&user_id, users_default)])
+                            .await?;
+                        Ok(())
+                    },
+                    window,
+                    cx,
+                );
+                Ok(None)
+            }
+            SlashCommand::Ignore => {
+                let user_id = parse_user_id(args)?;
+                spawn_command(
+                    command,
+                    async move {
+                        client.account().ignore_user(&user_id).await?;
+                        Ok(())
+                    },
+                    window,
+                    cx,
+                );
+                Ok(None)
+            }
+            SlashCommand::Join => {
+                let room_or_alias = OwnedRoomOrAliasId::try_from(args).map_err(|_| {
+                    tr!(
+                        "SLASH_COMMAND_INVALID_ROOM",
+                        "{{room}} is not a valid room address.",
+                        room = args
+                    )
+                    .to_string()
+                })?;
+
+                let displayed_room = self.displayed_room.clone();
+                cx.spawn_in(
+                    window,
+                    async move |_: WeakEntity<Self>, cx: &mut AsyncWindowContext| match cx
+                        .spawn_tokio(async move {
+                            client.join_room_by_id_or_alias(&room_or_alias, &[]).await
+                        })
+                        .await
+                    {
+                        Ok(room) => {
+                            let _ = cx.update(|_, cx| {
+                                displayed_room
+                                    .write(cx, DisplayedRoom::Room(room.room_id().to_owned()));
+                            });
+                        }
+                        Err(e) => {
+                            error!("Failed to join room: {e:?}");
+                            let _ = cx.update(|window, cx| {
+                                post_error_toast(command, window, cx);
+                            });
+                        }
+                    },
+                )
+                .detach();
+                Ok(None)
+            }
+            SlashCommand::Part => {
+                spawn_command(
+                    command,
+                    async move {
+                        room.leave().await?;
+                        Ok(())
+                    },
+                    window,
+                    cx,
+                );
+                Ok(None)
+            }
+            SlashCommand::ConvertToDm => {
+                spawn_command(
+                    command,
+                    async move {
+                        room.set_is_direct(true).await?;
+                        Ok(())
+                    },
+                    window,
+                    cx,
+                );
+                Ok(None)
+            }
+            SlashCommand::Devtools => {
+                if let Some(listener) = &self.slash_command_listener {
+                    listener(&SlashCommandEvent::OpenDevtools, window, cx);
+                }
+                Ok(None)
+            }
+        }
+    }
+}
+
+fn parse_user_id(args: &str) -> Result<OwnedUserId, String> {
+    UserId::parse(args).map_err(|_| {
+        tr!(
+            "SLASH_COMMAND_INVALID_USER",
+            "{{user}} is not a valid user ID.",
+            user = args
+        )
+        .to_string()
+    })
+}
+
+/// Carry out a command in the background, telling the user if it fails
+fn spawn_command(
+    command: SlashCommand,
+    future: impl Future<Output = anyhow::Result<()>> + Send + 'static,
+    window: &mut Window,
+    cx: &mut Context<OpenRoom>,
+) {
+    cx.spawn_in(
+        window,
+        async move |_: WeakEntity<OpenRoom>, cx: &mut AsyncWindowContext| {
+            if let Err(e) = cx.spawn_tokio(future).await {
+                error!("Failed to run /{}: {e:?}", command.name());
+                let _ = cx.update(|window, cx| {
+                    post_error_toast(command, window, cx);
+                });
+            }
+        },
+    )
+    .detach();
+}
+
+fn post_error_toast(command: SlashCommand, window: &mut Window, cx: &mut App) {
+    Toast::new()
+        .title(&tr!("SLASH_COMMAND_ERROR_TITLE", "Unable to run command"))
+        .body(&tr!(
+            "SLASH_COMMAND_ERROR_TEXT",
+            "/{{command}} could not be completed.",
+            command = command.name()
+        ))
+        .severity(AdmonitionSeverity::Error)
+        .post(window, cx);
+}
+
+/// Colour each character of a message with a different hue
+fn rainbow_html(text: &str) -> String {
+    let count = text.chars().count().max(1) as f32;
+    text.chars()
+        .enumerate()
+        .map(|(i, c)| {
+            if c.is_whitespace() {
+                return c.to_string();
+            }
+
+            let colour = Rgba::from(hsla(i as f32 / count, 1., 0.5, 1.));
+            format!(
+                "<span data-mx-color=\"#{:02x}{:02x}{:02x}\">{}</span>",
+                (colour.r * 255.).round() as u8,
+                (colour.g * 255.).round() as u8,
+                (colour.b * 255.).round() as u8,
+                escape_html(&c.to_string())
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_message() {
+        assert_eq!(parse_message("hello"), ParsedMessage::Message("hello"));
+        assert_eq!(parse_message("//me"), ParsedMessage::Message("/me"));
+        assert_eq!(
+            parse_message("/me waves"),
+            ParsedMessage::Command(SlashCommand::Me, "waves")
+        );
+        assert_eq!(
+            parse_message("/ME  waves  "),
+            ParsedMessage::Command(SlashCommand::Me, "waves")
+        );
+        assert_eq!(
+            parse_message("/topic a new\ntopic"),
+            ParsedMessage::Command(SlashCommand::Topic, "a new\ntopic")
+        );
+        assert_eq!(
+            parse_message("/part"),
+            ParsedMessage::Command(SlashCommand::Part, "")
+        );
+    }
+
+    #[test]
+    fn test_parse_unknown_command() {
+        assert_eq!(
+            parse_message("/frobnicate now"),
+            ParsedMessage::UnknownCommand("frobnicate")
+        );
+        assert_eq!(parse_message("/"), ParsedMessage::UnknownCommand(""));
+    }
+
+    #[test]
+    fn test_command_names() {
+        for command in SlashCommand::ALL {
+            assert_eq!(SlashCommand::from_name(command.name()), Some(command));
+        }
+    }
+}
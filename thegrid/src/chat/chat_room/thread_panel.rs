@@ -0,0 +1,505 @@
+use crate::chat::chat_room::open_room::{OpenRoom, ThreadPanelContent};
+use crate::chat::chat_room::timeline_view::TimelineView;
+use crate::chat::chat_room::timeline_view::author_flyout::AuthorFlyoutUserActionListener;
+use crate::chat::displayed_room::DisplayedRoom;
+use cntp_i18n::{tr, trn};
+use contemporary::components::button::button;
+use contemporary::components::icon::icon;
+use contemporary::components::spinner::spinner;
+use contemporary::components::subtitle::subtitle;
+use contemporary::styling::theme::{Theme, VariableColor};
+use gpui::prelude::FluentBuilder;
+use gpui::{
+    AnyElement, AppContext, AsyncApp, Context, Entity, InteractiveElement, IntoElement,
+    ListAlignment, ListScrollEvent, ListState, ParentElement, Render, StatefulInteractiveElement,
+    Styled, Task, WeakEntity, Window, div, list, px,
+};
+use log::error;
+use matrix_sdk::room::ListThreadsOptions;
+use matrix_sdk::ruma::events::room::message::SyncRoomMessageEvent;
+use matrix_sdk::ruma::events::{AnySyncMessageLikeEvent, AnySyncTimelineEvent};
+use matrix_sdk::ruma::serde::Raw;
+use matrix_sdk::ruma::{OwnedEventId, OwnedMxcUri, OwnedUserId, UInt};
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+use thegrid_common::mxc_image::{SizePolicy, mxc_image};
+use thegrid_common::tokio_helper::TokioHelper;
+use tokio::task::JoinSet;
+
+const THREAD_LIST_PAGE_SIZE: u32 = 25;
+
+pub struct ThreadPanel {
+    open_room: Entity<OpenRoom>,
+    displayed_room: Entity<DisplayedRoom>,
+    on_user_action: Rc<Box<AuthorFlyoutUserActionListener>>,
+
+    thread: Option<(Entity<OpenRoom>, Entity<TimelineView>)>,
+    thread_list: ThreadListState,
+    thread_list_entries: Vec<ThreadListEntry>,
+    thread_list_members: HashMap<OwnedUserId, ThreadListMember>,
+    thread_list_next_batch: Option<String>,
+    thread_list_state: ListState,
+    thread_list_task: Option<Task<()>>,
+    showing_thread_list: bool,
+}
+
+enum ThreadListState {
+    Idle,
+    Loading,
+    Loaded,
+    Error,
+}
+
+struct ThreadListEntry {
+    root_event_id: OwnedEventId,
+    sender: OwnedUserId,
+    content: ThreadRootContent,
+    replies: u64,
+}
+
+enum ThreadRootContent {
+    Message(String),
+    Redacted,
+    UnableToDecrypt,
+    Unsupported,
+}
+
+struct ThreadListMember {
+    display_name: Option<String>,
+    avatar: Option<OwnedMxcUri>,
+}
+
+impl ThreadPanel {
+    pub fn new(
+        open_room: Entity<OpenRoom>,
+        displayed_room: Entity<DisplayedRoom>,
+        on_user_action: Rc<Box<AuthorFlyoutUserActionListener>>,
+        cx: &mut Context<Self>,
+    ) -> Self {
+        cx.observe(&open_room, |this, _, cx| {
+            this.sync_thread_panel(cx);
+        })
+        .detach();
+
+        let thread_list_state = ListState::new(0, ListAlignment::Top, px(200.));
+        thread_list_state.set_scroll_handler(cx.listener(
+            |this: &mut Self, event: &ListScrollEvent, _, cx| {
+                if event.visible_range.end > this.thread_list_entries.len().saturating_sub(5) {
+                    this.load_next_thread_list_page(cx);
+                }
+            },
+        ));
+
+        let mut this = Self {
+            open_room,
+            displayed_room,
+            on_user_action,
+            thread: None,
+            thread_list: ThreadListState::Idle,
+            thread_list_entries: Vec::new(),
+            thread_list_members: HashMap::new(),
+            thread_list_next_batch: None,
+            thread_list_state,
+            thread_list_task: None,
+            showing_thread_list: false,
+        };
+        this.sync_thread_panel(cx);
+        this
+    }
+
+    fn sync_thread_panel(&mut self, cx: &mut Context<Self>) {
+        match self.open_room.read(cx).thread_panel.clone() {
+            ThreadPanelContent::Closed => {
+                self.thread = None;
+                self.thread_list = ThreadListState::Idle;
+                self.thread_list_task = None;
+                self.showing_thread_list = false;
+            }
+            ThreadPanelContent::ThreadList => {
+                self.thread = None;
+
+                // Refresh the list every time it is shown so that new threads turn up
+                if !self.showing_thread_list {
+                    self.showing_thread_list = true;
+                    self.load_thread_list(cx);
+                }
+            }
+            ThreadPanelContent::Thread(thread) => {
+                self.showing_thread_list = false;
+                if self
+                    .thread
+                    .as_ref()
+                    .is_none_or(|(current_thread, _)| *current_thread != thread)
+                {
+                    let on_user_action = self.on_user_action.clone();
+                    let timeline_view = cx.new(|cx| {
+                        TimelineView::new(
+                            thread.clone(),
+                            self.displayed_room.clone(),
+                            move |event, window, cx| on_user_action(event, window, cx),
+                            cx,
+                        )
+                    });
+                    self.thread = Some((thread, timeline_view));
+                }
+            }
+        }
+        cx.notify();
+    }
+
+    fn load_thread_list(&mut self, cx: &mut Context<Self>) {
+        self.thread_list = ThreadListState::Idle;
+        self.thread_list_entries.clear();
+        self.thread_list_next_batch = None;
+        // One extra item for the footer showing the loading and error states
+        self.thread_list_state.reset(1);
+        self.load_next_thread_list_page(cx);
+    }
+
+    fn load_next_thread_list_page(&mut self, cx: &mut Context<Self>) {
+        match self.thread_list {
+            ThreadListState::Loading => return,
+            ThreadListState::Loaded if self.thread_list_next_batch.is_none() => return,
+            _ => {}
+        }
+
+        let Some(room) = self.open_room.read(cx).room.clone() else {
+            return;
+        };
+
+        let from = self.thread_list_next_batch.clone();
+        let known_members: HashSet<OwnedUserId> =
+            self.thread_list_members.keys().cloned().collect();
+
+        self.thread_list = ThreadListState::Loading;
+        self.thread_list_task = Some(cx.spawn(
+            async move |weak_this: WeakEntity<Self>, cx: &mut AsyncApp| {
+                let result = cx
+                    .spawn_tokio(async move {
+                        let thread_roots = room
+                            .list_threads(ListThreadsOptions {
+                                from,
+                                limit: Some(UInt::from(THREAD_LIST_PAGE_SIZE)),
+                                ..Default::default()
+                            })
+                            .await?;
+
+                        let entries = thread_roots
+                            .chunk
+                            .iter()
+                            .filter_map(|event| thread_list_entry(event.raw()))
+                            .collect::<Vec<_>>();
+
+                        // Look up each sender on this page once, all at the same time
+                        let mut member_lookups = JoinSet::new();
+                        let senders: HashSet<OwnedUserId> =
+                            entries.iter().map(|entry| entry.sender.clone()).collect();
+                        for sender in senders.difference(&known_members).cloned() {
+                            let room = room.clone();
+                            member_lookups.spawn(async move {
+                                let member = room.get_member_no_sync(&sender).await.ok().flatten();
+                                (
+                                    sender,
+                                    ThreadListMember {
+                                        display_name: member.as_ref().and_then(|member| {
+                                            member.display_name().map(|name| name.to_string())
+                                        }),
+                                        avatar: member.as_ref().and_then(|member| {
+                                            member.avatar_url().map(|url| url.to_owned())
+                                        }),
+                                    },
+                                )
+                            });
+                        }
+                        let members = member_lookups.join_all().await;
+
+                        Ok::<_, matrix_sdk::Error>((
+                            entries,
+                            members,
+                            thread_roots.prev_batch_token,
+                        ))
+                    })
+                    .await;
+
+                let _ = weak_this.update(cx, |this, cx| {
+                    match result {
+                        Ok((entries, members, next_batch)) => {
+                            let old_len = this.thread_list_entries.len();
+                            this.thread_list_state
+                                .splice(old_len..old_len, entries.len());
+                            this.thread_list_entries.extend(entries);
+                            this.thread_list_members.extend(members);
+                            this.thread_list_next_batch = next_batch;
+                            this.thread_list = ThreadListState::Loaded;
+                        }
+                        Err(e) => {
+                            error!("Failed to list threads: {:?}", e);
+                            this.thread_list = ThreadListState::Error;
+                        }
+                    };
+                    cx.notify();
+                });
+            },
+        ));
+    }
+
+    fn render_thread_list_error(&self, cx: &mut Context<Self>) -> AnyElement {
+        let theme = cx.global::<Theme>();
+
+        div()
+            .flex()
+            .flex_col()
+            .items_center()
+            .justify_center()
+            .gap(px(4.))
+            .p(px(4.))
+            .child(
+                div()
+                    .text_color(theme.foreground.disabled())
+                    .child(tr!("THREAD_LIST_ERROR", "Unable to load threads")),
+            )
+            .child(
+                button("thread-list-retry-button")
+                    .child(tr!("THREAD_LIST_RETRY", "Try Again"))
+                    .on_click(cx.listener(|this, _, _, cx| {
+                        this.load_next_thread_list_page(cx);
+                        cx.notify();
+                    })),
+            )
+            .into_any_element()
+    }
+
+    fn render_thread_list_item(
+        &mut self,
+        i: usize,
+        _: &mut Window,
+        cx: &mut Context<Self>,
+    ) -> AnyElement {
+        let Some(entry) = self.thread_list_entries.get(i) else {
+            // The footer after the last thread
+            return match self.thread_list {
+                ThreadListState::Idle | ThreadListState::Loading => div()
+                    .flex()
+                    .justify_center()
+                    .p(px(4.))
+                    .child(spinner())
+                    .into_any_element(),
+                ThreadListState::Error => self.render_thread_list_error(cx),
+                ThreadListState::Loaded => div().into_any_element(),
+            };
+        };
+
+        let theme = cx.global::<Theme>();
+        let member = self.thread_list_members.get(&entry.sender);
+
+        div()
+            .id(i)
+            .flex()
+            .gap(px(4.))
+            .p(px(4.))
+            .mb(px(4.))
+            .rounded(theme.border_radius)
+            .bg(theme.layer_background)
+            .cursor_pointer()
+            .child(
+                mxc_image(member.and_then(|member| member.avatar.clone()))
+                    .fallback_image(&entry.sender)
+                    .fixed_square(px(24.))
+                    .size_policy(SizePolicy::Fit)
+                    .rounded(theme.border_radius),
+            )
+            .child(
+                div()
+                    .flex()
+                    .flex_col()
+                    .overflow_hidden()
+                    .child(
+                        member
+                            .and_then(|member| member.display_name.clone())
+                            .unwrap_or_else(|| entry.sender.to_string()),
+                    )
+                    .child(
+                        div()
+                            .text_ellipsis()
+                            .when(
+                                !matches!(entry.content, ThreadRootContent::Message(_)),
+                                |david| david.text_color(theme.foreground.disabled()),
+                            )
+                            .child(match &entry.content {
+                                ThreadRootContent::Message(body) => body.clone(),
+                                ThreadRootContent::Redacted => tr!("MESSAGE_REDACTED").to_string(),
+                                ThreadRootContent::UnableToDecrypt => {
+                                    tr!("MESSAGE_UNABLE_TO_DECRYPT").to_string()
+                                }
+                                ThreadRootContent::Unsupported => {
+                                    tr!("MESSAGE_UNSUPPORTED").to_string()
+                                }
+                            }),
+                    )
+                    .child(
+                        div()
+                            .text_color(theme.foreground.disabled())
+                            .text_size(theme.system_font_size * 0.8)
+                            .child(trn!(
+                                "THREAD_SUMMARY_REPLIES",
+                                count = entry.replies as isize
+                            )),
+                    ),
+            )
+            .on_click(cx.listener({
+                let root_event_id = entry.root_event_id.clone();
+                move |this, _, _, cx| {
+                    this.open_room.update(cx, |open_room, cx| {
+                        open_room.open_thread(root_event_id.clone(), cx);
+                    })
+                }
+            }))
+            .into_any_element()
+    }
+
+    fn render_thread_list(&self, cx: &mut Context<Self>) -> AnyElement {
+        let theme = cx.global::<Theme>();
+
+        if self.thread_list_entries.is_empty() {
+            return match self.thread_list {
+                ThreadListState::Idle | ThreadListState::Loading => div()
+                    .flex()
+                    .flex_grow(1.)
+                    .items_center()
+                    .justify_center()
+                    .child(spinner())
+                    .into_any_element(),
+                ThreadListState::Error => div()
+                    .flex()
+                    .flex_col()
+                    .flex_grow(1.)
+                    .justify_center()
+                    .child(self.render_thread_list_error(cx))
+                    .into_any_element(),
+                ThreadListState::Loaded => div()
+                    .flex()
+                    .flex_grow(1.)
+                    .items_center()
+                    .justify_center()
+                    .text_color(theme.foreground.disabled())
+                    .child(tr!(
+                        "THREAD_LIST_EMPTY",
+                        "There are no threads in this room"
+                    ))
+                    .into_any_element(),
+            };
+        }
+
+        div()
+            .flex()
+            .flex_col()
+            .flex_grow(1.)
+            .p(px(4.))
+            .child(
+                list(
+                    self.thread_list_state.clone(),
+                    cx.processor(Self::render_thread_list_item),
+                )
+                .size_full(),
+            )
+            .into_any_element()
+    }
+}
+
+/// Builds a thread list entry for a thread root.
+///
+/// Roots that aren't plain messages, such as ones that couldn't be decrypted, still get an entry
+/// so that the thread can be opened.
+fn thread_list_entry(raw: &Raw<AnySyncTimelineEvent>) -> Option<ThreadListEntry> {
+    let root_event_id = raw.get_field::<OwnedEventId>("event_id").ok().flatten()?;
+    let sender = raw.get_field::<OwnedUserId>("sender").ok().flatten()?;
+    let replies = raw
+        .get_field::<serde_json::Value>("unsigned")
+        .ok()
+        .flatten()
+        .and_then(|unsigned| unsigned.pointer("/m.relations/m.thread/count")?.as_u64())
+        .unwrap_or_default();
+
+    let content = match raw.deserialize() {
+        Ok(AnySyncTimelineEvent::MessageLike(AnySyncMessageLikeEvent::RoomMessage(
+            SyncRoomMessageEvent::Original(original),
+        ))) => ThreadRootContent::Message(original.content.body().to_string()),
+        Ok(AnySyncTimelineEvent::MessageLike(AnySyncMessageLikeEvent::RoomEncrypted(_))) => {
+            ThreadRootContent::UnableToDecrypt
+        }
+        Ok(AnySyncTimelineEvent::MessageLike(AnySyncMessageLikeEvent::RoomMessage(
+            SyncRoomMessageEvent::Redacted(_),
+        ))) => ThreadRootContent::Redacted,
+        _ => ThreadRootContent::Unsupported,
+    };
+
+    Some(ThreadListEntry {
+        root_event_id,
+        sender,
+        content,
+        replies,
+    })
+}
+
+impl Render for ThreadPanel {
+    fn render(&mut self, _: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let showing_thread = self.thread.is_some();
+        let content = match &self.thread {
+            Some((thread, timeline_view)) => div()
+                .flex()
+                .flex_col()
+                .flex_grow(1.)
+                .child(timeline_view.clone())
+                .child(thread.read(cx).chat_bar.clone())
+                .into_any_element(),
+            None => self.render_thread_list(cx),
+        };
+
+        let theme = cx.global::<Theme>();
+
+        div()
+            .flex()
+            .flex_col()
+            .w(px(400.))
+            .h_full()
+            .border_l(px(1.))
+            .border_color(theme.border_color)
+            .child(
+                div()
+                    .flex()
+                    .items_center()
+                    .gap(px(4.))
+                    .p(px(4.))
+                    .when(showing_thread, |david| {
+                        david.child(
+                            button("thread-list-button")
+                                .flat()
+                                .child(icon("go-previous"))
+                                .on_click(cx.listener(|this, _, _, cx| {
+                                    this.open_room.update(cx, |open_room, cx| {
+                                        open_room.show_thread_list(cx);
+                                    })
+                                })),
+                        )
+                    })
+                    .child(
+                        div().flex_grow(1.).child(subtitle(if showing_thread {
+                            tr!("THREAD_PANEL_THREAD", "Thread")
+                        } else {
+                            tr!("THREAD_PANEL_THREADS", "Threads")
+                        })),
+                    )
+                    .child(
+                        button("thread-panel-close-button")
+                            .flat()
+                            .child(icon("window-close"))
+                            .on_click(cx.listener(|this, _, _, cx| {
+                                this.open_room.update(cx, |open_room, cx| {
+                                    open_room.close_thread_panel(cx);
+                                })
+                            })),
+                    ),
+            )
+            .child(content)
+    }
+}
@@ -1,8 +1,11 @@
+mod audio_message_item;
 pub mod author_flyout;
 pub mod event_filter;
 pub mod flag_event_popover;
+pub mod identity_shield;
 mod membership_change_item;
 mod message_error_item;
+mod poll_item;
 mod profile_change_item;
 pub(crate) mod reply_fragment;
 pub mod room_head;
@@ -10,8 +13,12 @@ mod rtc_notification_item;
 mod state_change_element;
 mod state_event_item;
 mod timeline_item;
-mod timeline_message_item;
+pub(crate) mod timeline_message_item;
+mod url_preview_item;
+mod verification_request_item;
+pub(crate) mod video_message_item;
 
+use crate::chat::chat_room::media_viewer::MediaViewer;
 use crate::chat::chat_room::open_room::OpenRoom;
 use crate::chat::chat_room::timeline_view::author_flyout::{
     AuthorFlyoutUserActionEvent, AuthorFlyoutUserActionListener,
@@ -22,9 +29,9 @@ use contemporary::components::context_menu::ContextMenuExt;
 use contemporary::components::spinner::spinner;
 use gpui::prelude::FluentBuilder;
 use gpui::{
-    App, AsyncApp, Context, Element, ElementId, Entity, InteractiveElement, IntoElement,
-    ListAlignment, ListScrollEvent, ListState, ParentElement, Render, Styled, Window, div, list,
-    px,
+    App, AppContext, AsyncApp, Context, Element, ElementId, Entity, InteractiveElement,
+    IntoElement, ListAlignment, ListScrollEvent, ListState, ParentElement, Render, Styled, Window,
+    div, list, px,
 };
 use matrix_sdk::ruma::api::client::receipt::create_receipt::v3::ReceiptType;
 use thegrid_common::tokio_helper::TokioHelper;
@@ -33,6 +40,7 @@ pub struct TimelineView {
     open_room: Entity<OpenRoom>,
     displayed_room: Entity<DisplayedRoom>,
     list_state: ListState,
+    media_viewer: Entity<MediaViewer>,
     on_user_action: Box<AuthorFlyoutUserActionListener>,
 }
 
@@ -82,6 +90,7 @@ impl TimelineView {
         ));
 
         let mut this = Self {
+            media_viewer: cx.new(|cx| MediaViewer::new(open_room.clone(), cx)),
             open_room,
             displayed_room,
             list_state,
@@ -189,6 +198,7 @@ impl Render for TimelineView {
                 )
                 .size_full(),
             )
+            .child(self.media_viewer.clone())
             .into_any_element()
     }
 }
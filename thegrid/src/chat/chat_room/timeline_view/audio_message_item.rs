@@ -0,0 +1,226 @@
+use contemporary::components::anchorer::WithAnchorer;
+use contemporary::components::button::button;
+use contemporary::components::icon::icon;
+use contemporary::components::progress_bar::progress_bar;
+use contemporary::components::spinner::spinner;
+use contemporary::styling::theme::{Theme, VariableColor};
+use gpui::prelude::FluentBuilder;
+use gpui::{
+    App, BorrowAppContext, InteractiveElement, IntoElement, MouseButton, MouseDownEvent,
+    ParentElement, RenderOnce, Styled, Window, div, px, rgba,
+};
+use matrix_sdk::ruma::events::room::message::{AudioMessageEventContent, UnstableAmplitude};
+use std::time::Duration;
+use thegrid_common::audio_player::{AudioPlaybackState, AudioPlayer};
+use thegrid_common::session::media_cache::MediaCacheEntry;
+use thegrid_common::session::session_manager::SessionManager;
+
+/// The maximum number of bars drawn in a waveform
+const WAVEFORM_BARS: usize = 60;
+
+/// The height of the tallest bar in a waveform
+const WAVEFORM_HEIGHT: f32 = 24.;
+
+#[derive(IntoElement)]
+pub struct AudioMessageItem {
+    audio: AudioMessageEventContent,
+}
+
+pub fn audio_message_item(audio: AudioMessageEventContent) -> AudioMessageItem {
+    AudioMessageItem { audio }
+}
+
+impl RenderOnce for AudioMessageItem {
+    fn render(self, window: &mut Window, cx: &mut App) -> impl IntoElement {
+        let theme = cx.global::<Theme>().clone();
+
+        let media = MediaCacheEntry::from(self.audio.source.clone());
+        let media_file = cx.update_global::<SessionManager, _>(|session_manager, cx| {
+            session_manager.media().media_file_lazy(media.clone(), cx)
+        });
+
+        let audio_player = AudioPlayer::global(cx);
+        let playback_state = audio_player.read(cx).state(&media);
+        let position = match playback_state {
+            AudioPlaybackState::Playing(position) | AudioPlaybackState::Paused(position) => {
+                position
+            }
+            AudioPlaybackState::Stopped | AudioPlaybackState::Loading => Duration::ZERO,
+        };
+
+        let duration = self
+            .audio
+            .audio
+            .as_ref()
+            .map(|audio| audio.duration)
+            .or_else(|| self.audio.info.as_ref().and_then(|info| info.duration));
+        let progress = duration
+            .filter(|duration| !duration.is_zero())
+            .map(|duration| (position.as_secs_f32() / duration.as_secs_f32()).min(1.))
+            .unwrap_or_default();
+
+        let waveform = self
+            .audio
+            .audio
+            .as_ref()
+            .map(|audio| waveform_bars(&audio.waveform))
+            .unwrap_or_default();
+
+        let waveform_bounds = window.use_state(cx, |_, _| None);
+        let seek_listener = {
+            let waveform_bounds = waveform_bounds.clone();
+            let audio_player = audio_player.clone();
+            let media = media.clone();
+            move |event: &MouseDownEvent, _: &mut Window, cx: &mut App| {
+                let (Some(bounds), Some(duration)) = (*waveform_bounds.read(cx), duration) else {
+                    return;
+                };
+
+                let fraction =
+                    ((event.position.x - bounds.left()) / bounds.size.width).clamp(0., 1.);
+                audio_player.update(cx, |audio_player, cx| {
+                    audio_player.seek(&media, duration.mul_f32(fraction), cx);
+                });
+            }
+        };
+
+        div()
+            .p(px(2.))
+            .bg(rgba(0x00C8FF10))
+            .rounded(theme.border_radius)
+            .max_w(px(500.))
+            .child(
+                div()
+                    .flex()
+                    .items_center()
+                    .gap(px(4.))
+                    .child(match playback_state {
+                        AudioPlaybackState::Loading => div()
+                            .flex()
+                            .items_center()
+                            .justify_center()
+                            .size(px(32.))
+                            .child(spinner().size(px(16.)))
+                            .into_any_element(),
+                        AudioPlaybackState::Playing(_) => button("audio-pause-button")
+                            .child(icon("media-playback-pause"))
+                            .on_click({
+                                let audio_player = audio_player.clone();
+                                let media = media.clone();
+                                move |_, _, cx| {
+                                    audio_player.update(cx, |audio_player, cx| {
+                                        audio_player.toggle_pause(&media, cx);
+                                    });
+                                }
+                            })
+                            .into_any_element(),
+                        AudioPlaybackState::Paused(_) => button("audio-play-button")
+                            .child(icon("media-playback-start"))
+                            .on_click({
+                                let audio_player = audio_player.clone();
+                                let media = media.clone();
+                                move |_, _, cx| {
+                                    audio_player.update(cx, |audio_player, cx| {
+                                        audio_player.toggle_pause(&media, cx);
+                                    });
+                                }
+                            })
+                            .into_any_element(),
+                        AudioPlaybackState::Stopped => button("audio-play-button")
+                            .child(icon("media-playback-start"))
+                            .on_click({
+                                let audio_player = audio_player.clone();
+                                let media = media.clone();
+                                move |_, _, cx| {
+                                    audio_player.update(cx, |audio_player, cx| {
+                                        audio_player.play(media.clone(), media_file.clone(), cx);
+                                    });
+                                }
+                            })
+                            .into_any_element(),
+                    })
+                    .child(
+                        div()
+                            .flex()
+                            .flex_grow(1.)
+                            .items_center()
+                            .h(px(WAVEFORM_HEIGHT))
+                            .when(
+                                !matches!(playback_state, AudioPlaybackState::Stopped),
+                                |david| {
+                                    david
+                                        .cursor_pointer()
+                                        .on_mouse_down(MouseButton::Left, seek_listener)
+                                },
+                            )
+                            .when_else(
+                                waveform.is_empty(),
+                                |david| david.child(progress_bar().w_full().value(progress)),
+                                |david| {
+                                    let bar_count = waveform.len();
+                                    david.gap(px(1.)).children(waveform.iter().enumerate().map(
+                                        |(i, amplitude)| {
+                                            let played = (i as f32 + 0.5) / bar_count as f32
+                                                <= progress;
+                                            div()
+                                                .flex_grow(1.)
+                                                .min_w(px(1.))
+                                                .h(px((amplitude * WAVEFORM_HEIGHT).max(2.)))
+                                                .rounded(px(1.))
+                                                .when_else(
+                                                    played,
+                                                    |david| david.bg(theme.info_accent_color),
+                                                    |david| {
+                                                        david.bg(theme.foreground.disabled())
+                                                    },
+                                                )
+                                        },
+                                    ))
+                                },
+                            )
+                            .with_anchorer(move |david, bounds, _, cx| {
+                                waveform_bounds.write(cx, Some(bounds));
+                                david
+                            }),
+                    )
+                    .child(
+                        div()
+                            .text_color(theme.foreground.disabled())
+                            .text_size(theme.system_font_size * 0.8)
+                            .child(match (playback_state, duration) {
+                                (AudioPlaybackState::Stopped, Some(duration)) => {
+                                    format_playback_time(duration)
+                                }
+                                (AudioPlaybackState::Stopped, None) => String::new(),
+                                (_, Some(duration)) => format!(
+                                    "{} / {}",
+                                    format_playback_time(position),
+                                    format_playback_time(duration)
+                                ),
+                                (_, None) => format_playback_time(position),
+                            }),
+                    ),
+            )
+    }
+}
+
+/// Reduce a waveform to at most [`WAVEFORM_BARS`] bars, each between 0 and 1
+fn waveform_bars(waveform: &[UnstableAmplitude]) -> Vec<f32> {
+    if waveform.is_empty() {
+        return Vec::new();
+    }
+
+    let chunk_size = waveform.len().div_ceil(WAVEFORM_BARS);
+    waveform
+        .chunks(chunk_size)
+        .map(|chunk| {
+            let total: u64 = chunk.iter().map(|amplitude| u64::from(amplitude.get())).sum();
+            total as f32 / chunk.len() as f32 / u64::from(UnstableAmplitude::MAX) as f32
+        })
+        .collect()
+}
+
+pub fn format_playback_time(time: Duration) -> String {
+    let seconds = time.as_secs();
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
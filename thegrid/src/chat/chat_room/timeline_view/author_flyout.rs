@@ -1,4 +1,5 @@
 use crate::chat::chat_room::open_room::OpenRoom;
+use crate::chat::chat_room::timeline_view::identity_shield::identity_shield;
 use crate::chat::displayed_room::DisplayedRoom;
 use cntp_i18n::{Quote, tr};
 use contemporary::components::button::button;
@@ -23,6 +24,7 @@ use matrix_sdk::ruma::events::room::power_levels::UserPowerLevel;
 use matrix_sdk_ui::timeline::RoomExt;
 use std::rc::Rc;
 use thegrid_common::mxc_image::{SizePolicy, mxc_image};
+use thegrid_common::session::identities_cache::IdentityState;
 use thegrid_common::session::session_manager::SessionManager;
 use thegrid_common::tokio_helper::TokioHelper;
 
@@ -47,6 +49,7 @@ pub enum UserAction {
     Kick,
     Ban,
     Unban,
+    Verify,
 }
 
 #[derive(IntoElement)]
@@ -138,11 +141,13 @@ impl RenderOnce for AuthorFlyout {
         let on_close_7 = on_close.clone();
         let on_close_8 = on_close.clone();
         let on_close_9 = on_close.clone();
+        let on_close_10 = on_close.clone();
 
         let on_user_action = Rc::new(self.on_user_action);
         let on_user_action_2 = on_user_action.clone();
         let on_user_action_3 = on_user_action.clone();
         let on_user_action_4 = on_user_action.clone();
+        let on_user_action_5 = on_user_action.clone();
 
         let displayed_room = self.displayed_room;
         let displayed_room_2 = displayed_room.clone();
@@ -161,6 +166,7 @@ impl RenderOnce for AuthorFlyout {
         let room_4 = room.clone();
         let room_5 = room.clone();
         let room_6 = room.clone();
+        let room_7 = room.clone();
 
         flyout(self.bounds)
             .visible(self.visible)
@@ -172,16 +178,22 @@ impl RenderOnce for AuthorFlyout {
                     let room_member_3 = room_member.clone();
                     let room_member_4 = room_member.clone();
                     let room_member_5 = room_member.clone();
+                    let room_member_6 = room_member.clone();
                     let room_member_id = room_member.user_id().to_owned();
                     let room_member_id_2 = room_member_id.clone();
                     let room_member_id_3 = room_member_id.clone();
                     let room_member_id_4 = room_member_id.clone();
+                    let room_member_id_5 = room_member_id.clone();
                     let suggested_role = room_member.suggested_role_for_power_level();
 
                     let session_manager = cx.global::<SessionManager>();
                     let client = session_manager.client().unwrap().read(cx).clone();
                     let current_dm_room = client.get_dm_room(room_member.user_id());
                     let current_dm_room_2 = current_dm_room.clone();
+                    let identities = session_manager.identities();
+                    let identity_state = identities.update(cx, |identities, cx| {
+                        identities.identity_state(room_member.user_id(), cx)
+                    });
 
                     let direct_message_box = window.use_state(cx, |_, cx| {
                         let mut text_field = TextField::new("direct-message", cx);
@@ -232,6 +244,7 @@ impl RenderOnce for AuthorFlyout {
                             || room_member.user_id() == me.user_id()
                     });
                     let is_ignored = room_member.is_ignored();
+                    let is_account_user = room_member.is_account_user();
 
                     let theme = cx.global::<Theme>();
 
@@ -497,6 +510,71 @@ impl RenderOnce for AuthorFlyout {
                                         }),
                                 ),
                         )
+                        .when_some(
+                            identity_state.filter(|identity_state| {
+                                *identity_state != IdentityState::NoIdentity && !is_account_user
+                            }),
+                            |david, identity_state| {
+                                david.child(
+                                    layer()
+                                        .p(px(4.))
+                                        .flex()
+                                        .flex_col()
+                                        .gap(px(4.))
+                                        .child(subtitle(tr!("USER_VERIFICATION", "Verification")))
+                                        .child(
+                                            div()
+                                                .flex()
+                                                .items_center()
+                                                .gap(px(4.))
+                                                .child(identity_shield(room_member_id_5))
+                                                .child(match identity_state {
+                                                    IdentityState::Verified => tr!(
+                                                        "USER_VERIFIED",
+                                                        "You have verified this user."
+                                                    ),
+                                                    IdentityState::VerificationViolation => tr!(
+                                                        "USER_VERIFICATION_VIOLATION",
+                                                        "This user's identity has changed since \
+                                                        you verified them. Verify them again to \
+                                                        make sure you're still talking to them."
+                                                    ),
+                                                    _ => tr!(
+                                                        "USER_UNVERIFIED",
+                                                        "You haven't verified this user. Verify \
+                                                        them to make sure you're talking to who \
+                                                        you think you are."
+                                                    ),
+                                                }),
+                                        )
+                                        .when(identity_state != IdentityState::Verified, |david| {
+                                            david.child(
+                                                button("verify-user-button")
+                                                    .child(icon_text(
+                                                        "security-high",
+                                                        tr!("VERIFY_USER", "Verify"),
+                                                    ))
+                                                    .on_click(move |_, window, cx| {
+                                                        on_close_10(
+                                                            &AuthorFlyoutCloseEvent,
+                                                            window,
+                                                            cx,
+                                                        );
+                                                        on_user_action_5(
+                                                            &AuthorFlyoutUserActionEvent {
+                                                                action: UserAction::Verify,
+                                                                room: room_7.clone(),
+                                                                user: room_member_6.clone(),
+                                                            },
+                                                            window,
+                                                            cx,
+                                                        );
+                                                    }),
+                                            )
+                                        }),
+                                )
+                            },
+                        )
                         .when(!is_ignored && !room_member_5.is_account_user(), |david| {
                             david.child(
                                 layer()
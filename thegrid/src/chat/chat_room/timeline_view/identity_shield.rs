@@ -0,0 +1,121 @@
+use cntp_i18n::tr;
+use contemporary::components::icon::icon;
+use contemporary::components::tooltip::simple_tooltip;
+use gpui::{
+    App, InteractiveElement, IntoElement, ParentElement, RenderOnce, StatefulInteractiveElement,
+    Window, div,
+};
+use matrix_sdk::deserialized_responses::{VerificationLevel, VerificationState};
+use matrix_sdk::ruma::OwnedUserId;
+use matrix_sdk_ui::timeline::EventTimelineItem;
+use thegrid_common::session::identities_cache::IdentityState;
+use thegrid_common::session::session_manager::SessionManager;
+
+#[derive(IntoElement)]
+pub struct IdentityShield {
+    user_id: OwnedUserId,
+    size: f32,
+}
+
+/// A shield showing whether the identity of `user_id` is verified
+pub fn identity_shield(user_id: OwnedUserId) -> IdentityShield {
+    IdentityShield { user_id, size: 16. }
+}
+
+impl IdentityShield {
+    pub fn size(mut self, size: f32) -> Self {
+        self.size = size;
+        self
+    }
+}
+
+impl RenderOnce for IdentityShield {
+    fn render(self, _: &mut Window, cx: &mut App) -> impl IntoElement {
+        let identities = cx.global::<SessionManager>().identities();
+        let identity_state = identities.update(cx, |identities, cx| {
+            identities.identity_state(&self.user_id, cx)
+        });
+
+        let (icon_name, tooltip) = match identity_state {
+            Some(IdentityState::Verified) => {
+                ("security-high", tr!("IDENTITY_VERIFIED", "Verified"))
+            }
+            Some(IdentityState::Unverified) => (
+                "security-medium",
+                tr!("IDENTITY_UNVERIFIED", "Not verified"),
+            ),
+            Some(IdentityState::VerificationViolation) => (
+                "security-low",
+                tr!(
+                    "IDENTITY_VERIFICATION_VIOLATION",
+                    "Identity changed since verification"
+                ),
+            ),
+            Some(IdentityState::NoIdentity) | None => return div().into_any_element(),
+        };
+
+        div()
+            .id("identity-shield")
+            .flex_none()
+            .child(icon(icon_name).size(self.size))
+            .tooltip(simple_tooltip(tooltip))
+            .into_any_element()
+    }
+}
+
+#[derive(IntoElement)]
+pub struct EventShield {
+    verification_state: Option<VerificationState>,
+}
+
+/// A shield warning about the authenticity of an encrypted event.
+///
+/// Nothing is shown for events sent by verified devices, or by devices of users whose identity is
+/// simply unverified, as that is already shown next to the author.
+pub fn event_shield(event: &EventTimelineItem) -> EventShield {
+    EventShield {
+        verification_state: event
+            .encryption_info()
+            .map(|encryption_info| encryption_info.verification_state.clone()),
+    }
+}
+
+impl RenderOnce for EventShield {
+    fn render(self, _: &mut Window, _: &mut App) -> impl IntoElement {
+        let (icon_name, tooltip) = match self.verification_state {
+            None
+            | Some(VerificationState::Verified)
+            | Some(VerificationState::Unverified(VerificationLevel::UnverifiedIdentity)) => {
+                return div().into_any_element();
+            }
+            Some(VerificationState::Unverified(VerificationLevel::VerificationViolation)) => (
+                "security-low",
+                tr!(
+                    "EVENT_SHIELD_VERIFICATION_VIOLATION",
+                    "Sent by a user whose identity has changed since you verified them"
+                ),
+            ),
+            Some(VerificationState::Unverified(VerificationLevel::UnsignedDevice)) => (
+                "security-medium",
+                tr!(
+                    "EVENT_SHIELD_UNSIGNED_DEVICE",
+                    "Sent from a device that its owner has not verified"
+                ),
+            ),
+            Some(VerificationState::Unverified(_)) => (
+                "security-low",
+                tr!(
+                    "EVENT_SHIELD_UNAUTHENTICATED",
+                    "The authenticity of this message can't be guaranteed"
+                ),
+            ),
+        };
+
+        div()
+            .id("event-shield")
+            .flex_none()
+            .child(icon(icon_name).size(16.))
+            .tooltip(simple_tooltip(tooltip))
+            .into_any_element()
+    }
+}
@@ -0,0 +1,208 @@
+use crate::chat::chat_room::open_room::OpenRoom;
+use cntp_i18n::{I18N_MANAGER, tr, trn};
+use contemporary::components::button::button;
+use contemporary::components::icon::icon;
+use contemporary::components::icon_text::icon_text;
+use contemporary::components::progress_bar::progress_bar;
+use contemporary::components::subtitle::subtitle;
+use contemporary::styling::theme::{Theme, VariableColor};
+use gpui::prelude::FluentBuilder;
+use gpui::{
+    App, Entity, InteractiveElement, IntoElement, ParentElement, RenderOnce,
+    StatefulInteractiveElement, Styled, Window, div, px,
+};
+use matrix_sdk::ruma::events::poll::start::PollKind;
+use matrix_sdk_ui::timeline::{EventTimelineItem, PollState};
+use std::collections::HashSet;
+use thegrid_common::session::session_manager::SessionManager;
+
+#[derive(IntoElement)]
+pub struct PollItem {
+    poll: PollState,
+    event: EventTimelineItem,
+    room: Entity<OpenRoom>,
+}
+
+pub fn poll_item(poll: PollState, event: EventTimelineItem, room: Entity<OpenRoom>) -> PollItem {
+    PollItem { poll, event, room }
+}
+
+impl RenderOnce for PollItem {
+    fn render(self, _: &mut Window, cx: &mut App) -> impl IntoElement {
+        let theme = cx.global::<Theme>().clone();
+
+        let session_manager = cx.global::<SessionManager>();
+        let client = session_manager.client().unwrap().read(cx).clone();
+        let own_user_id = client.user_id().map(|user_id| user_id.to_string());
+
+        let results = self.poll.results();
+        let ended = results.end_time.is_some();
+        let show_results = ended || results.kind == PollKind::Disclosed;
+        let can_vote = !ended && self.event.event_id().is_some();
+
+        let own_votes = results
+            .votes
+            .iter()
+            .filter(|(_, voters)| {
+                own_user_id
+                    .as_ref()
+                    .is_some_and(|own_user_id| {
+                        voters.iter().any(|voter| voter.to_string() == *own_user_id)
+                    })
+            })
+            .map(|(answer_id, _)| answer_id.clone())
+            .collect::<Vec<_>>();
+        let total_voters = results
+            .votes
+            .values()
+            .flatten()
+            .collect::<HashSet<_>>()
+            .len();
+        let winning_votes = results
+            .votes
+            .values()
+            .map(|voters| voters.len())
+            .max()
+            .unwrap_or_default();
+
+        div()
+            .flex()
+            .flex_col()
+            .gap(px(4.))
+            .max_w(px(500.))
+            .p(px(8.))
+            .border(px(1.))
+            .border_color(theme.border_color)
+            .rounded(theme.border_radius)
+            .bg(theme.layer_background)
+            .child(
+                div()
+                    .flex()
+                    .items_center()
+                    .gap(px(4.))
+                    .child(icon("view-statistics"))
+                    .child(subtitle(if ended {
+                        tr!("POLL_ENDED", "Poll Ended")
+                    } else {
+                        tr!("POLL", "Poll")
+                    })),
+            )
+            .child(results.question.clone())
+            .children(results.answers.iter().map(|answer| {
+                let votes = results
+                    .votes
+                    .get(&answer.id)
+                    .map(|voters| voters.len())
+                    .unwrap_or_default();
+                let selected = own_votes.contains(&answer.id);
+                let winning = ended && votes > 0 && votes == winning_votes;
+
+                div()
+                    .id(answer.id.clone())
+                    .flex()
+                    .flex_col()
+                    .gap(px(2.))
+                    .p(px(4.))
+                    .border(px(1.))
+                    .rounded(theme.border_radius)
+                    .when_else(
+                        selected || winning,
+                        |david| david.border_color(theme.info_accent_color),
+                        |david| david.border_color(theme.border_color),
+                    )
+                    .child(
+                        div()
+                            .flex()
+                            .items_center()
+                            .gap(px(4.))
+                            .when(selected, |david| david.child(icon("dialog-ok")))
+                            .child(div().flex_grow(1.).child(answer.text.clone()))
+                            .when(show_results, |david| {
+                                david.child(
+                                    div()
+                                        .text_color(theme.foreground.disabled())
+                                        .child(I18N_MANAGER.locale().format_decimal(votes)),
+                                )
+                            }),
+                    )
+                    .when(show_results, |david| {
+                        david.child(progress_bar().w_full().value(if total_voters == 0 {
+                            0.
+                        } else {
+                            votes as f32 / total_voters as f32
+                        }))
+                    })
+                    .when(can_vote, |david| {
+                        david.cursor_pointer().on_click({
+                            let open_room = self.room.clone();
+                            let event = self.event.clone();
+                            let answer_id = answer.id.clone();
+                            let max_selections = results.max_selections as usize;
+                            let own_votes = own_votes.clone();
+                            move |_, _, cx| {
+                                let mut answers = own_votes.clone();
+                                if max_selections <= 1 {
+                                    answers = vec![answer_id.clone()];
+                                } else if answers.contains(&answer_id) {
+                                    answers.retain(|answer| answer != &answer_id);
+                                } else if answers.len() < max_selections {
+                                    answers.push(answer_id.clone());
+                                }
+
+                                open_room.update(cx, |open_room, cx| {
+                                    open_room.respond_to_poll(&event, answers, cx);
+                                });
+                            }
+                        })
+                    })
+            }))
+            .child(
+                div()
+                    .flex()
+                    .items_center()
+                    .gap(px(4.))
+                    .child(
+                        div()
+                            .flex_grow(1.)
+                            .text_color(theme.foreground.disabled())
+                            .text_size(theme.system_font_size * 0.8)
+                            .child(if show_results {
+                                trn!(
+                                    "POLL_TOTAL_VOTES",
+                                    "{{count}} vote cast",
+                                    "{{count}} votes cast",
+                                    count = total_voters as isize
+                                )
+                            } else if own_votes.is_empty() {
+                                tr!(
+                                    "POLL_UNDISCLOSED",
+                                    "Results will be shown when the poll ends"
+                                )
+                            } else {
+                                tr!(
+                                    "POLL_UNDISCLOSED_VOTED",
+                                    "You have voted. Results will be shown when the poll ends"
+                                )
+                            }),
+                    )
+                    .when(can_vote && self.event.is_own(), |david| {
+                        david.child(
+                            button("end-poll")
+                                .child(icon_text(
+                                    "media-playback-stop",
+                                    tr!("POLL_END", "End Poll"),
+                                ))
+                                .on_click({
+                                    let open_room = self.room.clone();
+                                    let event = self.event.clone();
+                                    move |_, _, cx| {
+                                        open_room.update(cx, |open_room, cx| {
+                                            open_room.end_poll(&event, cx);
+                                        });
+                                    }
+                                }),
+                        )
+                    }),
+            )
+    }
+}
@@ -24,6 +24,7 @@ pub struct ReplyFragment {
     displayed_room: Entity<DisplayedRoom>,
     on_user_action: Rc<Box<AuthorFlyoutUserActionListener>>,
     event_id: Option<OwnedEventId>,
+    thread_root: Option<OwnedEventId>,
 }
 
 pub fn reply_fragment(
@@ -42,6 +43,7 @@ pub fn reply_fragment(
         displayed_room,
         on_user_action,
         event_id: None,
+        thread_root: None,
     }
 }
 
@@ -60,6 +62,10 @@ pub fn reply_fragment_in_reply_to(
     } else {
         (None, None, None)
     };
+    let thread_root = match &content {
+        Some(TimelineItemContent::MsgLike(msg_like)) => msg_like.thread_root.clone(),
+        _ => None,
+    };
 
     ReplyFragment {
         content,
@@ -69,12 +75,19 @@ pub fn reply_fragment_in_reply_to(
         displayed_room,
         on_user_action,
         event_id: Some(details.event_id),
+        thread_root,
     }
 }
 
 impl RenderOnce for ReplyFragment {
     fn render(self, window: &mut Window, cx: &mut App) -> impl IntoElement {
         let theme = cx.theme();
+
+        // Threaded events are hidden from the main timeline, so replies to them open the thread.
+        // Within a thread, the replied event is already part of the timeline being shown.
+        let viewing_thread = self.room.read(cx).current_focus.thread_root().is_some();
+        let in_thread = self.thread_root.is_some() && !viewing_thread;
+
         div()
             .id("reply_fragment")
             .flex()
@@ -82,6 +95,9 @@ impl RenderOnce for ReplyFragment {
             .text_size(theme.system_font_size * 0.8)
             // TODO: RTL?
             .child("⬐ ")
+            .when(in_thread, |david| {
+                david.child(format!("{} ", tr!("REPLY_IN_THREAD", "In thread:")))
+            })
             .child({
                 match self.content {
                     Some(TimelineItemContent::MsgLike(msg_like)) => match msg_like.kind {
@@ -109,21 +125,28 @@ impl RenderOnce for ReplyFragment {
                     tr!("REPLY_UNAVAILABLE", "Reply message could not be loaded").into_any_element()
                 })
             })
-            .when_some(self.event_id, |david, event_id| {
+            .when_some(self.event_id.filter(|_| !viewing_thread), |david, event_id| {
                 david.cursor_pointer().on_click({
                     let open_room = self.room.clone();
                     let event_id = event_id.clone();
+                    let thread_root = self.thread_root.clone();
                     move |_, _, cx| {
                         open_room.update(cx, {
                             let event_id = event_id.clone();
+                            let thread_root = thread_root.clone();
                             move |open_room, cx| {
+                                if let Some(thread_root) = thread_root {
+                                    open_room.open_thread(thread_root, cx);
+                                    return;
+                                }
+
                                 open_room.focus_timeline(
                                     OpenRoomFocus {
                                         timeline_focus: TimelineFocus::Event {
                                             target: event_id,
                                             num_context_events: 0,
                                             thread_mode: TimelineEventFocusThreadMode::Automatic {
-                                                hide_threaded_events: false,
+                                                hide_threaded_events: true,
                                             },
                                         },
                                         reason: OpenRoomFocusReason::Reply,
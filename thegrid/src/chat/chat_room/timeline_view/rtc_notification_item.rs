@@ -1,7 +1,7 @@
 use crate::chat::chat_room::timeline_view::state_change_element::state_change_element;
-use cntp_i18n::{Quote, tr};
-use gpui::{App, IntoElement, ParentElement, RenderOnce, Window, div};
-use matrix_sdk_ui::timeline::{EventTimelineItem, MemberProfileChange};
+use cntp_i18n::tr;
+use gpui::{App, IntoElement, RenderOnce, Window};
+use matrix_sdk_ui::timeline::{EventTimelineItem, TimelineDetails};
 
 #[derive(IntoElement)]
 pub struct RtcNotificationItem {
@@ -14,6 +14,19 @@ pub fn rtc_notification_item(rtc_notification: EventTimelineItem) -> RtcNotifica
 
 impl RenderOnce for RtcNotificationItem {
     fn render(self, _: &mut Window, cx: &mut App) -> impl IntoElement {
-        div().child(tr!("RTC_NOTIFICATION_TEXT", "Call started"))
+        let sender = match self.rtc_notification.sender_profile() {
+            TimelineDetails::Ready(profile) => profile.display_name.clone(),
+            _ => None,
+        }
+        .unwrap_or_else(|| self.rtc_notification.sender().to_string());
+
+        state_change_element(
+            Some("call-start".to_string()),
+            tr!(
+                "RTC_NOTIFICATION_CALL_STARTED",
+                "{{user}} started a call",
+                user = sender
+            ),
+        )
     }
 }
@@ -5,6 +5,7 @@ use crate::chat::chat_room::timeline_view::author_flyout::{
     AuthorFlyoutUserActionEvent, AuthorFlyoutUserActionListener, author_flyout,
 };
 use crate::chat::chat_room::timeline_view::flag_event_popover::FlagEventPopover;
+use crate::chat::chat_room::timeline_view::identity_shield::{event_shield, identity_shield};
 use crate::chat::chat_room::timeline_view::membership_change_item::membership_change_item;
 use crate::chat::chat_room::timeline_view::message_error_item::message_error_item;
 use crate::chat::chat_room::timeline_view::profile_change_item::profile_change_item;
@@ -43,6 +44,8 @@ use matrix_sdk_ui::timeline::{
 use std::rc::Rc;
 use std::sync::Arc;
 use thegrid_common::mxc_image::{SizePolicy, mxc_image};
+use thegrid_common::session::image_packs_cache::PackUsage;
+use thegrid_common::session::session_manager::SessionManager;
 use thegrid_common::tokio_helper::TokioHelper;
 
 #[derive(IntoElement)]
@@ -98,6 +101,7 @@ impl TimelineItem {
             } => Some(target.clone()),
             _ => None,
         };
+        let viewing_thread = open_room_read.current_focus.thread_root().is_some();
 
         let emoji_flyout_visible = window.use_state(cx, |_, _| false);
         let emoji_flyout = window.use_state(cx, {
@@ -105,18 +109,26 @@ impl TimelineItem {
             let open_room = open_room.clone();
             let event = event.clone();
             move |_, cx| {
+                let image_packs = cx
+                    .global::<SessionManager>()
+                    .image_packs()
+                    .read(cx)
+                    .packs_for_room(&open_room.read(cx).room_id);
                 let mut emoji_flyout = EmojiFlyout::new(cx);
+                emoji_flyout.set_image_packs(image_packs, PackUsage::Emoticon, cx);
                 emoji_flyout.set_emoji_selected_listener({
                     let emoji_flyout_visible = emoji_flyout_visible.clone();
                     let open_room = open_room.clone();
                     move |emoji_selected_event, _, cx| {
                         emoji_flyout_visible.write(cx, false);
+
+                        // Reactions with custom emoji use the image as the key
+                        let reaction = match &emoji_selected_event.image {
+                            Some(image) => image.url.to_string(),
+                            None => emoji_selected_event.emoji.clone(),
+                        };
                         open_room.update(cx, |open_room, cx| {
-                            open_room.toggle_reaction_on_event(
-                                &event,
-                                emoji_selected_event.emoji.clone(),
-                                cx,
-                            )
+                            open_room.toggle_reaction_on_event(&event, reaction, cx)
                         })
                     }
                 });
@@ -221,6 +233,40 @@ impl TimelineItem {
                             })
                             .build(),
                     );
+                    if !viewing_thread && msg.thread_root.is_none() {
+                        context_menu.push(
+                            ContextMenuItem::menu_item()
+                                .label(if msg.thread_summary.is_some() {
+                                    tr!("MESSAGE_VIEW_THREAD", "View Thread")
+                                } else {
+                                    tr!("MESSAGE_REPLY_IN_THREAD", "Reply in Thread")
+                                })
+                                .icon("mail-reply-all")
+                                .when(
+                                    event.event_id().is_none()
+                                        || (msg.thread_summary.is_none()
+                                            && current_user.as_ref().is_some_and(|user| {
+                                                !user.can_send_message(
+                                                    MessageLikeEventType::RoomMessage,
+                                                )
+                                            })),
+                                    |david| david.disabled(),
+                                )
+                                .on_triggered({
+                                    let open_room = open_room.clone();
+                                    let event_id = event.event_id().map(|id| id.to_owned());
+                                    move |_, _, cx| {
+                                        let Some(event_id) = event_id.clone() else {
+                                            return;
+                                        };
+                                        open_room.update(cx, |open_room, cx| {
+                                            open_room.open_thread(event_id, cx);
+                                        });
+                                    }
+                                })
+                                .build(),
+                        );
+                    }
                     context_menu.push(
                         ContextMenuItem::menu_item()
                             .label(tr!("MESSAGE_FLAG", "Flag message as inappropriate"))
@@ -536,18 +582,30 @@ impl TimelineItem {
                             .child(
                                 div()
                                     .child(
-                                        match sender_profile {
-                                            TimelineDetails::Ready(profile) => profile
-                                                .display_name
-                                                .clone()
-                                                .or(Some(event.sender().to_string())),
-                                            _ => None,
-                                        }
-                                        .unwrap_or_default(),
+                                        div()
+                                            .flex()
+                                            .items_center()
+                                            .gap(px(4.))
+                                            .child(
+                                                match sender_profile {
+                                                    TimelineDetails::Ready(profile) => profile
+                                                        .display_name
+                                                        .clone()
+                                                        .or(Some(event.sender().to_string())),
+                                                    _ => None,
+                                                }
+                                                .unwrap_or_default(),
+                                            )
+                                            .when(event.encryption_info().is_some(), |david| {
+                                                david.child(identity_shield(
+                                                    event.sender().to_owned(),
+                                                ))
+                                            }),
                                     )
                                     .child(event_content),
                             ),
                     )
+                    .child(event_shield(event))
                     .when(!context_menu.is_empty(), |david| {
                         david.with_context_menu(context_menu)
                     })
@@ -588,6 +646,7 @@ impl TimelineItem {
                         .overflow_hidden()
                         .child(event_content),
                 )
+                .child(event_shield(event))
                 .when(!context_menu.is_empty(), |david| {
                     david.with_context_menu(context_menu)
                 })
@@ -1,11 +1,18 @@
 use crate::chat::chat_room::open_room::OpenRoom;
+use crate::chat::chat_room::timeline_view::audio_message_item::audio_message_item;
 use crate::chat::chat_room::timeline_view::author_flyout::{
     AuthorFlyoutUserActionListener, author_flyout,
 };
 use crate::chat::chat_room::timeline_view::message_error_item::message_error_item;
+use crate::chat::chat_room::timeline_view::poll_item::poll_item;
 use crate::chat::chat_room::timeline_view::reply_fragment::reply_fragment_in_reply_to;
+use crate::chat::chat_room::timeline_view::url_preview_item::{
+    first_previewable_url, url_preview_item,
+};
+use crate::chat::chat_room::timeline_view::verification_request_item::verification_request_item;
+use crate::chat::chat_room::timeline_view::video_message_item::video_message_item;
 use crate::chat::displayed_room::DisplayedRoom;
-use cntp_i18n::{I18N_MANAGER, Quote, tr};
+use cntp_i18n::{I18N_MANAGER, Quote, tr, trn};
 use contemporary::components::admonition::{AdmonitionSeverity, admonition};
 use contemporary::components::anchorer::WithAnchorer;
 use contemporary::components::button::button;
@@ -15,6 +22,7 @@ use contemporary::components::icon::icon;
 use contemporary::components::icon_text::icon_text;
 use contemporary::components::layer::layer;
 use contemporary::components::spinner::spinner;
+use contemporary::components::tooltip::simple_tooltip;
 use contemporary::styling::theme::{Theme, ThemeStorage, VariableColor};
 use directories::UserDirs;
 use gpui::prelude::FluentBuilder;
@@ -24,21 +32,24 @@ use gpui::{
     StatefulInteractiveElement, Styled, Window, canvas, div, point, px, rgba,
 };
 use matrix_sdk::room::RoomMember;
+use matrix_sdk::ruma::events::room::MediaSource;
 use matrix_sdk::ruma::events::room::message::{
     FileMessageEventContent, FormattedBody, MessageFormat, MessageType,
 };
 use matrix_sdk::ruma::matrix_uri::MatrixId;
-use matrix_sdk::ruma::{MatrixToUri, OwnedUserId, UserId};
+use matrix_sdk::ruma::{MatrixToUri, OwnedEventId, OwnedMxcUri, OwnedUserId, UserId};
 use matrix_sdk_ui::timeline::{
-    EventTimelineItem, MsgLikeContent, MsgLikeKind, Profile, TimelineDetails,
+    EventTimelineItem, MsgLikeContent, MsgLikeKind, Profile, ThreadSummary, TimelineDetails,
+    TimelineItemContent,
 };
+use std::collections::HashMap;
 use std::fs::copy;
 use std::rc::Rc;
 use thegrid_common::mxc_image::{SizePolicy, mxc_image};
 use thegrid_common::session::media_cache::{MediaCacheEntry, MediaFile, MediaState};
 use thegrid_common::session::session_manager::SessionManager;
 use thegrid_common::tokio_helper::TokioHelper;
-use thegrid_text_rendering::TextView;
+use thegrid_text_rendering::{MentionEvent, MentionPill, TextView};
 use tracing::info;
 
 #[derive(IntoElement)]
@@ -78,6 +89,25 @@ impl RenderOnce for TimelineMessageItem {
 
         let reactions = self.content.reactions;
         let open_room = self.room.clone();
+
+        // Thread summaries are only useful outside of the thread they summarise
+        let thread_summary = self
+            .content
+            .thread_summary
+            .filter(|_| open_room.read(cx).current_focus.thread_root().is_none())
+            .zip(self.event.event_id().map(|event_id| event_id.to_owned()))
+            .map(|(thread_summary, root_event_id)| {
+                thread_summary_item(
+                    thread_summary,
+                    root_event_id,
+                    self.room.clone(),
+                    self.displayed_room.clone(),
+                    self.on_user_action.clone(),
+                    window,
+                    cx,
+                )
+            });
+
         div()
             .flex()
             .flex_col()
@@ -90,17 +120,66 @@ impl RenderOnce for TimelineMessageItem {
                 ))
             })
             .child(match self.content.kind {
-                MsgLikeKind::Message(message) => div().child(msgtype_to_message_line(
-                    message.msgtype(),
-                    sender,
-                    sender_profile.clone(),
-                    false,
-                    self.room,
-                    self.displayed_room,
-                    self.on_user_action,
-                    window,
-                    cx,
-                )),
+                MsgLikeKind::Message(message) => match message.msgtype() {
+                    MessageType::VerificationRequest(verification_request) => {
+                        div().child(verification_request_item(
+                            verification_request.clone(),
+                            self.event.clone(),
+                            self.room.clone(),
+                        ))
+                    }
+                    msgtype => {
+                        // Images open in the media viewer when clicked
+                        let viewable_event_id = self
+                            .event
+                            .event_id()
+                            .filter(|_| matches!(msgtype, MessageType::Image(_)))
+                            .map(|event_id| event_id.to_owned());
+                        let message_line = msgtype_to_message_line(
+                            msgtype,
+                            sender,
+                            sender_profile.clone(),
+                            false,
+                            self.room.clone(),
+                            self.displayed_room,
+                            self.on_user_action,
+                            window,
+                            cx,
+                        );
+
+                        let open_room = open_room.clone();
+                        match viewable_event_id {
+                            Some(event_id) => div().child(
+                                div()
+                                    .id("view-media")
+                                    .cursor_pointer()
+                                    .child(message_line)
+                                    .on_click(move |_, _, cx| {
+                                        open_room.update(cx, |open_room, cx| {
+                                            open_room.view_media(event_id.clone(), cx)
+                                        })
+                                    }),
+                            ),
+                            None => div().child(message_line),
+                        }
+                    }
+                },
+                MsgLikeKind::Poll(poll) => {
+                    div().child(poll_item(poll, self.event.clone(), self.room.clone()))
+                }
+                MsgLikeKind::Sticker(sticker) => {
+                    let sticker = sticker.content();
+                    div().child(
+                        div()
+                            .id("sticker")
+                            .child(
+                                mxc_image(MediaSource::from(sticker.source.clone()))
+                                    .fixed_square(px(160.))
+                                    .size_policy(SizePolicy::Fit),
+                            )
+                            .tooltip(simple_tooltip(sticker.body.clone())),
+                    )
+                }
                 MsgLikeKind::Redacted => div().child(message_error_item(
                     "edit-delete",
                     tr!("MESSAGE_REDACTED", "Removed"),
@@ -117,6 +196,7 @@ impl RenderOnce for TimelineMessageItem {
                     cx,
                 )),
             })
+            .children(thread_summary)
             .when(!reactions.is_empty(), |david| {
                 david.child(reactions.iter().fold(
                     div().flex().mt(px(4.)).gap(px(4.)),
@@ -150,7 +230,7 @@ impl RenderOnce for TimelineMessageItem {
                                         })
                                     }
                                 })
-                                .child(reaction.clone())
+                                .child(reaction_key(reaction))
                                 .child(I18N_MANAGER.locale().format_decimal(reactees.len())),
                         )
                     },
@@ -159,6 +239,86 @@ impl RenderOnce for TimelineMessageItem {
     }
 }
 
+/// Draw the key of a reaction, which is an image for reactions with a custom emoji
+fn reaction_key(reaction: &str) -> AnyElement {
+    if reaction.starts_with("mxc://") {
+        mxc_image(OwnedMxcUri::from(reaction))
+            .fixed_square(px(16.))
+            .size_policy(SizePolicy::Fit)
+            .into_any_element()
+    } else {
+        reaction.to_string().into_any_element()
+    }
+}
+
+fn thread_summary_item(
+    thread_summary: ThreadSummary,
+    root_event_id: OwnedEventId,
+    room: Entity<OpenRoom>,
+    displayed_room: Entity<DisplayedRoom>,
+    on_user_action: Rc<Box<AuthorFlyoutUserActionListener>>,
+    window: &mut Window,
+    cx: &mut App,
+) -> AnyElement {
+    let theme = cx.global::<Theme>().clone();
+
+    let latest_event = match thread_summary.latest_event {
+        TimelineDetails::Ready(latest_event) => match latest_event.content {
+            TimelineItemContent::MsgLike(MsgLikeContent {
+                kind: MsgLikeKind::Message(message),
+                ..
+            }) => Some(
+                div()
+                    .flex()
+                    .text_color(theme.foreground.disabled())
+                    .child(msgtype_to_message_line(
+                        message.msgtype(),
+                        latest_event.sender,
+                        latest_event.sender_profile,
+                        true,
+                        room.clone(),
+                        displayed_room,
+                        on_user_action,
+                        window,
+                        cx,
+                    ))
+                    .into_any_element(),
+            ),
+            _ => None,
+        },
+        _ => None,
+    };
+
+    div()
+        .id("thread-summary")
+        .flex()
+        .flex_col()
+        .mt(px(4.))
+        .p(px(4.))
+        .gap(px(2.))
+        .border(px(1.))
+        .border_color(theme.border_color)
+        .bg(theme.layer_background)
+        .rounded(theme.border_radius)
+        .cursor_pointer()
+        .child(icon_text(
+            "mail-reply-all",
+            trn!(
+                "THREAD_SUMMARY_REPLIES",
+                "{{count}} reply",
+                "{{count}} replies",
+                count = thread_summary.num_replies as isize
+            ),
+        ))
+        .children(latest_event)
+        .on_click(move |_, _, cx| {
+            room.update(cx, |room, cx| {
+                room.open_thread(root_event_id.clone(), cx);
+            })
+        })
+        .into_any_element()
+}
+
 pub fn msgtype_to_message_line<'a>(
     msgtype: &MessageType,
     sender: OwnedUserId,
@@ -389,7 +549,7 @@ pub fn msgtype_to_message_line<'a>(
                                         .icon("document-save-as")
                                         .on_triggered(move |_, _, cx| {
                                             save_file(
-                                                file.clone(),
+                                                file.filename(),
                                                 media_file_entity_3.clone(),
                                                 cx,
                                             );
@@ -401,9 +561,17 @@ pub fn msgtype_to_message_line<'a>(
                 )
                 .into_any_element()
         }
-        MessageType::VerificationRequest(verification_request) => {
-            "Key Verification Request".into_any_element()
-        }
+        MessageType::Audio(audio) if as_reply => div()
+            .child(icon_text("audio-x-generic", audio.body.clone()))
+            .into_any_element(),
+        MessageType::Audio(audio) => audio_message_item(audio.clone()).into_any_element(),
+        MessageType::Video(video) if as_reply => div()
+            .child(icon_text("video-x-generic", video.body.clone()))
+            .into_any_element(),
+        MessageType::Video(video) => video_message_item(video.clone()).into_any_element(),
+        MessageType::VerificationRequest(_) => div()
+            .child(icon_text("security-high", tr!("VERIFICATION_REQUEST_ITEM")))
+            .into_any_element(),
         _ => message_error_item(
             "dialog-warning",
             tr!("MESSAGE_UNSUPPORTED", "Unsupported Message"),
@@ -431,6 +599,7 @@ fn text_message(
 ) -> AnyElement {
     let current_link_confirmation = window.use_state(cx, |_, _| None);
     let author_flyout_information_entity = window.use_state(cx, |_, _| None);
+    let mentioned_members = window.use_state(cx, |_, _| HashMap::new());
 
     let body = match &formatted {
         Some(FormattedBody {
@@ -481,10 +650,35 @@ fn text_message(
                     }
                 }
             })
+            .on_resolve_mention({
+                let room = room.clone();
+                move |event, _, cx| mention_pill(event, &room, &mentioned_members, cx)
+            })
+            .on_resolve_emoticon(|event, _, _| {
+                Some(
+                    mxc_image(OwnedMxcUri::from(event.url.as_ref()))
+                        .fixed_square(event.font_size * 1.2)
+                        .size_policy(SizePolicy::Fit)
+                        .into_any_element(),
+                )
+            })
             .into_any_element(),
         _ => body.clone().into_any_element(),
     };
 
+    let preview_url = if as_reply {
+        None
+    } else {
+        first_previewable_url(body).filter(|_| {
+            room.read(cx).room.as_ref().is_some_and(|room| {
+                cx.global::<SessionManager>()
+                    .url_previews()
+                    .read(cx)
+                    .previews_enabled(room)
+            })
+        })
+    };
+
     let theme = cx.global::<Theme>();
     let current_link = current_link_confirmation.read(cx).clone();
     let author_flyout_information = author_flyout_information_entity.read(cx).as_ref();
@@ -494,15 +688,31 @@ fn text_message(
         .max_w_full()
         .child(
             div()
+                .flex()
+                .flex_col()
+                .items_start()
+                .gap(px(4.))
                 .max_w_full()
-                .p(px(6.))
-                .when_else(
-                    as_reply,
-                    |david| david.bg(rgba(0x00C8FF05)),
-                    |david| david.bg(rgba(0x00C8FF10)),
+                .child(
+                    div()
+                        .max_w_full()
+                        .p(px(6.))
+                        .when_else(
+                            as_reply,
+                            |david| david.bg(rgba(0x00C8FF05)),
+                            |david| david.bg(rgba(0x00C8FF10)),
+                        )
+                        .rounded(theme.border_radius)
+                        .child(body),
                 )
-                .rounded(theme.border_radius)
-                .child(body),
+                .when_some(preview_url, |david, url| {
+                    let current_link_confirmation = current_link_confirmation.clone();
+                    let link = url.clone();
+                    david.child(url_preview_item(url, move |_, _, cx| {
+                        // Ask the user if they want to go to this link
+                        current_link_confirmation.write(cx, Some(link.clone().into()));
+                    }))
+                }),
         )
         .child(
             dialog_box("link-open-confirmation")
@@ -597,6 +807,114 @@ fn text_message(
         .into_any_element()
 }
 
+enum MentionedMember {
+    Loading,
+    Loaded(Option<RoomMember>),
+}
+
+/// Resolve a mention of a room member or a room to a pill.
+///
+/// Room members are looked up in the background the first time they are mentioned, and the
+/// mention is shown as a link until then.
+fn mention_pill(
+    event: &MentionEvent,
+    room: &Entity<OpenRoom>,
+    mentioned_members: &Entity<HashMap<OwnedUserId, MentionedMember>>,
+    cx: &mut App,
+) -> Option<MentionPill> {
+    let uri = MatrixToUri::parse(&event.url).ok()?;
+    let theme = cx.global::<Theme>();
+    let avatar_size = event.font_size * 0.9;
+    let avatar_radius = theme.border_radius;
+
+    match uri.id() {
+        MatrixId::User(user_id) => {
+            let Some(member) = mentioned_members.read(cx).get(user_id) else {
+                let room = room.read(cx).room.clone()?;
+                mentioned_members.update(cx, |mentioned_members, _| {
+                    mentioned_members.insert(user_id.clone(), MentionedMember::Loading);
+                });
+
+                let mentioned_members = mentioned_members.clone();
+                let user_id = user_id.clone();
+                cx.spawn(async move |cx: &mut AsyncApp| {
+                    let member = cx
+                        .spawn_tokio({
+                            let user_id = user_id.clone();
+                            async move { room.get_member(&user_id).await }
+                        })
+                        .await
+                        .ok()
+                        .flatten();
+                    let _ = mentioned_members.update(cx, |mentioned_members, cx| {
+                        mentioned_members.insert(user_id, MentionedMember::Loaded(member));
+                        cx.notify();
+                    });
+                })
+                .detach();
+                return None;
+            };
+            let MentionedMember::Loaded(member) = member else {
+                return None;
+            };
+
+            Some(MentionPill {
+                display_name: member
+                    .as_ref()
+                    .and_then(|member| member.display_name())
+                    .unwrap_or(user_id.as_str())
+                    .to_string()
+                    .into(),
+                avatar: Some(
+                    mxc_image(
+                        member
+                            .as_ref()
+                            .and_then(|member| member.avatar_url())
+                            .map(|url| url.to_owned()),
+                    )
+                    .fallback_image(user_id)
+                    .fixed_square(avatar_size)
+                    .size_policy(SizePolicy::Fit)
+                    .rounded(avatar_radius)
+                    .into_any_element(),
+                ),
+            })
+        }
+        MatrixId::Room(_) | MatrixId::RoomAlias(_) => {
+            let client = cx.global::<SessionManager>().client()?.read(cx).clone();
+            let (mentioned_room, fallback_name) = match uri.id() {
+                MatrixId::Room(room_id) => (client.get_room(room_id), room_id.to_string()),
+                MatrixId::RoomAlias(room_alias) => (
+                    client.joined_rooms().into_iter().find(|room| {
+                        room.canonical_alias().as_ref() == Some(room_alias)
+                            || room.alt_aliases().contains(room_alias)
+                    }),
+                    room_alias.to_string(),
+                ),
+                _ => return None,
+            };
+
+            let display_name = mentioned_room
+                .as_ref()
+                .and_then(|room| room.cached_display_name())
+                .map(|name| name.to_string())
+                .unwrap_or(fallback_name);
+            Some(MentionPill {
+                display_name: display_name.into(),
+                avatar: mentioned_room.map(|mentioned_room| {
+                    mxc_image(mentioned_room.avatar_url())
+                        .fallback_image(mentioned_room.room_id())
+                        .fixed_square(avatar_size)
+                        .size_policy(SizePolicy::Fit)
+                        .rounded(avatar_radius)
+                        .into_any_element()
+                }),
+            })
+        }
+        _ => None,
+    }
+}
+
 fn download_file(file: FileMessageEventContent, media_file: Entity<MediaFile>, cx: &mut App) {
     // Trigger a job
     media_file.update(cx, |media_file, cx| {
@@ -609,9 +927,9 @@ fn download_file(file: FileMessageEventContent, media_file: Entity<MediaFile>, c
     })
 }
 
-fn save_file(file: FileMessageEventContent, media_file: Entity<MediaFile>, cx: &mut App) {
+pub(crate) fn save_file(filename: &str, media_file: Entity<MediaFile>, cx: &mut App) {
     let user_dirs = UserDirs::new().unwrap();
-    let prompt = cx.prompt_for_new_path(user_dirs.download_dir().unwrap(), Some(file.filename()));
+    let prompt = cx.prompt_for_new_path(user_dirs.download_dir().unwrap(), Some(filename));
     let media_file = media_file.read(cx);
     let MediaState::Loaded(media_file) = &media_file.media_state else {
         return;
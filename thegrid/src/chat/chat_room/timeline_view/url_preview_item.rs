@@ -0,0 +1,114 @@
+use contemporary::styling::theme::{Theme, VariableColor};
+use gpui::prelude::FluentBuilder;
+use gpui::{
+    App, BorrowAppContext, ClickEvent, FontWeight, InteractiveElement, IntoElement, ParentElement,
+    RenderOnce, StatefulInteractiveElement, Styled, Window, div, px, rgba,
+};
+use matrix_sdk::ruma::MatrixToUri;
+use std::rc::Rc;
+use thegrid_common::mxc_image::{SizePolicy, mxc_image};
+use thegrid_common::session::media_cache::UrlPreviewState;
+use thegrid_common::session::session_manager::SessionManager;
+use url::Url;
+
+/// The size of the image shown next to a URL preview
+const PREVIEW_IMAGE_SIZE: f32 = 80.;
+
+#[derive(IntoElement)]
+pub struct UrlPreviewItem {
+    url: String,
+    on_click: Rc<Box<dyn Fn(&ClickEvent, &mut Window, &mut App)>>,
+}
+
+pub fn url_preview_item(
+    url: String,
+    on_click: impl Fn(&ClickEvent, &mut Window, &mut App) + 'static,
+) -> UrlPreviewItem {
+    UrlPreviewItem {
+        url,
+        on_click: Rc::new(Box::new(on_click)),
+    }
+}
+
+/// Find the first web link in a message body that a preview can be shown for
+pub fn first_previewable_url(body: &str) -> Option<String> {
+    body.match_indices("http")
+        .filter_map(|(start, _)| {
+            let candidate = body[start..]
+                .split(|c: char| c.is_whitespace() || matches!(c, '<' | '>' | '"' | ')' | ']'))
+                .next()?
+                .trim_end_matches(['.', ',', ';', ':', '!', '?', '\'']);
+            let url = Url::parse(candidate).ok()?;
+            matches!(url.scheme(), "http" | "https")
+                .then_some(url)
+                .filter(|url| url.host_str().is_some())
+        })
+        // Links to Matrix users and rooms are shown as pills instead
+        .find(|url| MatrixToUri::parse(url.as_str()).is_err())
+        .map(|url| url.to_string())
+}
+
+impl RenderOnce for UrlPreviewItem {
+    fn render(self, _: &mut Window, cx: &mut App) -> impl IntoElement {
+        let url_preview = cx.update_global::<SessionManager, _>(|session_manager, cx| {
+            session_manager.media().url_preview(self.url.clone(), cx)
+        });
+        let UrlPreviewState::Loaded(preview) = &url_preview.read(cx).preview_state else {
+            return div().into_any_element();
+        };
+        if preview.is_empty() {
+            return div().into_any_element();
+        }
+
+        let theme = cx.global::<Theme>();
+        let on_click = self.on_click;
+
+        div()
+            .id("url-preview")
+            .flex()
+            .gap(px(8.))
+            .p(px(6.))
+            .max_w(px(500.))
+            .bg(rgba(0x00C8FF10))
+            .rounded(theme.border_radius)
+            .border_l(px(2.))
+            .border_color(theme.info_accent_color)
+            .cursor_pointer()
+            .on_click(move |event, window, cx| on_click(event, window, cx))
+            .child(
+                div()
+                    .flex()
+                    .flex_col()
+                    .flex_grow(1.)
+                    .min_w_0()
+                    .gap(px(2.))
+                    .when_some(preview.site_name.clone(), |david, site_name| {
+                        david.child(
+                            div()
+                                .text_color(theme.foreground.disabled())
+                                .child(site_name),
+                        )
+                    })
+                    .when_some(preview.title.clone(), |david, title| {
+                        david.child(div().font_weight(FontWeight::BOLD).child(title))
+                    })
+                    .when_some(preview.description.clone(), |david, description| {
+                        david.child(
+                            div()
+                                .text_color(theme.foreground.disabled())
+                                .line_clamp(3)
+                                .child(description),
+                        )
+                    }),
+            )
+            .when_some(preview.image.clone(), |david, image| {
+                david.child(
+                    mxc_image(image)
+                        .fixed_square(px(PREVIEW_IMAGE_SIZE))
+                        .size_policy(SizePolicy::Fit)
+                        .rounded(theme.border_radius),
+                )
+            })
+            .into_any_element()
+    }
+}
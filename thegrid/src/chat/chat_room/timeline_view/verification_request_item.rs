@@ -0,0 +1,226 @@
+use crate::chat::chat_room::open_room::OpenRoom;
+use cntp_i18n::{Quote, tr};
+use contemporary::components::button::button;
+use contemporary::components::icon::icon;
+use contemporary::components::icon_text::icon_text;
+use contemporary::components::subtitle::subtitle;
+use contemporary::styling::theme::{Theme, VariableColor};
+use gpui::prelude::FluentBuilder;
+use gpui::{App, Entity, IntoElement, ParentElement, RenderOnce, Styled, Window, div, px};
+use matrix_sdk::encryption::verification::VerificationRequestState;
+use matrix_sdk::ruma::events::room::message::KeyVerificationRequestEventContent;
+use matrix_sdk_ui::timeline::EventTimelineItem;
+use thegrid_common::session::session_manager::SessionManager;
+
+#[derive(IntoElement)]
+pub struct VerificationRequestItem {
+    content: KeyVerificationRequestEventContent,
+    event: EventTimelineItem,
+    room: Entity<OpenRoom>,
+}
+
+pub fn verification_request_item(
+    content: KeyVerificationRequestEventContent,
+    event: EventTimelineItem,
+    room: Entity<OpenRoom>,
+) -> VerificationRequestItem {
+    VerificationRequestItem {
+        content,
+        event,
+        room,
+    }
+}
+
+impl RenderOnce for VerificationRequestItem {
+    fn render(self, _: &mut Window, cx: &mut App) -> impl IntoElement {
+        let theme = cx.global::<Theme>().clone();
+
+        let session_manager = cx.global::<SessionManager>();
+        let verification_requests = session_manager.verification_requests().read(cx);
+
+        // In-room verification requests use the event ID of the request as the flow ID
+        let verification_request_entity = self
+            .event
+            .event_id()
+            .and_then(|event_id| verification_requests.verification_request(event_id.as_str(), cx));
+        let verification_request = verification_request_entity
+            .as_ref()
+            .map(|verification_request| verification_request.read(cx).clone());
+
+        let verification_popover = self.room.read(cx).verification_popover.clone();
+
+        let description = if self.event.is_own() {
+            tr!(
+                "VERIFICATION_REQUEST_ITEM_OUTGOING",
+                "You asked {{user}} to verify their identity.",
+                user:Quote = self.content.to.to_string()
+            )
+        } else {
+            tr!(
+                "VERIFICATION_REQUEST_ITEM_INCOMING",
+                "{{user}} asked to verify their identity with you.",
+                user:Quote = self.event.sender().to_string()
+            )
+        };
+
+        let status = match &verification_request {
+            None => Some(tr!(
+                "VERIFICATION_REQUEST_ITEM_EXPIRED",
+                "This request is no longer active."
+            )),
+            Some(verification_request) => match verification_request.inner.state() {
+                VerificationRequestState::Done => Some(tr!(
+                    "VERIFICATION_REQUEST_ITEM_DONE",
+                    "Verification completed."
+                )),
+                VerificationRequestState::Cancelled(_) => Some(tr!(
+                    "VERIFICATION_REQUEST_ITEM_CANCELLED",
+                    "Verification cancelled."
+                )),
+                _ => None,
+            },
+        };
+
+        let can_accept = verification_request
+            .as_ref()
+            .is_some_and(|verification_request| {
+                !verification_request.inner.we_started()
+                    && matches!(
+                        verification_request.inner.state(),
+                        VerificationRequestState::Requested { .. }
+                    )
+            });
+        let can_resume = verification_request
+            .as_ref()
+            .is_some_and(|verification_request| verification_request.is_active())
+            && !can_accept;
+
+        div()
+            .flex()
+            .flex_col()
+            .gap(px(4.))
+            .max_w(px(500.))
+            .p(px(8.))
+            .border(px(1.))
+            .border_color(theme.border_color)
+            .rounded(theme.border_radius)
+            .bg(theme.layer_background)
+            .child(
+                div()
+                    .flex()
+                    .items_center()
+                    .gap(px(4.))
+                    .child(icon("security-high"))
+                    .child(subtitle(tr!(
+                        "VERIFICATION_REQUEST_ITEM",
+                        "Verification Request"
+                    ))),
+            )
+            .child(description)
+            .when_some(status, |david, status| {
+                david.child(div().text_color(theme.foreground.disabled()).child(status))
+            })
+            .when_some(
+                verification_request_entity.filter(|_| can_accept || can_resume),
+                |david, verification_request_entity| {
+                    david.child(
+                        div()
+                            .flex()
+                            .rounded(theme.border_radius)
+                            .bg(theme.button_background)
+                            .when_else(
+                                can_accept,
+                                |david| {
+                                    david
+                                        .child(
+                                            button("verification-request-accept")
+                                                .child(icon_text(
+                                                    "dialog-ok",
+                                                    tr!("INCOMING_VERIFICATION_ACCEPT"),
+                                                ))
+                                                .on_click({
+                                                    let verification_request_entity =
+                                                        verification_request_entity.clone();
+                                                    let verification_popover =
+                                                        verification_popover.clone();
+                                                    move |_, _, cx| {
+                                                        verification_request_entity.update(
+                                                            cx,
+                                                            |verification_request, cx| {
+                                                                verification_request.accept(cx);
+                                                            },
+                                                        );
+
+                                                        verification_popover.update(
+                                                            cx,
+                                                            |verification_popover, cx| {
+                                                                verification_popover
+                                                                    .set_verification_request(
+                                                                        verification_request_entity
+                                                                            .clone(),
+                                                                        cx,
+                                                                    );
+                                                                cx.notify();
+                                                            },
+                                                        );
+                                                    }
+                                                }),
+                                        )
+                                        .child(
+                                            button("verification-request-decline")
+                                                .child(icon_text(
+                                                    "dialog-cancel",
+                                                    tr!("INCOMING_VERIFICATION_DECLINE"),
+                                                ))
+                                                .on_click({
+                                                    let verification_request_entity =
+                                                        verification_request_entity.clone();
+                                                    move |_, _, cx| {
+                                                        verification_request_entity.update(
+                                                            cx,
+                                                            |verification_request, cx| {
+                                                                verification_request.cancel(cx);
+                                                            },
+                                                        );
+                                                    }
+                                                }),
+                                        )
+                                },
+                                |david| {
+                                    david.child(
+                                        button("verification-request-resume")
+                                            .child(icon_text(
+                                                "go-next",
+                                                tr!(
+                                                    "VERIFICATION_REQUEST_ITEM_RESUME",
+                                                    "Continue Verification"
+                                                ),
+                                            ))
+                                            .on_click({
+                                                let verification_request_entity =
+                                                    verification_request_entity.clone();
+                                                let verification_popover =
+                                                    verification_popover.clone();
+                                                move |_, _, cx| {
+                                                    verification_popover.update(
+                                                        cx,
+                                                        |verification_popover, cx| {
+                                                            verification_popover
+                                                                .set_verification_request(
+                                                                    verification_request_entity
+                                                                        .clone(),
+                                                                    cx,
+                                                                );
+                                                            cx.notify();
+                                                        },
+                                                    );
+                                                }
+                                            }),
+                                    )
+                                },
+                            ),
+                    )
+                },
+            )
+    }
+}
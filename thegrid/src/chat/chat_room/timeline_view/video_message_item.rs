@@ -0,0 +1,190 @@
+use crate::chat::chat_room::timeline_view::audio_message_item::format_playback_time;
+use cntp_i18n::tr;
+use contemporary::components::button::button;
+use contemporary::components::icon::icon;
+use contemporary::components::spinner::spinner;
+use contemporary::styling::theme::Theme;
+use gpui::prelude::FluentBuilder;
+use gpui::{
+    App, AppContext, AsyncApp, BorrowAppContext, IntoElement, ParentElement, RenderImage,
+    RenderOnce, SharedString, Styled, Window, div, img, px, rgba,
+};
+use log::warn;
+use matrix_sdk::ruma::events::room::message::VideoMessageEventContent;
+use std::sync::Arc;
+use thegrid_common::mxc_image::{SizePolicy, mxc_image};
+use thegrid_common::session::media_cache::{
+    MediaCacheEntry, MediaState, VideoFrameError, extract_video_frame,
+};
+use thegrid_common::session::session_manager::SessionManager;
+
+/// The widest a video is displayed in the timeline
+const MAX_VIDEO_WIDTH: f32 = 500.;
+
+#[derive(IntoElement)]
+pub struct VideoMessageItem {
+    video: VideoMessageEventContent,
+}
+
+pub fn video_message_item(video: VideoMessageEventContent) -> VideoMessageItem {
+    VideoMessageItem { video }
+}
+
+enum VideoFrame {
+    NotRequested,
+    Requested,
+    Decoded(Arc<RenderImage>),
+    Unavailable(SharedString),
+}
+
+impl RenderOnce for VideoMessageItem {
+    fn render(self, window: &mut Window, cx: &mut App) -> impl IntoElement {
+        let theme = cx.global::<Theme>().clone();
+
+        let info = self.video.info.as_deref();
+        let width = info
+            .and_then(|info| info.width)
+            .map(|width| i64::from(width) as f32);
+        let height = info
+            .and_then(|info| info.height)
+            .map(|height| i64::from(height) as f32);
+        let aspect_ratio = width.zip(height).map(|(width, height)| width / height);
+        let display_width = width.unwrap_or(MAX_VIDEO_WIDTH).min(MAX_VIDEO_WIDTH);
+        let display_height = display_width / aspect_ratio.unwrap_or(16. / 9.);
+
+        let media = MediaCacheEntry::from(self.video.source.clone());
+        let media_file = cx.update_global::<SessionManager, _>(|session_manager, cx| {
+            session_manager.media().media_file_lazy(media, cx)
+        });
+        let loaded_path = match &media_file.read(cx).media_state {
+            MediaState::Loaded(media_file_handle) => Some(media_file_handle.path().to_path_buf()),
+            _ => None,
+        };
+        let is_loading = matches!(media_file.read(cx).media_state, MediaState::Loading);
+
+        // Once the video is downloaded, show its first frame in place of the thumbnail
+        let video_frame = window.use_state(cx, |_, _| VideoFrame::NotRequested);
+        if let Some(path) = loaded_path.clone()
+            && matches!(video_frame.read(cx), VideoFrame::NotRequested)
+        {
+            video_frame.write(cx, VideoFrame::Requested);
+            let video_frame = video_frame.clone();
+            cx.spawn(async move |cx: &mut AsyncApp| {
+                let frame = cx
+                    .background_spawn(async move { extract_video_frame(path).await })
+                    .await;
+                let state = match frame {
+                    Ok(frame) => VideoFrame::Decoded(Arc::new(frame)),
+                    Err(VideoFrameError::FfmpegMissing) => VideoFrame::Unavailable(
+                        tr!(
+                            "VIDEO_PREVIEW_FFMPEG_MISSING",
+                            "Install FFmpeg to preview videos"
+                        )
+                        .into(),
+                    ),
+                    Err(VideoFrameError::Failed(e)) => {
+                        warn!("Unable to decode video frame: {:?}", e);
+                        VideoFrame::Unavailable(
+                            tr!("VIDEO_PREVIEW_FAILED", "Unable to preview this video").into(),
+                        )
+                    }
+                };
+                let _ = video_frame.write(cx, state);
+            })
+            .detach();
+        }
+
+        let unavailable_reason = match video_frame.read(cx) {
+            VideoFrame::Unavailable(reason) => Some(reason.clone()),
+            _ => None,
+        };
+
+        let preview = match video_frame.read(cx) {
+            VideoFrame::Decoded(frame) => img(frame.clone()).size_full().into_any_element(),
+            _ => match info.and_then(|info| info.thumbnail_source.clone()) {
+                Some(thumbnail_source) => mxc_image(thumbnail_source)
+                    .size_policy(SizePolicy::Fit)
+                    .size_full()
+                    .into_any_element(),
+                None => div()
+                    .size_full()
+                    .flex()
+                    .items_center()
+                    .justify_center()
+                    .child(icon("video-x-generic").size(48.))
+                    .into_any_element(),
+            },
+        };
+
+        div()
+            .relative()
+            .w(px(display_width))
+            .h(px(display_height))
+            .bg(rgba(0x00000064))
+            .rounded(theme.border_radius)
+            .overflow_hidden()
+            .child(preview)
+            .child(
+                div()
+                    .absolute()
+                    .size_full()
+                    .flex()
+                    .items_center()
+                    .justify_center()
+                    .child(match loaded_path {
+                        // Videos are played in the system's video player
+                        Some(path) => button("video-play-button")
+                            .child(icon("media-playback-start"))
+                            .child(tr!("VIDEO_OPEN_EXTERNALLY", "Open in Video Player"))
+                            .on_click(move |_, _, cx| cx.open_with_system(&path))
+                            .into_any_element(),
+                        None if is_loading => spinner().into_any_element(),
+                        None => button("video-download-button")
+                            .child(icon("cloud-download"))
+                            .on_click({
+                                let video = self.video.clone();
+                                move |_, _, cx| {
+                                    media_file.update(cx, |media_file, cx| {
+                                        media_file.request_media(
+                                            Some(video.body.clone()),
+                                            video
+                                                .info
+                                                .as_ref()
+                                                .and_then(|info| info.mimetype.clone()),
+                                            false,
+                                            cx,
+                                        );
+                                    });
+                                }
+                            })
+                            .into_any_element(),
+                    }),
+            )
+            .when_some(unavailable_reason, |david, reason| {
+                david.child(
+                    div()
+                        .absolute()
+                        .top(px(4.))
+                        .left(px(4.))
+                        .px(px(4.))
+                        .rounded(theme.border_radius)
+                        .bg(rgba(0x000000C8))
+                        .text_size(theme.system_font_size * 0.8)
+                        .child(reason),
+                )
+            })
+            .when_some(info.and_then(|info| info.duration), |david, duration| {
+                david.child(
+                    div()
+                        .absolute()
+                        .bottom(px(4.))
+                        .right(px(4.))
+                        .px(px(4.))
+                        .rounded(theme.border_radius)
+                        .bg(rgba(0x000000C8))
+                        .text_size(theme.system_font_size * 0.8)
+                        .child(format_playback_time(duration)),
+                )
+            })
+    }
+}
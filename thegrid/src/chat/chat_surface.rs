@@ -30,6 +30,9 @@ use std::rc::Rc;
 use thegrid_common::session::error_handling::ClientError;
 use thegrid_common::session::session_manager::SessionManager;
 use thegrid_common::surfaces::{MainWindowSurface, SurfaceChangeEvent, SurfaceChangeHandler};
+use thegrid_rtc_livekit::call_shortcuts::{
+    HangUp, PushToTalk, ToggleCamera, ToggleDeafen, ToggleMute, ToggleScreenshare,
+};
 
 pub type ChangeRoomHandler = dyn Fn(&ChangeRoomEvent, &mut Window, &mut App) + 'static;
 pub type RequestCryptographicResetHandler =
@@ -89,6 +92,13 @@ impl ChatSurface {
                         MenuItem::action(tr!("ROOMS_CREATE_SPACE"), CreateSpace),
                         MenuItem::action(tr!("ROOMS_DIRECT_JOIN"), DirectJoinRoom),
                         MenuItem::separator(),
+                        MenuItem::action(tr!("CALL_MENU_TOGGLE_MUTE"), ToggleMute),
+                        MenuItem::action(tr!("CALL_MENU_TOGGLE_DEAFEN"), ToggleDeafen),
+                        MenuItem::action(tr!("CALL_MENU_PUSH_TO_TALK"), PushToTalk),
+                        MenuItem::action(tr!("CALL_MENU_TOGGLE_CAMERA"), ToggleCamera),
+                        MenuItem::action(tr!("CALL_MENU_TOGGLE_SCREENSHARE"), ToggleScreenshare),
+                        MenuItem::action(tr!("CALL_MENU_HANG_UP"), HangUp),
+                        MenuItem::separator(),
                         MenuItem::action(
                             tr!("ACCOUNT_ACCOUNT_SETTINGS", "Account Settings"),
                             AccountSettings,
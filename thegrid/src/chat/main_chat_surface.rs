@@ -3,6 +3,7 @@ use crate::actions::{
 };
 use crate::auth::logout_popover::logout_popover;
 use crate::chat::chat_room::ChatRoom;
+use crate::chat::chat_room::search_flyout::SearchResultSelectedEvent;
 use crate::chat::chat_surface::SelfVerificationUi;
 use crate::chat::displayed_room::DisplayedRoom;
 use crate::chat::join_room::JoinRoom;
@@ -41,6 +42,9 @@ pub struct MainChatSurface {
 
     on_surface_change: Rc<Box<SurfaceChangeHandler>>,
     call_disconnect_confirmation_dialog: Entity<CallDisconnectConfirmationDialog>,
+
+    /// A search result in another room, to open once that room is displayed
+    pending_search_result: Option<SearchResultSelectedEvent>,
 }
 
 impl MainChatSurface {
@@ -69,14 +73,25 @@ impl MainChatSurface {
                 let create_space_popover = create_space_popover.clone();
                 move |this, displayed_room, cx| match displayed_room.read(cx).clone() {
                     DisplayedRoom::Room(room_id) => {
-                        this.chat_room = Some(ChatRoom::new(
+                        let search_result = this
+                            .pending_search_result
+                            .take()
+                            .filter(|search_result| search_result.room_id == room_id)
+                            .map(|search_result| search_result.event_id);
+                        let search_result_listener = cx.listener(Self::other_room_search_result);
+                        let chat_room = ChatRoom::new(
                             room_id.clone(),
+                            search_result,
                             displayed_room,
                             create_room_popover.clone(),
                             create_space_popover.clone(),
                             this.on_surface_change.clone(),
                             cx,
-                        ))
+                        );
+                        chat_room.update(cx, |chat_room, _| {
+                            chat_room.on_other_room_search_result(search_result_listener)
+                        });
+                        this.chat_room = Some(chat_room)
                     }
                     DisplayedRoom::Directory(server_name) => {
                         this.room_directory =
@@ -114,10 +129,23 @@ impl MainChatSurface {
                 create_space_popover,
                 direct_join_room_popover,
                 call_disconnect_confirmation_dialog,
+                pending_search_result: None,
             }
         })
     }
 
+    fn other_room_search_result(
+        &mut self,
+        event: &SearchResultSelectedEvent,
+        _: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        // The chat room for the other room is created once it becomes the displayed room
+        self.pending_search_result = Some(event.clone());
+        self.displayed_room
+            .write(cx, DisplayedRoom::Room(event.room_id.clone()));
+    }
+
     pub fn log_out(&mut self, _: &LogOut, window: &mut Window, cx: &mut Context<Self>) {
         let on_complete = cx.listener(|this, _, _, cx| {
             this.logout_popover_visible.write(cx, true);
@@ -1,6 +1,7 @@
 mod directory_sidebar_page;
 mod root_sidebar_page;
 mod sidebar_list;
+pub mod sidebar_sections;
 mod space_sidebar_page;
 mod standard_room_element;
 
@@ -30,6 +31,7 @@ use matrix_sdk::ruma::room_id;
 use std::rc::Rc;
 use thegrid_common::mxc_image::{SizePolicy, mxc_image};
 use thegrid_common::session::error_handling::{ClientError, RecoverableClientError};
+use thegrid_common::session::incoming_calls_cache::IncomingCall;
 use thegrid_common::session::room_cache::RoomCategory;
 use thegrid_common::session::session_manager::SessionManager;
 use thegrid_common::session::verification_requests_cache::VerificationRequestDetails;
@@ -39,6 +41,7 @@ use thegrid_common::surfaces::{
 use thegrid_common::tokio_helper::TokioHelper;
 use thegrid_rtc_livekit::active_call_sidebar_alert::active_call_sidebar_alert;
 use thegrid_rtc_livekit::call_manager::LivekitCallManager;
+use thegrid_rtc_livekit::incoming_call_sidebar_alert::incoming_call_sidebar_alert;
 
 pub struct Sidebar {
     displayed_room: Entity<DisplayedRoom>,
@@ -65,6 +68,7 @@ enum SidebarAlert {
     RecoverRecovery(SelfVerificationUi),
     UnverifiedDevices(usize, Option<Rc<Box<SurfaceChangeHandler>>>),
     ClientError(RecoverableClientError),
+    IncomingCall(IncomingCall, Option<Rc<Box<SurfaceChangeHandler>>>),
     ActiveCall(Option<Rc<Box<SurfaceChangeHandler>>>),
 }
 
@@ -103,6 +107,14 @@ impl Sidebar {
             return SidebarAlert::ClientError(recoverable_error);
         }
 
+        let incoming_calls = session_manager.incoming_calls().read(cx);
+        if let Some(incoming_call) = incoming_calls.incoming_calls().last() {
+            return SidebarAlert::IncomingCall(
+                incoming_call.clone(),
+                self.on_surface_change.clone(),
+            );
+        }
+
         let verification_requests = session_manager.verification_requests().read(cx);
         let shown_verification_requests: Vec<_> = verification_requests
             .pending_verification_requests
@@ -593,6 +605,18 @@ impl RenderOnce for SidebarAlert {
                         .title(recoverable_client_error.title())
                         .child(recoverable_client_error.description()),
                 ),
+                SidebarAlert::IncomingCall(incoming_call, handler) => {
+                    div()
+                        .p(px(4.))
+                        .child(incoming_call_sidebar_alert(
+                            incoming_call,
+                            Rc::new(Box::new(move |event, window, cx| {
+                                if let Some(handler) = handler.clone() {
+                                    handler(event, window, cx);
+                                }
+                            })),
+                        ))
+                }
                 SidebarAlert::ActiveCall(handler) => {
                     div()
                         .p(px(4.))
@@ -2,17 +2,21 @@ use crate::chat::chat_room::invite_popover::InvitePopover;
 use crate::chat::displayed_room::DisplayedRoom;
 use crate::chat::sidebar::directory_sidebar_page::DirectorySidebarPage;
 use crate::chat::sidebar::sidebar_list::{SidebarItem, SidebarListEvent, sidebar_list};
+use crate::chat::sidebar::sidebar_sections::SectionDialog;
 use crate::chat::sidebar::space_sidebar_page::SpaceSidebarPage;
 use crate::chat::sidebar::standard_room_element::InviteEvent;
 use crate::chat::sidebar::{Sidebar, SidebarPage};
 use cntp_i18n::tr;
+use contemporary::components::button::button;
 use contemporary::components::grandstand::grandstand;
+use contemporary::components::text_field::TextField;
 use gpui::{
     AppContext, Context, Entity, IntoElement, ListAlignment, ListState, ParentElement, Render,
-    Styled, Subscription, Window, div, px,
+    SharedString, Styled, Subscription, Window, div, px,
 };
 use matrix_sdk::ruma::OwnedRoomId;
-use thegrid_common::session::room_cache::{CachedRoom, RoomCategory};
+use std::rc::Rc;
+use thegrid_common::session::room_cache::{CachedRoom, QuickFilter, RoomCategory};
 use thegrid_common::session::session_manager::SessionManager;
 
 pub struct RootSidebarPage {
@@ -21,7 +25,12 @@ pub struct RootSidebarPage {
     displayed_room: Entity<DisplayedRoom>,
     items: Vec<SidebarItem>,
     room_cache_subscription: Option<Subscription>,
+    sidebar_sections_subscription: Option<Subscription>,
+    drafts_subscription: Option<Subscription>,
     invite_popover: Entity<InvitePopover>,
+    section_dialog: Entity<SectionDialog>,
+    filter_field: Entity<TextField>,
+    quick_filter: Option<QuickFilter>,
 }
 
 impl RootSidebarPage {
@@ -36,16 +45,30 @@ impl RootSidebarPage {
             let session_manager = cx.global::<SessionManager>();
             if session_manager.client().is_none() {
                 this.room_cache_subscription = None;
+                this.sidebar_sections_subscription = None;
+                this.drafts_subscription = None;
                 return;
             }
 
             let room_cache = session_manager.rooms();
+            let sidebar_sections = session_manager.sidebar_sections();
+            let drafts = session_manager.drafts();
             this.room_cache_subscription =
                 Some(cx.observe(&room_cache, |this, _, cx| this.update_sidebar_rooms(cx)));
+            this.sidebar_sections_subscription = Some(
+                cx.observe(&sidebar_sections, |this, _, cx| this.update_sidebar_rooms(cx)),
+            );
+            this.drafts_subscription = Some(cx.observe(&drafts, |_, _, cx| cx.notify()));
         })
         .detach();
 
         let invite_popover = cx.new(|cx| InvitePopover::new(cx));
+        let section_dialog = cx.new(|cx| SectionDialog::new(cx));
+
+        let text_changed_listener = Rc::new(cx.listener(|this, _, _, cx| {
+            this.update_sidebar_rooms(cx);
+            cx.notify();
+        }));
 
         Self {
             list_state: ListState::new(0, ListAlignment::Top, px(200.)),
@@ -53,7 +76,28 @@ impl RootSidebarPage {
             displayed_room,
             items: Vec::new(),
             room_cache_subscription: None,
+            sidebar_sections_subscription: None,
+            drafts_subscription: None,
             invite_popover,
+            section_dialog,
+            filter_field: cx.new(|cx| {
+                let mut text_field = TextField::new("room-filter", cx);
+                text_field.set_placeholder(
+                    &tr!("ROOT_SIDEBAR_FILTER", "Filter rooms...").to_string(),
+                );
+                text_field.on_text_changed({
+                    let text_changed_listener = text_changed_listener.clone();
+                    move |event, window, cx| {
+                        let event = event.clone();
+                        let text_changed_listener = text_changed_listener.clone();
+                        window.defer(cx, move |window, cx| {
+                            text_changed_listener(&event, window, cx)
+                        });
+                    }
+                });
+                text_field
+            }),
+            quick_filter: None,
         }
     }
 
@@ -91,6 +135,16 @@ impl RootSidebarPage {
         })
     }
 
+    fn set_quick_filter(&mut self, quick_filter: QuickFilter, cx: &mut Context<Self>) {
+        if self.quick_filter == Some(quick_filter) {
+            self.quick_filter = None;
+        } else {
+            self.quick_filter = Some(quick_filter);
+        }
+        self.update_sidebar_rooms(cx);
+        cx.notify();
+    }
+
     fn update_sidebar_rooms(&mut self, cx: &mut Context<Self>) {
         let session_manager = cx.global::<SessionManager>();
         if session_manager.client().is_none() {
@@ -100,40 +154,81 @@ impl RootSidebarPage {
         }
 
         let room_cache = session_manager.rooms().read(cx);
+        let query = self.filter_field.read(cx).text().trim().to_string();
+        if self.quick_filter.is_some() || !query.is_empty() {
+            let mut vec = room_cache
+                .filtered_rooms(self.quick_filter, &query, cx)
+                .into_iter()
+                .map(|room| {
+                    if room.read(cx).inner.is_space() {
+                        SidebarItem::Space(room)
+                    } else {
+                        SidebarItem::Room(room)
+                    }
+                })
+                .collect::<Vec<_>>();
+            if vec.is_empty() {
+                vec.push(SidebarItem::Heading(
+                    tr!("ROOT_SIDEBAR_NO_MATCHES", "No rooms match").into(),
+                ));
+            }
+
+            if self.list_state.item_count() != vec.len() {
+                self.list_state.reset(vec.len());
+            }
+            self.items = vec;
+            return;
+        }
+
         let all_rooms = room_cache.cached_rooms().clone();
         let root_rooms = room_cache.rooms_in_category(RoomCategory::Root, cx).clone();
 
-        let mut faves = all_rooms
-            .iter()
-            .filter(|room| !room.read(cx).inner.is_space())
-            .filter(|room| room.read(cx).inner.is_favourite())
-            .map(|room| SidebarItem::Room(room.clone()))
-            .collect::<Vec<_>>();
-        let mut spaces = root_rooms
-            .iter()
-            .filter(|room| room.read(cx).inner.is_space())
-            .map(|room| SidebarItem::Space(room.clone()))
-            .collect::<Vec<_>>();
-        let mut direct_rooms = root_rooms
-            .iter()
-            .filter(|room| !room.read(cx).inner.is_space())
-            .filter(|room| !room.read(cx).inner.is_low_priority())
-            .filter(|room| room.read(cx).is_direct())
-            .map(|room| SidebarItem::Room(room.clone()))
-            .collect::<Vec<_>>();
-        let mut rooms = root_rooms
-            .iter()
-            .filter(|room| !room.read(cx).inner.is_space())
-            .filter(|room| !room.read(cx).inner.is_low_priority())
-            .filter(|room| !room.read(cx).is_direct())
-            .map(|room| SidebarItem::Room(room.clone()))
-            .collect::<Vec<_>>();
-        let mut low_priority = root_rooms
-            .iter()
-            .filter(|room| !room.read(cx).inner.is_space())
-            .filter(|room| room.read(cx).inner.is_low_priority())
-            .map(|room| SidebarItem::Room(room.clone()))
+        let bucket = |rooms: &Vec<Entity<CachedRoom>>,
+                      predicate: &dyn Fn(&CachedRoom) -> bool,
+                      item: fn(Entity<CachedRoom>) -> SidebarItem| {
+            let mut rooms = rooms
+                .iter()
+                .filter(|room| predicate(room.read(cx)))
+                .cloned()
+                .collect::<Vec<_>>();
+            room_cache.sort_rooms(&mut rooms, cx);
+            rooms.into_iter().map(item).collect::<Vec<_>>()
+        };
+
+        let mut faves = bucket(
+            &all_rooms,
+            &|room| !room.inner.is_space() && room.inner.is_favourite(),
+            SidebarItem::Room,
+        );
+        let sidebar_sections = session_manager.sidebar_sections().read(cx);
+        let mut user_sections = sidebar_sections
+            .sections(cx)
+            .into_iter()
+            .flat_map(|section| {
+                let rooms = sidebar_sections
+                    .rooms_in_section(&section, cx)
+                    .into_iter()
+                    .map(|room| SidebarItem::SectionRoom(section.clone(), room))
+                    .collect::<Vec<_>>();
+                std::iter::once(SidebarItem::Section(section)).chain(rooms)
+            })
             .collect::<Vec<_>>();
+        let mut spaces = bucket(&root_rooms, &|room| room.inner.is_space(), SidebarItem::Space);
+        let mut direct_rooms = bucket(
+            &root_rooms,
+            &|room| !room.inner.is_space() && !room.inner.is_low_priority() && room.is_direct(),
+            SidebarItem::Room,
+        );
+        let mut rooms = bucket(
+            &root_rooms,
+            &|room| !room.inner.is_space() && !room.inner.is_low_priority() && !room.is_direct(),
+            SidebarItem::Room,
+        );
+        let mut low_priority = bucket(
+            &root_rooms,
+            &|room| !room.inner.is_space() && room.inner.is_low_priority(),
+            SidebarItem::Room,
+        );
 
         let mut vec = Vec::new();
         vec.push(SidebarItem::Create);
@@ -144,6 +239,7 @@ impl RootSidebarPage {
             ));
             vec.append(&mut faves);
         }
+        vec.append(&mut user_sections);
         if !spaces.is_empty() {
             vec.push(SidebarItem::Heading(
                 tr!("ROOT_SIDEBAR_SPACES", "Spaces").into(),
@@ -187,6 +283,50 @@ impl Render for RootSidebarPage {
                     .text(tr!("ROOMS_SPACES", "Rooms and Spaces"))
                     .pt(px(36.)),
             )
+            .child(
+                div()
+                    .flex()
+                    .flex_col()
+                    .gap(px(4.))
+                    .p(px(2.))
+                    .child(self.filter_field.clone())
+                    .child(
+                        [
+                            (
+                                QuickFilter::Unread,
+                                tr!("ROOT_SIDEBAR_FILTER_UNREAD", "Unread"),
+                            ),
+                            (
+                                QuickFilter::Mentions,
+                                tr!("ROOT_SIDEBAR_FILTER_MENTIONS", "Mentions"),
+                            ),
+                            (
+                                QuickFilter::DirectMessages,
+                                tr!("ROOT_SIDEBAR_FILTER_DIRECT", "DMs"),
+                            ),
+                            (
+                                QuickFilter::Invites,
+                                tr!("ROOT_SIDEBAR_FILTER_INVITES", "Invites"),
+                            ),
+                        ]
+                        .into_iter()
+                        .enumerate()
+                        .fold(
+                            div().flex().flex_wrap().gap(px(2.)),
+                            |david, (i, (quick_filter, text))| {
+                                david.child(
+                                    button(("quick-filter", i))
+                                        .flat()
+                                        .child(SharedString::from(text.to_string()))
+                                        .checked_when(self.quick_filter == Some(quick_filter))
+                                        .on_click(cx.listener(move |this, _, _, cx| {
+                                            this.set_quick_filter(quick_filter, cx)
+                                        })),
+                                )
+                            },
+                        ),
+                    ),
+            )
             .child(div().flex_grow(1.).child(sidebar_list(
                 self.list_state.clone(),
                 self.items.clone(),
@@ -199,8 +339,24 @@ impl Render for RootSidebarPage {
                     SidebarListEvent::InviteToRoom(invite_event) => {
                         this.invite_to_room(invite_event, window, cx)
                     }
+                    SidebarListEvent::NewSection(event) => {
+                        this.section_dialog.update(cx, |section_dialog, cx| {
+                            section_dialog.open_create(&event.room_id, cx)
+                        })
+                    }
+                    SidebarListEvent::RenameSection(section) => {
+                        this.section_dialog.update(cx, |section_dialog, cx| {
+                            section_dialog.open_rename(section.clone(), cx)
+                        })
+                    }
+                    SidebarListEvent::DeleteSection(section) => {
+                        this.section_dialog.update(cx, |section_dialog, cx| {
+                            section_dialog.open_delete(section.clone(), cx)
+                        })
+                    }
                 }),
             )))
             .child(self.invite_popover.clone())
+            .child(self.section_dialog.clone())
     }
 }
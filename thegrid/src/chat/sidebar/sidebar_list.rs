@@ -1,8 +1,10 @@
 use crate::chat::displayed_room::DisplayedRoom;
+use crate::chat::sidebar::sidebar_sections::section_heading_menu;
 use crate::chat::sidebar::standard_room_element::{
-    InviteEvent, StandardRoomElement, StandardRoomElementType,
+    InviteEvent, NewSectionEvent, StandardRoomElement, StandardRoomElementType,
 };
 use cntp_i18n::{I18N_MANAGER, tr, trn};
+use contemporary::components::context_menu::ContextMenuExt;
 use contemporary::components::icon::icon;
 use contemporary::components::subtitle::subtitle;
 use contemporary::styling::theme::ThemeStorage;
@@ -12,10 +14,12 @@ use gpui::{
     RenderOnce, StatefulInteractiveElement, Styled, Window, div, list, px,
 };
 use matrix_sdk::ruma::OwnedRoomId;
+use matrix_sdk::ruma::events::tag::TagName;
 use std::rc::Rc;
 use thegrid_common::mxc_image::{SizePolicy, mxc_image};
 use thegrid_common::session::room_cache::CachedRoom;
 use thegrid_common::session::session_manager::SessionManager;
+use thegrid_common::session::sidebar_sections_cache::section_name;
 
 #[derive(IntoElement)]
 pub struct SidebarList {
@@ -29,12 +33,17 @@ pub enum SidebarListEvent {
     OpenDirectory,
     ChangeRoom(OwnedRoomId),
     InviteToRoom(InviteEvent),
+    NewSection(NewSectionEvent),
+    RenameSection(TagName),
+    DeleteSection(TagName),
 }
 
 #[derive(Clone)]
 pub enum SidebarItem {
     Heading(String),
+    Section(TagName),
     Room(Entity<CachedRoom>),
+    SectionRoom(TagName, Entity<CachedRoom>),
     Space(Entity<CachedRoom>),
     SpaceLobby(Entity<CachedRoom>),
     Create,
@@ -139,12 +148,50 @@ impl RenderOnce for SidebarList {
                     .pl(px(4.))
                     .child(subtitle(heading))
                     .into_any_element(),
-                SidebarItem::Room(room_entity) | SidebarItem::SpaceLobby(room_entity) => {
+                SidebarItem::Section(section) => div()
+                    .id(ElementId::Name(section.as_ref().to_string().into()))
+                    .pt(px(8.))
+                    .pl(px(4.))
+                    .child(subtitle(section_name(section).to_string()))
+                    .with_context_menu(section_heading_menu(
+                        section.clone(),
+                        {
+                            let event_handler = event_handler.clone();
+                            let section = section.clone();
+                            move |window, cx| {
+                                event_handler(
+                                    &SidebarListEvent::RenameSection(section.clone()),
+                                    window,
+                                    cx,
+                                )
+                            }
+                        },
+                        {
+                            let event_handler = event_handler.clone();
+                            let section = section.clone();
+                            move |window, cx| {
+                                event_handler(
+                                    &SidebarListEvent::DeleteSection(section.clone()),
+                                    window,
+                                    cx,
+                                )
+                            }
+                        },
+                    ))
+                    .into_any_element(),
+                SidebarItem::Room(room_entity)
+                | SidebarItem::SectionRoom(_, room_entity)
+                | SidebarItem::SpaceLobby(room_entity) => {
                     let room = room_entity.read(cx);
                     let room_id = room.inner.room_id().to_owned();
 
                     div()
-                        .id(ElementId::Name(room.inner.room_id().to_string().into()))
+                        .id(ElementId::Name(match item {
+                            SidebarItem::SectionRoom(section, _) => {
+                                format!("{}/{}", section.as_ref(), room_id).into()
+                            }
+                            _ => room_id.to_string().into(),
+                        }))
                         .child(StandardRoomElement {
                             room: room_entity.clone(),
                             render_as: if matches!(item, SidebarItem::SpaceLobby(_)) {
@@ -153,6 +200,10 @@ impl RenderOnce for SidebarList {
                                 StandardRoomElementType::Room
                             },
                             current_room,
+                            section: match item {
+                                SidebarItem::SectionRoom(section, _) => Some(section.clone()),
+                                _ => None,
+                            },
                             on_click: Rc::new(Box::new({
                                 let event_handler = event_handler.clone();
                                 move |_, window, cx| {
@@ -173,6 +224,16 @@ impl RenderOnce for SidebarList {
                                     )
                                 }
                             })),
+                            on_new_section: Rc::new(Box::new({
+                                let event_handler = event_handler.clone();
+                                move |event, window, cx| {
+                                    event_handler(
+                                        &SidebarListEvent::NewSection(event.clone()),
+                                        window,
+                                        cx,
+                                    )
+                                }
+                            })),
                         })
                         .into_any_element()
                 }
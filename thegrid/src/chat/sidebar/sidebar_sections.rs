@@ -0,0 +1,343 @@
+use cntp_i18n::{Quote, tr};
+use contemporary::components::admonition::AdmonitionSeverity;
+use contemporary::components::button::button;
+use contemporary::components::context_menu::ContextMenuItem;
+use contemporary::components::dialog_box::{StandardButton, dialog_box};
+use contemporary::components::icon_text::icon_text;
+use contemporary::components::text_field::TextField;
+use contemporary::components::toast::Toast;
+use gpui::prelude::FluentBuilder;
+use gpui::private::anyhow;
+use gpui::{
+    App, AppContext, AsyncWindowContext, Context, Entity, IntoElement, ParentElement, Render,
+    Styled, Task, WeakEntity, Window, div, px,
+};
+use matrix_sdk::Room;
+use matrix_sdk::ruma::RoomId;
+use matrix_sdk::ruma::events::tag::TagName;
+use thegrid_common::session::room_cache::CachedRoom;
+use thegrid_common::session::session_manager::SessionManager;
+use thegrid_common::session::sidebar_sections_cache::section_name;
+use tracing::error;
+
+/// Menu items to add or remove a room from the user's sidebar sections.
+///
+/// When the room is being displayed inside a section, items to move it within that section are
+/// included as well.
+pub fn room_sections_menu(
+    room: &CachedRoom,
+    current_section: Option<TagName>,
+    on_new_section: impl Fn(&mut Window, &mut App) + 'static,
+    cx: &App,
+) -> Vec<ContextMenuItem> {
+    let sidebar_sections = cx.global::<SessionManager>().sidebar_sections();
+    let room_tags = room.user_tags();
+
+    let mut menu = vec![
+        ContextMenuItem::separator()
+            .label(tr!("SIDEBAR_SECTIONS", "Sections"))
+            .build(),
+    ];
+    menu.extend(
+        sidebar_sections
+            .read(cx)
+            .sections(cx)
+            .into_iter()
+            .map(|section| {
+                let in_section = room_tags.contains(&section);
+                let matrix_room = room.inner.clone();
+                ContextMenuItem::menu_item()
+                    .label(section_name(&section).to_string())
+                    .when(in_section, |david| david.icon("dialog-ok"))
+                    .on_triggered(move |_, window, cx| {
+                        let matrix_room = matrix_room.clone();
+                        let sidebar_sections = cx.global::<SessionManager>().sidebar_sections();
+                        let task = sidebar_sections.update(cx, |sidebar_sections, cx| {
+                            if in_section {
+                                sidebar_sections.remove_room(&section, matrix_room, cx)
+                            } else {
+                                sidebar_sections.add_room(&section, matrix_room, cx)
+                            }
+                        });
+                        report_section_errors(task, window, cx);
+                    })
+                    .build()
+            }),
+    );
+    menu.push(
+        ContextMenuItem::menu_item()
+            .label(tr!("SIDEBAR_SECTION_NEW", "New Section..."))
+            .icon("list-add")
+            .on_triggered(move |_, window, cx| on_new_section(window, cx))
+            .build(),
+    );
+
+    if let Some(current_section) = current_section {
+        let room_id = room.inner.room_id().to_owned();
+        menu.extend([(-1, "go-up"), (1, "go-down")].map(|(offset, icon)| {
+            let current_section = current_section.clone();
+            let room_id = room_id.clone();
+            ContextMenuItem::menu_item()
+                .label(if offset < 0 {
+                    tr!(
+                        "SIDEBAR_SECTION_ROOM_MOVE_UP",
+                        "Move Up in {{section}}",
+                        section:Quote=section_name(&current_section)
+                    )
+                } else {
+                    tr!(
+                        "SIDEBAR_SECTION_ROOM_MOVE_DOWN",
+                        "Move Down in {{section}}",
+                        section:Quote=section_name(&current_section)
+                    )
+                })
+                .icon(icon)
+                .on_triggered(move |_, window, cx| {
+                    let sidebar_sections = cx.global::<SessionManager>().sidebar_sections();
+                    let task = sidebar_sections.update(cx, |sidebar_sections, cx| {
+                        sidebar_sections.move_room(&current_section, &room_id, offset, cx)
+                    });
+                    report_section_errors(task, window, cx);
+                })
+                .build()
+        }));
+    }
+
+    menu
+}
+
+/// Menu items to manage a sidebar section from its heading
+pub fn section_heading_menu(
+    section: TagName,
+    on_rename: impl Fn(&mut Window, &mut App) + 'static,
+    on_delete: impl Fn(&mut Window, &mut App) + 'static,
+) -> Vec<ContextMenuItem> {
+    let mut menu = vec![
+        ContextMenuItem::separator()
+            .label(tr!(
+                "SIDEBAR_SECTION_FOR",
+                "For {{section}}",
+                section:Quote=section_name(&section)
+            ))
+            .build(),
+        ContextMenuItem::menu_item()
+            .label(tr!("SIDEBAR_SECTION_RENAME", "Rename Section..."))
+            .icon("edit-rename")
+            .on_triggered(move |_, window, cx| on_rename(window, cx))
+            .build(),
+    ];
+    menu.extend([(-1, "go-up"), (1, "go-down")].map(|(offset, icon)| {
+        let section = section.clone();
+        ContextMenuItem::menu_item()
+            .label(if offset < 0 {
+                tr!("SIDEBAR_SECTION_MOVE_UP", "Move Section Up")
+            } else {
+                tr!("SIDEBAR_SECTION_MOVE_DOWN", "Move Section Down")
+            })
+            .icon(icon)
+            .on_triggered(move |_, window, cx| {
+                let sidebar_sections = cx.global::<SessionManager>().sidebar_sections();
+                let task = sidebar_sections.update(cx, |sidebar_sections, cx| {
+                    sidebar_sections.move_section(&section, offset, cx)
+                });
+                report_section_errors(task, window, cx);
+            })
+            .build()
+    }));
+    menu.push(
+        ContextMenuItem::menu_item()
+            .label(tr!("SIDEBAR_SECTION_DELETE", "Delete Section"))
+            .icon("edit-delete")
+            .on_triggered(move |_, window, cx| on_delete(window, cx))
+            .build(),
+    );
+
+    menu
+}
+
+/// Show a toast if a change to the sidebar sections fails
+pub fn report_section_errors(task: Task<anyhow::Result<()>>, window: &mut Window, cx: &mut App) {
+    window
+        .spawn(cx, async move |cx: &mut AsyncWindowContext| {
+            if let Err(e) = task.await {
+                error!("Unable to update sidebar sections: {e:?}");
+                let _ = cx.update(|window, cx| {
+                    Toast::new()
+                        .title(&tr!(
+                            "SIDEBAR_SECTIONS_ERROR_TITLE",
+                            "Unable to update sections"
+                        ))
+                        .body(&tr!(
+                            "SIDEBAR_SECTIONS_ERROR_TEXT",
+                            "The change to your sidebar sections could not be saved."
+                        ))
+                        .severity(AdmonitionSeverity::Error)
+                        .post(window, cx);
+                });
+            }
+        })
+        .detach();
+}
+
+enum SectionDialogMode {
+    Create(Room),
+    Rename(TagName),
+    Delete(TagName),
+}
+
+/// Dialog to name a new sidebar section, rename an existing one or confirm deleting one
+pub struct SectionDialog {
+    mode: Option<SectionDialogMode>,
+    name_field: Entity<TextField>,
+    busy: bool,
+}
+
+impl SectionDialog {
+    pub fn new(cx: &mut Context<Self>) -> Self {
+        Self {
+            mode: None,
+            name_field: cx.new(|cx| {
+                let mut text_field = TextField::new("section-name", cx);
+                text_field.set_placeholder(
+                    tr!("SIDEBAR_SECTION_NAME_PLACEHOLDER", "Section Name")
+                        .to_string()
+                        .as_str(),
+                );
+                text_field
+            }),
+            busy: false,
+        }
+    }
+
+    pub fn open_create(&mut self, room_id: &RoomId, cx: &mut Context<Self>) {
+        let session_manager = cx.global::<SessionManager>();
+        let Some(room) = session_manager.rooms().read(cx).room(room_id) else {
+            return;
+        };
+        let room = room.read(cx).inner.clone();
+
+        self.name_field
+            .update(cx, |name_field, _| name_field.set_text(""));
+        self.mode = Some(SectionDialogMode::Create(room));
+        cx.notify();
+    }
+
+    pub fn open_rename(&mut self, section: TagName, cx: &mut Context<Self>) {
+        self.name_field.update(cx, |name_field, _| {
+            name_field.set_text(section_name(&section))
+        });
+        self.mode = Some(SectionDialogMode::Rename(section));
+        cx.notify();
+    }
+
+    pub fn open_delete(&mut self, section: TagName, cx: &mut Context<Self>) {
+        self.mode = Some(SectionDialogMode::Delete(section));
+        cx.notify();
+    }
+
+    fn accept(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(mode) = &self.mode else {
+            return;
+        };
+
+        let name = self.name_field.read(cx).text().to_string();
+        let sidebar_sections = cx.global::<SessionManager>().sidebar_sections();
+        let task = sidebar_sections.update(cx, |sidebar_sections, cx| match mode {
+            SectionDialogMode::Create(room) => {
+                sidebar_sections.create_section(&name, room.clone(), cx)
+            }
+            SectionDialogMode::Rename(section) => {
+                sidebar_sections.rename_section(section, &name, cx)
+            }
+            SectionDialogMode::Delete(section) => sidebar_sections.delete_section(section, cx),
+        });
+
+        self.busy = true;
+        cx.notify();
+
+        cx.spawn_in(
+            window,
+            async move |weak_this: WeakEntity<Self>, cx: &mut AsyncWindowContext| {
+                let result = task.await;
+                let _ = weak_this.update_in(cx, |this, window, cx| {
+                    this.busy = false;
+                    if result.is_ok() {
+                        this.mode = None;
+                    }
+                    cx.notify();
+
+                    if let Err(e) = result {
+                        report_section_errors(Task::ready(Err(e)), window, cx);
+                    }
+                });
+            },
+        )
+        .detach();
+    }
+}
+
+impl Render for SectionDialog {
+    fn render(&mut self, window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let (title, description, accept_text) = match &self.mode {
+            Some(SectionDialogMode::Rename(section)) => (
+                tr!("SIDEBAR_SECTION_RENAME_TITLE", "Rename Section"),
+                tr!(
+                    "SIDEBAR_SECTION_RENAME_DESCRIPTION",
+                    "What do you want to call {{section}}?",
+                    section:Quote=section_name(section)
+                ),
+                tr!("SIDEBAR_SECTION_RENAME_ACCEPT", "Rename"),
+            ),
+            Some(SectionDialogMode::Delete(section)) => (
+                tr!("SIDEBAR_SECTION_DELETE_TITLE", "Delete Section"),
+                tr!(
+                    "SIDEBAR_SECTION_DELETE_DESCRIPTION",
+                    "Do you want to delete {{section}}? The rooms in this section will not be \
+                    affected.",
+                    section:Quote=section_name(section)
+                ),
+                tr!("SIDEBAR_SECTION_DELETE_ACCEPT", "Delete"),
+            ),
+            _ => (
+                tr!("SIDEBAR_SECTION_NEW_TITLE", "New Section"),
+                tr!(
+                    "SIDEBAR_SECTION_NEW_DESCRIPTION",
+                    "Sections group rooms together in the sidebar. What do you want to call \
+                    this section?"
+                ),
+                tr!("SIDEBAR_SECTION_NEW_ACCEPT", "Create"),
+            ),
+        };
+        let deleting = matches!(self.mode, Some(SectionDialogMode::Delete(_)));
+
+        dialog_box("section-dialog")
+            .visible(self.mode.is_some())
+            .processing(self.busy)
+            .title(title)
+            .content(
+                div()
+                    .flex()
+                    .flex_col()
+                    .w(px(500.))
+                    .gap(px(12.))
+                    .child(description)
+                    .when(!deleting, |david| david.child(self.name_field.clone())),
+            )
+            .standard_button(
+                StandardButton::Cancel,
+                cx.listener(|this, _, _, cx| {
+                    this.mode = None;
+                    cx.notify()
+                }),
+            )
+            .button(
+                button("section-dialog-accept")
+                    .when(deleting, |david| david.destructive())
+                    .child(icon_text(
+                        if deleting { "edit-delete" } else { "dialog-ok" },
+                        accept_text,
+                    ))
+                    .on_click(cx.listener(|this, _, window, cx| this.accept(window, cx))),
+            )
+    }
+}
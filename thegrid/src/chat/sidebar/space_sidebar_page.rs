@@ -1,6 +1,7 @@
 use crate::chat::chat_room::invite_popover::InvitePopover;
 use crate::chat::displayed_room::DisplayedRoom;
 use crate::chat::sidebar::sidebar_list::{SidebarItem, SidebarListEvent, sidebar_list};
+use crate::chat::sidebar::sidebar_sections::SectionDialog;
 use crate::chat::sidebar::standard_room_element::InviteEvent;
 use crate::chat::sidebar::{Sidebar, SidebarPage};
 use cntp_i18n::tr;
@@ -23,6 +24,7 @@ pub struct SpaceSidebarPage {
     sidebar: Entity<Sidebar>,
     displayed_room: Entity<DisplayedRoom>,
     invite_popover: Entity<InvitePopover>,
+    section_dialog: Entity<SectionDialog>,
     items: Vec<SidebarItem>,
     have_rooms: bool,
 }
@@ -35,11 +37,14 @@ impl SpaceSidebarPage {
         cx: &mut Context<Self>,
     ) -> Self {
         let invite_popover = cx.new(|cx| InvitePopover::new(cx));
+        let section_dialog = cx.new(|cx| SectionDialog::new(cx));
 
         let session_manager = cx.global::<SessionManager>();
         let room_cache = session_manager.rooms();
+        let drafts = session_manager.drafts();
         cx.observe(&room_cache, |this, _, cx| this.update_sidebar_rooms(cx))
             .detach();
+        cx.observe(&drafts, |_, _, cx| cx.notify()).detach();
 
         let mut this = Self {
             list_state: ListState::new(0, ListAlignment::Top, px(200.)),
@@ -47,6 +52,7 @@ impl SpaceSidebarPage {
             sidebar,
             displayed_room,
             invite_popover,
+            section_dialog,
             items: Vec::new(),
             have_rooms: false,
         };
@@ -64,9 +70,10 @@ impl SpaceSidebarPage {
             room_cache.room(&self.room_id).unwrap(),
         ));
 
-        let space_rooms = room_cache
+        let mut space_rooms = room_cache
             .rooms_in_category(RoomCategory::Space(self.room_id.clone()), cx)
             .clone();
+        room_cache.sort_rooms(&mut space_rooms, cx);
 
         let mut rooms = Vec::new();
         let mut subordinate_spaces = Vec::new();
@@ -165,6 +172,11 @@ impl Render for SpaceSidebarPage {
                             SidebarListEvent::InviteToRoom(invite_event) => {
                                 this.invite_to_room(invite_event, window, cx)
                             }
+                            SidebarListEvent::NewSection(event) => {
+                                this.section_dialog.update(cx, |section_dialog, cx| {
+                                    section_dialog.open_create(&event.room_id, cx)
+                                })
+                            }
                             _ => {}
                         }),
                     )))
@@ -194,5 +206,6 @@ impl Render for SpaceSidebarPage {
                 },
             )
             .child(self.invite_popover.clone())
+            .child(self.section_dialog.clone())
     }
 }
@@ -1,9 +1,12 @@
+use crate::account_settings::notifications_settings::room_notification_mode_menu;
+use crate::chat::sidebar::sidebar_sections::room_sections_menu;
 use cntp_i18n::{I18N_MANAGER, Quote, tr};
 use contemporary::components::button::button;
 use contemporary::components::context_menu::{ContextMenuExt, ContextMenuItem};
 use contemporary::components::dialog_box::{StandardButton, dialog_box};
+use contemporary::components::icon::icon;
 use contemporary::components::icon_text::icon_text;
-use contemporary::styling::theme::ThemeStorage;
+use contemporary::styling::theme::{ThemeStorage, VariableColor};
 use gpui::prelude::FluentBuilder;
 use gpui::private::anyhow;
 use gpui::{
@@ -11,12 +14,15 @@ use gpui::{
     ParentElement, RenderOnce, StatefulInteractiveElement, Styled, Window, div, px,
 };
 use matrix_sdk::RoomMemberships;
+use matrix_sdk::notification_settings::RoomNotificationMode;
 use matrix_sdk::ruma::OwnedRoomId;
 use matrix_sdk::ruma::api::client::receipt::create_receipt::v3::ReceiptType;
+use matrix_sdk::ruma::events::tag::TagName;
 use matrix_sdk_ui::timeline::RoomExt;
 use std::collections::HashMap;
 use std::rc::Rc;
 use thegrid_common::session::room_cache::CachedRoom;
+use thegrid_common::session::session_manager::SessionManager;
 use thegrid_common::tokio_helper::TokioHelper;
 
 #[derive(Clone)]
@@ -24,13 +30,20 @@ pub struct InviteEvent {
     pub room_id: OwnedRoomId,
 }
 
+#[derive(Clone)]
+pub struct NewSectionEvent {
+    pub room_id: OwnedRoomId,
+}
+
 #[derive(IntoElement)]
 pub struct StandardRoomElement {
     pub room: Entity<CachedRoom>,
     pub render_as: StandardRoomElementType,
     pub current_room: Option<OwnedRoomId>,
+    pub section: Option<TagName>,
     pub on_click: Rc<Box<dyn Fn(&ClickEvent, &mut Window, &mut App)>>,
     pub on_invite: Rc<Box<dyn Fn(&InviteEvent, &mut Window, &mut App)>>,
+    pub on_new_section: Rc<Box<dyn Fn(&NewSectionEvent, &mut Window, &mut App)>>,
 }
 
 #[derive(Clone, Copy)]
@@ -52,8 +65,10 @@ impl RenderOnce for StandardRoomElement {
         let room = self.room.read(cx);
         let room_id = room.inner.room_id().to_owned();
         let room_id_2 = room_id.clone();
+        let room_id_3 = room_id.clone();
         let on_click = self.on_click;
         let on_invite = self.on_invite;
+        let on_new_section = self.on_new_section;
         let matrix_room = room.inner.clone();
         let locale = I18N_MANAGER.locale();
 
@@ -72,9 +87,31 @@ impl RenderOnce for StandardRoomElement {
             StandardRoomElementType::Space => Default::default(),
         };
 
+        // Quieter rooms only get a full badge when something needs the user's attention
+        let notification_mode = room.notification_mode();
+        let (badge_count, dimmed) = match notification_mode {
+            Some(RoomNotificationMode::Mute) => (0, true),
+            Some(RoomNotificationMode::MentionsAndKeywordsOnly) => {
+                (unread_state.unread_mentions, true)
+            }
+            _ => (unread_state.unread_notifications, false),
+        };
+
+        let user_defined_notification_mode = cx
+            .global::<SessionManager>()
+            .notification_settings()
+            .read(cx)
+            .room_mode(&room_id);
+
+        let has_draft = cx
+            .global::<SessionManager>()
+            .drafts()
+            .read(cx)
+            .has_draft(&room_id);
+
         let theme = cx.theme();
 
-        let context_menu = vec![
+        let mut context_menu = vec![
             ContextMenuItem::separator()
                 .label(tr!("FOR_ROOM", "For {{room}}", room:Quote=display_name))
                 .build(),
@@ -100,11 +137,16 @@ impl RenderOnce for StandardRoomElement {
                     }
                 })
                 .build(),
-            ContextMenuItem::menu_item()
-                .label(tr!("ROOM_NOTIFICATIONS", "Notification Settings..."))
-                .icon("reminder")
-                .disabled()
+            ContextMenuItem::separator()
+                .label(tr!("ROOM_NOTIFICATIONS", "Notifications"))
                 .build(),
+        ];
+        context_menu.extend(room_notification_mode_menu(
+            room_id.clone(),
+            notification_mode.clone(),
+            user_defined_notification_mode,
+        ));
+        context_menu.extend(vec![
             ContextMenuItem::separator().build(),
             ContextMenuItem::menu_item()
                 .label(tr!("ROOM_INVITE", "Invite Someone..."))
@@ -227,6 +269,22 @@ impl RenderOnce for StandardRoomElement {
                     }
                 })
                 .build(),
+        ]);
+        context_menu.extend(room_sections_menu(
+            room,
+            self.section.clone(),
+            move |window, cx| {
+                on_new_section(
+                    &NewSectionEvent {
+                        room_id: room_id_3.clone(),
+                    },
+                    window,
+                    cx,
+                )
+            },
+            cx,
+        ));
+        context_menu.extend(vec![
             ContextMenuItem::separator().build(),
             ContextMenuItem::menu_item()
                 .label(tr!("ROOM_SETTINGS_ACTION", "Room Settings..."))
@@ -240,7 +298,7 @@ impl RenderOnce for StandardRoomElement {
                     current_dialog_box.write(cx, CurrentDialogBox::LeaveRoom(false));
                 })
                 .build(),
-        ];
+        ]);
 
         div()
             .id("item")
@@ -268,15 +326,18 @@ impl RenderOnce for StandardRoomElement {
                 .into_any_element(),
             })
             .child(div().flex_grow(1.))
+            .when(has_draft, |david| {
+                david.child(div().m(px(2.)).child(icon("document-edit").size(12.)))
+            })
             .when_else(
-                unread_state.unread_notifications > 0,
+                badge_count > 0,
                 |david| {
                     david.font_weight(FontWeight::BOLD).child(
                         div()
                             .rounded(theme.border_radius)
                             .bg(theme.error_accent_color)
                             .p(px(2.))
-                            .child(locale.format_decimal(unread_state.unread_notifications)),
+                            .child(locale.format_decimal(badge_count)),
                     )
                 },
                 |david| {
@@ -284,7 +345,11 @@ impl RenderOnce for StandardRoomElement {
                         david.child(
                             div()
                                 .m(px(4.))
-                                .bg(theme.foreground)
+                                .when_else(
+                                    dimmed,
+                                    |david| david.bg(theme.foreground.disabled()),
+                                    |david| david.bg(theme.foreground),
+                                )
                                 .size(px(8.))
                                 .rounded(px(4.)),
                         )
@@ -19,6 +19,7 @@ use crate::actions::{
     LogOut,
 };
 use crate::chat::chat_input::bind_chat_input_keys;
+use crate::chat::chat_room::media_viewer::bind_media_viewer_keys;
 use crate::main_window::MainWindow;
 use cntp_i18n::{tr, tr_load, I18N_MANAGER};
 use cntp_icon_tool_macros::application_icon;
@@ -38,10 +39,14 @@ use std::cell::RefCell;
 use std::ptr;
 use std::rc::Rc;
 use std::str::FromStr;
+use thegrid_common::audio_player::setup_audio_player;
 use thegrid_common::session::session_manager::{setup_session_manager, SessionManager};
 use thegrid_common::session::sso_login::SsoLogin;
 use thegrid_common::setup_thegrid_common;
 use thegrid_rtc_livekit::call_manager::setup_call_manager;
+use thegrid_rtc_livekit::call_shortcuts::{
+    HangUp, PushToTalk, ToggleCamera, ToggleDeafen, ToggleMute, ToggleScreenshare,
+};
 use thegrid_rtc_livekit::setup_thegrid_rtc_livekit;
 use tracing::error;
 use url::Url;
@@ -149,6 +154,35 @@ fn mane() {
                             ],
                             disabled: false,
                         },
+                        Menu {
+                            name: tr!("MENU_CALL", "Call").into(),
+                            items: vec![
+                                MenuItem::action(
+                                    tr!("CALL_MENU_TOGGLE_MUTE", "Mute Microphone"),
+                                    ToggleMute,
+                                ),
+                                MenuItem::action(
+                                    tr!("CALL_MENU_TOGGLE_DEAFEN", "Deafen"),
+                                    ToggleDeafen,
+                                ),
+                                MenuItem::action(
+                                    tr!("CALL_MENU_PUSH_TO_TALK", "Push to Talk"),
+                                    PushToTalk,
+                                ),
+                                MenuItem::separator(),
+                                MenuItem::action(
+                                    tr!("CALL_MENU_TOGGLE_CAMERA", "Camera"),
+                                    ToggleCamera,
+                                ),
+                                MenuItem::action(
+                                    tr!("CALL_MENU_TOGGLE_SCREENSHARE", "Share Screen"),
+                                    ToggleScreenshare,
+                                ),
+                                MenuItem::separator(),
+                                MenuItem::action(tr!("CALL_MENU_HANG_UP", "Hang Up"), HangUp),
+                            ],
+                            disabled: false,
+                        },
                     ],
                     on_about: Rc::new({
                         let outer_window = outer_window.clone();
@@ -190,7 +224,9 @@ fn mane() {
 
         setup_session_manager(cx);
         setup_call_manager(cx);
+        setup_audio_player(cx);
         bind_chat_input_keys(cx);
+        bind_media_viewer_keys(cx);
 
         cx.spawn(async move |cx: &mut AsyncApp| {
             while let Ok(urls) = open_urls_rx.recv().await {
@@ -224,8 +260,8 @@ fn mane() {
                 window_bounds: Some(WindowBounds::Windowed(bounds)),
                 ..default_window_options
             },
-            |_, cx| {
-                let window = cx.new(MainWindow::new);
+            |window, cx| {
+                let window = cx.new(|cx| MainWindow::new(window, cx));
                 *outer_window.borrow_mut() = window.downgrade();
 
                 window
@@ -10,11 +10,15 @@ use contemporary::about_surface::about_surface;
 use contemporary::components::pager::lift_animation::LiftAnimation;
 use contemporary::components::pager::pager;
 use contemporary::window::contemporary_window;
-use gpui::{div, AppContext, Context, Entity, IntoElement, ParentElement, Render, Styled, Window};
+use gpui::{
+    div, AppContext, Context, Entity, FocusHandle, InteractiveElement, IntoElement, ParentElement,
+    Render, Styled, Window,
+};
 use thegrid_common::session::session_manager::SessionManager;
 use thegrid_common::surfaces::{
     AccountSettingsDeepLink, MainWindowSurface, NotReadyReason, SurfaceChange, SurfaceChangeEvent,
 };
+use thegrid_rtc_livekit::call_shortcuts::{push_to_talk_key_released, release_push_to_talk};
 use thegrid_rtc_livekit::call_surface::CallSurface;
 use uuid::Uuid;
 
@@ -28,10 +32,19 @@ pub struct MainWindow {
     deactivate_account_surface: Entity<DeactivateSurface>,
     call_surface: Option<Entity<CallSurface>>,
     current_surface: Vec<MainWindowSurface>,
+    focus_handle: FocusHandle,
 }
 
 impl MainWindow {
-    pub fn new(cx: &mut Context<Self>) -> MainWindow {
+    pub fn new(window: &mut Window, cx: &mut Context<Self>) -> MainWindow {
+        cx.observe_window_activation(window, |_, window, cx| {
+            // We won't find out when the push to talk key is released while we're in the background
+            if !window.is_window_active() {
+                release_push_to_talk(cx);
+            }
+        })
+        .detach();
+
         let session_manager = cx.global::<SessionManager>();
         let start_page = if keyring_core::get_default_store().is_none()
             || session_manager
@@ -74,6 +87,7 @@ impl MainWindow {
             },
             call_surface: None,
             current_surface: vec![start_page],
+            focus_handle: cx.focus_handle(),
         }
     }
 
@@ -136,52 +150,63 @@ impl MainWindow {
 
 impl Render for MainWindow {
     fn render(&mut self, window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        // Make sure that key releases reach us, even when nothing else has focus
+        if window.focused(cx).is_none() {
+            self.focus_handle.focus(window, cx);
+        }
+
         let session_manager = cx.global::<SessionManager>();
 
         contemporary_window().child(
-            pager(
-                "main-pager",
-                match self.current_surface.last().unwrap() {
-                    MainWindowSurface::Main => match session_manager.current_session() {
-                        Some(_) => 0,
-                        None => 1,
-                    },
-                    MainWindowSurface::Call(_) => 2,
-                    MainWindowSurface::Register => 3,
-                    MainWindowSurface::AccountSettings(_) => 4,
-                    MainWindowSurface::IdentityReset => 5,
-                    MainWindowSurface::PasswordChange => 6,
-                    MainWindowSurface::DeactivateAccount => 7,
-                    MainWindowSurface::NotReady(_) => 8,
-                    MainWindowSurface::About => 9,
-                },
-            )
-            .w_full()
-            .h_full()
-            .animation(LiftAnimation::new())
-            .page(self.main_surface.clone())
-            .page(self.auth_surface.clone())
-            .page(
-                self.call_surface
-                    .clone()
-                    .map(|call_surface| call_surface.into_any_element())
-                    .unwrap_or_else(|| div().into_any_element()),
-            )
-            .page(self.register_surface.clone())
-            .page(self.account_settings_surface.clone())
-            .page(self.identity_reset_surface.clone())
-            .page(self.password_change_surface.clone())
-            .page(self.deactivate_account_surface.clone())
-            .page(match self.current_surface.last().unwrap() {
-                MainWindowSurface::NotReady(reason) => {
-                    not_ready_surface(*reason).into_any_element()
-                }
-                _ => div().into_any_element(),
-            })
-            .page(about_surface().on_back_click(cx.listener(|this, _, _, cx| {
-                this.current_surface.pop();
-                cx.notify();
-            }))),
+            div()
+                .size_full()
+                .track_focus(&self.focus_handle)
+                .on_key_up(|event, _, cx| push_to_talk_key_released(event, cx))
+                .child(
+                    pager(
+                        "main-pager",
+                        match self.current_surface.last().unwrap() {
+                            MainWindowSurface::Main => match session_manager.current_session() {
+                                Some(_) => 0,
+                                None => 1,
+                            },
+                            MainWindowSurface::Call(_) => 2,
+                            MainWindowSurface::Register => 3,
+                            MainWindowSurface::AccountSettings(_) => 4,
+                            MainWindowSurface::IdentityReset => 5,
+                            MainWindowSurface::PasswordChange => 6,
+                            MainWindowSurface::DeactivateAccount => 7,
+                            MainWindowSurface::NotReady(_) => 8,
+                            MainWindowSurface::About => 9,
+                        },
+                    )
+                    .w_full()
+                    .h_full()
+                    .animation(LiftAnimation::new())
+                    .page(self.main_surface.clone())
+                    .page(self.auth_surface.clone())
+                    .page(
+                        self.call_surface
+                            .clone()
+                            .map(|call_surface| call_surface.into_any_element())
+                            .unwrap_or_else(|| div().into_any_element()),
+                    )
+                    .page(self.register_surface.clone())
+                    .page(self.account_settings_surface.clone())
+                    .page(self.identity_reset_surface.clone())
+                    .page(self.password_change_surface.clone())
+                    .page(self.deactivate_account_surface.clone())
+                    .page(match self.current_surface.last().unwrap() {
+                        MainWindowSurface::NotReady(reason) => {
+                            not_ready_surface(*reason).into_any_element()
+                        }
+                        _ => div().into_any_element(),
+                    })
+                    .page(about_surface().on_back_click(cx.listener(|this, _, _, cx| {
+                        this.current_surface.pop();
+                        cx.notify();
+                    }))),
+                ),
         )
     }
 }
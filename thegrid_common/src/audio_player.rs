@@ -0,0 +1,220 @@
+use crate::session::media_cache::{MediaCacheEntry, MediaFile, MediaState};
+use gpui::{App, AppContext, AsyncApp, Context, Entity, Global, Subscription, WeakEntity};
+use log::error;
+use rodio::{DeviceSinkBuilder, MixerDeviceSink, Player};
+use std::fs::File;
+use std::io::BufReader;
+use std::time::Duration;
+
+/// How often the playback position is refreshed while audio is playing
+const POSITION_REFRESH_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Plays audio attachments.
+///
+/// Only one attachment plays at a time; starting another one stops whatever is already playing.
+pub struct AudioPlayer {
+    now_playing: Option<NowPlaying>,
+    generation: usize,
+}
+
+struct NowPlaying {
+    media: MediaCacheEntry,
+    output: NowPlayingOutput,
+}
+
+enum NowPlayingOutput {
+    Loading(Subscription),
+    Playing {
+        player: Player,
+        _sink: MixerDeviceSink,
+    },
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum AudioPlaybackState {
+    Stopped,
+    Loading,
+    Playing(Duration),
+    Paused(Duration),
+}
+
+struct GlobalAudioPlayer(Entity<AudioPlayer>);
+
+impl Global for GlobalAudioPlayer {}
+
+pub fn setup_audio_player(cx: &mut App) {
+    let audio_player = cx.new(|_| AudioPlayer {
+        now_playing: None,
+        generation: 0,
+    });
+    cx.set_global(GlobalAudioPlayer(audio_player));
+}
+
+impl AudioPlayer {
+    pub fn global(cx: &App) -> Entity<Self> {
+        cx.global::<GlobalAudioPlayer>().0.clone()
+    }
+
+    /// Play an audio attachment, downloading it first if required.
+    pub fn play(
+        &mut self,
+        media: MediaCacheEntry,
+        media_file: Entity<MediaFile>,
+        cx: &mut Context<Self>,
+    ) {
+        self.generation += 1;
+        let generation = self.generation;
+
+        if matches!(
+            media_file.read(cx).media_state,
+            MediaState::Idle | MediaState::Failed
+        ) {
+            media_file.update(cx, |media_file, cx| {
+                media_file.request_media(None, None, false, cx);
+            });
+        }
+
+        let subscription = cx.observe(&media_file, move |this, media_file, cx| {
+            if this.generation == generation {
+                this.start_if_loaded(media_file, cx);
+            }
+        });
+        self.now_playing = Some(NowPlaying {
+            media,
+            output: NowPlayingOutput::Loading(subscription),
+        });
+        self.start_if_loaded(media_file, cx);
+        cx.notify();
+    }
+
+    fn start_if_loaded(&mut self, media_file: Entity<MediaFile>, cx: &mut Context<Self>) {
+        let Some(now_playing) = &mut self.now_playing else {
+            return;
+        };
+        if !matches!(now_playing.output, NowPlayingOutput::Loading(_)) {
+            return;
+        }
+
+        let path = match &media_file.read(cx).media_state {
+            MediaState::Loaded(media_file_handle) => media_file_handle.path().to_path_buf(),
+            MediaState::Failed => {
+                self.now_playing = None;
+                cx.notify();
+                return;
+            }
+            MediaState::Idle | MediaState::Loading => return,
+        };
+
+        let output = DeviceSinkBuilder::open_default_sink()
+            .map_err(|e| e.to_string())
+            .and_then(|sink| {
+                let file = File::open(&path).map_err(|e| e.to_string())?;
+                let player =
+                    rodio::play(sink.mixer(), BufReader::new(file)).map_err(|e| e.to_string())?;
+                Ok((sink, player))
+            });
+
+        match output {
+            Ok((sink, player)) => {
+                now_playing.output = NowPlayingOutput::Playing {
+                    player,
+                    _sink: sink,
+                };
+                self.track_position(cx);
+            }
+            Err(e) => {
+                error!("Unable to play audio: {e}");
+                self.now_playing = None;
+            }
+        }
+        cx.notify();
+    }
+
+    fn track_position(&mut self, cx: &mut Context<Self>) {
+        let generation = self.generation;
+        cx.spawn(
+            async move |weak_this: WeakEntity<Self>, cx: &mut AsyncApp| {
+                loop {
+                    cx.background_executor()
+                        .timer(POSITION_REFRESH_INTERVAL)
+                        .await;
+
+                    let Ok(still_playing) = weak_this.update(cx, |this, cx| {
+                        if this.generation != generation {
+                            return false;
+                        }
+
+                        if let Some(NowPlaying {
+                            output: NowPlayingOutput::Playing { player, .. },
+                            ..
+                        }) = &this.now_playing
+                            && player.empty()
+                        {
+                            this.now_playing = None;
+                        }
+                        cx.notify();
+                        this.now_playing.is_some()
+                    }) else {
+                        return;
+                    };
+
+                    if !still_playing {
+                        return;
+                    }
+                }
+            },
+        )
+        .detach();
+    }
+
+    fn player(&self, media: &MediaCacheEntry) -> Option<&Player> {
+        match &self.now_playing {
+            Some(NowPlaying {
+                media: now_playing_media,
+                output: NowPlayingOutput::Playing { player, .. },
+            }) if now_playing_media == media => Some(player),
+            _ => None,
+        }
+    }
+
+    pub fn toggle_pause(&mut self, media: &MediaCacheEntry, cx: &mut Context<Self>) {
+        if let Some(player) = self.player(media) {
+            if player.is_paused() {
+                player.play();
+            } else {
+                player.pause();
+            }
+            cx.notify();
+        }
+    }
+
+    pub fn seek(&mut self, media: &MediaCacheEntry, position: Duration, cx: &mut Context<Self>) {
+        if let Some(player) = self.player(media) {
+            if let Err(e) = player.try_seek(position) {
+                error!("Unable to seek audio: {e}");
+            }
+            cx.notify();
+        }
+    }
+
+    pub fn stop(&mut self, cx: &mut Context<Self>) {
+        self.generation += 1;
+        self.now_playing = None;
+        cx.notify();
+    }
+
+    pub fn state(&self, media: &MediaCacheEntry) -> AudioPlaybackState {
+        match &self.now_playing {
+            Some(now_playing) if now_playing.media == *media => match &now_playing.output {
+                NowPlayingOutput::Loading(_) => AudioPlaybackState::Loading,
+                NowPlayingOutput::Playing { player, .. } if player.is_paused() => {
+                    AudioPlaybackState::Paused(player.get_pos())
+                }
+                NowPlayingOutput::Playing { player, .. } => {
+                    AudioPlaybackState::Playing(player.get_pos())
+                }
+            },
+            _ => AudioPlaybackState::Stopped,
+        }
+    }
+}
@@ -5,6 +5,7 @@ use contemporary::icon_tool::Url;
 use contemporary::setup_parlance::setup_parlance_i18n_if_enabled;
 use gpui::App;
 
+pub mod audio_player;
 pub mod mxc_image;
 pub mod outbound_track;
 pub mod room;
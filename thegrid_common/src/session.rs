@@ -3,12 +3,20 @@ mod caches;
 pub mod capability_cache;
 pub mod database_secret;
 pub mod devices_cache;
+pub mod drafts_cache;
 pub mod error_handling;
+pub mod identities_cache;
+pub mod image_packs_cache;
 mod ignored_users_cache;
+pub mod incoming_calls_cache;
 pub mod media_cache;
+pub mod notification_settings_cache;
 mod notifications;
 pub mod room_cache;
+pub mod search_index;
 pub mod session_manager;
+pub mod sidebar_sections_cache;
 pub mod spaces_cache;
 pub mod sso_login;
+pub mod url_previews_cache;
 pub mod verification_requests_cache;
@@ -1,43 +1,82 @@
 use crate::session::account_cache::AccountCache;
 use crate::session::capability_cache::CapabilityCache;
 use crate::session::devices_cache::DevicesCache;
+use crate::session::drafts_cache::DraftsCache;
+use crate::session::identities_cache::IdentitiesCache;
+use crate::session::image_packs_cache::ImagePacksCache;
 use crate::session::ignored_users_cache::IgnoredUsersCache;
+use crate::session::incoming_calls_cache::IncomingCallsCache;
 use crate::session::media_cache::MediaCache;
+use crate::session::notification_settings_cache::NotificationSettingsCache;
 use crate::session::room_cache::RoomCache;
+use crate::session::search_index::SearchIndex;
+use crate::session::sidebar_sections_cache::SidebarSectionsCache;
 use crate::session::spaces_cache::SpacesCache;
+use crate::session::url_previews_cache::UrlPreviewsCache;
 use crate::session::verification_requests_cache::VerificationRequestsCache;
 use gpui::{AppContext, AsyncApp, Entity};
 use matrix_sdk::ruma::api::client::discovery::discover_homeserver::RtcFocusInfo;
 use matrix_sdk::Client;
+use matrix_sdk_ui::room_list_service::RoomListService;
+use std::sync::Arc;
 
 pub struct Caches {
     pub verification_requests: Entity<VerificationRequestsCache>,
     pub account_cache: Entity<AccountCache>,
     pub capability_cache: Entity<CapabilityCache>,
     pub devices_cache: Entity<DevicesCache>,
+    pub identities_cache: Entity<IdentitiesCache>,
     pub media_cache: MediaCache,
     pub room_cache: Entity<RoomCache>,
     pub spaces_cache: Entity<SpacesCache>,
     pub ignored_users_cache: Entity<IgnoredUsersCache>,
+    pub notification_settings_cache: Entity<NotificationSettingsCache>,
+    pub search_index: Entity<SearchIndex>,
+    pub sidebar_sections_cache: Entity<SidebarSectionsCache>,
+    pub image_packs_cache: Entity<ImagePacksCache>,
+    pub url_previews_cache: Entity<UrlPreviewsCache>,
+    pub drafts_cache: Entity<DraftsCache>,
+    pub incoming_calls_cache: Entity<IncomingCallsCache>,
 
     pub rtc_foci: Vec<RtcFocusInfo>,
 }
 
 impl Caches {
-    pub async fn new(client: &Client, cx: &mut AsyncApp) -> Self {
+    pub async fn new(
+        client: &Client,
+        room_list_service: Option<Arc<RoomListService>>,
+        cx: &mut AsyncApp,
+    ) -> Self {
         let spaces_cache = SpacesCache::new(client, cx).await;
         let spaces_cache = cx.new(|cx| spaces_cache.start_listening(cx));
 
-        cx.update(|cx| Self {
-            verification_requests: VerificationRequestsCache::new(client, cx),
-            account_cache: AccountCache::new(client, cx),
-            devices_cache: DevicesCache::new(client, cx),
-            capability_cache: cx.new(|cx| CapabilityCache::new(client, cx)),
-            media_cache: MediaCache::new(client),
-            room_cache: RoomCache::new(client, cx),
-            spaces_cache,
-            ignored_users_cache: cx.new(|cx| IgnoredUsersCache::new(client, cx)),
-            rtc_foci: Vec::new(),
+        cx.update(|cx| {
+            let notification_settings_cache =
+                cx.new(|cx| NotificationSettingsCache::new(client, cx));
+
+            let room_cache =
+                RoomCache::new(client, room_list_service, &notification_settings_cache, cx);
+
+            Self {
+                verification_requests: VerificationRequestsCache::new(client, cx),
+                account_cache: AccountCache::new(client, cx),
+                devices_cache: DevicesCache::new(client, cx),
+                identities_cache: cx.new(|cx| IdentitiesCache::new(client, cx)),
+                capability_cache: cx.new(|cx| CapabilityCache::new(client, cx)),
+                media_cache: MediaCache::new(client),
+                sidebar_sections_cache: cx
+                    .new(|cx| SidebarSectionsCache::new(client, &room_cache, cx)),
+                room_cache,
+                spaces_cache,
+                ignored_users_cache: cx.new(|cx| IgnoredUsersCache::new(client, cx)),
+                notification_settings_cache,
+                search_index: cx.new(|cx| SearchIndex::new(client, cx)),
+                image_packs_cache: cx.new(|cx| ImagePacksCache::new(client, cx)),
+                url_previews_cache: cx.new(|cx| UrlPreviewsCache::new(client, cx)),
+                drafts_cache: cx.new(|cx| DraftsCache::new(client, cx)),
+                incoming_calls_cache: cx.new(|cx| IncomingCallsCache::new(client, cx)),
+                rtc_foci: Vec::new(),
+            }
         })
     }
 }
@@ -0,0 +1,171 @@
+use crate::tokio_helper::TokioHelper;
+use gpui::{App, AsyncApp, Context, Task, WeakEntity};
+use log::error;
+use matrix_sdk::ruma::{EventId, OwnedEventId, OwnedRoomId, RoomId};
+use matrix_sdk::store::{ComposerDraft, DraftAttachment};
+use matrix_sdk::{Client, Room};
+use std::collections::HashSet;
+
+/// Keeps track of the messages the user has started writing but not sent yet.
+///
+/// Drafts are kept in the session store, so each account has its own drafts and they survive
+/// restarts. Attachments are stored next to the draft rather than inside it, so that saving the
+/// text as the user types doesn't write the attachments out again.
+pub struct DraftsCache {
+    rooms_with_drafts: HashSet<OwnedRoomId>,
+}
+
+impl DraftsCache {
+    pub fn new(client: &Client, cx: &mut Context<Self>) -> Self {
+        let client = client.clone();
+        cx.spawn(
+            async move |weak_this: WeakEntity<Self>, cx: &mut AsyncApp| {
+                let rooms_with_drafts = cx
+                    .spawn_tokio(async move {
+                        let mut rooms_with_drafts = HashSet::new();
+                        for room in client.joined_rooms() {
+                            if let Ok(Some(_)) = room.load_composer_draft(None).await {
+                                rooms_with_drafts.insert(room.room_id().to_owned());
+                            }
+                        }
+                        Ok::<_, matrix_sdk::Error>(rooms_with_drafts)
+                    })
+                    .await
+                    .unwrap_or_default();
+
+                let _ = weak_this.update(cx, |this, cx| {
+                    this.rooms_with_drafts.extend(rooms_with_drafts);
+                    cx.notify();
+                });
+            },
+        )
+        .detach();
+
+        Self {
+            rooms_with_drafts: HashSet::new(),
+        }
+    }
+
+    /// Whether there is an unsent message in the main timeline of a room
+    pub fn has_draft(&self, room_id: &RoomId) -> bool {
+        self.rooms_with_drafts.contains(room_id)
+    }
+
+    pub fn load_draft(
+        &self,
+        room: Room,
+        thread_root: Option<OwnedEventId>,
+        cx: &mut Context<Self>,
+    ) -> Task<Option<ComposerDraft>> {
+        cx.spawn(async move |_, cx: &mut AsyncApp| {
+            cx.spawn_tokio(async move {
+                let Some(mut draft) = room.load_composer_draft(thread_root.as_deref()).await?
+                else {
+                    return Ok(None);
+                };
+
+                let key = attachments_key(&room, thread_root.as_deref());
+                if let Some(attachments) =
+                    room.client().state_store().get_custom_value(&key).await?
+                {
+                    draft.attachments = serde_json::from_slice(&attachments)?;
+                }
+
+                Ok::<_, matrix_sdk::Error>(Some(draft))
+            })
+            .await
+            .unwrap_or_else(|e| {
+                error!("Unable to load draft: {e:?}");
+                None
+            })
+        })
+    }
+
+    /// Save the draft of a room or thread, or clear it if `draft` is `None`
+    pub fn save_draft(
+        &mut self,
+        room: Room,
+        thread_root: Option<OwnedEventId>,
+        draft: Option<ComposerDraft>,
+        cx: &mut Context<Self>,
+    ) {
+        if thread_root.is_none() {
+            let changed = if draft.is_some() {
+                self.rooms_with_drafts.insert(room.room_id().to_owned())
+            } else {
+                self.rooms_with_drafts.remove(room.room_id())
+            };
+            if changed {
+                cx.notify();
+            }
+        }
+
+        write_draft(room, thread_root, draft, cx);
+    }
+}
+
+/// Save the draft of a room or thread in the session store, without updating any cache.
+///
+/// This is for saving drafts of rooms in an account that isn't the current one.
+pub fn write_draft(
+    room: Room,
+    thread_root: Option<OwnedEventId>,
+    draft: Option<ComposerDraft>,
+    cx: &mut App,
+) {
+    cx.spawn(async move |cx: &mut AsyncApp| {
+        if let Err(e) = cx
+            .spawn_tokio(async move {
+                match draft {
+                    Some(draft) => {
+                        room.save_composer_draft(draft, thread_root.as_deref())
+                            .await
+                    }
+                    None => room.clear_composer_draft(thread_root.as_deref()).await,
+                }
+            })
+            .await
+        {
+            error!("Unable to save draft: {e:?}");
+        }
+    })
+    .detach();
+}
+
+/// Save the attachments of a draft, or clear them if there are none.
+///
+/// This should only be called when the attachments change, as they can be large.
+pub fn write_draft_attachments(
+    room: Room,
+    thread_root: Option<OwnedEventId>,
+    attachments: Vec<DraftAttachment>,
+    cx: &mut App,
+) {
+    cx.spawn(async move |cx: &mut AsyncApp| {
+        if let Err(e) = cx
+            .spawn_tokio(async move {
+                let key = attachments_key(&room, thread_root.as_deref());
+                let client = room.client();
+                if attachments.is_empty() {
+                    client.state_store().remove_custom_value(&key).await?;
+                } else {
+                    let value = serde_json::to_vec(&attachments)?;
+                    client.state_store().set_custom_value(&key, value).await?;
+                }
+                Ok::<_, matrix_sdk::Error>(())
+            })
+            .await
+        {
+            error!("Unable to save draft attachments: {e:?}");
+        }
+    })
+    .detach();
+}
+
+fn attachments_key(room: &Room, thread_root: Option<&EventId>) -> Vec<u8> {
+    match thread_root {
+        Some(thread_root) => format!("thegrid.draft_attachments.{}.{thread_root}", room.room_id()),
+        None => format!("thegrid.draft_attachments.{}", room.room_id()),
+    }
+    .into_bytes()
+}
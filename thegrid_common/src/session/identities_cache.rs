@@ -0,0 +1,124 @@
+use crate::tokio_helper::TokioHelper;
+use gpui::{AsyncApp, Context, WeakEntity};
+use log::error;
+use matrix_sdk::Client;
+use matrix_sdk::encryption::identities::UserIdentity;
+use matrix_sdk::ruma::{OwnedUserId, UserId};
+use matrix_sdk::stream::StreamExt;
+use std::collections::HashMap;
+
+pub struct IdentitiesCache {
+    client: Client,
+    identities: HashMap<OwnedUserId, Option<IdentityState>>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum IdentityState {
+    /// The user has not set up cross-signing, so their identity can't be verified
+    NoIdentity,
+    Unverified,
+    Verified,
+    /// The user was verified, but their identity has changed since
+    VerificationViolation,
+}
+
+impl From<Option<&UserIdentity>> for IdentityState {
+    fn from(identity: Option<&UserIdentity>) -> Self {
+        match identity {
+            None => IdentityState::NoIdentity,
+            Some(identity) if identity.has_verification_violation() => {
+                IdentityState::VerificationViolation
+            }
+            Some(identity) if identity.is_verified() => IdentityState::Verified,
+            Some(_) => IdentityState::Unverified,
+        }
+    }
+}
+
+impl IdentitiesCache {
+    pub fn new(client: &Client, cx: &mut Context<Self>) -> Self {
+        let client_clone = client.clone();
+        cx.spawn(
+            async move |weak_this: WeakEntity<Self>, cx: &mut AsyncApp| {
+                let client = client_clone.clone();
+                let Ok(mut identities_stream) = cx
+                    .spawn_tokio(async move { client.encryption().user_identities_stream().await })
+                    .await
+                else {
+                    return;
+                };
+
+                while let Some(updates) = tokio::task::unconstrained(identities_stream.next()).await
+                {
+                    if weak_this
+                        .update(cx, |this, cx| {
+                            for (user_id, identity) in updates.new.iter().chain(&updates.changed) {
+                                this.identities
+                                    .insert(user_id.clone(), Some(Some(identity).into()));
+                            }
+                            cx.notify()
+                        })
+                        .is_err()
+                    {
+                        return;
+                    }
+                }
+            },
+        )
+        .detach();
+
+        Self {
+            client: client.clone(),
+            identities: HashMap::new(),
+        }
+    }
+
+    /// Get the verification state of a user's identity.
+    ///
+    /// Returns `None` while the identity is being loaded from the crypto store.
+    pub fn identity_state(
+        &mut self,
+        user_id: &UserId,
+        cx: &mut Context<Self>,
+    ) -> Option<IdentityState> {
+        if let Some(state) = self.identities.get(user_id) {
+            return *state;
+        }
+
+        self.refresh(user_id.to_owned(), cx);
+        None
+    }
+
+    pub fn refresh(&mut self, user_id: OwnedUserId, cx: &mut Context<Self>) {
+        self.identities.entry(user_id.clone()).or_insert(None);
+
+        let client = self.client.clone();
+        cx.spawn(
+            async move |weak_this: WeakEntity<Self>, cx: &mut AsyncApp| {
+                let identity = cx
+                    .spawn_tokio({
+                        let user_id = user_id.clone();
+                        async move { client.encryption().get_user_identity(&user_id).await }
+                    })
+                    .await;
+
+                let _ = weak_this.update(cx, |this, cx| match identity {
+                    Ok(identity) => {
+                        this.identities
+                            .insert(user_id, Some(identity.as_ref().into()));
+                        cx.notify()
+                    }
+                    Err(e) => {
+                        // Forget about the user so that the identity is loaded again next time
+                        // it is needed
+                        error!("Unable to load identity for {user_id}: {e:?}");
+                        if this.identities.get(&user_id) == Some(&None) {
+                            this.identities.remove(&user_id);
+                        }
+                    }
+                });
+            },
+        )
+        .detach();
+    }
+}
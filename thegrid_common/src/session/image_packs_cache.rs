@@ -0,0 +1,404 @@
+use crate::tokio_helper::TokioHelper;
+use cntp_i18n::tr;
+use gpui::http_client::anyhow;
+use gpui::private::anyhow;
+use gpui::{AsyncApp, Context, Task, WeakEntity};
+use matrix_sdk::deserialized_responses::SyncOrStrippedState;
+use matrix_sdk::ruma::events::macros::EventContent;
+use matrix_sdk::ruma::events::room::ImageInfo;
+use matrix_sdk::ruma::events::{GlobalAccountDataEvent, OriginalSyncStateEvent, SyncStateEvent};
+use matrix_sdk::ruma::{OwnedMxcUri, OwnedRoomId, RoomId};
+use matrix_sdk::{Client, Room};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+/// What an image in an image pack can be used for
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PackUsage {
+    Emoticon,
+    Sticker,
+}
+
+/// A single image within an image pack
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PackImage {
+    pub url: OwnedMxcUri,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub info: Option<ImageInfo>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub usage: Vec<PackUsage>,
+}
+
+/// Metadata about an image pack
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct PackInfo {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub avatar_url: Option<OwnedMxcUri>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub usage: Vec<PackUsage>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attribution: Option<String>,
+}
+
+/// An image pack shared with everyone in a room, keyed by the state key of the event
+#[derive(Clone, Debug, Default, Deserialize, Serialize, EventContent)]
+#[ruma_event(type = "im.ponies.room_emotes", kind = State, state_key_type = String)]
+pub struct RoomImagePackEventContent {
+    #[serde(default)]
+    pub images: BTreeMap<String, PackImage>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pack: Option<PackInfo>,
+}
+
+/// The user's personal image pack, available in every room
+#[derive(Clone, Debug, Default, Deserialize, Serialize, EventContent)]
+#[ruma_event(type = "im.ponies.user_emotes", kind = GlobalAccountData)]
+pub struct UserImagePackEventContent {
+    #[serde(default)]
+    pub images: BTreeMap<String, PackImage>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pack: Option<PackInfo>,
+}
+
+/// Room image packs that the user has enabled in every room, keyed by room and then state key
+#[derive(Clone, Debug, Default, Deserialize, Serialize, EventContent)]
+#[ruma_event(type = "im.ponies.emote_rooms", kind = GlobalAccountData)]
+pub struct EmoteRoomsEventContent {
+    #[serde(default)]
+    pub rooms: BTreeMap<OwnedRoomId, BTreeMap<String, serde_json::Value>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ImagePackSource {
+    User,
+    Room(OwnedRoomId, String),
+}
+
+/// An image pack that is available to the user, resolved from either account data or room state
+#[derive(Clone, Debug)]
+pub struct ImagePack {
+    pub source: ImagePackSource,
+    pub info: PackInfo,
+    pub images: BTreeMap<String, PackImage>,
+}
+
+impl ImagePack {
+    /// The images in this pack that can be used for the given purpose.
+    ///
+    /// Images without an explicit usage inherit the usage of the pack, and if the pack has none
+    /// either, they can be used for anything.
+    pub fn images_for(&self, usage: PackUsage) -> impl Iterator<Item = (&String, &PackImage)> {
+        self.images.iter().filter(move |(_, image)| {
+            let image_usage = if image.usage.is_empty() {
+                &self.info.usage
+            } else {
+                &image.usage
+            };
+            image_usage.is_empty() || image_usage.contains(&usage)
+        })
+    }
+
+    pub fn display_name(&self) -> String {
+        if let Some(display_name) = &self.info.display_name {
+            return display_name.clone();
+        }
+
+        match &self.source {
+            ImagePackSource::User => tr!("IMAGE_PACK_PERSONAL", "Personal").to_string(),
+            ImagePackSource::Room(_, state_key) if state_key.is_empty() => {
+                tr!("IMAGE_PACK_ROOM", "Room").to_string()
+            }
+            ImagePackSource::Room(_, state_key) => state_key.clone(),
+        }
+    }
+}
+
+pub struct ImagePacksCache {
+    client: Client,
+    user_pack: UserImagePackEventContent,
+    emote_rooms: EmoteRoomsEventContent,
+    room_packs: HashMap<OwnedRoomId, BTreeMap<String, RoomImagePackEventContent>>,
+}
+
+enum ImagePacksUpdate {
+    UserPack(UserImagePackEventContent),
+    EmoteRooms(EmoteRoomsEventContent),
+    RoomPack(OwnedRoomId, String, RoomImagePackEventContent),
+}
+
+impl ImagePacksCache {
+    pub fn new(client: &Client, cx: &mut Context<Self>) -> Self {
+        let (tx_update, rx_update) = async_channel::unbounded();
+        client.add_event_handler({
+            let tx_update = tx_update.clone();
+            move |event: GlobalAccountDataEvent<UserImagePackEventContent>| {
+                let tx_update = tx_update.clone();
+                async move {
+                    let _ = tx_update
+                        .send(ImagePacksUpdate::UserPack(event.content))
+                        .await;
+                }
+            }
+        });
+        client.add_event_handler({
+            let tx_update = tx_update.clone();
+            move |event: GlobalAccountDataEvent<EmoteRoomsEventContent>| {
+                let tx_update = tx_update.clone();
+                async move {
+                    let _ = tx_update
+                        .send(ImagePacksUpdate::EmoteRooms(event.content))
+                        .await;
+                }
+            }
+        });
+        client.add_event_handler(
+            move |event: OriginalSyncStateEvent<RoomImagePackEventContent>, room: Room| {
+                let tx_update = tx_update.clone();
+                async move {
+                    let _ = tx_update
+                        .send(ImagePacksUpdate::RoomPack(
+                            room.room_id().to_owned(),
+                            event.state_key,
+                            event.content,
+                        ))
+                        .await;
+                }
+            },
+        );
+
+        let client_clone = client.clone();
+        cx.spawn(
+            async move |weak_this: WeakEntity<Self>, cx: &mut AsyncApp| {
+                let Ok((user_pack, emote_rooms)) = cx
+                    .spawn_tokio(async move {
+                        let account = client_clone.account();
+                        let user_pack = account
+                            .account_data::<UserImagePackEventContent>()
+                            .await?
+                            .and_then(|user_pack| user_pack.deserialize().ok());
+                        let emote_rooms = account
+                            .account_data::<EmoteRoomsEventContent>()
+                            .await?
+                            .and_then(|emote_rooms| emote_rooms.deserialize().ok());
+                        Ok::<_, matrix_sdk::Error>((user_pack, emote_rooms))
+                    })
+                    .await
+                else {
+                    return;
+                };
+
+                if weak_this
+                    .update(cx, |this, cx| {
+                        if let Some(user_pack) = user_pack {
+                            this.user_pack = user_pack;
+                        }
+                        if let Some(emote_rooms) = emote_rooms {
+                            this.set_emote_rooms(emote_rooms, cx);
+                        }
+                        cx.notify()
+                    })
+                    .is_err()
+                {
+                    return;
+                }
+
+                while let Ok(update) = rx_update.recv().await {
+                    if weak_this
+                        .update(cx, |this, cx| {
+                            match update {
+                                ImagePacksUpdate::UserPack(user_pack) => {
+                                    this.user_pack = user_pack;
+                                }
+                                ImagePacksUpdate::EmoteRooms(emote_rooms) => {
+                                    this.set_emote_rooms(emote_rooms, cx);
+                                }
+                                ImagePacksUpdate::RoomPack(room_id, state_key, content) => {
+                                    // Only keep track of rooms that have already been loaded,
+                                    // the rest will pick up this pack when they load
+                                    if let Some(room_packs) = this.room_packs.get_mut(&room_id) {
+                                        room_packs.insert(state_key, content);
+                                    }
+                                }
+                            }
+                            cx.notify()
+                        })
+                        .is_err()
+                    {
+                        return;
+                    }
+                }
+            },
+        )
+        .detach();
+
+        Self {
+            client: client.clone(),
+            user_pack: UserImagePackEventContent::default(),
+            emote_rooms: EmoteRoomsEventContent::default(),
+            room_packs: HashMap::new(),
+        }
+    }
+
+    fn set_emote_rooms(&mut self, emote_rooms: EmoteRoomsEventContent, cx: &mut Context<Self>) {
+        for room_id in emote_rooms.rooms.keys() {
+            self.load_room_packs(room_id, cx);
+        }
+        self.emote_rooms = emote_rooms;
+    }
+
+    /// Start loading the image packs of a room, if they haven't been loaded already
+    pub fn load_room_packs(&mut self, room_id: &RoomId, cx: &mut Context<Self>) {
+        if self.room_packs.contains_key(room_id) {
+            return;
+        }
+        let Some(room) = self.client.get_room(room_id) else {
+            return;
+        };
+
+        self.room_packs.insert(room_id.to_owned(), BTreeMap::new());
+
+        let room_id = room_id.to_owned();
+        cx.spawn(
+            async move |weak_this: WeakEntity<Self>, cx: &mut AsyncApp| {
+                let Ok(pack_events) = cx
+                    .spawn_tokio(async move {
+                        room.get_state_events_static::<RoomImagePackEventContent>()
+                            .await
+                    })
+                    .await
+                else {
+                    return;
+                };
+
+                let packs = pack_events
+                    .iter()
+                    .filter_map(|pack_event| match pack_event.deserialize().ok()? {
+                        SyncOrStrippedState::Sync(SyncStateEvent::Original(pack_event)) => {
+                            Some((pack_event.state_key, pack_event.content))
+                        }
+                        _ => None,
+                    })
+                    .collect::<BTreeMap<_, _>>();
+
+                let _ = weak_this.update(cx, |this, cx| {
+                    this.room_packs.entry(room_id).or_default().extend(packs);
+                    cx.notify();
+                });
+            },
+        )
+        .detach();
+    }
+
+    /// The user's personal image pack
+    pub fn user_pack(&self) -> ImagePack {
+        ImagePack {
+            source: ImagePackSource::User,
+            info: self.user_pack.pack.clone().unwrap_or_default(),
+            images: self.user_pack.images.clone(),
+        }
+    }
+
+    /// The image packs defined in the state of a room
+    pub fn room_packs(&self, room_id: &RoomId) -> Vec<ImagePack> {
+        self.room_packs
+            .get(room_id)
+            .into_iter()
+            .flatten()
+            .map(|(state_key, content)| ImagePack {
+                source: ImagePackSource::Room(room_id.to_owned(), state_key.clone()),
+                info: content.pack.clone().unwrap_or_default(),
+                images: content.images.clone(),
+            })
+            .collect()
+    }
+
+    /// The content of a single image pack in a room, for editing
+    pub fn room_pack_content(
+        &self,
+        room_id: &RoomId,
+        state_key: &str,
+    ) -> RoomImagePackEventContent {
+        self.room_packs
+            .get(room_id)
+            .and_then(|room_packs| room_packs.get(state_key))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// All image packs that can be used in a room: the user's own pack, the packs of the room
+    /// itself, and any packs from other rooms that the user has enabled everywhere.
+    pub fn packs_for_room(&self, room_id: &RoomId) -> Vec<ImagePack> {
+        let mut packs = vec![self.user_pack()];
+        packs.extend(self.room_packs(room_id));
+        for (emote_room_id, state_keys) in &self.emote_rooms.rooms {
+            if emote_room_id == room_id {
+                continue;
+            }
+
+            packs.extend(self.room_packs(emote_room_id).into_iter().filter(
+                |pack| match &pack.source {
+                    ImagePackSource::Room(_, state_key) => state_keys.contains_key(state_key),
+                    ImagePackSource::User => false,
+                },
+            ));
+        }
+        packs.retain(|pack| !pack.images.is_empty());
+        packs
+    }
+
+    /// The emoticons that can be used in a room, keyed by shortcode. When two packs use the same
+    /// shortcode, the pack that comes first wins.
+    pub fn emoticons_for_room(&self, room_id: &RoomId) -> BTreeMap<String, OwnedMxcUri> {
+        let mut emoticons = BTreeMap::new();
+        for pack in self.packs_for_room(room_id) {
+            for (shortcode, image) in pack.images_for(PackUsage::Emoticon) {
+                emoticons
+                    .entry(shortcode.clone())
+                    .or_insert_with(|| image.url.clone());
+            }
+        }
+        emoticons
+    }
+
+    pub fn save_room_pack(
+        &mut self,
+        room: Room,
+        state_key: String,
+        content: RoomImagePackEventContent,
+        cx: &mut Context<Self>,
+    ) -> Task<anyhow::Result<()>> {
+        if let Some(room_packs) = self.room_packs.get_mut(room.room_id()) {
+            room_packs.insert(state_key.clone(), content.clone());
+            cx.notify();
+        }
+
+        cx.spawn(async move |_, cx: &mut AsyncApp| {
+            cx.spawn_tokio(async move { room.send_state_event_for_key(&state_key, content).await })
+                .await
+                .map(|_| ())
+                .map_err(|e| anyhow!(e))
+        })
+    }
+
+    pub fn save_user_pack(
+        &mut self,
+        content: UserImagePackEventContent,
+        cx: &mut Context<Self>,
+    ) -> Task<anyhow::Result<()>> {
+        self.user_pack = content.clone();
+        cx.notify();
+
+        let client = self.client.clone();
+        cx.spawn(async move |_, cx: &mut AsyncApp| {
+            cx.spawn_tokio(async move { client.account().set_account_data(content).await })
+                .await
+                .map(|_| ())
+                .map_err(|e| anyhow!(e))
+        })
+    }
+}
@@ -0,0 +1,305 @@
+use crate::session::session_manager::SessionManager;
+use crate::sfx::{LoopingSoundEffect, LoopingSoundEffectHandle, SoundEffect};
+use cntp_i18n::{Quote, tr};
+use contemporary::notification::Notification;
+use gpui::{AsyncApp, Context, WeakEntity};
+use matrix_sdk::ruma::events::call::notify::{NotifyType, OriginalSyncCallNotifyEvent};
+use matrix_sdk::ruma::events::rtc::notification::{
+    NotificationType, OriginalSyncRtcNotificationEvent,
+};
+use matrix_sdk::ruma::events::{AnyPossiblyRedactedStateEventContent, AnySyncStateEvent};
+use matrix_sdk::ruma::{MilliSecondsSinceUnixEpoch, OwnedRoomId, OwnedUserId, RoomId};
+use matrix_sdk::{Client, Room};
+use std::time::{Duration, Instant, SystemTime};
+
+/// How long to ring for when the notification doesn't say
+const DEFAULT_RING_LIFETIME: Duration = Duration::from_secs(30);
+
+/// The longest we'll ring for, regardless of what the notification asks for
+const MAX_RING_LIFETIME: Duration = Duration::from_secs(120);
+
+/// Keeps track of calls that other users have started and that we haven't answered yet.
+///
+/// Calls are announced with `m.rtc.notification` events (or `m.call.notify` from older clients).
+/// An incoming call goes away when it is answered or declined, when the notification expires,
+/// or when everyone leaves the call.
+pub struct IncomingCallsCache {
+    own_user_id: OwnedUserId,
+    incoming_calls: Vec<IncomingCall>,
+    ringtone: Option<LoopingSoundEffectHandle>,
+}
+
+#[derive(Clone)]
+pub struct IncomingCall {
+    pub room_id: OwnedRoomId,
+    pub sender: OwnedUserId,
+    pub sender_name: String,
+
+    /// Whether the caller wants us to ring, rather than just letting us know about the call
+    pub ring: bool,
+    pub expires_at: Instant,
+}
+
+enum CacheMutation {
+    Push(IncomingCall, String, bool),
+    CallMembersChanged(OwnedRoomId, Vec<OwnedUserId>),
+}
+
+impl IncomingCallsCache {
+    pub fn new(client: &Client, cx: &mut Context<Self>) -> Self {
+        let (tx, rx) = async_channel::unbounded();
+        let own_user_id = client.user_id().unwrap().to_owned();
+
+        let tx_clone = tx.clone();
+        let own_user_id_clone = own_user_id.clone();
+        client.add_event_handler(
+            |event: OriginalSyncRtcNotificationEvent, room: Room| async move {
+                let lifetime = event.content.lifetime.min(MAX_RING_LIFETIME);
+                let ring = event.content.notification_type == NotificationType::Ring;
+                if let Some(mutation) = incoming_call(
+                    &room,
+                    event.sender,
+                    event.origin_server_ts,
+                    lifetime,
+                    ring,
+                    &own_user_id_clone,
+                )
+                .await
+                {
+                    let _ = tx_clone.send(mutation).await;
+                }
+            },
+        );
+
+        let tx_clone = tx.clone();
+        let own_user_id_clone = own_user_id.clone();
+        client.add_event_handler(
+            |event: OriginalSyncCallNotifyEvent, room: Room| async move {
+                let ring = event.content.notify_type == NotifyType::Ring;
+                if let Some(mutation) = incoming_call(
+                    &room,
+                    event.sender,
+                    event.origin_server_ts,
+                    DEFAULT_RING_LIFETIME,
+                    ring,
+                    &own_user_id_clone,
+                )
+                .await
+                {
+                    let _ = tx_clone.send(mutation).await;
+                }
+            },
+        );
+
+        let tx_clone = tx.clone();
+        client.add_event_handler(|event: AnySyncStateEvent, room: Room| async move {
+            if let AnyPossiblyRedactedStateEventContent::CallMember(_) = event.content() {
+                let _ = tx_clone
+                    .send(CacheMutation::CallMembersChanged(
+                        room.room_id().to_owned(),
+                        room.active_room_call_participants(),
+                    ))
+                    .await;
+            }
+        });
+
+        cx.spawn(
+            async move |weak_this: WeakEntity<Self>, cx: &mut AsyncApp| {
+                while let Ok(mutation) = rx.recv().await {
+                    if weak_this
+                        .update(cx, |this, cx| match mutation {
+                            CacheMutation::Push(incoming_call, room_name, is_direct) => {
+                                this.push_incoming_call(incoming_call, room_name, is_direct, cx)
+                            }
+                            CacheMutation::CallMembersChanged(room_id, call_participants) => {
+                                // Stop ringing once the call is over, or once we've joined it
+                                // from this or another device
+                                if call_participants.is_empty()
+                                    || call_participants.contains(&this.own_user_id)
+                                {
+                                    this.dismiss(&room_id, cx);
+                                }
+                            }
+                        })
+                        .is_err()
+                    {
+                        return;
+                    }
+                }
+            },
+        )
+        .detach();
+
+        Self {
+            own_user_id,
+            incoming_calls: Vec::new(),
+            ringtone: None,
+        }
+    }
+
+    fn push_incoming_call(
+        &mut self,
+        mut incoming_call: IncomingCall,
+        room_name: String,
+        is_direct: bool,
+        cx: &mut Context<Self>,
+    ) {
+        // Calls in muted rooms, or with call notifications turned off, still show up in the
+        // sidebar, but they don't ring or notify
+        let session_manager = cx.global::<SessionManager>();
+        let is_muted = session_manager
+            .rooms()
+            .read(cx)
+            .room(&incoming_call.room_id)
+            .is_some_and(|room| room.read(cx).is_muted());
+        let notification_settings = session_manager.notification_settings().read(cx).state();
+        let calls_enabled =
+            notification_settings.notifications_enabled && notification_settings.calls;
+        if is_muted || !calls_enabled {
+            incoming_call.ring = false;
+        } else {
+            Self::notify_incoming_call(&incoming_call, room_name, is_direct, cx);
+        }
+
+        // Dismiss the call once the notification expires
+        let room_id = incoming_call.room_id.clone();
+        let expires_at = incoming_call.expires_at;
+        cx.spawn(
+            async move |weak_this: WeakEntity<Self>, cx: &mut AsyncApp| {
+                cx.background_executor()
+                    .timer(expires_at.saturating_duration_since(Instant::now()))
+                    .await;
+                let _ = weak_this.update(cx, |this, cx| this.dismiss_if_expired(&room_id, cx));
+            },
+        )
+        .detach();
+
+        self.incoming_calls
+            .retain(|call| call.room_id != incoming_call.room_id);
+        self.incoming_calls.push(incoming_call);
+        self.update_ringtone();
+        cx.notify();
+    }
+
+    /// Post the desktop notification for a call.
+    ///
+    /// This is the only place that notifies about calls; the push rule notifications skip call
+    /// events so that each call is only announced once.
+    fn notify_incoming_call(
+        incoming_call: &IncomingCall,
+        room_name: String,
+        is_direct: bool,
+        cx: &mut Context<Self>,
+    ) {
+        let (summary, body) = if is_direct {
+            (
+                tr!("NOTIFICATION_INCOMING_CALL_SUMMARY", "Incoming call").to_string(),
+                tr!(
+                    "NOTIFICATION_INCOMING_CALL_BODY",
+                    "{{user}} is calling you",
+                    user = incoming_call.sender_name.clone()
+                )
+                .to_string(),
+            )
+        } else {
+            (
+                tr!("NOTIFICATION_CALL_STARTED_SUMMARY", "Call started").to_string(),
+                tr!(
+                    "NOTIFICATION_CALL_STARTED_BODY",
+                    "{{user}} started a call in {{room}}",
+                    user = incoming_call.sender_name.clone(),
+                    room:Quote = room_name
+                )
+                .to_string(),
+            )
+        };
+        Notification::new()
+            .summary(summary.as_str())
+            .body(body.as_str())
+            .post(cx);
+        if !incoming_call.ring {
+            SoundEffect::Notification.play();
+        }
+    }
+
+    /// Stop showing an incoming call, either because it was answered or declined
+    pub fn dismiss(&mut self, room_id: &RoomId, cx: &mut Context<Self>) {
+        let count = self.incoming_calls.len();
+        self.incoming_calls.retain(|call| call.room_id != room_id);
+        if self.incoming_calls.len() != count {
+            self.update_ringtone();
+            cx.notify();
+        }
+    }
+
+    fn dismiss_if_expired(&mut self, room_id: &RoomId, cx: &mut Context<Self>) {
+        // The call might have been replaced by a newer notification in the meantime
+        if self
+            .incoming_calls
+            .iter()
+            .any(|call| call.room_id == room_id && call.expires_at <= Instant::now())
+        {
+            self.dismiss(room_id, cx);
+        }
+    }
+
+    pub fn incoming_calls(&self) -> &Vec<IncomingCall> {
+        &self.incoming_calls
+    }
+
+    fn update_ringtone(&mut self) {
+        if !self.incoming_calls.iter().any(|call| call.ring) {
+            self.ringtone = None;
+        } else if self.ringtone.is_none() {
+            self.ringtone = Some(LoopingSoundEffect::Ring.play());
+        }
+    }
+}
+
+async fn incoming_call(
+    room: &Room,
+    sender: OwnedUserId,
+    origin_server_ts: MilliSecondsSinceUnixEpoch,
+    lifetime: Duration,
+    ring: bool,
+    own_user_id: &OwnedUserId,
+) -> Option<CacheMutation> {
+    if sender == *own_user_id {
+        return None;
+    }
+
+    // Notifications that come in with a sync after they've expired shouldn't ring
+    let expires_in = origin_server_ts
+        .to_system_time()
+        .and_then(|sent_at| (sent_at + lifetime).duration_since(SystemTime::now()).ok())?;
+
+    if room.active_room_call_participants().contains(own_user_id) {
+        // We're already in this call
+        return None;
+    }
+
+    let sender_name = room
+        .get_member_no_sync(&sender)
+        .await
+        .ok()
+        .flatten()
+        .and_then(|member| member.display_name().map(|name| name.to_string()))
+        .unwrap_or_else(|| sender.to_string());
+    let room_name = room
+        .cached_display_name()
+        .map(|name| name.to_string())
+        .unwrap_or_else(|| room.room_id().to_string());
+    let is_direct = room.is_direct().await.unwrap_or(false);
+
+    Some(CacheMutation::Push(
+        IncomingCall {
+            room_id: room.room_id().to_owned(),
+            sender,
+            sender_name,
+            ring,
+            expires_at: Instant::now() + expires_in,
+        },
+        room_name,
+        is_direct,
+    ))
+}
@@ -2,21 +2,33 @@ use crate::tokio_helper::TokioHelper;
 use gpui::http_client::anyhow;
 use gpui::private::anyhow;
 use gpui::{App, AppContext, AsyncApp, Context, Entity, RenderImage, WeakEntity};
-use image::{Frame, ImageReader, Pixel, RgbaImage};
+use image::codecs::gif::GifDecoder;
+use image::codecs::webp::WebPDecoder;
+use image::{AnimationDecoder, Frame, ImageFormat, ImageReader, Pixel, RgbaImage};
+use log::error;
 use matrix_sdk::Client;
 use matrix_sdk::media::{MediaFileHandle, MediaFormat, MediaRequestParameters, UniqueKey};
+use matrix_sdk::ruma::api::MatrixVersion;
+use matrix_sdk::ruma::api::client::{authenticated_media, media};
 use matrix_sdk::ruma::events::room::MediaSource;
 use matrix_sdk::ruma::{MxcUri, OwnedMxcUri};
-use smallvec::smallvec;
+use serde::Deserialize;
+use serde_json::value::RawValue;
+use smallvec::{SmallVec, smallvec};
+use smol::process::Command;
 use std::cell::RefCell;
 use std::collections::HashMap;
 use std::fmt::Display;
 use std::hash::Hash;
+use std::fs::File;
+use std::io::{BufReader, Cursor, ErrorKind};
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex, Weak};
 
 pub struct MediaCache {
     client: Client,
     tracked_files: RefCell<HashMap<MediaCacheEntry, Entity<MediaFile>>>,
+    url_previews: RefCell<HashMap<String, Entity<UrlPreview>>>,
 }
 
 #[derive(Clone)]
@@ -119,6 +131,7 @@ impl MediaCache {
         Self {
             client: client.clone(),
             tracked_files: RefCell::new(HashMap::new()),
+            url_previews: RefCell::new(HashMap::new()),
         }
     }
 
@@ -159,6 +172,15 @@ impl MediaCache {
             .or_insert_with(|| MediaFile::new(self.client.clone(), media_source.clone(), false, cx))
             .to_owned()
     }
+
+    /// Get the preview of a URL, as generated by the homeserver
+    pub fn url_preview(&self, url: String, cx: &mut App) -> Entity<UrlPreview> {
+        self.url_previews
+            .borrow_mut()
+            .entry(url.clone())
+            .or_insert_with(|| UrlPreview::new(self.client.clone(), url, cx))
+            .to_owned()
+    }
 }
 
 pub struct MediaFile {
@@ -257,14 +279,7 @@ impl MediaFile {
                     return Err(anyhow!("Media file not loaded"));
                 };
 
-                let mut image = ImageReader::open(media_file.path())?
-                    .with_guessed_format()?
-                    .decode()?
-                    .into_rgba8();
-                rgb_to_bgr(&mut image);
-                let frame = Frame::new(image);
-
-                let arc = Arc::new(RenderImage::new(smallvec![frame]));
+                let arc = Arc::new(RenderImage::new(decode_frames(media_file.path())?));
                 *read_image = Arc::downgrade(&arc);
                 Ok(arc)
             }
@@ -273,6 +288,175 @@ impl MediaFile {
     }
 }
 
+/// The OpenGraph data that the homeserver returns for a URL
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct UrlPreviewData {
+    #[serde(rename = "og:title")]
+    pub title: Option<String>,
+    #[serde(rename = "og:description")]
+    pub description: Option<String>,
+    #[serde(rename = "og:site_name")]
+    pub site_name: Option<String>,
+    #[serde(rename = "og:image")]
+    pub image: Option<OwnedMxcUri>,
+    #[serde(rename = "og:image:width")]
+    pub image_width: Option<u64>,
+    #[serde(rename = "og:image:height")]
+    pub image_height: Option<u64>,
+}
+
+impl UrlPreviewData {
+    /// Whether there is anything worth showing in the preview
+    pub fn is_empty(&self) -> bool {
+        self.title.is_none() && self.description.is_none()
+    }
+}
+
+pub struct UrlPreview {
+    pub url: String,
+    pub preview_state: UrlPreviewState,
+}
+
+pub enum UrlPreviewState {
+    Loading,
+    Loaded(UrlPreviewData),
+    Failed,
+}
+
+impl UrlPreview {
+    fn new(client: Client, url: String, cx: &mut App) -> Entity<Self> {
+        cx.new(|cx| {
+            let request_url = url.clone();
+            cx.spawn(
+                async move |weak_this: WeakEntity<Self>, cx: &mut AsyncApp| {
+                    let response = cx
+                        .spawn_tokio(async move { fetch_url_preview(&client, request_url).await })
+                        .await;
+
+                    let preview_state = match response {
+                        Ok(data) => match data
+                            .map(|data| serde_json::from_str::<UrlPreviewData>(data.get()))
+                            .transpose()
+                        {
+                            Ok(data) => UrlPreviewState::Loaded(data.unwrap_or_default()),
+                            Err(e) => {
+                                error!("Unable to parse URL preview: {e:?}");
+                                UrlPreviewState::Failed
+                            }
+                        },
+                        Err(_) => UrlPreviewState::Failed,
+                    };
+
+                    let _ = weak_this.update(cx, |this, cx| {
+                        this.preview_state = preview_state;
+                        cx.notify()
+                    });
+                },
+            )
+            .detach();
+
+            Self {
+                url,
+                preview_state: UrlPreviewState::Loading,
+            }
+        })
+    }
+}
+
+/// Fetch the preview data for a URL, using authenticated media when the homeserver supports it
+#[allow(deprecated)]
+async fn fetch_url_preview(
+    client: &Client,
+    url: String,
+) -> anyhow::Result<Option<Box<RawValue>>> {
+    let supports_authenticated_media = client
+        .supported_versions()
+        .await?
+        .versions
+        .contains(&MatrixVersion::V1_11);
+
+    if supports_authenticated_media {
+        let request = authenticated_media::get_media_preview::v1::Request::new(url);
+        Ok(client.send(request).await?.data)
+    } else {
+        let request = media::get_media_preview::v3::Request::new(url);
+        Ok(client.send(request).await?.data)
+    }
+}
+
+/// Decode all of the frames of an image file.
+///
+/// Animated GIF and WebP images have one frame for each step of the animation, and every other
+/// image has a single frame.
+fn decode_frames(path: &Path) -> anyhow::Result<SmallVec<[Frame; 1]>> {
+    let reader = ImageReader::open(path)?.with_guessed_format()?;
+    let mut frames: SmallVec<[Frame; 1]> = match reader.format() {
+        Some(ImageFormat::Gif) => {
+            GifDecoder::new(BufReader::new(File::open(path)?))?
+                .into_frames()
+                .collect_frames()?
+                .into()
+        }
+        Some(ImageFormat::WebP) => {
+            let decoder = WebPDecoder::new(BufReader::new(File::open(path)?))?;
+            if decoder.has_animation() {
+                decoder.into_frames().collect_frames()?.into()
+            } else {
+                smallvec![Frame::new(reader.decode()?.into_rgba8())]
+            }
+        }
+        _ => smallvec![Frame::new(reader.decode()?.into_rgba8())],
+    };
+
+    for frame in frames.iter_mut() {
+        rgb_to_bgr(frame.buffer_mut());
+    }
+    Ok(frames)
+}
+
+#[derive(Debug)]
+pub enum VideoFrameError {
+    /// `ffmpeg` is not installed, so no video can be decoded
+    FfmpegMissing,
+    Failed(anyhow::Error),
+}
+
+/// Decode the first frame of a video file.
+///
+/// Decoding is done by the `ffmpeg` executable, which needs to be installed separately.
+pub async fn extract_video_frame(path: PathBuf) -> Result<RenderImage, VideoFrameError> {
+    let output = match Command::new("ffmpeg")
+        .arg("-v")
+        .arg("error")
+        .arg("-i")
+        .arg(path)
+        .args(["-frames:v", "1", "-f", "image2pipe", "-vcodec", "png", "-"])
+        .output()
+        .await
+    {
+        Ok(output) => output,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Err(VideoFrameError::FfmpegMissing),
+        Err(e) => return Err(VideoFrameError::Failed(e.into())),
+    };
+    if !output.status.success() || output.stdout.is_empty() {
+        return Err(VideoFrameError::Failed(anyhow!(
+            "Unable to decode the video frame: {}",
+            String::from_utf8_lossy(&output.stderr)
+        )));
+    }
+
+    decode_png_frame(output.stdout).map_err(VideoFrameError::Failed)
+}
+
+fn decode_png_frame(png: Vec<u8>) -> anyhow::Result<RenderImage> {
+    let mut image = ImageReader::new(Cursor::new(png))
+        .with_guessed_format()?
+        .decode()?
+        .into_rgba8();
+    rgb_to_bgr(&mut image);
+    Ok(RenderImage::new(smallvec![Frame::new(image)]))
+}
+
 fn rgb_to_bgr(image: &mut RgbaImage) {
     image.pixels_mut().for_each(|v| {
         let slice = v.channels();
@@ -0,0 +1,220 @@
+use crate::tokio_helper::TokioHelper;
+use gpui::http_client::anyhow;
+use gpui::private::anyhow;
+use gpui::{App, AsyncApp, Context, Task, WeakEntity};
+use matrix_sdk::Client;
+use matrix_sdk::notification_settings::{
+    IsEncrypted, IsOneToOne, NotificationSettings, RoomNotificationMode,
+};
+use matrix_sdk::ruma::push::{PredefinedOverrideRuleId, PredefinedUnderrideRuleId, RuleKind};
+use matrix_sdk::ruma::{OwnedRoomId, RoomId};
+use std::collections::HashMap;
+use tokio::sync::broadcast::error::RecvError;
+
+pub struct NotificationSettingsCache {
+    notification_settings: Option<NotificationSettings>,
+    state: NotificationSettingsState,
+}
+
+#[derive(Clone)]
+pub struct NotificationSettingsState {
+    pub notifications_enabled: bool,
+    pub group_mode: RoomNotificationMode,
+    pub direct_mode: RoomNotificationMode,
+    pub user_mentions: bool,
+    pub room_mentions: bool,
+    pub invites: bool,
+    pub calls: bool,
+    pub keywords: Vec<String>,
+    pub room_modes: HashMap<OwnedRoomId, RoomNotificationMode>,
+}
+
+impl Default for NotificationSettingsState {
+    fn default() -> Self {
+        Self {
+            notifications_enabled: true,
+            group_mode: RoomNotificationMode::MentionsAndKeywordsOnly,
+            direct_mode: RoomNotificationMode::AllMessages,
+            user_mentions: true,
+            room_mentions: true,
+            invites: true,
+            calls: true,
+            keywords: Vec::new(),
+            room_modes: HashMap::new(),
+        }
+    }
+}
+
+impl NotificationSettingsState {
+    async fn read(settings: &NotificationSettings) -> Self {
+        let is_enabled = async |kind: RuleKind, rule_id: &str| {
+            settings
+                .is_push_rule_enabled(kind, rule_id)
+                .await
+                .unwrap_or(true)
+        };
+
+        let mut room_modes = HashMap::new();
+        for room_id in settings.get_rooms_with_user_defined_rules(Some(true)).await {
+            let Ok(room_id) = RoomId::parse(room_id) else {
+                continue;
+            };
+
+            if let Some(mode) = settings
+                .get_user_defined_room_notification_mode(&room_id)
+                .await
+            {
+                room_modes.insert(room_id, mode);
+            }
+        }
+
+        Self {
+            // The master rule disables all notifications when it is enabled
+            notifications_enabled: !settings
+                .is_push_rule_enabled(RuleKind::Override, PredefinedOverrideRuleId::Master.as_str())
+                .await
+                .unwrap_or(false),
+            group_mode: settings
+                .get_default_room_notification_mode(IsEncrypted::No, IsOneToOne::No)
+                .await,
+            direct_mode: settings
+                .get_default_room_notification_mode(IsEncrypted::No, IsOneToOne::Yes)
+                .await,
+            user_mentions: is_enabled(
+                RuleKind::Override,
+                PredefinedOverrideRuleId::IsUserMention.as_str(),
+            )
+            .await,
+            room_mentions: is_enabled(
+                RuleKind::Override,
+                PredefinedOverrideRuleId::IsRoomMention.as_str(),
+            )
+            .await,
+            invites: is_enabled(
+                RuleKind::Override,
+                PredefinedOverrideRuleId::InviteForMe.as_str(),
+            )
+            .await,
+            calls: is_enabled(RuleKind::Underride, PredefinedUnderrideRuleId::Call.as_str()).await,
+            keywords: settings.enabled_keywords().await.into_iter().collect(),
+            room_modes,
+        }
+    }
+}
+
+impl NotificationSettingsCache {
+    pub fn new(client: &Client, cx: &mut Context<Self>) -> Self {
+        let client = client.clone();
+        cx.spawn(
+            async move |weak_this: WeakEntity<Self>, cx: &mut AsyncApp| {
+                let Ok((notification_settings, state)) = cx
+                    .spawn_tokio(async move {
+                        let notification_settings = client.notification_settings().await;
+                        let state = NotificationSettingsState::read(&notification_settings).await;
+                        Ok::<_, anyhow::Error>((notification_settings, state))
+                    })
+                    .await
+                else {
+                    return;
+                };
+
+                if weak_this
+                    .update(cx, |this, cx| {
+                        this.notification_settings = Some(notification_settings.clone());
+                        this.state = state;
+                        cx.notify()
+                    })
+                    .is_err()
+                {
+                    return;
+                }
+
+                let (tx_state, rx_state) = async_channel::bounded(1);
+                cx.spawn(async move |cx: &mut AsyncApp| {
+                    cx.spawn_tokio(async move {
+                        let mut changes = notification_settings.subscribe_to_changes();
+                        loop {
+                            match changes.recv().await {
+                                Ok(_) | Err(RecvError::Lagged(_)) => {
+                                    let state =
+                                        NotificationSettingsState::read(&notification_settings)
+                                            .await;
+                                    if tx_state.send(state).await.is_err() {
+                                        break;
+                                    }
+                                }
+                                Err(RecvError::Closed) => break,
+                            }
+                        }
+                        Ok::<_, anyhow::Error>(())
+                    })
+                    .await
+                })
+                .detach();
+
+                while let Ok(state) = rx_state.recv().await {
+                    if weak_this
+                        .update(cx, |this, cx| {
+                            this.state = state;
+                            cx.notify()
+                        })
+                        .is_err()
+                    {
+                        return;
+                    }
+                }
+            },
+        )
+        .detach();
+
+        Self {
+            notification_settings: None,
+            state: NotificationSettingsState::default(),
+        }
+    }
+
+    /// The push rule settings handle, or None if the push rules have not been loaded yet
+    pub fn notification_settings(&self) -> Option<NotificationSettings> {
+        self.notification_settings.clone()
+    }
+
+    pub fn state(&self) -> &NotificationSettingsState {
+        &self.state
+    }
+
+    /// The notification mode explicitly set for a room, if one has been set
+    pub fn room_mode(&self, room_id: &RoomId) -> Option<RoomNotificationMode> {
+        self.state.room_modes.get(room_id).cloned()
+    }
+
+    /// Set the notification mode for a room, or pass None to go back to the account defaults
+    pub fn set_room_mode(
+        &self,
+        room_id: OwnedRoomId,
+        mode: Option<RoomNotificationMode>,
+        cx: &mut App,
+    ) -> Task<anyhow::Result<()>> {
+        let Some(notification_settings) = self.notification_settings.clone() else {
+            return Task::ready(Err(anyhow!("Notification settings have not been loaded")));
+        };
+
+        cx.spawn(async move |cx: &mut AsyncApp| {
+            cx.spawn_tokio(async move {
+                match mode {
+                    Some(mode) => {
+                        notification_settings
+                            .set_room_notification_mode(&room_id, mode)
+                            .await
+                    }
+                    None => {
+                        notification_settings
+                            .delete_user_defined_room_rules(&room_id)
+                            .await
+                    }
+                }
+            })
+            .await
+            .map_err(|e| anyhow!(e))
+        })
+    }
+}
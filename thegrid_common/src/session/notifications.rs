@@ -1,4 +1,5 @@
 use crate::session::session_manager::SessionManager;
+use crate::sfx::SoundEffect;
 use cntp_i18n::{Quote, tr};
 use contemporary::notification::Notification;
 use gpui::App;
@@ -9,14 +10,24 @@ use matrix_sdk::ruma::events::room::member::MembershipState;
 use matrix_sdk::ruma::events::room::message::MessageType;
 use matrix_sdk::ruma::events::{
     AnyMessageLikeEventContent, AnyStateEventContent, AnyStrippedStateEvent, AnySyncTimelineEvent,
+    MessageLikeEventType,
 };
 use matrix_sdk::ruma::html::{HtmlSanitizerMode, RemoveReplyFallback};
+use matrix_sdk::ruma::push::Action;
 
 pub fn trigger_notification(
     notification: matrix_sdk::sync::Notification,
     room: Room,
     cx: &mut App,
 ) {
+    // The push rules decide whether this event should notify at all, and how loudly.
+    let actions = &notification.actions;
+    if !actions.iter().any(Action::should_notify) {
+        return;
+    }
+    let is_highlight = actions.iter().any(Action::is_highlight);
+    let play_sound = actions.iter().any(|action| action.sound().is_some());
+
     let session_manager = cx.global::<SessionManager>();
     let this_user = session_manager
         .client()
@@ -30,134 +41,133 @@ pub fn trigger_notification(
         return;
     };
     let room = room.read(cx);
+    if room.is_muted() {
+        return;
+    }
+    let room_display_name = room.display_name();
+
+    // Calls are announced by the incoming calls cache, which also decides whether to ring
+    if let RawAnySyncOrStrippedTimelineEvent::Sync(sync) = &notification.event
+        && let Ok(Some(MessageLikeEventType::CallNotify | MessageLikeEventType::RtcNotification)) =
+            sync.get_field::<MessageLikeEventType>("type")
+    {
+        return;
+    }
 
-    match notification.event {
+    let Some((summary, body)) = (match notification.event {
         RawAnySyncOrStrippedTimelineEvent::Sync(sync) => match sync.deserialize() {
-            Ok(AnySyncTimelineEvent::MessageLike(message_like)) => {
+            Ok(AnySyncTimelineEvent::MessageLike(message_like))
+                if *message_like.sender() != *this_user =>
+            {
                 if let Some(AnyMessageLikeEventContent::RoomMessage(mut message)) =
                     message_like.original_content()
                 {
                     message.sanitize(HtmlSanitizerMode::Compat, RemoveReplyFallback::Yes);
 
-                    let room_display_name = room.display_name();
-                    let summary = tr!(
-                        "NOTIFICATION_MESSAGE_SUMMARY",
-                        "{{sender}} in {{room}}",
-                        sender = message_like.sender().to_string(),
-                        room:Quote = room_display_name
-                    )
+                    let summary = if is_highlight {
+                        tr!(
+                            "NOTIFICATION_MESSAGE_SUMMARY_HIGHLIGHT",
+                            "{{sender}} mentioned you in {{room}}",
+                            sender = message_like.sender().to_string(),
+                            room:Quote = room_display_name
+                        )
+                    } else {
+                        tr!(
+                            "NOTIFICATION_MESSAGE_SUMMARY",
+                            "{{sender}} in {{room}}",
+                            sender = message_like.sender().to_string(),
+                            room:Quote = room_display_name
+                        )
+                    }
                     .to_string();
 
                     match message.msgtype {
-                        MessageType::Text(content) => {
-                            Notification::new()
-                                .summary(summary.as_str())
-                                .body(content.body.as_str())
-                                .post(cx);
-                        }
-                        MessageType::Image(content) => {
-                            Notification::new()
-                                .summary(summary.as_str())
-                                .body(
-                                    tr!("NOTIFICATION_MESSAGE_BODY_IMAGE", "sent an image")
-                                        .to_string()
-                                        .as_str(),
-                                )
-                                .post(cx);
-                        }
-                        MessageType::File(content) => {
-                            Notification::new()
-                                .summary(summary.as_str())
-                                .body(
-                                    tr!(
-                                        "NOTIFICATION_MESSAGE_BODY_FILE",
-                                        "sent {{filename}}",
-                                        filename = content.filename.unwrap_or_default()
-                                    )
-                                    .to_string()
-                                    .as_str(),
-                                )
-                                .post(cx);
-                        }
-                        MessageType::Audio(content) => {
-                            Notification::new()
-                                .summary(summary.as_str())
-                                .body(
-                                    tr!("NOTIFICATION_MESSAGE_BODY_AUDIO", "sent a voice message",)
-                                        .to_string()
-                                        .as_str(),
-                                )
-                                .post(cx);
-                        }
-                        MessageType::Video(content) => {
-                            Notification::new()
-                                .summary(summary.as_str())
-                                .body(
-                                    tr!("NOTIFICATION_MESSAGE_BODY_VIDEO", "sent a video",)
-                                        .to_string()
-                                        .as_str(),
-                                )
-                                .post(cx);
-                        }
-                        _ => {}
+                        MessageType::Text(content) => Some((summary, content.body)),
+                        MessageType::Notice(content) => Some((summary, content.body)),
+                        MessageType::Emote(content) => Some((
+                            summary,
+                            format!("* {} {}", message_like.sender(), content.body),
+                        )),
+                        MessageType::Image(_) => Some((
+                            summary,
+                            tr!("NOTIFICATION_MESSAGE_BODY_IMAGE", "sent an image").to_string(),
+                        )),
+                        MessageType::File(content) => Some((
+                            summary,
+                            tr!(
+                                "NOTIFICATION_MESSAGE_BODY_FILE",
+                                "sent {{filename}}",
+                                filename = content.filename.unwrap_or_default()
+                            )
+                            .to_string(),
+                        )),
+                        MessageType::Audio(_) => Some((
+                            summary,
+                            tr!("NOTIFICATION_MESSAGE_BODY_AUDIO", "sent a voice message",)
+                                .to_string(),
+                        )),
+                        MessageType::Video(_) => Some((
+                            summary,
+                            tr!("NOTIFICATION_MESSAGE_BODY_VIDEO", "sent a video",).to_string(),
+                        )),
+                        _ => None,
                     }
+                } else {
+                    None
                 }
             }
             Ok(AnySyncTimelineEvent::State(state_event)) => {
                 if let Some(AnyStateEventContent::RoomMember(room_member_event)) =
                     state_event.original_content()
+                    && room_member_event.membership == MembershipState::Invite
+                    && state_event.state_key() == this_user
                 {
-                    if room_member_event.membership == MembershipState::Invite
-                        && state_event.state_key() == this_user
-                    {
-                        let room_display_name = room.display_name();
-
-                        Notification::new()
-                            .summary(
-                                tr!("NOTIFICATION_INVITE_SUMMARY", "New room invitation")
-                                    .to_string()
-                                    .as_str(),
-                            )
-                            .body(
-                                tr!(
-                                    "NOTIFICATION_INVITE_BODY",
-                                    "{{user}} invited you to join {{room}}",
-                                    user = state_event.sender().to_string(),
-                                    room:Quote = room_display_name
-                                )
-                                .to_string()
-                                .as_str(),
-                            )
-                            .post(cx);
-                    }
+                    Some(invite_notification(
+                        state_event.sender().to_string(),
+                        room_display_name,
+                    ))
+                } else {
+                    None
                 }
             }
-            _ => {}
+            _ => None,
         },
         RawAnySyncOrStrippedTimelineEvent::Stripped(stripped) => {
             if let Ok(AnyStrippedStateEvent::RoomMember(room_member_event)) = stripped.deserialize()
+                && room_member_event.content.membership == MembershipState::Invite
+                && room_member_event.state_key == this_user
             {
-                if room_member_event.content.membership == MembershipState::Invite
-                    && room_member_event.state_key == this_user
-                {
-                    let room_display_name = room.display_name();
-
-                    Notification::new()
-                        .summary(tr!("NOTIFICATION_INVITE_SUMMARY").to_string().as_str())
-                        .body(
-                            tr!(
-                                "NOTIFICATION_INVITE_BODY",
-                                user = room_member_event.sender.to_string(),
-                                room:Quote = room_display_name
-                            )
-                            .to_string()
-                            .as_str(),
-                        )
-                        .post(cx);
-                }
+                Some(invite_notification(
+                    room_member_event.sender.to_string(),
+                    room_display_name,
+                ))
+            } else {
+                None
             }
         }
+    }) else {
+        return;
+    };
+
+    Notification::new()
+        .summary(summary.as_str())
+        .body(body.as_str())
+        .post(cx);
+
+    if play_sound {
+        SoundEffect::Notification.play();
     }
 }
 
-fn send_invite_notification() {}
+fn invite_notification(sender: String, room_display_name: String) -> (String, String) {
+    (
+        tr!("NOTIFICATION_INVITE_SUMMARY", "New room invitation").to_string(),
+        tr!(
+            "NOTIFICATION_INVITE_BODY",
+            "{{user}} invited you to join {{room}}",
+            user = sender,
+            room:Quote = room_display_name
+        )
+        .to_string(),
+    )
+}
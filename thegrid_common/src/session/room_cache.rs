@@ -1,15 +1,26 @@
+use crate::session::notification_settings_cache::NotificationSettingsCache;
 use crate::session::session_manager::SessionManager;
 use crate::tokio_helper::TokioHelper;
 use gpui::private::anyhow;
 use gpui::{App, AppContext, AsyncApp, AsyncWindowContext, Context, Entity, WeakEntity, Window};
 use imbl::Vector;
+use matrix_sdk::notification_settings::RoomNotificationMode;
 use matrix_sdk::room::{Invite, ParentSpace};
 use matrix_sdk::ruma::events::space::child::SpaceChildEventContent;
+use matrix_sdk::ruma::events::tag::{TagEvent, TagName, Tags};
 use matrix_sdk::ruma::{OwnedRoomId, OwnedRoomOrAliasId, RoomId};
 use matrix_sdk::Error;
 use matrix_sdk::{Client, OwnedServerName, Room, RoomState};
+use matrix_sdk_ui::room_list_service::filters::new_filter_non_left;
+use matrix_sdk_ui::room_list_service::RoomListService;
 use smol::stream::StreamExt;
 use std::collections::{HashMap, HashSet};
+use std::pin::pin;
+use std::sync::Arc;
+
+/// Rooms are filtered in the sidebar rather than by the room list service, so the whole room list
+/// is loaded in a single page.
+const ROOM_LIST_PAGE_SIZE: usize = 100_000;
 
 pub struct RoomCache {
     pub rooms: Entity<Vector<Room>>,
@@ -17,6 +28,8 @@ pub struct RoomCache {
     joined_rooms: Vec<Room>,
     space_rooms: Vec<Room>,
     joining_rooms: HashSet<OwnedRoomOrAliasId>,
+    room_list_service: Option<Arc<RoomListService>>,
+    room_list_positions: HashMap<OwnedRoomId, usize>,
 }
 
 pub enum RoomCategory {
@@ -24,17 +37,61 @@ pub enum RoomCategory {
     Space(OwnedRoomId),
 }
 
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum QuickFilter {
+    Unread,
+    Mentions,
+    DirectMessages,
+    Invites,
+}
+
 pub struct RoomJoinEvent {
     pub result: Result<Room, Error>,
 }
 
 impl RoomCache {
-    pub fn new(client: &Client, cx: &mut App) -> Entity<Self> {
+    pub fn new(
+        client: &Client,
+        room_list_service: Option<Arc<RoomListService>>,
+        notification_settings: &Entity<NotificationSettingsCache>,
+        cx: &mut App,
+    ) -> Entity<Self> {
         cx.new(|cx| {
             let rooms = cx.new(|_| Vector::new());
 
             let client = client.clone();
 
+            let (tx_tags, rx_tags) = async_channel::unbounded();
+            client.add_event_handler(move |event: TagEvent, room: Room| {
+                let tx_tags = tx_tags.clone();
+                async move {
+                    let _ = tx_tags
+                        .send((room.room_id().to_owned(), event.content.tags))
+                        .await;
+                }
+            });
+            cx.spawn(
+                async move |weak_this: WeakEntity<Self>, cx: &mut AsyncApp| {
+                    while let Ok((room_id, tags)) = rx_tags.recv().await {
+                        if weak_this
+                            .update(cx, |this, cx| {
+                                if let Some(room) = this.cached_rooms.get(&room_id) {
+                                    room.update(cx, |room, cx| {
+                                        room.tags = tags;
+                                        cx.notify();
+                                    });
+                                }
+                                cx.notify();
+                            })
+                            .is_err()
+                        {
+                            return;
+                        }
+                    }
+                },
+            )
+            .detach();
+
             let weak_rooms = rooms.downgrade();
             cx.spawn(
                 async move |weak_this: WeakEntity<Self>, cx: &mut AsyncApp| {
@@ -89,12 +146,67 @@ impl RoomCache {
             })
             .detach();
 
+            if let Some(room_list_service) = room_list_service.clone() {
+                cx.spawn(
+                    async move |weak_this: WeakEntity<Self>, cx: &mut AsyncApp| {
+                        let Ok(all_rooms) = cx
+                            .spawn_tokio(async move { room_list_service.all_rooms().await })
+                            .await
+                        else {
+                            return;
+                        };
+
+                        // The room list service keeps the rooms sorted by their latest activity
+                        let (entries_stream, entries_controller) =
+                            all_rooms.entries_with_dynamic_adapters(ROOM_LIST_PAGE_SIZE);
+                        entries_controller.set_filter(Box::new(new_filter_non_left()));
+                        let mut entries_stream = pin!(entries_stream);
+
+                        let mut entries = Vector::new();
+                        while let Some(diffs) =
+                            tokio::task::unconstrained(entries_stream.next()).await
+                        {
+                            for diff in diffs {
+                                diff.apply(&mut entries);
+                            }
+
+                            let room_list_positions = entries
+                                .iter()
+                                .enumerate()
+                                .map(|(position, room)| (room.room_id().to_owned(), position))
+                                .collect::<HashMap<_, _>>();
+                            if weak_this
+                                .update(cx, |this, cx| {
+                                    if this.room_list_positions != room_list_positions {
+                                        this.room_list_positions = room_list_positions;
+                                        cx.notify();
+                                    }
+                                })
+                                .is_err()
+                            {
+                                return;
+                            }
+                        }
+                    },
+                )
+                .detach();
+            }
+
+            cx.observe(notification_settings, |this: &mut Self, _, cx| {
+                for room in this.cached_rooms.values() {
+                    room.update(cx, |room, cx| room.sync_notification_mode(cx));
+                }
+            })
+            .detach();
+
             Self {
                 rooms,
                 cached_rooms: HashMap::new(),
                 joined_rooms: Vec::new(),
                 space_rooms: Vec::new(),
                 joining_rooms: HashSet::new(),
+                room_list_service,
+                room_list_positions: HashMap::new(),
             }
         })
     }
@@ -103,6 +215,27 @@ impl RoomCache {
         self.cached_rooms.get(room_id).cloned()
     }
 
+    /// Ask sliding sync for the full timeline of a room while it is open.
+    ///
+    /// Otherwise, sliding sync only sends the latest event of each room, which leaves gaps in the
+    /// open timeline. This does nothing when the classic sync loop is in use.
+    pub fn subscribe_to_room(&self, room_id: &RoomId, cx: &App) {
+        let Some(room_list_service) = self.room_list_service.clone() else {
+            return;
+        };
+
+        let room_id = room_id.to_owned();
+        cx.spawn(async move |cx: &mut AsyncApp| {
+            let _ = cx
+                .spawn_tokio(async move {
+                    room_list_service.subscribe_to_rooms(&[&room_id]).await;
+                    Ok::<_, anyhow::Error>(())
+                })
+                .await;
+        })
+        .detach();
+    }
+
     pub fn joined_rooms(&self) -> &Vec<Room> {
         &self.joined_rooms
     }
@@ -159,6 +292,53 @@ impl RoomCache {
         }
     }
 
+    /// Sort rooms so that rooms with unread activity come first, followed by the rest of the rooms
+    /// in order of their latest activity.
+    ///
+    /// The latest activity comes from the sliding sync room list service. If the homeserver
+    /// doesn't support sliding sync, rooms are sorted by name instead.
+    pub fn sort_rooms(&self, rooms: &mut [Entity<CachedRoom>], cx: &App) {
+        rooms.sort_by_cached_key(|room| {
+            let room = room.read(cx);
+            (
+                !room.has_unread_activity(cx),
+                self.room_list_positions
+                    .get(room.inner.room_id())
+                    .copied()
+                    .unwrap_or(usize::MAX),
+                room.display_name().to_lowercase(),
+            )
+        });
+    }
+
+    /// Find the rooms matching a quick filter and a search query, sorted by activity.
+    ///
+    /// Rooms inside spaces are included, and spaces themselves are only included when searching
+    /// by name.
+    pub fn filtered_rooms(
+        &self,
+        quick_filter: Option<QuickFilter>,
+        query: &str,
+        cx: &App,
+    ) -> Vec<Entity<CachedRoom>> {
+        let mut rooms = self
+            .cached_rooms
+            .values()
+            .filter(|room| {
+                let room = room.read(cx);
+                room.inner.state() != RoomState::Left
+                    && (quick_filter.is_none() || !room.inner.is_space())
+                    && quick_filter.is_none_or(|quick_filter| {
+                        room.matches_quick_filter(quick_filter, cx)
+                    })
+                    && fuzzy_match(&room.display_name(), query)
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        self.sort_rooms(&mut rooms, cx);
+        rooms
+    }
+
     pub fn join_room(
         &mut self,
         room_id: impl Into<OwnedRoomOrAliasId>,
@@ -208,11 +388,14 @@ pub struct CachedRoom {
     child_rooms: Vec<OwnedRoomId>,
     invite_details: Option<Invite>,
     is_direct: bool,
+    notification_mode: Option<RoomNotificationMode>,
+    tags: Tags,
 }
 
 #[derive(Default)]
 pub struct UnreadState {
     pub unread_notifications: u64,
+    pub unread_mentions: u64,
     pub unread_messages: u64,
 }
 
@@ -225,9 +408,12 @@ impl CachedRoom {
                 child_rooms: Vec::new(),
                 invite_details: None,
                 is_direct: false,
+                notification_mode: None,
+                tags: Tags::new(),
             };
 
             room.sync_changes(cx);
+            room.fetch_tags(cx);
 
             let (sync_changes_tx, sync_changes_rx) = async_channel::bounded(1);
 
@@ -355,6 +541,50 @@ impl CachedRoom {
             },
         )
         .detach();
+
+        self.sync_notification_mode(cx);
+    }
+
+    /// Loads the room's tags once; later changes arrive through the tag event handler
+    fn fetch_tags(&mut self, cx: &mut Context<Self>) {
+        let inner = self.inner.clone();
+        cx.spawn(
+            async move |weak_this: WeakEntity<Self>, cx: &mut AsyncApp| {
+                let Ok(tags) = cx.spawn_tokio(async move { inner.tags().await }).await else {
+                    return;
+                };
+
+                let _ = weak_this.update(cx, |this, cx| {
+                    this.tags = tags.unwrap_or_default();
+                    cx.notify();
+                });
+            },
+        )
+        .detach();
+    }
+
+    fn sync_notification_mode(&mut self, cx: &mut Context<Self>) {
+        let inner = self.inner.clone();
+        cx.spawn(
+            async move |weak_this: WeakEntity<Self>, cx: &mut AsyncApp| {
+                let Ok(notification_mode) = cx
+                    .spawn_tokio(async move {
+                        Ok::<_, anyhow::Error>(inner.notification_mode().await)
+                    })
+                    .await
+                else {
+                    return;
+                };
+
+                let _ = weak_this.update(cx, |this, cx| {
+                    if this.notification_mode != notification_mode {
+                        this.notification_mode = notification_mode;
+                        cx.notify();
+                    }
+                });
+            },
+        )
+        .detach();
     }
 
     pub fn invite_details(&self) -> Option<Invite> {
@@ -373,6 +603,56 @@ impl CachedRoom {
         self.is_direct
     }
 
+    /// All tags set on this room, including favourites and low priority
+    pub fn tags(&self) -> &Tags {
+        &self.tags
+    }
+
+    /// The user-defined `u.*` tags of this room, which make up the custom sidebar sections
+    pub fn user_tags(&self) -> Vec<TagName> {
+        self.tags
+            .keys()
+            .filter(|tag| matches!(tag, TagName::User(_)))
+            .cloned()
+            .collect()
+    }
+
+    /// The notification mode that applies to this room, taking the account defaults into account
+    pub fn notification_mode(&self) -> Option<RoomNotificationMode> {
+        self.notification_mode.clone()
+    }
+
+    pub fn is_muted(&self) -> bool {
+        self.notification_mode == Some(RoomNotificationMode::Mute)
+    }
+
+    /// Whether this room has unread activity that should bring it to the top of the room list
+    pub fn has_unread_activity(&self, cx: &App) -> bool {
+        if self.is_muted() {
+            return false;
+        }
+
+        let unread_state = self.unread_state(cx);
+        unread_state.unread_notifications > 0
+            || unread_state.unread_mentions > 0
+            || self.inner.is_marked_unread()
+    }
+
+    pub fn matches_quick_filter(&self, quick_filter: QuickFilter, cx: &App) -> bool {
+        match quick_filter {
+            QuickFilter::Unread => {
+                let unread_state = self.unread_state(cx);
+                self.inner.state() == RoomState::Joined
+                    && (unread_state.unread_messages > 0
+                        || unread_state.unread_notifications > 0
+                        || self.inner.is_marked_unread())
+            }
+            QuickFilter::Mentions => self.unread_state(cx).unread_mentions > 0,
+            QuickFilter::DirectMessages => self.is_direct,
+            QuickFilter::Invites => self.inner.state() == RoomState::Invited,
+        }
+    }
+
     pub fn unread_state(&self, cx: &App) -> UnreadState {
         if self.inner.is_space() {
             let session_manager = cx.global::<SessionManager>();
@@ -399,19 +679,38 @@ impl CachedRoom {
                 child_rooms.push(room.inner.clone())
             }
 
-            child_rooms.iter().filter(|room| !room.is_space()).fold(
-                UnreadState::default(),
-                |unread_state, room| UnreadState {
+            // Muted rooms shouldn't draw attention to the space they are in
+            child_rooms
+                .iter()
+                .filter(|room| !room.is_space())
+                .filter(|room| {
+                    !room_cache
+                        .room(room.room_id())
+                        .is_some_and(|room| room.read(cx).is_muted())
+                })
+                .fold(UnreadState::default(), |unread_state, room| UnreadState {
                     unread_notifications: unread_state.unread_notifications
                         + room.num_unread_notifications(),
+                    unread_mentions: unread_state.unread_mentions + room.num_unread_mentions(),
                     unread_messages: unread_state.unread_messages + room.num_unread_messages(),
-                },
-            )
+                })
         } else {
             UnreadState {
                 unread_messages: self.inner.num_unread_messages(),
+                unread_mentions: self.inner.num_unread_mentions(),
                 unread_notifications: self.inner.num_unread_notifications(),
             }
         }
     }
 }
+
+/// Check whether the characters of `query` appear in `text` in order, ignoring case and
+/// whitespace, so that "grd chat" matches "theGrid Chat".
+fn fuzzy_match(text: &str, query: &str) -> bool {
+    let mut text_chars = text.chars().flat_map(char::to_lowercase);
+    query
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .flat_map(char::to_lowercase)
+        .all(|query_char| text_chars.any(|text_char| text_char == query_char))
+}
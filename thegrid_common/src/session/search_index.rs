@@ -0,0 +1,442 @@
+use crate::tokio_helper::TokioHelper;
+use async_channel::Sender;
+use gpui::private::anyhow;
+use gpui::{AsyncApp, Context, WeakEntity};
+use log::warn;
+use matrix_sdk::deserialized_responses::TimelineEvent;
+use matrix_sdk::event_handler::RawEvent;
+use matrix_sdk::ruma::api::client::filter::RoomEventFilter;
+use matrix_sdk::ruma::api::client::search::search_events;
+use matrix_sdk::ruma::api::client::search::search_events::v3::{Categories, Criteria, OrderBy};
+use matrix_sdk::ruma::events::room::encrypted::{
+    EncryptedEventScheme, OriginalSyncRoomEncryptedEvent, SyncRoomEncryptedEvent,
+};
+use matrix_sdk::ruma::events::room::message::{
+    OriginalSyncRoomMessageEvent, Relation, SyncRoomMessageEvent,
+};
+use matrix_sdk::ruma::events::room::redaction::OriginalSyncRoomRedactionEvent;
+use matrix_sdk::ruma::events::{
+    AnyMessageLikeEvent, AnySyncMessageLikeEvent, AnySyncTimelineEvent, AnyTimelineEvent,
+    MessageLikeEvent,
+};
+use matrix_sdk::ruma::serde::Raw;
+use matrix_sdk::ruma::{MilliSecondsSinceUnixEpoch, OwnedEventId, OwnedRoomId, OwnedUserId, RoomId};
+use matrix_sdk::stream::StreamExt;
+use matrix_sdk::{Client, HttpError, Room};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// The maximum number of results returned from a single search
+const MAX_RESULTS: usize = 100;
+
+/// How many events to go back through in each room when building the index
+const MAX_BACKFILL_EVENTS: usize = 1000;
+
+const BACKFILL_BATCH_SIZE: u16 = 100;
+
+/// Events that could not be decrypted yet, keyed by the Megolm session they need
+type UndecryptedEvents =
+    Arc<Mutex<HashMap<String, Vec<(Room, Raw<OriginalSyncRoomEncryptedEvent>)>>>>;
+
+/// An in-memory index over the text of messages the client has seen.
+///
+/// Messages are indexed after decryption, so this also covers encrypted rooms, which the
+/// homeserver is unable to search. Events that can't be decrypted yet are indexed once their
+/// room key arrives.
+///
+/// When the index is created, it is filled in by going back through the history of each room, so
+/// messages from before this session can be found too.
+pub struct SearchIndex {
+    rooms: HashMap<OwnedRoomId, HashMap<OwnedEventId, IndexedMessage>>,
+
+    /// The newest edit seen for each message, keyed by the event it replaces.
+    ///
+    /// The index is backfilled from newest to oldest, so edits usually turn up before the message
+    /// they replace. They are kept here until it does.
+    edits: HashMap<OwnedEventId, Edit>,
+}
+
+struct Edit {
+    body: String,
+    timestamp: MilliSecondsSinceUnixEpoch,
+}
+
+#[derive(Clone)]
+pub struct IndexedMessage {
+    pub room_id: OwnedRoomId,
+    pub event_id: OwnedEventId,
+    pub sender: OwnedUserId,
+    pub body: String,
+    pub timestamp: MilliSecondsSinceUnixEpoch,
+}
+
+enum IndexUpdate {
+    Add(IndexedMessage),
+    Edit {
+        room_id: OwnedRoomId,
+        event_id: OwnedEventId,
+        body: String,
+        timestamp: MilliSecondsSinceUnixEpoch,
+    },
+    Remove {
+        room_id: OwnedRoomId,
+        event_id: OwnedEventId,
+    },
+}
+
+impl IndexUpdate {
+    fn from_message(room_id: OwnedRoomId, event: &OriginalSyncRoomMessageEvent) -> Self {
+        match &event.content.relates_to {
+            Some(Relation::Replacement(replacement)) => IndexUpdate::Edit {
+                room_id,
+                event_id: replacement.event_id.clone(),
+                body: replacement.new_content.msgtype.body().to_string(),
+                timestamp: event.origin_server_ts,
+            },
+            _ => IndexUpdate::Add(IndexedMessage {
+                room_id,
+                event_id: event.event_id.clone(),
+                sender: event.sender.clone(),
+                body: event.content.body().to_string(),
+                timestamp: event.origin_server_ts,
+            }),
+        }
+    }
+
+    /// Work out how to update the index for an event from the event cache.
+    ///
+    /// Events that are still encrypted are held on to until their room key arrives.
+    fn from_timeline_event(
+        room: &Room,
+        event: &TimelineEvent,
+        undecrypted_events: &UndecryptedEvents,
+    ) -> Option<Self> {
+        match event.raw().deserialize().ok()? {
+            AnySyncTimelineEvent::MessageLike(AnySyncMessageLikeEvent::RoomMessage(
+                SyncRoomMessageEvent::Original(event),
+            )) => Some(IndexUpdate::from_message(room.room_id().to_owned(), &event)),
+            AnySyncTimelineEvent::MessageLike(AnySyncMessageLikeEvent::RoomEncrypted(
+                SyncRoomEncryptedEvent::Original(encrypted_event),
+            )) => {
+                hold_undecrypted_event(
+                    room.clone(),
+                    &encrypted_event,
+                    Raw::from_json(event.raw().json().to_owned()),
+                    undecrypted_events,
+                );
+                None
+            }
+            _ => None,
+        }
+    }
+}
+
+fn hold_undecrypted_event(
+    room: Room,
+    event: &OriginalSyncRoomEncryptedEvent,
+    raw: Raw<OriginalSyncRoomEncryptedEvent>,
+    undecrypted_events: &UndecryptedEvents,
+) {
+    let EncryptedEventScheme::MegolmV1AesSha2(content) = &event.content.scheme else {
+        return;
+    };
+    undecrypted_events
+        .lock()
+        .unwrap()
+        .entry(content.session_id.clone())
+        .or_default()
+        .push((room, raw));
+}
+
+/// Index the events that were waiting for a room key as room keys arrive
+async fn index_decrypted_events(
+    client: Client,
+    undecrypted_events: UndecryptedEvents,
+    tx_update: Sender<IndexUpdate>,
+) {
+    let Some(mut room_keys) = client.encryption().room_keys_received_stream().await else {
+        return;
+    };
+
+    while let Some(room_keys) = room_keys.next().await {
+        let Ok(room_keys) = room_keys else {
+            continue;
+        };
+
+        for room_key in room_keys {
+            let events = undecrypted_events
+                .lock()
+                .unwrap()
+                .remove(&room_key.session_id)
+                .unwrap_or_default();
+            for (room, raw) in events {
+                let event = match room.decrypt_event(&raw, None).await {
+                    Ok(event) => event,
+                    Err(e) => {
+                        warn!("Unable to decrypt event for the search index: {e:?}");
+                        continue;
+                    }
+                };
+
+                let Some(update) =
+                    IndexUpdate::from_timeline_event(&room, &event, &undecrypted_events)
+                else {
+                    continue;
+                };
+                if tx_update.send(update).await.is_err() {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+impl SearchIndex {
+    pub fn new(client: &Client, cx: &mut Context<Self>) -> Self {
+        let (tx_update, rx_update) = async_channel::unbounded();
+
+        client.add_event_handler({
+            let tx_update = tx_update.clone();
+            move |event: OriginalSyncRoomMessageEvent, room: Room| {
+                let tx_update = tx_update.clone();
+                async move {
+                    let _ = tx_update
+                        .send(IndexUpdate::from_message(room.room_id().to_owned(), &event))
+                        .await;
+                }
+            }
+        });
+        let undecrypted_events = UndecryptedEvents::default();
+        client.add_event_handler({
+            let undecrypted_events = undecrypted_events.clone();
+            move |event: OriginalSyncRoomEncryptedEvent, room: Room, raw: RawEvent| {
+                hold_undecrypted_event(room, &event, Raw::from_json(raw.0), &undecrypted_events);
+                async {}
+            }
+        });
+        client.add_event_handler({
+            let tx_update = tx_update.clone();
+            move |event: OriginalSyncRoomRedactionEvent, room: Room| {
+                let tx_update = tx_update.clone();
+                async move {
+                    let Some(event_id) = event.redacts.or(event.content.redacts) else {
+                        return;
+                    };
+                    let _ = tx_update
+                        .send(IndexUpdate::Remove {
+                            room_id: room.room_id().to_owned(),
+                            event_id,
+                        })
+                        .await;
+                }
+            }
+        });
+
+        cx.spawn({
+            let client = client.clone();
+            let undecrypted_events = undecrypted_events.clone();
+            let tx_update = tx_update.clone();
+            async move |_, cx: &mut AsyncApp| {
+                let _: anyhow::Result<()> = cx
+                    .spawn_tokio(async move {
+                        index_decrypted_events(client, undecrypted_events, tx_update).await;
+                        Ok(())
+                    })
+                    .await;
+            }
+        })
+        .detach();
+
+        // Seed the index with the events held in the event cache, going back through the history
+        // of each room
+        let client = client.clone();
+        cx.spawn(async move |_, cx: &mut AsyncApp| {
+            let _: anyhow::Result<()> = cx
+                .spawn_tokio(async move {
+                    for room in client.joined_rooms() {
+                        let Ok((room_event_cache, _drop_handles)) = room.event_cache().await
+                        else {
+                            continue;
+                        };
+
+                        let mut events = room_event_cache.events().await.unwrap_or_default();
+                        let mut backfilled_events = 0;
+                        let mut reached_start = false;
+                        loop {
+                            for event in &events {
+                                let Some(update) = IndexUpdate::from_timeline_event(
+                                    &room,
+                                    event,
+                                    &undecrypted_events,
+                                ) else {
+                                    continue;
+                                };
+                                if tx_update.send(update).await.is_err() {
+                                    return Ok(());
+                                }
+                            }
+
+                            backfilled_events += events.len();
+                            if reached_start || backfilled_events >= MAX_BACKFILL_EVENTS {
+                                break;
+                            }
+
+                            let Ok(outcome) = room_event_cache
+                                .pagination()
+                                .run_backwards_once(BACKFILL_BATCH_SIZE)
+                                .await
+                            else {
+                                break;
+                            };
+                            events = outcome.events;
+                            reached_start = outcome.reached_start;
+                        }
+                    }
+
+                    Ok(())
+                })
+                .await;
+        })
+        .detach();
+
+        cx.spawn(
+            async move |weak_this: WeakEntity<Self>, cx: &mut AsyncApp| {
+                while let Ok(update) = rx_update.recv().await {
+                    if weak_this
+                        .update(cx, |this, _| this.apply_update(update))
+                        .is_err()
+                    {
+                        return;
+                    }
+                }
+            },
+        )
+        .detach();
+
+        Self {
+            rooms: HashMap::new(),
+            edits: HashMap::new(),
+        }
+    }
+
+    fn apply_update(&mut self, update: IndexUpdate) {
+        match update {
+            IndexUpdate::Add(mut message) => {
+                if let Some(edit) = self.edits.get(&message.event_id) {
+                    message.body = edit.body.clone();
+                }
+                self.rooms
+                    .entry(message.room_id.clone())
+                    .or_default()
+                    .insert(message.event_id.clone(), message);
+            }
+            IndexUpdate::Edit {
+                room_id,
+                event_id,
+                body,
+                timestamp,
+            } => {
+                // Edits can arrive in any order, so only the newest one counts
+                if self
+                    .edits
+                    .get(&event_id)
+                    .is_some_and(|edit| edit.timestamp > timestamp)
+                {
+                    return;
+                }
+
+                if let Some(message) = self
+                    .rooms
+                    .get_mut(&room_id)
+                    .and_then(|messages| messages.get_mut(&event_id))
+                {
+                    message.body = body.clone();
+                }
+                self.edits.insert(event_id, Edit { body, timestamp });
+            }
+            IndexUpdate::Remove { room_id, event_id } => {
+                if let Some(messages) = self.rooms.get_mut(&room_id) {
+                    messages.remove(&event_id);
+                }
+                self.edits.remove(&event_id);
+            }
+        }
+    }
+
+    /// Search the indexed messages, optionally only in a single room.
+    ///
+    /// A message matches when it contains every word of the query, ignoring case. Results are
+    /// ordered from newest to oldest.
+    pub fn search(&self, room_id: Option<&RoomId>, query: &str) -> Vec<IndexedMessage> {
+        let terms = query
+            .split_whitespace()
+            .map(|term| term.to_lowercase())
+            .collect::<Vec<_>>();
+        if terms.is_empty() {
+            return Vec::new();
+        }
+
+        let mut results = self
+            .rooms
+            .iter()
+            .filter(|(indexed_room_id, _)| {
+                room_id.is_none_or(|room_id| **indexed_room_id == *room_id)
+            })
+            .flat_map(|(_, messages)| messages.values())
+            .filter(|message| {
+                let body = message.body.to_lowercase();
+                terms.iter().all(|term| body.contains(term))
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+
+        results.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+        results.truncate(MAX_RESULTS);
+        results
+    }
+}
+
+/// Search messages on the homeserver.
+///
+/// The homeserver can only search unencrypted rooms, so this should only be used for rooms
+/// without encryption enabled.
+pub async fn search_server(
+    client: &Client,
+    room_ids: Vec<OwnedRoomId>,
+    query: String,
+) -> Result<Vec<IndexedMessage>, HttpError> {
+    let mut filter = RoomEventFilter::default();
+    filter.rooms = Some(room_ids);
+
+    let mut criteria = Criteria::new(query);
+    criteria.filter = filter;
+    criteria.order_by = Some(OrderBy::Recent);
+
+    let mut categories = Categories::new();
+    categories.room_events = Some(criteria);
+
+    let response = client
+        .send(search_events::v3::Request::new(categories))
+        .await?;
+
+    Ok(response
+        .search_categories
+        .room_events
+        .results
+        .into_iter()
+        .filter_map(|result| result.result?.deserialize().ok())
+        .filter_map(|event| match event {
+            AnyTimelineEvent::MessageLike(AnyMessageLikeEvent::RoomMessage(
+                MessageLikeEvent::Original(event),
+            )) => Some(IndexedMessage {
+                room_id: event.room_id,
+                event_id: event.event_id,
+                sender: event.sender,
+                body: event.content.body().to_string(),
+                timestamp: event.origin_server_ts,
+            }),
+            _ => None,
+        })
+        .take(MAX_RESULTS)
+        .collect())
+}
@@ -3,13 +3,23 @@ use crate::session::caches::Caches;
 use crate::session::capability_cache::CapabilityCache;
 use crate::session::database_secret::{DatabaseSecret, DatabaseSecretExt};
 use crate::session::devices_cache::DevicesCache;
-use crate::session::error_handling::{ClientError, TerminalClientError, handle_error};
+use crate::session::drafts_cache::DraftsCache;
+use crate::session::error_handling::{
+    ClientError, RecoverableClientError, TerminalClientError, handle_error,
+};
+use crate::session::identities_cache::IdentitiesCache;
 use crate::session::ignored_users_cache::IgnoredUsersCache;
+use crate::session::incoming_calls_cache::IncomingCallsCache;
 use crate::session::media_cache::MediaCache;
+use crate::session::notification_settings_cache::NotificationSettingsCache;
 use crate::session::notifications::trigger_notification;
 use crate::session::room_cache::RoomCache;
+use crate::session::search_index::SearchIndex;
+use crate::session::image_packs_cache::ImagePacksCache;
+use crate::session::sidebar_sections_cache::SidebarSectionsCache;
 use crate::session::spaces_cache::SpacesCache;
 use crate::session::sso_login::SsoLogin;
+use crate::session::url_previews_cache::UrlPreviewsCache;
 use crate::session::verification_requests_cache::VerificationRequestsCache;
 use crate::tokio_helper::TokioHelper;
 use base64::Engine;
@@ -29,9 +39,11 @@ use matrix_sdk::ruma::OwnedUserId;
 use matrix_sdk::ruma::api::client::discovery::discover_homeserver::RtcFocusInfo;
 use matrix_sdk::ruma::api::error::FromHttpResponseError;
 use matrix_sdk::ruma::events::key::verification::request::ToDeviceKeyVerificationRequestEvent;
+use matrix_sdk::sliding_sync::Version as SlidingSyncVersion;
 use matrix_sdk::store::RoomLoadSettings;
+use matrix_sdk::stream::StreamExt;
 use matrix_sdk::sync::Notification;
-use matrix_sdk::{Client, Error, HttpError, LoopCtrl, Room, RumaApiError};
+use matrix_sdk::{Client, Error, HttpError, LoopCtrl, Room, RumaApiError, SessionChange};
 use matrix_sdk_ui::spaces::{SpaceRoomList, SpaceService};
 use matrix_sdk_ui::sync_service::{State, SyncService};
 use std::cell::RefCell;
@@ -289,6 +301,77 @@ impl SessionManager {
         })
         .detach();
 
+        // Use sliding sync when the homeserver supports it, so that the room list service can keep
+        // rooms sorted by their latest activity. Homeservers without native sliding sync fall back
+        // to the classic sync loop, and the room list is then sorted by name (see
+        // `RoomCache::sort_rooms`).
+        let sync_service = cx
+            .spawn_tokio({
+                let client = client.clone();
+                async move {
+                    let sliding_sync_available = client
+                        .available_sliding_sync_versions()
+                        .await
+                        .iter()
+                        .any(|version| matches!(version, SlidingSyncVersion::Native));
+                    if !sliding_sync_available {
+                        return Ok::<_, anyhow::Error>(None);
+                    }
+
+                    client.set_sliding_sync_version(SlidingSyncVersion::Native);
+                    let sync_service = SyncService::builder(client)
+                        .with_offline_mode()
+                        .build()
+                        .await?;
+                    Ok(Some(Arc::new(sync_service)))
+                }
+            })
+            .await
+            .unwrap_or_else(|e| {
+                error!("Unable to set up sliding sync: {e:?}");
+                None
+            });
+
+        match &sync_service {
+            Some(sync_service) => Self::run_sync_service(uuid, sync_service.clone(), &client, cx),
+            None => Self::run_classic_sync(client.clone(), cx),
+        }
+
+        let room_list_service = sync_service
+            .as_ref()
+            .map(|sync_service| sync_service.room_list_service());
+        let caches = Caches::new(&client, room_list_service, cx).await;
+        cx.update_global::<Self, ()>({
+            let client = client.clone();
+            |session_manager, cx| {
+                session_manager.current_caches = Some(caches);
+                session_manager.current_session_client = Some(cx.new(|_| client));
+                session_manager.is_new_account = user_identity.is_none();
+            }
+        });
+
+        cx.spawn({
+            let client = client.clone();
+            async move |cx: &mut AsyncApp| {
+                let rtc_foci = cx
+                    .spawn_tokio(async move {
+                        let _ = client.reset_well_known().await;
+                        client.rtc_foci().await
+                    })
+                    .await;
+
+                cx.update_global::<Self, ()>(|session_manager, _| {
+                    session_manager.current_caches.as_mut().unwrap().rtc_foci =
+                        rtc_foci.unwrap_or_default();
+                });
+            }
+        })
+        .detach();
+
+        Ok(())
+    }
+
+    fn run_classic_sync(client: Client, cx: &mut AsyncApp) {
         let (tx_clear_error, rx_clear_error) = async_channel::bounded(1);
         cx.spawn(async move |cx: &mut AsyncApp| {
             loop {
@@ -346,36 +429,94 @@ impl SessionManager {
             }
         })
         .detach();
+    }
 
-        let caches = Caches::new(&client, cx).await;
-        cx.update_global::<Self, ()>({
-            let client = client.clone();
-            |session_manager, cx| {
-                session_manager.current_caches = Some(caches);
-                session_manager.current_session_client = Some(cx.new(|_| client));
-                session_manager.is_new_account = user_identity.is_none();
+    fn run_sync_service(
+        uuid: Uuid,
+        sync_service: Arc<SyncService>,
+        client: &Client,
+        cx: &mut AsyncApp,
+    ) {
+        let mut session_changes = client.subscribe_to_session_changes();
+        cx.spawn(async move |cx: &mut AsyncApp| {
+            while let Ok(session_change) = session_changes.recv().await {
+                if let SessionChange::UnknownToken { .. } = session_change {
+                    cx.update_global::<Self, ()>(|session_manager, _| {
+                        session_manager.current_client_error =
+                            ClientError::Terminal(TerminalClientError::UnknownToken);
+                    });
+                }
             }
-        });
+        })
+        .detach();
 
-        cx.spawn({
-            let client = client.clone();
-            async move |cx: &mut AsyncApp| {
-                let rtc_foci = cx
-                    .spawn_tokio(async move {
-                        let _ = client.reset_well_known().await;
-                        client.rtc_foci().await
-                    })
-                    .await;
+        cx.spawn(async move |cx: &mut AsyncApp| {
+            let mut states = sync_service.state();
+            let sync_service_clone = sync_service.clone();
+            let _ = cx
+                .spawn_tokio(async move {
+                    sync_service_clone.start().await;
+                    Ok::<_, anyhow::Error>(())
+                })
+                .await;
+
+            while let Some(state) = states.next().await {
+                match state {
+                    State::Idle => {}
+                    State::Running => {
+                        cx.update_global::<Self, ()>(|session_manager, _| {
+                            if let ClientError::Recoverable(_) =
+                                session_manager.current_client_error
+                            {
+                                session_manager.current_client_error = ClientError::None;
+                            }
+                        });
+                    }
+                    State::Offline => {
+                        cx.update_global::<Self, ()>(|session_manager, _| {
+                            session_manager.current_client_error = ClientError::Recoverable(
+                                RecoverableClientError::HomeserverUnavailable,
+                            );
+                        });
+                    }
+                    _ => {
+                        // The sync service stops when it runs into an error, so start it again
+                        // after a moment, unless the session has gone away in the meantime
+                        error!("Sync service stopped: {state:?}");
+                        cx.background_executor().timer(Duration::from_secs(5)).await;
+
+                        let session_ended = cx.read_global::<Self, _>(|session_manager, _| {
+                            session_manager
+                                .current_session
+                                .as_ref()
+                                .is_none_or(|session| session.uuid != uuid)
+                                || matches!(
+                                    session_manager.current_client_error,
+                                    ClientError::Terminal(_)
+                                )
+                        });
+                        if session_ended {
+                            let _ = cx
+                                .spawn_tokio(async move {
+                                    sync_service.stop().await;
+                                    Ok::<_, anyhow::Error>(())
+                                })
+                                .await;
+                            return;
+                        }
 
-                cx.update_global::<Self, ()>(|session_manager, _| {
-                    session_manager.current_caches.as_mut().unwrap().rtc_foci =
-                        rtc_foci.unwrap_or_default();
-                });
+                        let sync_service = sync_service.clone();
+                        let _ = cx
+                            .spawn_tokio(async move {
+                                sync_service.start().await;
+                                Ok::<_, anyhow::Error>(())
+                            })
+                            .await;
+                    }
+                }
             }
         })
         .detach();
-
-        Ok(())
     }
 
     pub fn clear_session(&mut self) {
@@ -413,6 +554,14 @@ impl SessionManager {
         self.current_caches.as_ref().unwrap().devices_cache.clone()
     }
 
+    pub fn identities(&self) -> Entity<IdentitiesCache> {
+        self.current_caches
+            .as_ref()
+            .unwrap()
+            .identities_cache
+            .clone()
+    }
+
     pub fn media(&self) -> &MediaCache {
         &self.current_caches.as_ref().unwrap().media_cache
     }
@@ -429,6 +578,54 @@ impl SessionManager {
             .clone()
     }
 
+    pub fn notification_settings(&self) -> Entity<NotificationSettingsCache> {
+        self.current_caches
+            .as_ref()
+            .unwrap()
+            .notification_settings_cache
+            .clone()
+    }
+
+    pub fn search_index(&self) -> Entity<SearchIndex> {
+        self.current_caches.as_ref().unwrap().search_index.clone()
+    }
+
+    pub fn sidebar_sections(&self) -> Entity<SidebarSectionsCache> {
+        self.current_caches
+            .as_ref()
+            .unwrap()
+            .sidebar_sections_cache
+            .clone()
+    }
+
+    pub fn image_packs(&self) -> Entity<ImagePacksCache> {
+        self.current_caches
+            .as_ref()
+            .unwrap()
+            .image_packs_cache
+            .clone()
+    }
+
+    pub fn url_previews(&self) -> Entity<UrlPreviewsCache> {
+        self.current_caches
+            .as_ref()
+            .unwrap()
+            .url_previews_cache
+            .clone()
+    }
+
+    pub fn drafts(&self) -> Entity<DraftsCache> {
+        self.current_caches.as_ref().unwrap().drafts_cache.clone()
+    }
+
+    pub fn incoming_calls(&self) -> Entity<IncomingCallsCache> {
+        self.current_caches
+            .as_ref()
+            .unwrap()
+            .incoming_calls_cache
+            .clone()
+    }
+
     pub fn rtc_foci(&self) -> &Vec<RtcFocusInfo> {
         &self.current_caches.as_ref().unwrap().rtc_foci
     }
@@ -0,0 +1,401 @@
+use crate::session::room_cache::{CachedRoom, RoomCache};
+use crate::tokio_helper::TokioHelper;
+use gpui::http_client::anyhow;
+use gpui::private::anyhow;
+use gpui::{App, AsyncApp, Context, Entity, Task, WeakEntity};
+use matrix_sdk::ruma::RoomId;
+use matrix_sdk::ruma::events::GlobalAccountDataEvent;
+use matrix_sdk::ruma::events::macros::EventContent;
+use matrix_sdk::ruma::events::tag::{TagInfo, TagName};
+use matrix_sdk::{Client, Room, RoomState};
+use serde::{Deserialize, Serialize};
+
+/// The order of the user's sidebar sections.
+///
+/// Sidebar sections are stored as `u.*` room tags so that they round-trip with other clients, but
+/// room tags have no order relative to each other, so the order of the sections themselves is kept
+/// in account data.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, EventContent)]
+#[ruma_event(type = "com.vicr123.thegrid.sidebar_sections", kind = GlobalAccountData)]
+pub struct SidebarSectionsEventContent {
+    pub order: Vec<String>,
+}
+
+pub struct SidebarSectionsCache {
+    client: Client,
+    room_cache: Entity<RoomCache>,
+    section_order: Vec<String>,
+}
+
+enum TagChange {
+    Set(TagName, TagInfo),
+    Remove(TagName),
+}
+
+impl SidebarSectionsCache {
+    pub fn new(client: &Client, room_cache: &Entity<RoomCache>, cx: &mut Context<Self>) -> Self {
+        cx.observe(room_cache, |_, _, cx| cx.notify()).detach();
+
+        let (tx_order, rx_order) = async_channel::unbounded();
+        client.add_event_handler(
+            move |event: GlobalAccountDataEvent<SidebarSectionsEventContent>| {
+                let tx_order = tx_order.clone();
+                async move {
+                    let _ = tx_order.send(event.content.order).await;
+                }
+            },
+        );
+
+        let client_clone = client.clone();
+        cx.spawn(
+            async move |weak_this: WeakEntity<Self>, cx: &mut AsyncApp| {
+                if let Ok(Some(sidebar_sections)) = cx
+                    .spawn_tokio(async move {
+                        client_clone
+                            .account()
+                            .account_data::<SidebarSectionsEventContent>()
+                            .await
+                    })
+                    .await
+                    && let Ok(sidebar_sections) = sidebar_sections.deserialize()
+                    && weak_this
+                        .update(cx, |this, cx| {
+                            this.section_order = sidebar_sections.order;
+                            cx.notify()
+                        })
+                        .is_err()
+                {
+                    return;
+                }
+
+                while let Ok(section_order) = rx_order.recv().await {
+                    if weak_this
+                        .update(cx, |this, cx| {
+                            this.section_order = section_order;
+                            cx.notify()
+                        })
+                        .is_err()
+                    {
+                        return;
+                    }
+                }
+            },
+        )
+        .detach();
+
+        Self {
+            client: client.clone(),
+            room_cache: room_cache.clone(),
+            section_order: Vec::new(),
+        }
+    }
+
+    /// The user's sidebar sections, in the order that they should be displayed.
+    ///
+    /// A section only exists while at least one room is tagged with it.
+    pub fn sections(&self, cx: &App) -> Vec<TagName> {
+        let mut sections = Vec::new();
+        for room in self.room_cache.read(cx).cached_rooms() {
+            let room = room.read(cx);
+            if room.inner.state() == RoomState::Left {
+                continue;
+            }
+
+            for tag in room.user_tags() {
+                if !sections.contains(&tag) {
+                    sections.push(tag);
+                }
+            }
+        }
+
+        sections.sort_by_cached_key(|section| {
+            (
+                self.section_order
+                    .iter()
+                    .position(|ordered| ordered == section.as_ref())
+                    .unwrap_or(usize::MAX),
+                section_name(section).to_lowercase(),
+            )
+        });
+        sections
+    }
+
+    /// The rooms in a sidebar section, ordered by the `order` of their tag. Rooms without an
+    /// explicit order are placed at the end, sorted by activity.
+    pub fn rooms_in_section(&self, section: &TagName, cx: &App) -> Vec<Entity<CachedRoom>> {
+        let room_cache = self.room_cache.read(cx);
+        let mut rooms = room_cache
+            .cached_rooms()
+            .into_iter()
+            .filter(|room| {
+                let room = room.read(cx);
+                room.inner.state() != RoomState::Left && room.tags().contains_key(section)
+            })
+            .collect::<Vec<_>>();
+
+        room_cache.sort_rooms(&mut rooms, cx);
+        rooms.sort_by(|first, second| {
+            let first = tag_order(first.read(cx), section).unwrap_or(f64::INFINITY);
+            let second = tag_order(second.read(cx), section).unwrap_or(f64::INFINITY);
+            first.total_cmp(&second)
+        });
+        rooms
+    }
+
+    pub fn create_section(
+        &mut self,
+        name: &str,
+        room: Room,
+        cx: &mut Context<Self>,
+    ) -> Task<anyhow::Result<()>> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Task::ready(Err(anyhow!("Section name is empty")));
+        }
+
+        let section = TagName::from(format!("u.{name}"));
+        if !self
+            .section_order
+            .iter()
+            .any(|ordered| ordered == section.as_ref())
+        {
+            self.section_order.push(section.as_ref().to_string());
+        }
+        cx.notify();
+
+        let mut tag_info = TagInfo::new();
+        tag_info.order = Some(0.5);
+
+        let save_order = self.save_section_order(cx);
+        let set_tag = apply_tag_changes(vec![(room, TagChange::Set(section, tag_info))], cx);
+        cx.spawn(async move |_, _| {
+            save_order.await?;
+            set_tag.await
+        })
+    }
+
+    pub fn rename_section(
+        &mut self,
+        section: &TagName,
+        name: &str,
+        cx: &mut Context<Self>,
+    ) -> Task<anyhow::Result<()>> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Task::ready(Err(anyhow!("Section name is empty")));
+        }
+
+        let new_section = TagName::from(format!("u.{name}"));
+        if new_section == *section {
+            return Task::ready(Ok(()));
+        }
+
+        let sections = self.sections(cx);
+        self.section_order = sections
+            .iter()
+            .map(|ordered| {
+                if ordered == section {
+                    new_section.as_ref().to_string()
+                } else {
+                    ordered.as_ref().to_string()
+                }
+            })
+            .collect();
+        cx.notify();
+
+        // Keep the order of each room so that it carries over into the renamed section
+        let changes = self
+            .rooms_in_section(section, cx)
+            .into_iter()
+            .flat_map(|room| {
+                let room = room.read(cx);
+                let tag_info = room.tags().get(section).cloned().unwrap_or_default();
+                [
+                    (
+                        room.inner.clone(),
+                        TagChange::Set(new_section.clone(), tag_info),
+                    ),
+                    (room.inner.clone(), TagChange::Remove(section.clone())),
+                ]
+            })
+            .collect();
+
+        let save_order = self.save_section_order(cx);
+        let change_tags = apply_tag_changes(changes, cx);
+        cx.spawn(async move |_, _| {
+            change_tags.await?;
+            save_order.await
+        })
+    }
+
+    pub fn delete_section(
+        &mut self,
+        section: &TagName,
+        cx: &mut Context<Self>,
+    ) -> Task<anyhow::Result<()>> {
+        self.section_order
+            .retain(|ordered| ordered != section.as_ref());
+        cx.notify();
+
+        let changes = self
+            .rooms_in_section(section, cx)
+            .into_iter()
+            .map(|room| {
+                (
+                    room.read(cx).inner.clone(),
+                    TagChange::Remove(section.clone()),
+                )
+            })
+            .collect();
+
+        let save_order = self.save_section_order(cx);
+        let remove_tags = apply_tag_changes(changes, cx);
+        cx.spawn(async move |_, _| {
+            remove_tags.await?;
+            save_order.await
+        })
+    }
+
+    /// Move a section up (negative offset) or down (positive offset) in the sidebar
+    pub fn move_section(
+        &mut self,
+        section: &TagName,
+        offset: isize,
+        cx: &mut Context<Self>,
+    ) -> Task<anyhow::Result<()>> {
+        let mut sections = self.sections(cx);
+        let Some(index) = sections.iter().position(|ordered| ordered == section) else {
+            return Task::ready(Ok(()));
+        };
+
+        let new_index = index.saturating_add_signed(offset).min(sections.len() - 1);
+        let section = sections.remove(index);
+        sections.insert(new_index, section);
+
+        self.section_order = sections
+            .iter()
+            .map(|section| section.as_ref().to_string())
+            .collect();
+        cx.notify();
+
+        self.save_section_order(cx)
+    }
+
+    pub fn add_room(
+        &self,
+        section: &TagName,
+        room: Room,
+        cx: &mut Context<Self>,
+    ) -> Task<anyhow::Result<()>> {
+        // New rooms go to the end of the section, keeping the order between 0 and 1 as the
+        // specification recommends
+        let last_order = self
+            .rooms_in_section(section, cx)
+            .iter()
+            .filter_map(|room| tag_order(room.read(cx), section))
+            .fold(0., f64::max);
+
+        let mut tag_info = TagInfo::new();
+        tag_info.order = Some((last_order + 1.) / 2.);
+
+        apply_tag_changes(vec![(room, TagChange::Set(section.clone(), tag_info))], cx)
+    }
+
+    pub fn remove_room(
+        &self,
+        section: &TagName,
+        room: Room,
+        cx: &mut Context<Self>,
+    ) -> Task<anyhow::Result<()>> {
+        apply_tag_changes(vec![(room, TagChange::Remove(section.clone()))], cx)
+    }
+
+    /// Move a room up (negative offset) or down (positive offset) within a section
+    pub fn move_room(
+        &self,
+        section: &TagName,
+        room_id: &RoomId,
+        offset: isize,
+        cx: &mut Context<Self>,
+    ) -> Task<anyhow::Result<()>> {
+        let mut rooms = self.rooms_in_section(section, cx);
+        let Some(index) = rooms
+            .iter()
+            .position(|room| room.read(cx).inner.room_id() == room_id)
+        else {
+            return Task::ready(Ok(()));
+        };
+
+        let new_index = index.saturating_add_signed(offset).min(rooms.len() - 1);
+        let room = rooms.remove(index);
+        rooms.insert(new_index, room);
+
+        // Spread the rooms evenly between 0 and 1, only updating the tags that changed
+        let count = rooms.len() as f64;
+        let changes = rooms
+            .iter()
+            .enumerate()
+            .filter_map(|(index, room)| {
+                let room = room.read(cx);
+                let order = (index as f64 + 1.) / (count + 1.);
+                if tag_order(room, section) == Some(order) {
+                    return None;
+                }
+
+                let mut tag_info = room.tags().get(section).cloned().unwrap_or_default();
+                tag_info.order = Some(order);
+                Some((
+                    room.inner.clone(),
+                    TagChange::Set(section.clone(), tag_info),
+                ))
+            })
+            .collect();
+
+        apply_tag_changes(changes, cx)
+    }
+
+    fn save_section_order(&self, cx: &mut Context<Self>) -> Task<anyhow::Result<()>> {
+        let client = self.client.clone();
+        let content = SidebarSectionsEventContent {
+            order: self.section_order.clone(),
+        };
+        cx.spawn(async move |_, cx: &mut AsyncApp| {
+            cx.spawn_tokio(async move { client.account().set_account_data(content).await })
+                .await
+                .map(|_| ())
+                .map_err(|e| anyhow!(e))
+        })
+    }
+}
+
+/// The user visible name of a sidebar section
+pub fn section_name(section: &TagName) -> &str {
+    section
+        .as_ref()
+        .strip_prefix("u.")
+        .unwrap_or(section.as_ref())
+}
+
+fn tag_order(room: &CachedRoom, section: &TagName) -> Option<f64> {
+    room.tags().get(section).and_then(|tag_info| tag_info.order)
+}
+
+fn apply_tag_changes(changes: Vec<(Room, TagChange)>, cx: &mut App) -> Task<anyhow::Result<()>> {
+    cx.spawn(async move |cx: &mut AsyncApp| {
+        cx.spawn_tokio(async move {
+            for (room, change) in changes {
+                match change {
+                    TagChange::Set(tag, tag_info) => {
+                        room.set_tag(tag, tag_info).await?;
+                    }
+                    TagChange::Remove(tag) => {
+                        room.remove_tag(tag).await?;
+                    }
+                }
+            }
+            Ok::<_, matrix_sdk::HttpError>(())
+        })
+        .await
+        .map_err(|e| anyhow!(e))
+    })
+}
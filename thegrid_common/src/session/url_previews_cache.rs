@@ -0,0 +1,216 @@
+use crate::tokio_helper::TokioHelper;
+use gpui::http_client::anyhow;
+use gpui::private::anyhow;
+use gpui::{AsyncApp, Context, Task, WeakEntity};
+use matrix_sdk::ruma::events::macros::EventContent;
+use matrix_sdk::ruma::events::{GlobalAccountDataEvent, RoomAccountDataEvent};
+use matrix_sdk::ruma::{OwnedRoomId, RoomId};
+use matrix_sdk::{Client, Room};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Whether the user has turned off URL previews everywhere.
+///
+/// This is the same account data that other clients use, so the setting follows the user around.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, EventContent)]
+#[ruma_event(type = "org.matrix.preview_urls", kind = GlobalAccountData)]
+pub struct PreviewUrlsEventContent {
+    #[serde(default)]
+    pub disable: bool,
+}
+
+/// Whether the user has turned URL previews on or off in a single room
+#[derive(Clone, Debug, Default, Deserialize, Serialize, EventContent)]
+#[ruma_event(type = "org.matrix.room.preview_urls", kind = RoomAccountData)]
+pub struct RoomPreviewUrlsEventContent {
+    #[serde(default)]
+    pub disable: bool,
+}
+
+pub struct UrlPreviewsCache {
+    client: Client,
+    globally_enabled: bool,
+    room_settings: HashMap<OwnedRoomId, Option<bool>>,
+}
+
+enum UrlPreviewsUpdate {
+    Global(bool),
+    Room(OwnedRoomId, bool),
+}
+
+impl UrlPreviewsCache {
+    pub fn new(client: &Client, cx: &mut Context<Self>) -> Self {
+        let (tx_update, rx_update) = async_channel::unbounded();
+        client.add_event_handler({
+            let tx_update = tx_update.clone();
+            move |event: GlobalAccountDataEvent<PreviewUrlsEventContent>| {
+                let tx_update = tx_update.clone();
+                async move {
+                    let _ = tx_update
+                        .send(UrlPreviewsUpdate::Global(!event.content.disable))
+                        .await;
+                }
+            }
+        });
+        client.add_event_handler(
+            move |event: RoomAccountDataEvent<RoomPreviewUrlsEventContent>, room: Room| {
+                let tx_update = tx_update.clone();
+                async move {
+                    let _ = tx_update
+                        .send(UrlPreviewsUpdate::Room(
+                            room.room_id().to_owned(),
+                            !event.content.disable,
+                        ))
+                        .await;
+                }
+            },
+        );
+
+        let client_clone = client.clone();
+        cx.spawn(
+            async move |weak_this: WeakEntity<Self>, cx: &mut AsyncApp| {
+                if let Ok(Some(preview_urls)) = cx
+                    .spawn_tokio(async move {
+                        client_clone
+                            .account()
+                            .account_data::<PreviewUrlsEventContent>()
+                            .await
+                    })
+                    .await
+                    && let Ok(preview_urls) = preview_urls.deserialize()
+                    && weak_this
+                        .update(cx, |this, cx| {
+                            this.globally_enabled = !preview_urls.disable;
+                            cx.notify()
+                        })
+                        .is_err()
+                {
+                    return;
+                }
+
+                while let Ok(update) = rx_update.recv().await {
+                    if weak_this
+                        .update(cx, |this, cx| {
+                            match update {
+                                UrlPreviewsUpdate::Global(enabled) => {
+                                    this.globally_enabled = enabled;
+                                }
+                                UrlPreviewsUpdate::Room(room_id, enabled) => {
+                                    this.room_settings.insert(room_id, Some(enabled));
+                                }
+                            }
+                            cx.notify()
+                        })
+                        .is_err()
+                    {
+                        return;
+                    }
+                }
+            },
+        )
+        .detach();
+
+        Self {
+            client: client.clone(),
+            globally_enabled: true,
+            room_settings: HashMap::new(),
+        }
+    }
+
+    /// Start loading the URL preview setting of a room, if it hasn't been loaded already
+    pub fn load_room_setting(&mut self, room_id: &RoomId, cx: &mut Context<Self>) {
+        if self.room_settings.contains_key(room_id) {
+            return;
+        }
+        let Some(room) = self.client.get_room(room_id) else {
+            return;
+        };
+
+        self.room_settings.insert(room_id.to_owned(), None);
+
+        let room_id = room_id.to_owned();
+        cx.spawn(
+            async move |weak_this: WeakEntity<Self>, cx: &mut AsyncApp| {
+                let Ok(Some(preview_urls)) = cx
+                    .spawn_tokio(async move {
+                        room.account_data_static::<RoomPreviewUrlsEventContent>()
+                            .await
+                    })
+                    .await
+                else {
+                    return;
+                };
+                let Ok(preview_urls) = preview_urls.deserialize() else {
+                    return;
+                };
+
+                let _ = weak_this.update(cx, |this, cx| {
+                    this.room_settings
+                        .insert(room_id, Some(!preview_urls.content.disable));
+                    cx.notify();
+                });
+            },
+        )
+        .detach();
+    }
+
+    /// Whether URL previews are turned on for all rooms
+    pub fn globally_enabled(&self) -> bool {
+        self.globally_enabled
+    }
+
+    /// Whether the user has explicitly turned URL previews on or off in a room
+    pub fn room_setting(&self, room_id: &RoomId) -> Option<bool> {
+        self.room_settings.get(room_id).copied().flatten()
+    }
+
+    /// Whether URL previews should be shown in a room.
+    ///
+    /// Fetching a preview sends the URL to the homeserver, so unless the user has turned them on
+    /// for a room, previews are not shown in encrypted rooms.
+    pub fn previews_enabled(&self, room: &Room) -> bool {
+        if !self.globally_enabled {
+            return false;
+        }
+
+        self.room_setting(room.room_id())
+            .unwrap_or_else(|| !room.encryption_state().is_encrypted())
+    }
+
+    pub fn set_globally_enabled(
+        &mut self,
+        enabled: bool,
+        cx: &mut Context<Self>,
+    ) -> Task<anyhow::Result<()>> {
+        self.globally_enabled = enabled;
+        cx.notify();
+
+        let client = self.client.clone();
+        let content = PreviewUrlsEventContent { disable: !enabled };
+        cx.spawn(async move |_, cx: &mut AsyncApp| {
+            cx.spawn_tokio(async move { client.account().set_account_data(content).await })
+                .await
+                .map(|_| ())
+                .map_err(|e| anyhow!(e))
+        })
+    }
+
+    pub fn set_room_enabled(
+        &mut self,
+        room: Room,
+        enabled: bool,
+        cx: &mut Context<Self>,
+    ) -> Task<anyhow::Result<()>> {
+        self.room_settings
+            .insert(room.room_id().to_owned(), Some(enabled));
+        cx.notify();
+
+        let content = RoomPreviewUrlsEventContent { disable: !enabled };
+        cx.spawn(async move |_, cx: &mut AsyncApp| {
+            cx.spawn_tokio(async move { room.set_account_data(content).await })
+                .await
+                .map(|_| ())
+                .map_err(|e| anyhow!(e))
+        })
+    }
+}
@@ -423,8 +423,47 @@ impl VerificationRequestsCache {
                             .update(cx, |this, cx| {
                                 match mutation {
                                     CacheMutation::Push(verification_request) => {
+                                        // In-room requests that we sent come back to us through
+                                        // sync after they have already been added
+                                        if this.pending_verification_requests.iter().any(
+                                            |request| {
+                                                request.read(cx).inner.flow_id()
+                                                    == verification_request.inner.flow_id()
+                                            },
+                                        ) {
+                                            return;
+                                        }
+
                                         if !verification_request.inner.we_started() {
                                             // Trigger a notification
+                                            let is_self =
+                                                verification_request.inner.is_self_verification();
+                                            let body = if is_self {
+                                                tr!(
+                                                    "INCOMING_SELF_VERIFICATION_DESCRIPTION",
+                                                    "Verify your other device ({{device_id}}) \
+                                                    to share encryption keys. The other device \
+                                                    will be able to decrypt your messages.",
+                                                    device_id = verification_request
+                                                        .device_id
+                                                        .clone()
+                                                        .map(|id| id.to_string())
+                                                        .unwrap_or_else(|| tr!(
+                                                            "UNKNOWN_DEVICE",
+                                                            "Unknown Device"
+                                                        )
+                                                        .to_string())
+                                                )
+                                            } else {
+                                                tr!(
+                                                    "INCOMING_VERIFICATION_DESCRIPTION",
+                                                    "{{user_id}} wants to verify your \
+                                                    communication with them to ensure that it \
+                                                    remains secure.",
+                                                    user_id =
+                                                        verification_request.peer_id.to_string()
+                                                )
+                                            };
                                             let _ = Notification::new()
                                                 .summary(
                                                     tr!(
@@ -434,25 +473,7 @@ impl VerificationRequestsCache {
                                                     .to_string()
                                                     .as_str(),
                                                 )
-                                                .body(
-                                                    tr!(
-                                                        "INCOMING_SELF_VERIFICATION_DESCRIPTION",
-                                                        "Verify your other device ({{device_id}}) \
-                                                        to share encryption keys. The other device \
-                                                        will be able to decrypt your messages.",
-                                                        device_id = verification_request
-                                                            .device_id
-                                                            .clone()
-                                                            .map(|id| id.to_string())
-                                                            .unwrap_or_else(|| tr!(
-                                                                "UNKNOWN_DEVICE",
-                                                                "Unknown Device"
-                                                            )
-                                                            .to_string())
-                                                    )
-                                                    .to_string()
-                                                    .as_str(),
-                                                )
+                                                .body(body.to_string().as_str())
                                                 .post(cx);
                                         }
 
@@ -1,5 +1,8 @@
 use std::io::{BufReader, Cursor, Read, Seek};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::thread;
+use std::time::Duration;
 
 pub enum SoundEffect {
     Notification,
@@ -41,3 +44,58 @@ pub fn play_sound_effect<R: Send + Sync + 'static + AsRef<[u8]>>(file: R) {
         player.sleep_until_end()
     });
 }
+
+pub enum LoopingSoundEffect {
+    Ring,
+}
+
+impl LoopingSoundEffect {
+    /// Play the sound effect over and over until the returned handle is dropped
+    pub fn play(&self) -> LoopingSoundEffectHandle {
+        match self {
+            LoopingSoundEffect::Ring => {
+                play_looping_sound_effect(include_bytes!("../assets/sfx/ring.wav"))
+            }
+        }
+    }
+}
+
+pub struct LoopingSoundEffectHandle {
+    stopped: Arc<AtomicBool>,
+}
+
+impl Drop for LoopingSoundEffectHandle {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::Relaxed);
+    }
+}
+
+pub fn play_looping_sound_effect(file: &'static [u8]) -> LoopingSoundEffectHandle {
+    let stopped = Arc::new(AtomicBool::new(false));
+    let handle = LoopingSoundEffectHandle {
+        stopped: stopped.clone(),
+    };
+
+    thread::spawn(move || {
+        let Ok(sink_handle) = rodio::DeviceSinkBuilder::open_default_sink() else {
+            return;
+        };
+
+        while !stopped.load(Ordering::Relaxed) {
+            let Ok(player) = rodio::play(sink_handle.mixer(), Cursor::new(file)) else {
+                return;
+            };
+
+            // Poll so that we can cut the sound off as soon as the handle is dropped
+            while !player.empty() {
+                if stopped.load(Ordering::Relaxed) {
+                    player.stop();
+                    return;
+                }
+                thread::sleep(Duration::from_millis(50));
+            }
+        }
+    });
+
+    handle
+}
@@ -0,0 +1,398 @@
+use crate::call_manager::LivekitCallManager;
+use gpui::{App, Subscription};
+use livekit::webrtc::native::apm::AudioProcessingModule;
+use log::warn;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+
+/// The audio processing module works on 10ms of audio at a time
+const FRAMES_PER_SECOND: u32 = 100;
+
+/// A rough guess of how long it takes for audio we play to come back through the mic.
+///
+/// The echo canceller adapts to the real delay, so this only needs to be in the right ballpark.
+const ESTIMATED_ECHO_DELAY_MS: i32 = 50;
+
+/// How many frames to keep transmitting for after the voice drops below the threshold, so that
+/// the ends of words don't get cut off
+const VOICE_ACTIVITY_HANGOVER_FRAMES: u32 = 30;
+
+/// How many seconds of played audio to hold on to when nobody is taking it out of the mix
+const MAX_PLAYBACK_MIX_SECONDS: u32 = 1;
+
+/// How many samples each stream collects before adding them to the mix
+const PLAYBACK_MIX_BATCH_SIZE: usize = 480;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum TransmitMode {
+    /// Transmit whenever the mic is unmuted
+    Continuous,
+
+    /// Only transmit when the mic picks up someone speaking
+    VoiceActivated,
+
+    /// Only transmit while the push to talk button is held
+    PushToTalk,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum VoiceActivitySensitivity {
+    Low,
+    Medium,
+    High,
+}
+
+impl VoiceActivitySensitivity {
+    /// The level, in dBFS, above which we consider the user to be speaking
+    fn threshold(&self) -> f32 {
+        match self {
+            VoiceActivitySensitivity::Low => -35.,
+            VoiceActivitySensitivity::Medium => -45.,
+            VoiceActivitySensitivity::High => -55.,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq)]
+pub struct AudioProcessingSettings {
+    pub noise_suppression: bool,
+    pub echo_cancellation: bool,
+    pub auto_gain_control: bool,
+    pub transmit_mode: TransmitMode,
+    pub voice_activity_sensitivity: VoiceActivitySensitivity,
+}
+
+impl Default for AudioProcessingSettings {
+    fn default() -> Self {
+        Self {
+            noise_suppression: true,
+            echo_cancellation: true,
+            auto_gain_control: true,
+            transmit_mode: TransmitMode::Continuous,
+            voice_activity_sensitivity: VoiceActivitySensitivity::Medium,
+        }
+    }
+}
+
+/// The settings that the audio processing module is created with
+#[derive(Clone, Copy, PartialEq)]
+struct AudioProcessingModuleConfig {
+    noise_suppression: bool,
+    echo_cancellation: bool,
+    auto_gain_control: bool,
+}
+
+impl AudioProcessingSettings {
+    fn audio_processing_module_config(&self) -> AudioProcessingModuleConfig {
+        AudioProcessingModuleConfig {
+            noise_suppression: self.noise_suppression,
+            echo_cancellation: self.echo_cancellation,
+            auto_gain_control: self.auto_gain_control,
+        }
+    }
+
+    fn uses_audio_processing_module(&self) -> bool {
+        self.noise_suppression || self.echo_cancellation || self.auto_gain_control
+    }
+}
+
+/// Audio from every stream in the call, mixed together as it is played.
+///
+/// The echo canceller uses this to work out what to remove from the mic, and the call recorder
+/// uses it to capture everyone else in the call.
+#[derive(Clone)]
+pub struct PlaybackMix {
+    inner: Arc<Mutex<PlaybackMixInner>>,
+}
+
+struct PlaybackMixInner {
+    samples: VecDeque<f32>,
+
+    /// The number of samples that have been taken out of the front of `samples`
+    consumed: u64,
+    sample_rate: u32,
+    channels: u16,
+}
+
+impl Default for PlaybackMix {
+    fn default() -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(PlaybackMixInner {
+                samples: VecDeque::new(),
+                consumed: 0,
+                sample_rate: 48000,
+                channels: 1,
+            })),
+        }
+    }
+}
+
+impl PlaybackMix {
+    /// Create a writer for a stream that is about to start playing
+    pub fn writer(&self, sample_rate: u32, channels: u16) -> PlaybackMixWriter {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.sample_rate != sample_rate || inner.channels != channels {
+            // The output device changed, so the audio we have is no use any more
+            inner.consumed += inner.samples.len() as u64;
+            inner.samples.clear();
+            inner.sample_rate = sample_rate;
+            inner.channels = channels;
+        }
+
+        PlaybackMixWriter {
+            inner: self.inner.clone(),
+            position: inner.consumed + inner.samples.len() as u64,
+            pending: Vec::with_capacity(PLAYBACK_MIX_BATCH_SIZE),
+        }
+    }
+
+    /// Take the next 10ms of played audio, along with its sample rate and channel count
+    pub(crate) fn take_frame(&self) -> Option<(Vec<i16>, u32, u16)> {
+        let mut inner = self.inner.lock().unwrap();
+        let frame_len = (inner.sample_rate / FRAMES_PER_SECOND) as usize * inner.channels as usize;
+        if inner.samples.len() < frame_len {
+            return None;
+        }
+
+        inner.consumed += frame_len as u64;
+        let frame = inner
+            .samples
+            .drain(..frame_len)
+            .map(|sample| (sample.clamp(-1., 1.) * i16::MAX as f32) as i16)
+            .collect();
+        Some((frame, inner.sample_rate, inner.channels))
+    }
+
+    /// Throw away everything that has been played so far
+    pub(crate) fn clear(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.consumed += inner.samples.len() as u64;
+        inner.samples.clear();
+    }
+}
+
+pub struct PlaybackMixWriter {
+    inner: Arc<Mutex<PlaybackMixInner>>,
+
+    /// Where the next sample from this stream goes, counted from the very first sample
+    position: u64,
+    pending: Vec<f32>,
+}
+
+impl PlaybackMixWriter {
+    /// Record a sample that has just been played
+    pub fn push(&mut self, sample: f32) {
+        self.pending.push(sample);
+        if self.pending.len() >= PLAYBACK_MIX_BATCH_SIZE {
+            self.flush();
+        }
+    }
+
+    fn flush(&mut self) {
+        let mut inner = self.inner.lock().unwrap();
+
+        // Skip ahead if the mix has already been taken past us
+        self.position = self.position.max(inner.consumed);
+
+        let start = (self.position - inner.consumed) as usize;
+        let end = start + self.pending.len();
+        if inner.samples.len() < end {
+            inner.samples.resize(end, 0.);
+        }
+        for (index, sample) in self.pending.drain(..).enumerate() {
+            inner.samples[start + index] += sample;
+        }
+        self.position = inner.consumed + end as u64;
+
+        // Don't let the audio pile up if nobody is using it
+        let max_len =
+            (inner.sample_rate * MAX_PLAYBACK_MIX_SECONDS) as usize * inner.channels as usize;
+        if inner.samples.len() > max_len {
+            let excess = inner.samples.len() - max_len;
+            inner.samples.drain(..excess);
+            inner.consumed += excess as u64;
+        }
+    }
+}
+
+/// The call settings that the audio processor needs, kept up to date so that it can read them
+/// from the thread that processes the mic.
+#[derive(Clone)]
+pub struct AudioProcessingControls {
+    settings: Arc<RwLock<AudioProcessingSettings>>,
+    push_to_talk: Arc<AtomicBool>,
+    echo_reference: PlaybackMix,
+}
+
+impl AudioProcessingControls {
+    /// Create controls that follow the call settings.
+    ///
+    /// The controls stop following the settings once the returned subscriptions are dropped, so
+    /// keep them on the main thread for as long as the mic is open.
+    pub fn new(cx: &mut App) -> (Self, Vec<Subscription>) {
+        let call_manager = cx.global::<LivekitCallManager>();
+        let settings_entity = call_manager.audio_processing_settings();
+        let push_to_talk_entity = call_manager.push_to_talk();
+        let echo_reference = call_manager.echo_reference();
+
+        let settings = Arc::new(RwLock::new(settings_entity.read(cx).clone()));
+        let settings_subscription = cx.observe(&settings_entity, {
+            let settings = settings.clone();
+            move |settings_entity, cx| {
+                *settings.write().unwrap() = settings_entity.read(cx).clone();
+            }
+        });
+
+        let push_to_talk = Arc::new(AtomicBool::new(*push_to_talk_entity.read(cx)));
+        let push_to_talk_subscription = cx.observe(&push_to_talk_entity, {
+            let push_to_talk = push_to_talk.clone();
+            move |push_to_talk_entity, cx| {
+                push_to_talk.store(*push_to_talk_entity.read(cx), Ordering::Relaxed);
+            }
+        });
+
+        (
+            Self {
+                settings,
+                push_to_talk,
+                echo_reference,
+            },
+            vec![settings_subscription, push_to_talk_subscription],
+        )
+    }
+}
+
+/// Cleans up audio from the mic before it is sent to the call, and decides whether it should be
+/// sent at all.
+pub struct AudioProcessor {
+    sample_rate: u32,
+    channels: u16,
+
+    controls: AudioProcessingControls,
+
+    audio_processing_module: Option<AudioProcessingModule>,
+    audio_processing_module_config: Option<AudioProcessingModuleConfig>,
+
+    pending: Vec<i16>,
+    voice_activity_hangover: u32,
+}
+
+impl AudioProcessor {
+    pub fn new(sample_rate: u32, channels: u16, controls: AudioProcessingControls) -> Self {
+        Self {
+            sample_rate,
+            channels,
+            controls,
+            audio_processing_module: None,
+            audio_processing_module_config: None,
+            pending: Vec::new(),
+            voice_activity_hangover: 0,
+        }
+    }
+
+    /// Process samples from the mic.
+    ///
+    /// Audio is processed in 10ms chunks, so samples that don't fill a whole chunk are held on to
+    /// until the next call.
+    pub fn process(&mut self, samples: &[i16]) -> Vec<i16> {
+        let settings = self.controls.settings.read().unwrap().clone();
+        let push_to_talk = self.controls.push_to_talk.load(Ordering::Relaxed);
+        self.update_audio_processing_module(&settings);
+
+        self.pending.extend_from_slice(samples);
+
+        let frame_len = (self.sample_rate / FRAMES_PER_SECOND) as usize * self.channels as usize;
+        let mut output = Vec::with_capacity(self.pending.len());
+        while self.pending.len() >= frame_len {
+            let mut frame = self.pending.drain(..frame_len).collect::<Vec<_>>();
+
+            if let Some(audio_processing_module) = &mut self.audio_processing_module {
+                // Catch the echo canceller up with everything that has been played
+                while let Some((mut far_end, sample_rate, channels)) =
+                    self.controls.echo_reference.take_frame()
+                {
+                    if settings.echo_cancellation
+                        && let Err(e) = audio_processing_module.process_reverse_stream(
+                            &mut far_end,
+                            sample_rate as i32,
+                            channels as i32,
+                        )
+                    {
+                        warn!("Unable to process far end audio: {e:?}");
+                    }
+                }
+
+                if settings.echo_cancellation {
+                    let _ = audio_processing_module.set_stream_delay_ms(ESTIMATED_ECHO_DELAY_MS);
+                }
+
+                if let Err(e) = audio_processing_module.process_stream(
+                    &mut frame,
+                    self.sample_rate as i32,
+                    self.channels as i32,
+                ) {
+                    warn!("Unable to process mic audio: {e:?}");
+                }
+            }
+
+            if !self.should_transmit(&frame, &settings, push_to_talk) {
+                frame.fill(0);
+            }
+            output.extend(frame);
+        }
+
+        output
+    }
+
+    fn update_audio_processing_module(&mut self, settings: &AudioProcessingSettings) {
+        let config = settings.audio_processing_module_config();
+        if self.audio_processing_module_config == Some(config) {
+            return;
+        }
+
+        // The audio processing module can't be reconfigured, so start again with a new one
+        self.audio_processing_module = settings.uses_audio_processing_module().then(|| {
+            AudioProcessingModule::new(
+                settings.echo_cancellation,
+                settings.auto_gain_control,
+                true,
+                settings.noise_suppression,
+            )
+        });
+        self.audio_processing_module_config = Some(config);
+    }
+
+    fn should_transmit(
+        &mut self,
+        frame: &[i16],
+        settings: &AudioProcessingSettings,
+        push_to_talk: bool,
+    ) -> bool {
+        match settings.transmit_mode {
+            TransmitMode::Continuous => true,
+            TransmitMode::PushToTalk => push_to_talk,
+            TransmitMode::VoiceActivated => {
+                if level(frame) > settings.voice_activity_sensitivity.threshold() {
+                    self.voice_activity_hangover = VOICE_ACTIVITY_HANGOVER_FRAMES;
+                } else {
+                    self.voice_activity_hangover = self.voice_activity_hangover.saturating_sub(1);
+                }
+                self.voice_activity_hangover > 0
+            }
+        }
+    }
+}
+
+/// The RMS level of some audio, in dBFS
+fn level(samples: &[i16]) -> f32 {
+    if samples.is_empty() {
+        return f32::NEG_INFINITY;
+    }
+
+    let sum_of_squares = samples
+        .iter()
+        .map(|sample| (*sample as f32 / i16::MAX as f32).powi(2))
+        .sum::<f32>();
+    20. * (sum_of_squares / samples.len() as f32).sqrt().log10()
+}
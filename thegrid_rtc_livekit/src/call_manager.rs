@@ -1,5 +1,9 @@
+use crate::audio_processing::{AudioProcessingSettings, PlaybackMix};
+use crate::call_shortcuts::{CallShortcuts, setup_call_shortcuts};
 use crate::focus::get_focus_url;
 use crate::{CallState, LivekitCall, TrackType};
+use contemporary::permissions::{GrantStatus, PermissionType, Permissions};
+use cpal::traits::HostTrait;
 use gpui::{App, AppContext, AsyncApp, BorrowAppContext, Context, Entity, Global, WeakEntity};
 use livekit::track::TrackSource;
 use matrix_sdk::ruma::{OwnedDeviceId, OwnedRoomId, OwnedUserId};
@@ -46,6 +50,11 @@ pub struct LivekitCallManager {
 
     active_output_device: Entity<Option<rodio::MixerDeviceSink>>,
     active_input_device: Entity<Option<cpal::Device>>,
+
+    audio_processing_settings: Entity<AudioProcessingSettings>,
+    push_to_talk: Entity<bool>,
+    echo_reference: PlaybackMix,
+    recording_mix: PlaybackMix,
 }
 
 #[derive(Clone)]
@@ -89,6 +98,12 @@ impl LivekitCallManager {
                     {
                         call_manager.current_call = next_active_call.cloned();
                     }
+
+                    if call_manager.active_calls.is_empty() {
+                        cx.update_global::<CallShortcuts, _>(|call_shortcuts, _| {
+                            call_shortcuts.set_global_hotkeys_enabled(false)
+                        });
+                    }
                 }
             });
         })
@@ -96,10 +111,34 @@ impl LivekitCallManager {
 
         self.active_calls.push(call.clone());
         self.current_call = Some(call.clone());
+        cx.update_global::<CallShortcuts, _>(|call_shortcuts, _| {
+            call_shortcuts.set_global_hotkeys_enabled(true)
+        });
 
         Some(call)
     }
 
+    /// Join a call that someone else started, without going through the call start page.
+    ///
+    /// The default audio devices are used unless the user has already picked some.
+    pub fn answer_call(&mut self, room: OwnedRoomId, cx: &mut App) -> Option<Entity<LivekitCall>> {
+        let host = cpal::default_host();
+        if self.active_output_device.read(cx).is_none() {
+            self.set_active_output_device(host.default_output_device(), cx);
+        }
+
+        let mic_permitted = matches!(
+            Permissions::grant_status(PermissionType::Microphone),
+            GrantStatus::Granted | GrantStatus::PlatformUnsupported
+        );
+        if self.active_input_device.read(cx).is_none() && mic_permitted && !*self.mute.read(cx) {
+            self.active_input_device
+                .write(cx, host.default_input_device());
+        }
+
+        self.start_call(room, HashMap::new(), cx)
+    }
+
     pub fn current_call(&self) -> Option<Entity<LivekitCall>> {
         self.current_call.clone()
     }
@@ -169,6 +208,24 @@ impl LivekitCallManager {
         self.active_input_device.clone()
     }
 
+    pub fn audio_processing_settings(&self) -> Entity<AudioProcessingSettings> {
+        self.audio_processing_settings.clone()
+    }
+
+    /// Whether the push to talk button is being held down
+    pub fn push_to_talk(&self) -> Entity<bool> {
+        self.push_to_talk.clone()
+    }
+
+    pub fn echo_reference(&self) -> PlaybackMix {
+        self.echo_reference.clone()
+    }
+
+    /// Audio from everyone else in the call, before it is muted or has its volume changed
+    pub fn recording_mix(&self) -> PlaybackMix {
+        self.recording_mix.clone()
+    }
+
     pub fn set_active_output_device(&mut self, output_device: Option<cpal::Device>, cx: &mut App) {
         self.active_output_device.update(cx, |device, cx| {
             *device = output_device
@@ -200,6 +257,10 @@ pub fn setup_call_manager(cx: &mut App) {
     let active_input_device = cx.new(|_| None);
     let active_output_device = cx.new(|_| None);
 
+    // TODO: Load and save these
+    let audio_processing_settings = cx.new(|_| AudioProcessingSettings::default());
+    let push_to_talk = cx.new(|_| false);
+
     cx.set_global(LivekitCallManager {
         current_call: None,
         active_calls: Vec::new(),
@@ -208,7 +269,12 @@ pub fn setup_call_manager(cx: &mut App) {
         volumes,
         active_input_device,
         active_output_device,
+        audio_processing_settings,
+        push_to_talk,
+        echo_reference: PlaybackMix::default(),
+        recording_mix: PlaybackMix::default(),
     });
 
     setup_screenshare_manager(cx);
+    setup_call_shortcuts(cx);
 }
@@ -0,0 +1,354 @@
+use crate::audio_processing::PlaybackMix;
+use crate::call_manager::LivekitCallManager;
+use crate::voice_recorder::{OPUS_SAMPLE_RATE, OggOpusWriter, Resampler};
+use gpui::http_client::anyhow;
+use gpui::private::anyhow;
+use gpui::{App, RenderImage};
+use image::ExtendedColorType;
+use image::codecs::jpeg::JpegEncoder;
+use log::error;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, mpsc};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How often the recorder should be given the latest audio and video.
+///
+/// Video is recorded at this rate too.
+pub const RECORDING_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How far behind the call the recording runs, so that audio arriving a little late still makes
+/// it in
+const RECORDING_LATENCY: Duration = Duration::from_millis(200);
+
+/// The most audio to hold on to from one side of the call while the other catches up
+const MAX_BACKLOG_SAMPLES: usize = OPUS_SAMPLE_RATE as usize;
+
+const JPEG_QUALITY: u8 = 80;
+
+/// The size of the blank frames written before there is any video to record
+const PLACEHOLDER_FRAME_SIZE: (u32, u32) = (1280, 720);
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum CallRecordingFormat {
+    OggOpus,
+    Wav,
+}
+
+impl CallRecordingFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            CallRecordingFormat::OggOpus => "ogg",
+            CallRecordingFormat::Wav => "wav",
+        }
+    }
+}
+
+enum RecorderMessage {
+    Audio(Vec<i16>),
+
+    /// The next video frame, or `None` to repeat the previous one
+    VideoFrame(Option<Arc<RenderImage>>),
+}
+
+/// Records a call to a file on this device.
+///
+/// Everyone else in the call is taken from the recording mix and mixed with our own mic into a
+/// single 48kHz mono track. If video is being recorded, it is written next to the audio as a
+/// Motion JPEG file with the same name.
+///
+/// The files are written on their own thread, and are finished once the recorder is dropped.
+pub struct CallRecorder {
+    path: PathBuf,
+    sender: mpsc::Sender<RecorderMessage>,
+    recording_mix: PlaybackMix,
+
+    remote_resampler: Resampler,
+    mic_resampler: Resampler,
+    remote_samples: VecDeque<i16>,
+    mic_samples: VecDeque<i16>,
+
+    started_at: Instant,
+    recorded_samples: u64,
+
+    records_video: bool,
+    recorded_frames: u64,
+    last_frame: Option<Arc<RenderImage>>,
+}
+
+impl CallRecorder {
+    pub fn start(
+        path: PathBuf,
+        format: CallRecordingFormat,
+        records_video: bool,
+        cx: &App,
+    ) -> anyhow::Result<Self> {
+        let mut audio_writer = match format {
+            CallRecordingFormat::OggOpus => {
+                AudioWriter::OggOpus(OggOpusWriter::new(BufWriter::new(File::create(&path)?))?)
+            }
+            CallRecordingFormat::Wav => AudioWriter::Wav(WavWriter::new(File::create(&path)?)?),
+        };
+        let mut video_writer = if records_video {
+            Some(BufWriter::new(File::create(video_path(&path))?))
+        } else {
+            None
+        };
+
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            if let Err(e) = write_recording(receiver, &mut audio_writer, &mut video_writer) {
+                error!("Unable to write call recording: {e:?}");
+            }
+            if let Err(e) = audio_writer.finish() {
+                error!("Unable to finish call recording: {e:?}");
+            }
+            if let Some(mut video_writer) = video_writer
+                && let Err(e) = video_writer.flush()
+            {
+                error!("Unable to finish call video recording: {e:?}");
+            }
+        });
+
+        // Only record what gets played from now on
+        let recording_mix = cx.global::<LivekitCallManager>().recording_mix();
+        recording_mix.clear();
+
+        Ok(Self {
+            path,
+            sender,
+            recording_mix,
+            remote_resampler: Resampler::default(),
+            mic_resampler: Resampler::default(),
+            remote_samples: VecDeque::new(),
+            mic_samples: VecDeque::new(),
+            started_at: Instant::now(),
+            recorded_samples: 0,
+            records_video,
+            recorded_frames: 0,
+            last_frame: None,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn records_video(&self) -> bool {
+        self.records_video
+    }
+
+    /// Add audio that we've sent to the call
+    pub fn push_mic(&mut self, samples: &[i16], sample_rate: u32, channels: u16) {
+        self.mic_samples
+            .extend(self.mic_resampler.process(samples, sample_rate, channels));
+        trim_backlog(&mut self.mic_samples);
+    }
+
+    /// Write everything that is due to be recorded, along with the frame currently shown for the
+    /// focused video stream.
+    ///
+    /// This should be called every [`RECORDING_INTERVAL`].
+    pub fn tick(&mut self, video_frame: Option<Arc<RenderImage>>) {
+        while let Some((frame, sample_rate, channels)) = self.recording_mix.take_frame() {
+            self.remote_samples.extend(self.remote_resampler.process(
+                &frame,
+                sample_rate,
+                channels,
+            ));
+        }
+
+        let elapsed = self.started_at.elapsed().saturating_sub(RECORDING_LATENCY);
+
+        // Keep the recording in step with the clock. Whichever side has nothing to give us is
+        // silent, e.g. because we're muted.
+        let due_samples = (elapsed.as_secs_f64() * OPUS_SAMPLE_RATE as f64) as u64;
+        let sample_count = due_samples.saturating_sub(self.recorded_samples) as usize;
+        if sample_count > 0 {
+            let samples = (0..sample_count)
+                .map(|_| {
+                    let remote = self.remote_samples.pop_front().unwrap_or_default() as i32;
+                    let mic = self.mic_samples.pop_front().unwrap_or_default() as i32;
+                    (remote + mic).clamp(i16::MIN as i32, i16::MAX as i32) as i16
+                })
+                .collect();
+            self.recorded_samples += sample_count as u64;
+            let _ = self.sender.send(RecorderMessage::Audio(samples));
+        }
+        trim_backlog(&mut self.remote_samples);
+
+        if self.records_video {
+            let due_frames = elapsed.as_millis() as u64 / RECORDING_INTERVAL.as_millis() as u64;
+            while self.recorded_frames < due_frames {
+                let frame = match &video_frame {
+                    Some(video_frame)
+                        if self
+                            .last_frame
+                            .as_ref()
+                            .is_none_or(|last_frame| !Arc::ptr_eq(last_frame, video_frame)) =>
+                    {
+                        self.last_frame = Some(video_frame.clone());
+                        Some(video_frame.clone())
+                    }
+                    _ => None,
+                };
+                let _ = self.sender.send(RecorderMessage::VideoFrame(frame));
+                self.recorded_frames += 1;
+            }
+        }
+    }
+}
+
+/// The file that the video for a recording is written to
+pub fn video_path(path: &Path) -> PathBuf {
+    path.with_extension("mjpeg")
+}
+
+fn trim_backlog(samples: &mut VecDeque<i16>) {
+    if samples.len() > MAX_BACKLOG_SAMPLES {
+        let excess = samples.len() - MAX_BACKLOG_SAMPLES;
+        samples.drain(..excess);
+    }
+}
+
+fn write_recording(
+    receiver: mpsc::Receiver<RecorderMessage>,
+    audio_writer: &mut AudioWriter,
+    video_writer: &mut Option<BufWriter<File>>,
+) -> anyhow::Result<()> {
+    let mut last_jpeg = None;
+    while let Ok(message) = receiver.recv() {
+        match message {
+            RecorderMessage::Audio(samples) => audio_writer.write(&samples)?,
+            RecorderMessage::VideoFrame(frame) => {
+                let Some(video_writer) = video_writer else {
+                    continue;
+                };
+
+                if let Some(frame) = frame {
+                    match encode_jpeg(&frame) {
+                        Ok(jpeg) => last_jpeg = Some(jpeg),
+                        Err(e) => error!("Unable to encode call video frame: {e:?}"),
+                    }
+                }
+                if last_jpeg.is_none() {
+                    // Keep the video in step with the audio until there is something to show
+                    last_jpeg = Some(placeholder_jpeg()?);
+                }
+                if let Some(jpeg) = &last_jpeg {
+                    video_writer.write_all(jpeg)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn encode_jpeg(frame: &RenderImage) -> anyhow::Result<Vec<u8>> {
+    let size = frame.size(0);
+    let bgra = frame
+        .as_bytes(0)
+        .ok_or_else(|| anyhow!("The video frame has no data"))?;
+    let rgb = bgra
+        .chunks_exact(4)
+        .flat_map(|pixel| [pixel[2], pixel[1], pixel[0]])
+        .collect::<Vec<_>>();
+
+    let mut jpeg = Vec::new();
+    JpegEncoder::new_with_quality(&mut jpeg, JPEG_QUALITY).encode(
+        &rgb,
+        size.width.0 as u32,
+        size.height.0 as u32,
+        ExtendedColorType::Rgb8,
+    )?;
+    Ok(jpeg)
+}
+
+fn placeholder_jpeg() -> anyhow::Result<Vec<u8>> {
+    let (width, height) = PLACEHOLDER_FRAME_SIZE;
+    let mut jpeg = Vec::new();
+    JpegEncoder::new_with_quality(&mut jpeg, JPEG_QUALITY).encode(
+        &vec![0; (width * height * 3) as usize],
+        width,
+        height,
+        ExtendedColorType::Rgb8,
+    )?;
+    Ok(jpeg)
+}
+
+enum AudioWriter {
+    OggOpus(OggOpusWriter<BufWriter<File>>),
+    Wav(WavWriter),
+}
+
+impl AudioWriter {
+    fn write(&mut self, samples: &[i16]) -> anyhow::Result<()> {
+        match self {
+            AudioWriter::OggOpus(writer) => writer.write(samples),
+            AudioWriter::Wav(writer) => writer.write(samples),
+        }
+    }
+
+    fn finish(self) -> anyhow::Result<()> {
+        match self {
+            AudioWriter::OggOpus(writer) => {
+                writer.finish()?.flush()?;
+                Ok(())
+            }
+            AudioWriter::Wav(writer) => writer.finish(),
+        }
+    }
+}
+
+/// Writes 16 bit mono 48kHz PCM audio to a WAV file
+struct WavWriter {
+    file: BufWriter<File>,
+    data_length: u32,
+}
+
+impl WavWriter {
+    fn new(file: File) -> anyhow::Result<Self> {
+        let mut file = BufWriter::new(file);
+
+        // The sizes are filled in once we know how long the recording is
+        file.write_all(b"RIFF")?;
+        file.write_all(&36u32.to_le_bytes())?;
+        file.write_all(b"WAVE")?;
+        file.write_all(b"fmt ")?;
+        file.write_all(&16u32.to_le_bytes())?;
+        file.write_all(&1u16.to_le_bytes())?;
+        file.write_all(&1u16.to_le_bytes())?;
+        file.write_all(&OPUS_SAMPLE_RATE.to_le_bytes())?;
+        file.write_all(&(OPUS_SAMPLE_RATE * 2).to_le_bytes())?;
+        file.write_all(&2u16.to_le_bytes())?;
+        file.write_all(&16u16.to_le_bytes())?;
+        file.write_all(b"data")?;
+        file.write_all(&0u32.to_le_bytes())?;
+
+        Ok(Self {
+            file,
+            data_length: 0,
+        })
+    }
+
+    fn write(&mut self, samples: &[i16]) -> anyhow::Result<()> {
+        for sample in samples {
+            self.file.write_all(&sample.to_le_bytes())?;
+        }
+        self.data_length = self.data_length.saturating_add((samples.len() * 2) as u32);
+        Ok(())
+    }
+
+    fn finish(mut self) -> anyhow::Result<()> {
+        self.file.seek(SeekFrom::Start(4))?;
+        self.file
+            .write_all(&(36u32.saturating_add(self.data_length)).to_le_bytes())?;
+        self.file.seek(SeekFrom::Start(40))?;
+        self.file.write_all(&self.data_length.to_le_bytes())?;
+        self.file.flush()?;
+        Ok(())
+    }
+}
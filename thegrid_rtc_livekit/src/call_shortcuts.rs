@@ -0,0 +1,485 @@
+use crate::TrackType;
+use crate::call_manager::LivekitCallManager;
+use cntp_i18n::tr;
+use contemporary::application::Details;
+use global_hotkey::hotkey::HotKey;
+use global_hotkey::{GlobalHotKeyEvent, GlobalHotKeyManager, HotKeyState};
+use gpui::private::serde_json;
+use gpui::{
+    App, AsyncApp, BorrowAppContext, Global, KeyBinding, KeyUpEvent, Keystroke, KeystrokeEvent,
+    NoAction, Window, actions,
+};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+actions!(
+    thegrid_rtc_livekit,
+    [
+        ToggleMute,
+        ToggleDeafen,
+        ToggleCamera,
+        ToggleScreenshare,
+        HangUp,
+        PushToTalk
+    ]
+);
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum CallShortcut {
+    ToggleMute,
+    ToggleDeafen,
+    ToggleCamera,
+    ToggleScreenshare,
+    HangUp,
+    PushToTalk,
+}
+
+impl CallShortcut {
+    pub fn all() -> [CallShortcut; 6] {
+        [
+            CallShortcut::ToggleMute,
+            CallShortcut::ToggleDeafen,
+            CallShortcut::ToggleCamera,
+            CallShortcut::ToggleScreenshare,
+            CallShortcut::HangUp,
+            CallShortcut::PushToTalk,
+        ]
+    }
+
+    pub fn label(&self) -> String {
+        match self {
+            CallShortcut::ToggleMute => tr!("CALL_SHORTCUT_TOGGLE_MUTE", "Mute Microphone"),
+            CallShortcut::ToggleDeafen => tr!("CALL_SHORTCUT_TOGGLE_DEAFEN", "Deafen"),
+            CallShortcut::ToggleCamera => tr!("CALL_SHORTCUT_TOGGLE_CAMERA", "Camera"),
+            CallShortcut::ToggleScreenshare => {
+                tr!("CALL_SHORTCUT_TOGGLE_SCREENSHARE", "Share Screen")
+            }
+            CallShortcut::HangUp => tr!("CALL_SHORTCUT_HANG_UP", "Hang Up"),
+            CallShortcut::PushToTalk => tr!("CALL_SHORTCUT_PUSH_TO_TALK", "Push to Talk"),
+        }
+        .to_string()
+    }
+
+    fn default_keystrokes(&self) -> &'static str {
+        match self {
+            CallShortcut::ToggleMute => "secondary-shift-m",
+            CallShortcut::ToggleDeafen => "secondary-shift-d",
+            CallShortcut::ToggleCamera => "secondary-shift-o",
+            CallShortcut::ToggleScreenshare => "secondary-shift-e",
+            CallShortcut::HangUp => "secondary-shift-h",
+            CallShortcut::PushToTalk => "secondary-shift-space",
+        }
+    }
+
+    fn key_binding(&self, keystrokes: &str) -> KeyBinding {
+        match self {
+            CallShortcut::ToggleMute => KeyBinding::new(keystrokes, ToggleMute, None),
+            CallShortcut::ToggleDeafen => KeyBinding::new(keystrokes, ToggleDeafen, None),
+            CallShortcut::ToggleCamera => KeyBinding::new(keystrokes, ToggleCamera, None),
+            CallShortcut::ToggleScreenshare => KeyBinding::new(keystrokes, ToggleScreenshare, None),
+            CallShortcut::HangUp => KeyBinding::new(keystrokes, HangUp, None),
+            CallShortcut::PushToTalk => KeyBinding::new(keystrokes, PushToTalk, None),
+        }
+    }
+}
+
+/// What started push to talk, so that we know when to stop talking
+enum HeldPushToTalk {
+    /// A key pressed in one of our windows, which we stop at when it is released
+    Key(String),
+
+    /// The system-wide hotkey, which tells us when it is released even if we're in the background
+    GlobalHotKey,
+}
+
+/// The keys that control the current call, which the user can change to suit them.
+///
+/// While a call is active, the shortcuts are also registered as system-wide hotkeys so that they
+/// work when theGrid isn't focused. This isn't possible everywhere (e.g. on Wayland), in which
+/// case they only work inside theGrid.
+///
+/// Changes are saved to `call_shortcuts.json` in the configuration directory.
+pub struct CallShortcuts {
+    keystrokes: HashMap<CallShortcut, String>,
+
+    held_push_to_talk: Option<HeldPushToTalk>,
+
+    global_hotkey_manager: Option<GlobalHotKeyManager>,
+    global_hotkeys: HashMap<u32, (HotKey, CallShortcut)>,
+    global_hotkeys_enabled: bool,
+}
+
+impl CallShortcuts {
+    fn load(cx: &App) -> Self {
+        let keystrokes = config_path(cx)
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|contents| {
+                serde_json::from_str::<HashMap<CallShortcut, String>>(&contents)
+                    .inspect_err(|e| warn!("Unable to read call shortcuts: {e}"))
+                    .ok()
+            })
+            .unwrap_or_default()
+            .into_iter()
+            // A bad keystroke would panic when we try to bind it
+            .filter(|(_, keystrokes)| is_valid_keystrokes(keystrokes))
+            .collect();
+
+        let global_hotkey_manager = GlobalHotKeyManager::new()
+            .inspect_err(|e| warn!("System-wide call shortcuts are unavailable: {e}"))
+            .ok();
+
+        Self {
+            keystrokes,
+            held_push_to_talk: None,
+            global_hotkey_manager,
+            global_hotkeys: HashMap::new(),
+            global_hotkeys_enabled: false,
+        }
+    }
+
+    fn save(&self, cx: &App) {
+        let Some(path) = config_path(cx) else {
+            return;
+        };
+
+        if let Some(parent) = path.parent()
+            && let Err(e) = fs::create_dir_all(parent)
+        {
+            warn!("Unable to create configuration directory: {e}");
+            return;
+        }
+
+        match serde_json::to_string_pretty(&self.keystrokes) {
+            Ok(contents) => {
+                if let Err(e) = fs::write(path, contents) {
+                    warn!("Unable to save call shortcuts: {e}");
+                }
+            }
+            Err(e) => warn!("Unable to save call shortcuts: {e}"),
+        }
+    }
+
+    pub fn keystrokes(&self, shortcut: CallShortcut) -> &str {
+        self.keystrokes
+            .get(&shortcut)
+            .map(|keystrokes| keystrokes.as_str())
+            .unwrap_or_else(|| shortcut.default_keystrokes())
+    }
+
+    pub fn key_bindings(&self) -> Vec<KeyBinding> {
+        CallShortcut::all()
+            .iter()
+            .map(|shortcut| shortcut.key_binding(self.keystrokes(*shortcut)))
+            .collect()
+    }
+
+    /// Change the keys for a shortcut, or go back to the default keys if `keystrokes` is `None`
+    pub fn set_keystrokes(
+        &mut self,
+        shortcut: CallShortcut,
+        keystrokes: Option<String>,
+        cx: &mut App,
+    ) {
+        if keystrokes
+            .as_ref()
+            .is_some_and(|keystrokes| !is_valid_keystrokes(keystrokes))
+        {
+            return;
+        }
+
+        let old_keystrokes = self.keystrokes(shortcut).to_string();
+        match keystrokes {
+            Some(keystrokes) => self.keystrokes.insert(shortcut, keystrokes),
+            None => self.keystrokes.remove(&shortcut),
+        };
+        let new_keystrokes = self.keystrokes(shortcut).to_string();
+        if old_keystrokes == new_keystrokes {
+            return;
+        }
+
+        // Key bindings can't be removed, but bindings made later win, so cover up the old keys
+        // unless another shortcut is still using them
+        let mut key_bindings = Vec::new();
+        if !CallShortcut::all()
+            .iter()
+            .any(|shortcut| self.keystrokes(*shortcut) == old_keystrokes)
+        {
+            key_bindings.push(KeyBinding::new(&old_keystrokes, NoAction, None));
+        }
+        key_bindings.push(shortcut.key_binding(&new_keystrokes));
+        cx.bind_keys(key_bindings);
+
+        if self.global_hotkeys_enabled {
+            self.register_global_hotkeys();
+        }
+
+        self.save(cx);
+    }
+
+    /// Register the shortcuts as system-wide hotkeys, or unregister them if `enabled` is false
+    pub(crate) fn set_global_hotkeys_enabled(&mut self, enabled: bool) {
+        if self.global_hotkeys_enabled == enabled {
+            return;
+        }
+        self.global_hotkeys_enabled = enabled;
+
+        if enabled {
+            self.register_global_hotkeys();
+        } else {
+            self.unregister_global_hotkeys();
+        }
+    }
+
+    fn register_global_hotkeys(&mut self) {
+        self.unregister_global_hotkeys();
+        let Some(global_hotkey_manager) = &self.global_hotkey_manager else {
+            return;
+        };
+
+        for shortcut in CallShortcut::all() {
+            // Shortcuts that can't be a system-wide hotkey still work inside theGrid
+            let Some(hotkey) = global_hotkey(self.keystrokes(shortcut)) else {
+                continue;
+            };
+            if self.global_hotkeys.contains_key(&hotkey.id()) {
+                continue;
+            }
+
+            match global_hotkey_manager.register(hotkey) {
+                Ok(()) => {
+                    self.global_hotkeys.insert(hotkey.id(), (hotkey, shortcut));
+                }
+                Err(e) => warn!("Unable to register system-wide call shortcut: {e}"),
+            }
+        }
+    }
+
+    fn unregister_global_hotkeys(&mut self) {
+        let Some(global_hotkey_manager) = &self.global_hotkey_manager else {
+            return;
+        };
+
+        for (_, (hotkey, _)) in self.global_hotkeys.drain() {
+            if let Err(e) = global_hotkey_manager.unregister(hotkey) {
+                warn!("Unable to unregister system-wide call shortcut: {e}");
+            }
+        }
+    }
+}
+
+impl Global for CallShortcuts {}
+
+fn config_path(cx: &App) -> Option<PathBuf> {
+    let details = cx.global::<Details>();
+    details
+        .standard_dirs()
+        .map(|directories| directories.config_dir().join("call_shortcuts.json"))
+}
+
+fn is_valid_keystrokes(keystrokes: &str) -> bool {
+    !keystrokes.trim().is_empty()
+        && keystrokes
+            .split_whitespace()
+            .all(|keystroke| Keystroke::parse(keystroke).is_ok())
+}
+
+/// Convert keystrokes to a system-wide hotkey, if they are a single keystroke that can be one
+fn global_hotkey(keystrokes: &str) -> Option<HotKey> {
+    let mut keystrokes = keystrokes.split_whitespace();
+    let keystroke = Keystroke::parse(keystrokes.next()?).ok()?;
+    if keystrokes.next().is_some() || keystroke.modifiers.function {
+        return None;
+    }
+
+    let mut parts = Vec::new();
+    if keystroke.modifiers.control {
+        parts.push("ctrl");
+    }
+    if keystroke.modifiers.alt {
+        parts.push("alt");
+    }
+    if keystroke.modifiers.shift {
+        parts.push("shift");
+    }
+    if keystroke.modifiers.platform {
+        parts.push("super");
+    }
+    parts.push(&keystroke.key);
+
+    parts.join("+").parse().ok()
+}
+
+/// Format keystrokes the way they should be shown to the user
+pub fn display_keystrokes(keystrokes: &str) -> String {
+    keystrokes
+        .split_whitespace()
+        .map(|keystroke| {
+            Keystroke::parse(keystroke)
+                .map(|keystroke| keystroke.to_string())
+                .unwrap_or_else(|_| keystroke.to_string())
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+pub fn setup_call_shortcuts(cx: &mut App) {
+    let call_shortcuts = CallShortcuts::load(cx);
+    cx.set_global(call_shortcuts);
+
+    cx.on_action(|_: &ToggleMute, cx| toggle_mute(cx));
+    cx.on_action(|_: &ToggleDeafen, cx| toggle_deafen(cx));
+    cx.on_action(|_: &ToggleCamera, cx| stop_camera(cx));
+    cx.on_action(|_: &ToggleScreenshare, cx| stop_screenshare(cx));
+    cx.on_action(|_: &HangUp, cx| hang_up(cx));
+
+    cx.on_action(|_: &PushToTalk, cx| {
+        // Holding the key down repeats the action, but we're already talking
+        if cx.global::<CallShortcuts>().held_push_to_talk.is_some() {
+            return;
+        }
+
+        // When this doesn't come from a key (e.g. from the menu), there's nothing to hold down,
+        // so it turns talking on and off instead. Keys are picked up by the keystroke observer
+        // below, which keeps us talking until the key is released.
+        let push_to_talk = cx.global::<LivekitCallManager>().push_to_talk();
+        let talking = *push_to_talk.read(cx);
+        push_to_talk.write(cx, !talking);
+    });
+
+    cx.observe_keystrokes(|event: &KeystrokeEvent, _: &mut Window, cx: &mut App| {
+        if event
+            .action
+            .as_ref()
+            .is_some_and(|action| action.as_any().is::<PushToTalk>())
+        {
+            let key = event.keystroke.key.clone();
+            cx.update_global::<CallShortcuts, _>(|call_shortcuts, _| {
+                call_shortcuts.held_push_to_talk = Some(HeldPushToTalk::Key(key));
+            });
+            cx.global::<LivekitCallManager>()
+                .push_to_talk()
+                .write(cx, true);
+        }
+    })
+    .detach();
+
+    // System-wide hotkeys are reported from another thread
+    let (tx_hotkey, rx_hotkey) = async_channel::unbounded();
+    GlobalHotKeyEvent::set_event_handler(Some(move |event| {
+        let _ = tx_hotkey.try_send(event);
+    }));
+    cx.spawn(async move |cx: &mut AsyncApp| {
+        while let Ok(event) = rx_hotkey.recv().await {
+            cx.update(|cx| global_hotkey_event(event, cx));
+        }
+    })
+    .detach();
+}
+
+fn global_hotkey_event(event: GlobalHotKeyEvent, cx: &mut App) {
+    let Some(&(_, shortcut)) = cx.global::<CallShortcuts>().global_hotkeys.get(&event.id()) else {
+        return;
+    };
+    let held_push_to_talk = &cx.global::<CallShortcuts>().held_push_to_talk;
+
+    match (shortcut, event.state()) {
+        (CallShortcut::PushToTalk, HotKeyState::Pressed) => {
+            // Holding the key down repeats the hotkey, but we're already talking
+            if held_push_to_talk.is_some() {
+                return;
+            }
+
+            cx.update_global::<CallShortcuts, _>(|call_shortcuts, _| {
+                call_shortcuts.held_push_to_talk = Some(HeldPushToTalk::GlobalHotKey);
+            });
+            cx.global::<LivekitCallManager>()
+                .push_to_talk()
+                .write(cx, true);
+        }
+        (CallShortcut::PushToTalk, HotKeyState::Released) => {
+            if matches!(held_push_to_talk, Some(HeldPushToTalk::GlobalHotKey)) {
+                stop_push_to_talk(cx);
+            }
+        }
+        (CallShortcut::ToggleMute, HotKeyState::Pressed) => toggle_mute(cx),
+        (CallShortcut::ToggleDeafen, HotKeyState::Pressed) => toggle_deafen(cx),
+        (CallShortcut::ToggleCamera, HotKeyState::Pressed) => stop_camera(cx),
+        (CallShortcut::ToggleScreenshare, HotKeyState::Pressed) => stop_screenshare(cx),
+        (CallShortcut::HangUp, HotKeyState::Pressed) => hang_up(cx),
+        (_, HotKeyState::Released) => {}
+    }
+}
+
+fn toggle_mute(cx: &mut App) {
+    let mute = cx.global::<LivekitCallManager>().mute();
+    let muted = *mute.read(cx);
+    mute.write(cx, !muted);
+}
+
+fn toggle_deafen(cx: &mut App) {
+    let deaf = cx.global::<LivekitCallManager>().deaf();
+    let deafened = *deaf.read(cx);
+    deaf.write(cx, !deafened);
+}
+
+// Starting the camera or a screenshare needs the call page to ask which one to use, so these can
+// only turn them off from elsewhere
+fn stop_camera(cx: &mut App) {
+    if let Some(call) = cx.global::<LivekitCallManager>().current_call()
+        && call.read(cx).active_camera().is_some()
+    {
+        call.update(cx, |call, cx| {
+            call.publish_track(TrackType::Camera, None, cx)
+        });
+    }
+}
+
+fn stop_screenshare(cx: &mut App) {
+    if let Some(call) = cx.global::<LivekitCallManager>().current_call()
+        && call.read(cx).active_screenshare().is_some()
+    {
+        call.update(cx, |call, cx| {
+            call.publish_track(TrackType::ScreenshareAudio, None, cx);
+            call.publish_track(TrackType::Screenshare, None, cx);
+        });
+    }
+}
+
+fn hang_up(cx: &mut App) {
+    if let Some(call) = cx.global::<LivekitCallManager>().current_call() {
+        call.update(cx, |call, cx| call.end_call(cx));
+    }
+}
+
+/// Stop talking if the push to talk key has been released
+pub fn push_to_talk_key_released(event: &KeyUpEvent, cx: &mut App) {
+    if let Some(HeldPushToTalk::Key(key)) = &cx.global::<CallShortcuts>().held_push_to_talk
+        && key == &event.keystroke.key
+    {
+        stop_push_to_talk(cx);
+    }
+}
+
+/// Stop talking, e.g. because the window lost focus and we won't find out when the key goes up.
+///
+/// Push to talk started by the system-wide hotkey keeps going, since we'll still hear about it.
+pub fn release_push_to_talk(cx: &mut App) {
+    if matches!(
+        cx.global::<CallShortcuts>().held_push_to_talk,
+        Some(HeldPushToTalk::Key(_))
+    ) {
+        stop_push_to_talk(cx);
+    }
+}
+
+fn stop_push_to_talk(cx: &mut App) {
+    cx.update_global::<CallShortcuts, _>(|call_shortcuts, _| {
+        call_shortcuts.held_push_to_talk = None;
+    });
+    cx.global::<LivekitCallManager>()
+        .push_to_talk()
+        .write(cx, false);
+}
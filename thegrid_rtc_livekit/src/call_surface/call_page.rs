@@ -1,9 +1,14 @@
+use crate::audio_processing::TransmitMode;
 use crate::call_manager::{LivekitCallManager, VolumeKey};
+use crate::call_recorder::CallRecordingFormat;
+use crate::call_shortcuts::{HangUp, ToggleCamera, ToggleScreenshare};
 use crate::call_surface::call_page::webcam_start_dialog::WebcamStartDialog;
+use crate::encryption::CallEncryptionState;
 use crate::{CallMember, CallState, LivekitCall, StreamState, TrackType};
 use cntp_i18n::tr;
+use contemporary::components::admonition::AdmonitionSeverity;
 use contemporary::components::anchorer::WithAnchorer;
-use contemporary::components::button::button;
+use contemporary::components::button::{ButtonMenuOpenPolicy, button};
 use contemporary::components::context_menu::{ContextMenuExt, ContextMenuItem};
 use contemporary::components::grandstand::grandstand;
 use contemporary::components::icon::icon;
@@ -11,17 +16,20 @@ use contemporary::components::icon_text::icon_text;
 use contemporary::components::interstitial::interstitial;
 use contemporary::components::layer::layer;
 use contemporary::components::spinner::spinner;
+use contemporary::components::toast::Toast;
 use contemporary::easing::ease_out_cubic;
 use contemporary::lerp::Lerpable;
 use contemporary::styling::theme::ThemeStorage;
+use directories::UserDirs;
 use gpui::prelude::FluentBuilder;
 use gpui::{
-    Along, App, AppContext, Axis, BorrowAppContext, Bounds, Context, ElementId, Entity,
-    InteractiveElement, IntoElement, ObjectFit, ParentElement, Pixels, Point, Render, RenderOnce,
-    StatefulInteractiveElement, Styled, StyledImage, Window, anchored, div, img, px, rgb,
+    Along, App, AppContext, AsyncWindowContext, Axis, BorrowAppContext, Bounds, Context, ElementId,
+    Entity, FocusHandle, InteractiveElement, IntoElement, MouseButton, ObjectFit, ParentElement,
+    Pixels, Point, Render, RenderOnce, StatefulInteractiveElement, Styled, StyledImage, Window,
+    anchored, div, img, px, rgb,
 };
 use livekit::prelude::TrackSource;
-use log::{debug, info};
+use log::{debug, error, info};
 use matrix_sdk::ruma::{OwnedDeviceId, OwnedRoomId, OwnedUserId};
 use std::collections::HashMap;
 use std::iter;
@@ -51,6 +59,8 @@ pub struct CallPage {
     on_surface_change: Rc<Box<SurfaceChangeHandler>>,
 
     webcam_start_dialog: Entity<WebcamStartDialog>,
+    focus_handle: FocusHandle,
+    needs_focus: bool,
 
     animation_start: Instant,
     old_coordinates: HashMap<usize, Bounds<Pixels>>,
@@ -82,6 +92,8 @@ impl CallPage {
             room_id,
             on_surface_change,
             webcam_start_dialog,
+            focus_handle: cx.focus_handle(),
+            needs_focus: true,
             animation_start: Instant::now(),
             old_coordinates: HashMap::new(),
             overview_coordinates: HashMap::new(),
@@ -112,10 +124,11 @@ impl CallPage {
                     };
                     self.animation_start = Instant::now();
                 }
-                self.focus = Focus::Focus(
-                    call_member.room_member.user_id().to_owned(),
-                    call_member.device_id,
-                );
+                let user_id = call_member.room_member.user_id().to_owned();
+                self.call.update(cx, |call, _| {
+                    call.set_focused_member(Some((user_id.clone(), call_member.device_id.clone())))
+                });
+                self.focus = Focus::Focus(user_id, call_member.device_id);
                 cx.notify();
             }
         }
@@ -128,6 +141,8 @@ impl CallPage {
             self.animation_start = Instant::now();
         }
         self.focus = Focus::Overview;
+        self.call
+            .update(cx, |call, _| call.set_focused_member(None));
         cx.notify();
     }
 
@@ -153,6 +168,91 @@ impl CallPage {
             });
         }
     }
+
+    fn camera(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        if self.call.read(cx).active_camera().is_some() {
+            self.call.update(cx, |call, cx| {
+                call.publish_track(TrackType::Camera, None, cx)
+            });
+        } else {
+            let call = self.call.clone();
+            self.webcam_start_dialog
+                .update(cx, |webcam_start_dialog, cx| {
+                    webcam_start_dialog.open(call, window, cx)
+                })
+        }
+    }
+
+    fn hang_up(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        self.call.update(cx, |call, cx| {
+            call.end_call(cx);
+        });
+        (self.on_surface_change)(
+            &SurfaceChangeEvent {
+                change: SurfaceChange::Pop,
+            },
+            window,
+            cx,
+        )
+    }
+
+    fn start_recording(
+        &mut self,
+        format: CallRecordingFormat,
+        records_video: bool,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let user_dirs = UserDirs::new().unwrap();
+        let file_name = format!(
+            "{}.{}",
+            tr!("CALL_RECORDING_FILE_NAME", "Call Recording"),
+            format.extension()
+        );
+        let prompt = cx.prompt_for_new_path(
+            user_dirs.audio_dir().unwrap_or(user_dirs.home_dir()),
+            Some(&file_name),
+        );
+
+        let call = self.call.clone();
+        window
+            .spawn(cx, async move |cx: &mut AsyncWindowContext| {
+                let Some(path) = prompt.await.ok().and_then(|result| result.ok()).flatten() else {
+                    return;
+                };
+
+                let _ = cx.update(|window, cx| {
+                    if let Err(e) = call.update(cx, |call, cx| {
+                        call.start_recording(path, format, records_video, cx)
+                    }) {
+                        error!("Unable to start recording the call: {e:?}");
+                        Toast::new()
+                            .title(&tr!("CALL_RECORDING_ERROR_TITLE", "Unable to record call"))
+                            .body(&e.to_string())
+                            .severity(AdmonitionSeverity::Error)
+                            .post(window, cx);
+                    }
+                });
+            })
+            .detach();
+    }
+
+    fn toggle_camera(&mut self, _: &ToggleCamera, window: &mut Window, cx: &mut Context<Self>) {
+        self.camera(window, cx)
+    }
+
+    fn toggle_screenshare(
+        &mut self,
+        _: &ToggleScreenshare,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        self.screenshare(window, cx)
+    }
+
+    fn hang_up_action(&mut self, _: &HangUp, window: &mut Window, cx: &mut Context<Self>) {
+        self.hang_up(window, cx)
+    }
 }
 
 impl Render for CallPage {
@@ -171,6 +271,12 @@ impl Render for CallPage {
         let call_manager = cx.global::<LivekitCallManager>();
         let mute = call_manager.mute();
         let deaf = call_manager.deaf();
+        let push_to_talk = call_manager.push_to_talk();
+        let push_to_talk_mode = call_manager
+            .audio_processing_settings()
+            .read(cx)
+            .transmit_mode
+            == TransmitMode::PushToTalk;
 
         let (rows, cols) = match call_members.len() {
             1 => (1, 1),
@@ -189,9 +295,19 @@ impl Render for CallPage {
             PickerRequired::SystemPicker
         );
 
+        if self.needs_focus {
+            self.needs_focus = false;
+            self.focus_handle.focus(window, cx);
+        }
+
         let theme = cx.theme();
 
         div()
+            .track_focus(&self.focus_handle)
+            .key_context("CallPage")
+            .on_action(cx.listener(Self::toggle_camera))
+            .on_action(cx.listener(Self::toggle_screenshare))
+            .on_action(cx.listener(Self::hang_up_action))
             .size_full()
             .bg(rgb(0x000000))
             .flex()
@@ -211,6 +327,35 @@ impl Render for CallPage {
                         )
                     })),
             )
+            .when(matches!(call.state, CallState::Active { .. }), |david| {
+                david.child(
+                    div()
+                        .flex()
+                        .items_center()
+                        .justify_center()
+                        .p(px(4.))
+                        .gap(px(4.))
+                        .child(match call.encryption_state() {
+                            CallEncryptionState::Encrypted => icon_text(
+                                "security-high",
+                                tr!("CALL_ENCRYPTED", "This call is end-to-end encrypted"),
+                            ),
+                            CallEncryptionState::Unencrypted => icon_text(
+                                "security-low",
+                                tr!(
+                                    "CALL_NOT_ENCRYPTED",
+                                    "This call is not end-to-end encrypted"
+                                ),
+                            ),
+                        })
+                        .when(call.is_being_recorded(), |david| {
+                            david.child(icon_text(
+                                "media-record",
+                                tr!("CALL_BEING_RECORDED", "This call is being recorded"),
+                            ))
+                        }),
+                )
+            })
             .child(
                 div()
                     .flex_grow(1.)
@@ -455,25 +600,7 @@ impl Render for CallPage {
                                                     .checked_when(call.active_camera().is_some())
                                                     .on_click(cx.listener(
                                                         move |this, _, window, cx| {
-                                                            let call = this.call.read(cx);
-                                                            if call.active_camera().is_some() {
-                                                                this.call.update(cx, |call, cx| {
-                                                                    call.publish_track(
-                                                                        TrackType::Camera,
-                                                                        None,
-                                                                        cx,
-                                                                    )
-                                                                });
-                                                            } else {
-                                                                let call = this.call.clone();
-                                                                this.webcam_start_dialog.update(
-                                                                    cx,
-                                                                    |webcam_start_dialog, cx| {
-                                                                        webcam_start_dialog
-                                                                            .open(call, window, cx)
-                                                                    },
-                                                                )
-                                                            }
+                                                            this.camera(window, cx)
                                                         },
                                                     )),
                                             )
@@ -512,22 +639,69 @@ impl Render for CallPage {
                                                     }),
                                             ),
                                     )
+                                    .when(matches!(call.state, CallState::Active { .. }), |david| {
+                                        let recording = call.recording_path().is_some();
+                                        david.child(
+                                            button("record")
+                                                .p(px(16.))
+                                                .child(icon("media-record").size(24.))
+                                                .checked_when(recording)
+                                                .when_else(
+                                                    recording,
+                                                    |david| {
+                                                        david.on_click(cx.listener(
+                                                            |this, _, _, cx| {
+                                                                this.call.update(cx, |call, cx| {
+                                                                    call.stop_recording(cx)
+                                                                })
+                                                            },
+                                                        ))
+                                                    },
+                                                    |david| {
+                                                        david
+                                                            .with_menu_open_policy(
+                                                                ButtonMenuOpenPolicy::AnyClick,
+                                                            )
+                                                            .with_menu(record_menu(cx))
+                                                    },
+                                                ),
+                                        )
+                                    })
+                                    .when(push_to_talk_mode, |david| {
+                                        let talking = *push_to_talk.read(cx);
+                                        david.child(
+                                            div()
+                                                .id("push-to-talk")
+                                                .on_mouse_down(MouseButton::Left, {
+                                                    let push_to_talk = push_to_talk.clone();
+                                                    move |_, _, cx| push_to_talk.write(cx, true)
+                                                })
+                                                .on_mouse_up(MouseButton::Left, {
+                                                    let push_to_talk = push_to_talk.clone();
+                                                    move |_, _, cx| push_to_talk.write(cx, false)
+                                                })
+                                                .on_mouse_up_out(MouseButton::Left, {
+                                                    let push_to_talk = push_to_talk.clone();
+                                                    move |_, _, cx| push_to_talk.write(cx, false)
+                                                })
+                                                .child(
+                                                    button("push-to-talk-button")
+                                                        .p(px(16.))
+                                                        .child(icon_text(
+                                                            "mic-on",
+                                                            tr!("CALL_PUSH_TO_TALK", "Talk"),
+                                                        ))
+                                                        .checked_when(talking),
+                                                ),
+                                        )
+                                    })
                                     .child(
                                         button("hangup-call")
                                             .p(px(16.))
                                             .destructive()
                                             .child(icon("call-stop").size(24.))
                                             .on_click(cx.listener(move |this, _, window, cx| {
-                                                this.call.update(cx, |call, cx| {
-                                                    call.end_call(cx);
-                                                });
-                                                (this.on_surface_change)(
-                                                    &SurfaceChangeEvent {
-                                                        change: SurfaceChange::Pop,
-                                                    },
-                                                    window,
-                                                    cx,
-                                                )
+                                                this.hang_up(window, cx)
                                             })),
                                     ),
                             )
@@ -556,6 +730,29 @@ impl Render for CallPage {
     }
 }
 
+fn record_menu(cx: &Context<CallPage>) -> Vec<ContextMenuItem> {
+    vec![
+        ContextMenuItem::menu_item()
+            .label(tr!("CALL_RECORD_OGG_OPUS", "Record Audio as Ogg/Opus..."))
+            .on_triggered(cx.listener(|this, _, window, cx| {
+                this.start_recording(CallRecordingFormat::OggOpus, false, window, cx)
+            }))
+            .build(),
+        ContextMenuItem::menu_item()
+            .label(tr!("CALL_RECORD_WAV", "Record Audio as WAV..."))
+            .on_triggered(cx.listener(|this, _, window, cx| {
+                this.start_recording(CallRecordingFormat::Wav, false, window, cx)
+            }))
+            .build(),
+        ContextMenuItem::menu_item()
+            .label(tr!("CALL_RECORD_VIDEO", "Record Audio and Video..."))
+            .on_triggered(cx.listener(|this, _, window, cx| {
+                this.start_recording(CallRecordingFormat::OggOpus, true, window, cx)
+            }))
+            .build(),
+    ]
+}
+
 #[derive(IntoElement)]
 struct CallMemberDisplay {
     call: Entity<LivekitCall>,
@@ -623,6 +820,7 @@ impl RenderOnce for CallMemberDisplay {
             && matches!(call_member.camera_state, StreamState::Unavailable)
             && matches!(call_member.screenshare_state, StreamState::Unavailable);
         let is_muted = matches!(call_member.mic_state, StreamState::Off);
+        let is_recording = call_member.recording;
 
         let call = self.call.read(cx);
         let camera_image = match call_member.camera_state {
@@ -828,6 +1026,9 @@ impl RenderOnce for CallMemberDisplay {
                                         .flex_grow(1.)
                                         .child(display_name)
                                         .child(div().flex_grow(1.))
+                                        .when(is_recording, |david| {
+                                            david.child(icon("media-record"))
+                                        })
                                         .when(is_muted, |david| david.child(icon("mic-off"))),
                                 ),
                         ),
@@ -1,8 +1,12 @@
 use crate::TrackType;
+use crate::audio_processing::{AudioProcessingSettings, TransmitMode, VoiceActivitySensitivity};
 use crate::call_manager::{FocusUrl, LivekitCallManager};
+use crate::call_shortcuts::{CallShortcut, CallShortcuts, display_keystrokes};
+use crate::call_surface::call_start_page::call_shortcuts_dialog::CallShortcutsDialog;
 use crate::webcam::Webcam;
 use cntp_i18n::{tr, trn};
 use contemporary::components::button::{ButtonMenuOpenPolicy, button};
+use contemporary::components::checkbox::{CheckState, CheckedChangeEvent, checkbox};
 use contemporary::components::context_menu::ContextMenuItem;
 use contemporary::components::grandstand::grandstand;
 use contemporary::components::icon::icon;
@@ -31,6 +35,8 @@ use thegrid_common::room::active_call_participants::track_active_call_participan
 use thegrid_common::session::session_manager::SessionManager;
 use thegrid_common::surfaces::SurfaceChangeHandler;
 
+mod call_shortcuts_dialog;
+
 pub struct CallStartPage {
     room_id: OwnedRoomId,
     on_surface_change: Rc<Box<SurfaceChangeHandler>>,
@@ -43,6 +49,8 @@ pub struct CallStartPage {
 
     active_camera: Option<Entity<Webcam>>,
     camera_info: Option<Vec<CameraInfo>>,
+
+    call_shortcuts_dialog: Entity<CallShortcutsDialog>,
 }
 
 #[derive(Clone)]
@@ -94,6 +102,7 @@ impl CallStartPage {
             active_call_users,
             active_camera: None,
             camera_info: None,
+            call_shortcuts_dialog: cx.new(|cx| CallShortcutsDialog::new(cx)),
         };
         this.fetch_camera_info(cx);
         this
@@ -497,6 +506,177 @@ impl CallStartPage {
             )
     }
 
+    fn render_audio_processing_setup(
+        &mut self,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) -> impl IntoElement {
+        let settings_entity = cx
+            .global::<LivekitCallManager>()
+            .audio_processing_settings();
+        let settings = settings_entity.read(cx).clone();
+        let push_to_talk_shortcut = display_keystrokes(
+            cx.global::<CallShortcuts>()
+                .keystrokes(CallShortcut::PushToTalk),
+        );
+
+        let transmit_mode_menu = [
+            TransmitMode::Continuous,
+            TransmitMode::VoiceActivated,
+            TransmitMode::PushToTalk,
+        ]
+        .into_iter()
+        .map(|transmit_mode| {
+            let settings_entity = settings_entity.clone();
+            ContextMenuItem::menu_item()
+                .label(transmit_mode_label(transmit_mode))
+                .on_triggered(move |_, _, cx| {
+                    settings_entity.update(cx, |settings, cx| {
+                        settings.transmit_mode = transmit_mode;
+                        cx.notify();
+                    })
+                })
+                .build()
+        })
+        .collect::<Vec<_>>();
+
+        let sensitivity_menu = [
+            VoiceActivitySensitivity::Low,
+            VoiceActivitySensitivity::Medium,
+            VoiceActivitySensitivity::High,
+        ]
+        .into_iter()
+        .map(|sensitivity| {
+            let settings_entity = settings_entity.clone();
+            ContextMenuItem::menu_item()
+                .label(sensitivity_label(sensitivity))
+                .on_triggered(move |_, _, cx| {
+                    settings_entity.update(cx, |settings, cx| {
+                        settings.voice_activity_sensitivity = sensitivity;
+                        cx.notify();
+                    })
+                })
+                .build()
+        })
+        .collect::<Vec<_>>();
+
+        div()
+            .flex()
+            .flex_col()
+            .size_full()
+            .p(px(8.))
+            .child(subtitle(tr!("AUDIO_PROCESSING_SETUP", "Audio Processing")))
+            .child(
+                div()
+                    .flex()
+                    .gap(px(8.))
+                    .child(
+                        div()
+                            .flex()
+                            .flex_col()
+                            .gap(px(4.))
+                            .w(px(292.))
+                            .child(audio_processing_checkbox(
+                                "noise-suppression",
+                                tr!("AUDIO_PROCESSING_NOISE_SUPPRESSION", "Suppress noise")
+                                    .to_string(),
+                                settings.noise_suppression,
+                                settings_entity.clone(),
+                                |settings, enabled| settings.noise_suppression = enabled,
+                            ))
+                            .child(audio_processing_checkbox(
+                                "echo-cancellation",
+                                tr!("AUDIO_PROCESSING_ECHO_CANCELLATION", "Cancel echo")
+                                    .to_string(),
+                                settings.echo_cancellation,
+                                settings_entity.clone(),
+                                |settings, enabled| settings.echo_cancellation = enabled,
+                            ))
+                            .child(audio_processing_checkbox(
+                                "auto-gain-control",
+                                tr!(
+                                    "AUDIO_PROCESSING_AUTO_GAIN_CONTROL",
+                                    "Adjust volume automatically"
+                                )
+                                .to_string(),
+                                settings.auto_gain_control,
+                                settings_entity.clone(),
+                                |settings, enabled| settings.auto_gain_control = enabled,
+                            )),
+                    )
+                    .child(
+                        div()
+                            .flex()
+                            .flex_col()
+                            .gap(px(4.))
+                            .flex_grow(1.)
+                            .child(
+                                div()
+                                    .flex()
+                                    .items_center()
+                                    .child(tr!("AUDIO_PROCESSING_TRANSMIT_MODE", "Transmit"))
+                                    .child(div().flex_grow(1.))
+                                    .child(
+                                        button("transmit-mode")
+                                            .child(transmit_mode_label(settings.transmit_mode))
+                                            .with_menu(transmit_mode_menu),
+                                    ),
+                            )
+                            .when(
+                                settings.transmit_mode == TransmitMode::VoiceActivated,
+                                |david| {
+                                    david.child(
+                                        div()
+                                            .flex()
+                                            .items_center()
+                                            .child(tr!(
+                                                "AUDIO_PROCESSING_SENSITIVITY",
+                                                "Sensitivity"
+                                            ))
+                                            .child(div().flex_grow(1.))
+                                            .child(
+                                                button("voice-activity-sensitivity")
+                                                    .child(sensitivity_label(
+                                                        settings.voice_activity_sensitivity,
+                                                    ))
+                                                    .with_menu(sensitivity_menu),
+                                            ),
+                                    )
+                                },
+                            )
+                            .when(
+                                settings.transmit_mode == TransmitMode::PushToTalk,
+                                |david| {
+                                    david.child(tr!(
+                                        "AUDIO_PROCESSING_PUSH_TO_TALK_HINT",
+                                        "Hold the Talk button or {{shortcut}} during the call to \
+                                        speak",
+                                        shortcut = push_to_talk_shortcut
+                                    ))
+                                },
+                            )
+                            .child(div().flex_grow(1.))
+                            .child(
+                                div().flex().justify_end().child(
+                                    button("call-shortcuts")
+                                        .child(icon_text(
+                                            "configure",
+                                            tr!("CALL_SHORTCUTS_BUTTON", "Keyboard Shortcuts..."),
+                                        ))
+                                        .on_click(cx.listener(|this, _, _, cx| {
+                                            this.call_shortcuts_dialog.update(
+                                                cx,
+                                                |call_shortcuts_dialog, cx| {
+                                                    call_shortcuts_dialog.open(cx)
+                                                },
+                                            )
+                                        })),
+                                ),
+                            ),
+                    ),
+            )
+    }
+
     fn turn_on_camera(
         &mut self,
         camera_info: Option<CameraInfo>,
@@ -720,6 +900,13 @@ impl Render for CallStartPage {
                                             .w(px(300.)),
                                     ),
                             )
+                            .child(
+                                layer()
+                                    .border(px(1.))
+                                    .border_color(theme.border_color)
+                                    .child(self.render_audio_processing_setup(window, cx))
+                                    .w(px(608.)),
+                            )
                             .child(
                                 div()
                                     .flex()
@@ -781,5 +968,42 @@ impl Render for CallStartPage {
                             }),
                     ),
             )
+            .child(self.call_shortcuts_dialog.clone())
+    }
+}
+
+fn audio_processing_checkbox(
+    id: &'static str,
+    label: String,
+    checked: bool,
+    settings_entity: Entity<AudioProcessingSettings>,
+    set: fn(&mut AudioProcessingSettings, bool),
+) -> impl IntoElement {
+    checkbox(id)
+        .label(label)
+        .when(checked, |david| david.checked())
+        .on_checked_changed(move |event: &CheckedChangeEvent, _, cx| {
+            settings_entity.update(cx, |settings, cx| {
+                set(settings, event.check_state == CheckState::On);
+                cx.notify();
+            })
+        })
+}
+
+fn transmit_mode_label(transmit_mode: TransmitMode) -> String {
+    match transmit_mode {
+        TransmitMode::Continuous => tr!("TRANSMIT_MODE_CONTINUOUS", "Always"),
+        TransmitMode::VoiceActivated => tr!("TRANSMIT_MODE_VOICE_ACTIVATED", "When I speak"),
+        TransmitMode::PushToTalk => tr!("TRANSMIT_MODE_PUSH_TO_TALK", "Push to talk"),
+    }
+    .to_string()
+}
+
+fn sensitivity_label(sensitivity: VoiceActivitySensitivity) -> String {
+    match sensitivity {
+        VoiceActivitySensitivity::Low => tr!("VOICE_ACTIVITY_SENSITIVITY_LOW", "Low"),
+        VoiceActivitySensitivity::Medium => tr!("VOICE_ACTIVITY_SENSITIVITY_MEDIUM", "Medium"),
+        VoiceActivitySensitivity::High => tr!("VOICE_ACTIVITY_SENSITIVITY_HIGH", "High"),
     }
+    .to_string()
 }
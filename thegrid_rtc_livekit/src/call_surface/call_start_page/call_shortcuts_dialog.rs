@@ -0,0 +1,126 @@
+use crate::call_shortcuts::{CallShortcut, CallShortcuts, display_keystrokes};
+use cntp_i18n::tr;
+use contemporary::components::button::button;
+use contemporary::components::dialog_box::dialog_box;
+use contemporary::components::icon::icon;
+use contemporary::components::icon_text::icon_text;
+use gpui::{
+    BorrowAppContext, Context, FocusHandle, InteractiveElement, IntoElement, KeyDownEvent,
+    ParentElement, Render, Styled, Window, div, px,
+};
+
+pub struct CallShortcutsDialog {
+    visible: bool,
+    focus_handle: FocusHandle,
+
+    /// The shortcut that is waiting for the user to press its new keys
+    recording: Option<CallShortcut>,
+}
+
+impl CallShortcutsDialog {
+    pub fn new(cx: &mut Context<Self>) -> Self {
+        Self {
+            visible: false,
+            focus_handle: cx.focus_handle(),
+            recording: None,
+        }
+    }
+
+    pub fn open(&mut self, cx: &mut Context<Self>) {
+        self.visible = true;
+        self.recording = None;
+        cx.notify();
+    }
+
+    pub fn close(&mut self, cx: &mut Context<Self>) {
+        self.visible = false;
+        self.recording = None;
+        cx.notify();
+    }
+
+    fn key_down(&mut self, event: &KeyDownEvent, _: &mut Window, cx: &mut Context<Self>) {
+        let Some(shortcut) = self.recording.take() else {
+            return;
+        };
+
+        // Don't let the keys trigger anything else while we're recording them
+        cx.stop_propagation();
+        cx.notify();
+
+        if event.keystroke.key == "escape" && !event.keystroke.modifiers.modified() {
+            return;
+        }
+
+        let keystrokes = event.keystroke.unparse();
+        cx.update_global::<CallShortcuts, _>(|call_shortcuts, cx| {
+            call_shortcuts.set_keystrokes(shortcut, Some(keystrokes), cx);
+        });
+    }
+}
+
+impl Render for CallShortcutsDialog {
+    fn render(&mut self, _: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let call_shortcuts = cx.global::<CallShortcuts>();
+
+        dialog_box("call-shortcuts")
+            .visible(self.visible)
+            .title(tr!("CALL_SHORTCUTS_TITLE", "Keyboard Shortcuts"))
+            .content(
+                div()
+                    .track_focus(&self.focus_handle)
+                    .on_key_down(cx.listener(Self::key_down))
+                    .flex()
+                    .flex_col()
+                    .gap(px(4.))
+                    .w(px(400.))
+                    .child(tr!(
+                        "CALL_SHORTCUTS_DESCRIPTION",
+                        "These shortcuts work anywhere in theGrid while you're in a call. Click \
+                        a shortcut to change it."
+                    ))
+                    .children(CallShortcut::all().into_iter().enumerate().map(
+                        |(index, shortcut)| {
+                            div()
+                                .flex()
+                                .items_center()
+                                .gap(px(4.))
+                                .child(shortcut.label())
+                                .child(div().flex_grow(1.))
+                                .child(
+                                    button(("call-shortcut", index))
+                                        .child(if self.recording == Some(shortcut) {
+                                            tr!("CALL_SHORTCUTS_RECORDING", "Press keys...")
+                                                .to_string()
+                                        } else {
+                                            display_keystrokes(call_shortcuts.keystrokes(shortcut))
+                                        })
+                                        .checked_when(self.recording == Some(shortcut))
+                                        .on_click(cx.listener(move |this, _, window, cx| {
+                                            this.recording = Some(shortcut);
+                                            this.focus_handle.focus(window, cx);
+                                            cx.notify();
+                                        })),
+                                )
+                                .child(
+                                    button(("call-shortcut-reset", index))
+                                        .child(icon("edit-undo"))
+                                        .on_click(cx.listener(move |_, _, _, cx| {
+                                            cx.update_global::<CallShortcuts, _>(
+                                                |call_shortcuts, cx| {
+                                                    call_shortcuts
+                                                        .set_keystrokes(shortcut, None, cx);
+                                                },
+                                            );
+                                            cx.notify();
+                                        })),
+                                )
+                        },
+                    )),
+            )
+            .button(
+                button("call-shortcuts-done")
+                    .child(icon_text("dialog-ok", tr!("CALL_SHORTCUTS_DONE", "Done")))
+                    .on_click(cx.listener(|this, _, _, cx| this.close(cx))),
+            )
+    }
+}
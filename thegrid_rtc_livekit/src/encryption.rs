@@ -0,0 +1,282 @@
+use base64::Engine;
+use base64::prelude::BASE64_STANDARD_NO_PAD;
+use gpui::http_client::anyhow;
+use gpui::private::anyhow;
+use livekit::e2ee::key_provider::{KeyProvider, KeyProviderOptions};
+use livekit::e2ee::manager::E2eeManager;
+use livekit::e2ee::{E2eeOptions, EncryptionType};
+use livekit::prelude::ParticipantIdentity;
+use log::{info, warn};
+use matrix_sdk::Room;
+use matrix_sdk::crypto::CollectStrategy;
+use matrix_sdk::deserialized_responses::EncryptionInfo;
+use matrix_sdk::event_handler::EventHandlerHandle;
+use matrix_sdk::ruma::events::macros::EventContent;
+use matrix_sdk::ruma::events::{AnyToDeviceEventContent, ToDeviceEvent};
+use matrix_sdk::ruma::serde::Raw;
+use matrix_sdk::ruma::{OwnedDeviceId, OwnedRoomId, OwnedUserId, UserId};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The length of the media keys we generate, in bytes
+const KEY_LENGTH: usize = 16;
+
+/// The number of key slots each participant has in the key provider
+const KEY_RING_SIZE: i32 = 16;
+
+/// How long to wait after sending a new key before using it, so that everyone has it in time
+pub const KEY_USE_DELAY: Duration = Duration::from_secs(5);
+
+const CALL_ENCRYPTION_KEYS_EVENT_TYPE: &str = "io.element.call.encryption_keys";
+
+/// The media key of a call member, sent straight to the devices of the other call members.
+///
+/// This is the same event that Element Call uses, so we can take part in its encrypted calls.
+#[derive(Clone, Debug, Deserialize, Serialize, EventContent)]
+#[ruma_event(type = "io.element.call.encryption_keys", kind = ToDevice)]
+pub struct CallEncryptionKeysEventContent {
+    pub keys: CallEncryptionKey,
+    pub member: CallEncryptionKeysMember,
+    pub room_id: OwnedRoomId,
+    pub session: CallEncryptionKeysSession,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sent_ts: Option<u64>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct CallEncryptionKey {
+    pub index: i32,
+    pub key: String,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct CallEncryptionKeysMember {
+    pub claimed_device_id: OwnedDeviceId,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct CallEncryptionKeysSession {
+    pub application: String,
+    pub call_id: String,
+    pub scope: String,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum CallEncryptionState {
+    /// Media is only protected between us and the SFU
+    Unencrypted,
+
+    /// Media is encrypted end to end with keys that only call members have
+    Encrypted,
+}
+
+/// Per-participant media encryption for a MatrixRTC call.
+///
+/// Each call member encrypts their media with their own key and sends it in encrypted to-device
+/// messages to the devices in the call, so that nobody else in the room gets it. The keys of the
+/// other members are fed into the LiveKit key provider as they arrive.
+pub struct CallEncryption {
+    room: Room,
+    key_provider: KeyProvider,
+    own_identity: ParticipantIdentity,
+    own_device_id: OwnedDeviceId,
+    own_key_index: i32,
+    own_key: Vec<u8>,
+    e2ee_manager: Option<E2eeManager>,
+    event_handler: EventHandlerHandle,
+}
+
+impl CallEncryption {
+    pub fn new(room: Room, user_id: &UserId, device_id: OwnedDeviceId) -> anyhow::Result<Self> {
+        let key_provider = KeyProvider::new(KeyProviderOptions::default());
+
+        let own_identity = livekit_identity(user_id, &device_id);
+        let own_key = generate_key()?;
+        key_provider.set_key(&own_identity, 0, own_key.clone());
+
+        let event_handler = room.client().add_event_handler({
+            let room_id = room.room_id().to_owned();
+            let key_provider = key_provider.clone();
+            let own_identity = own_identity.clone();
+            move |event: ToDeviceEvent<CallEncryptionKeysEventContent>,
+                  encryption_info: Option<EncryptionInfo>| {
+                let room_id = room_id.clone();
+                let key_provider = key_provider.clone();
+                let own_identity = own_identity.clone();
+                async move {
+                    if event.content.room_id != room_id {
+                        return;
+                    }
+
+                    // Only trust keys that were encrypted by the device they claim to be from
+                    let Some(encryption_info) = encryption_info else {
+                        warn!(
+                            "Ignoring an unencrypted call encryption key from {}",
+                            event.sender
+                        );
+                        return;
+                    };
+                    let device_id = event.content.member.claimed_device_id;
+                    if encryption_info.sender != event.sender
+                        || encryption_info.sender_device.as_ref() != Some(&device_id)
+                    {
+                        warn!("Ignoring a call encryption key sent on behalf of another device");
+                        return;
+                    }
+
+                    let identity = livekit_identity(&event.sender, &device_id);
+                    if identity == own_identity {
+                        return;
+                    }
+
+                    let key = event.content.keys;
+                    let Ok(key_bytes) =
+                        BASE64_STANDARD_NO_PAD.decode(key.key.trim_end_matches('='))
+                    else {
+                        warn!("Received an invalid call encryption key from {identity:?}");
+                        return;
+                    };
+
+                    info!(
+                        "Received call encryption key {} for {identity:?}",
+                        key.index
+                    );
+                    key_provider.set_key(&identity, key.index, key_bytes);
+                }
+            }
+        });
+
+        Ok(Self {
+            room,
+            key_provider,
+            own_identity,
+            own_device_id: device_id,
+            own_key_index: 0,
+            own_key,
+            e2ee_manager: None,
+            event_handler,
+        })
+    }
+
+    pub fn e2ee_options(&self) -> E2eeOptions {
+        E2eeOptions {
+            encryption_type: EncryptionType::Gcm,
+            key_provider: self.key_provider.clone(),
+        }
+    }
+
+    /// Start encrypting and decrypting media in the LiveKit room
+    pub fn set_e2ee_manager(&mut self, e2ee_manager: E2eeManager) {
+        e2ee_manager.set_enabled(true);
+        self.e2ee_manager = Some(e2ee_manager);
+        self.use_own_key_index();
+    }
+
+    /// The event that tells the other call members about our current key
+    pub fn key_event(&self) -> CallEncryptionKeysEventContent {
+        CallEncryptionKeysEventContent {
+            keys: CallEncryptionKey {
+                index: self.own_key_index,
+                key: BASE64_STANDARD_NO_PAD.encode(&self.own_key),
+            },
+            member: CallEncryptionKeysMember {
+                claimed_device_id: self.own_device_id.clone(),
+            },
+            room_id: self.room.room_id().to_owned(),
+            session: CallEncryptionKeysSession {
+                application: "m.call".to_string(),
+                call_id: "".to_string(),
+                scope: "m.room".to_string(),
+            },
+            sent_ts: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .ok()
+                .map(|duration| duration.as_millis() as u64),
+        }
+    }
+
+    /// Replace our key with a new one, so that members who have left can't decrypt our media.
+    ///
+    /// The new key should be sent to the other members, and then used after [`KEY_USE_DELAY`].
+    pub fn rotate_key(&mut self) -> anyhow::Result<i32> {
+        self.own_key = generate_key()?;
+        self.own_key_index = (self.own_key_index + 1) % KEY_RING_SIZE;
+        self.key_provider
+            .set_key(&self.own_identity, self.own_key_index, self.own_key.clone());
+        Ok(self.own_key_index)
+    }
+
+    /// Encrypt our outgoing media with our current key
+    pub fn use_own_key_index(&self) {
+        let Some(e2ee_manager) = &self.e2ee_manager else {
+            return;
+        };
+
+        for ((identity, _), frame_cryptor) in e2ee_manager.frame_cryptors() {
+            if identity == self.own_identity {
+                frame_cryptor.set_key_index(self.own_key_index);
+            }
+        }
+    }
+
+    pub fn own_key_index(&self) -> i32 {
+        self.own_key_index
+    }
+
+    pub fn room(&self) -> &Room {
+        &self.room
+    }
+}
+
+impl Drop for CallEncryption {
+    fn drop(&mut self) {
+        self.room
+            .client()
+            .remove_event_handler(self.event_handler.clone());
+    }
+}
+
+/// Send our key to the given call member devices, encrypted for each of them
+pub async fn send_key_event(
+    room: Room,
+    content: CallEncryptionKeysEventContent,
+    recipients: Vec<(OwnedUserId, OwnedDeviceId)>,
+) -> anyhow::Result<()> {
+    let encryption = room.client().encryption();
+
+    let mut devices = Vec::new();
+    for (user_id, device_id) in recipients {
+        match encryption.get_device(&user_id, &device_id).await? {
+            Some(device) => devices.push(device),
+            None => warn!("Unable to send call encryption key to unknown device {device_id}"),
+        }
+    }
+    if devices.is_empty() {
+        return Ok(());
+    }
+
+    let content: Raw<AnyToDeviceEventContent> = Raw::new(&content)?.cast_unchecked();
+    let failures = encryption
+        .encrypt_and_send_raw_to_device(
+            devices.iter().collect(),
+            CALL_ENCRYPTION_KEYS_EVENT_TYPE,
+            content,
+            CollectStrategy::AllDevices,
+        )
+        .await?;
+    for (user_id, device_id) in failures {
+        warn!("Unable to send call encryption key to {user_id} {device_id}");
+    }
+
+    Ok(())
+}
+
+fn livekit_identity(user_id: &UserId, device_id: &OwnedDeviceId) -> ParticipantIdentity {
+    ParticipantIdentity(format!("{user_id}:{device_id}"))
+}
+
+fn generate_key() -> anyhow::Result<Vec<u8>> {
+    let mut key = vec![0; KEY_LENGTH];
+    getrandom::fill(&mut key).map_err(|e| anyhow!("Unable to generate key: {e}"))?;
+    Ok(key)
+}
@@ -0,0 +1,150 @@
+use crate::call_manager::LivekitCallManager;
+use cntp_i18n::tr;
+use contemporary::components::admonition::{AdmonitionSeverity, admonition};
+use contemporary::components::button::button;
+use contemporary::components::icon_text::icon_text;
+use contemporary::styling::theme::ThemeStorage;
+use gpui::{
+    App, BorrowAppContext, IntoElement, ParentElement, RenderOnce, Styled, Window, div, px,
+};
+use std::rc::Rc;
+use thegrid_common::mxc_image::{SizePolicy, mxc_image};
+use thegrid_common::session::incoming_calls_cache::IncomingCall;
+use thegrid_common::session::session_manager::SessionManager;
+use thegrid_common::surfaces::{
+    MainWindowSurface, SurfaceChange, SurfaceChangeEvent, SurfaceChangeHandler,
+};
+
+#[derive(IntoElement)]
+pub struct IncomingCallSidebarAlert {
+    incoming_call: IncomingCall,
+    on_surface_change: Rc<Box<SurfaceChangeHandler>>,
+}
+
+pub fn incoming_call_sidebar_alert(
+    incoming_call: IncomingCall,
+    on_surface_change: Rc<Box<SurfaceChangeHandler>>,
+) -> IncomingCallSidebarAlert {
+    IncomingCallSidebarAlert {
+        incoming_call,
+        on_surface_change,
+    }
+}
+
+impl RenderOnce for IncomingCallSidebarAlert {
+    fn render(self, window: &mut Window, cx: &mut App) -> impl IntoElement {
+        let theme = cx.theme();
+        let session_manager = cx.global::<SessionManager>();
+        let Some(room) = session_manager
+            .rooms()
+            .read(cx)
+            .room(&self.incoming_call.room_id)
+        else {
+            return div();
+        };
+        let room = room.read(cx);
+
+        let accept_room_id = self.incoming_call.room_id.clone();
+        let decline_room_id = self.incoming_call.room_id.clone();
+        let on_surface_change = self.on_surface_change.clone();
+
+        div().child(
+            admonition()
+                .title(if self.incoming_call.ring {
+                    tr!("INCOMING_CALL", "Incoming Call")
+                } else {
+                    tr!("CALL_STARTED", "Call Started")
+                })
+                .severity(AdmonitionSeverity::Info)
+                .child(
+                    div()
+                        .flex()
+                        .flex_col()
+                        .gap(px(4.))
+                        .child(
+                            div()
+                                .flex()
+                                .items_center()
+                                .gap(px(4.))
+                                .child(
+                                    mxc_image(room.inner.avatar_url())
+                                        .fallback_image(room.inner.room_id())
+                                        .fixed_square(px(24.))
+                                        .size_policy(SizePolicy::Fit)
+                                        .rounded(theme.border_radius),
+                                )
+                                .child(room.display_name()),
+                        )
+                        .child(if self.incoming_call.ring {
+                            tr!(
+                                "INCOMING_CALL_DESCRIPTION",
+                                "{{user}} is calling you",
+                                user = self.incoming_call.sender_name
+                            )
+                        } else {
+                            tr!(
+                                "CALL_STARTED_DESCRIPTION",
+                                "{{user}} started a call",
+                                user = self.incoming_call.sender_name
+                            )
+                        })
+                        .child(
+                            div()
+                                .flex()
+                                .bg(theme.button_background)
+                                .rounded(theme.border_radius)
+                                .child(
+                                    button("incoming-call-accept")
+                                        .child(icon_text(
+                                            "call-start",
+                                            tr!("INCOMING_CALL_ACCEPT", "Accept"),
+                                        ))
+                                        .flex_grow(1.)
+                                        .on_click(move |_, window, cx| {
+                                            let incoming_calls =
+                                                cx.global::<SessionManager>().incoming_calls();
+                                            incoming_calls.update(cx, |incoming_calls, cx| {
+                                                incoming_calls.dismiss(&accept_room_id, cx)
+                                            });
+
+                                            cx.update_global::<LivekitCallManager, _>(
+                                                |call_manager, cx| {
+                                                    call_manager
+                                                        .answer_call(accept_room_id.clone(), cx);
+                                                },
+                                            );
+
+                                            on_surface_change(
+                                                &SurfaceChangeEvent {
+                                                    change: SurfaceChange::Push(
+                                                        MainWindowSurface::Call(
+                                                            accept_room_id.clone(),
+                                                        ),
+                                                    ),
+                                                },
+                                                window,
+                                                cx,
+                                            )
+                                        }),
+                                )
+                                .child(
+                                    button("incoming-call-decline")
+                                        .destructive()
+                                        .child(icon_text(
+                                            "call-stop",
+                                            tr!("INCOMING_CALL_DECLINE", "Decline"),
+                                        ))
+                                        .flex_grow(1.)
+                                        .on_click(move |_, _, cx| {
+                                            let incoming_calls =
+                                                cx.global::<SessionManager>().incoming_calls();
+                                            incoming_calls.update(cx, |incoming_calls, cx| {
+                                                incoming_calls.dismiss(&decline_room_id, cx)
+                                            });
+                                        }),
+                                ),
+                        ),
+                ),
+        )
+    }
+}
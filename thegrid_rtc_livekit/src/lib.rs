@@ -1,17 +1,25 @@
 use async_ringbuf::traits::{AsyncProducer, Consumer, Producer, Split};
 use std::collections::{HashMap, HashSet};
 pub mod active_call_sidebar_alert;
+pub mod audio_processing;
 pub mod call_disconnect_confirmation_dialog;
 pub mod call_manager;
+pub mod call_recorder;
+pub mod call_shortcuts;
 pub mod call_surface;
+pub mod encryption;
 mod focus;
-mod mic;
+pub mod incoming_call_sidebar_alert;
+pub mod mic;
 pub mod rtc_audio_stream_source;
+pub mod voice_recorder;
 mod webcam;
 
 use crate::call_manager::LivekitCallManager;
+use crate::call_recorder::{CallRecorder, CallRecordingFormat, RECORDING_INTERVAL};
+use crate::encryption::{send_key_event, CallEncryption, CallEncryptionState, KEY_USE_DELAY};
 use crate::focus::{get_focus_url, FocusUrlError};
-use crate::mic::open_mic;
+use crate::mic::open_call_mic;
 use crate::rtc_audio_stream_source::RtcAudioStreamSource;
 use crate::webcam::Webcam;
 use async_ringbuf::consumer::AsyncConsumer;
@@ -25,7 +33,8 @@ use cpal::Host;
 use gpui::http_client::anyhow;
 use gpui::private::{anyhow, serde_json};
 use gpui::{
-    App, AppContext, AsyncApp, BorrowAppContext, Context, Entity, Image, RenderImage, WeakEntity,
+    App, AppContext, AsyncApp, BorrowAppContext, Context, Entity, Image, RenderImage, Task,
+    WeakEntity,
 };
 use image::{Frame, RgbaImage};
 use livekit::id::TrackSid;
@@ -55,11 +64,13 @@ use matrix_sdk::ruma::events::call::member::{
     ActiveFocus, ActiveLivekitFocus, Application, CallApplicationContent, CallMemberEvent,
     CallMemberEventContent, CallMemberStateKey, CallScope, Focus, FocusSelection, LivekitFocus,
 };
-use matrix_sdk::ruma::events::rtc::notification::RtcNotificationEvent;
-use matrix_sdk::ruma::events::{AnySyncStateEvent, StateEventType};
+use matrix_sdk::ruma::events::rtc::notification::{NotificationType, RtcNotificationEventContent};
+use matrix_sdk::ruma::events::{AnySyncStateEvent, Mentions, StateEventType};
 use matrix_sdk::ruma::exports::serde_json::json;
 use matrix_sdk::ruma::serde::Raw;
-use matrix_sdk::ruma::{OwnedDeviceId, OwnedRoomId, OwnedUserId, RoomId, UserId};
+use matrix_sdk::ruma::{
+    MilliSecondsSinceUnixEpoch, OwnedDeviceId, OwnedRoomId, OwnedUserId, RoomId, UserId,
+};
 use matrix_sdk::stream::StreamExt;
 use matrix_sdk::{reqwest, HttpError};
 use nokhwa::utils::FrameFormat;
@@ -68,6 +79,7 @@ use ringbuffer::RingBuffer;
 use serde::{Deserialize, Serialize};
 use smallvec::smallvec;
 use std::fmt::Display;
+use std::path::{Path, PathBuf};
 use std::rc::Weak;
 use std::sync::{Arc, Mutex, RwLock};
 use std::thread;
@@ -84,6 +96,12 @@ use yuv::{
     YuvRange, YuvStandardMatrix,
 };
 
+/// How long other clients should ring for when we start a call
+const CALL_NOTIFICATION_LIFETIME: Duration = Duration::from_secs(30);
+
+/// The LiveKit participant attribute that tells everyone else in the call that we're recording it
+const RECORDING_ATTRIBUTE: &str = "com.vicr123.thegrid.recording";
+
 pub fn setup_thegrid_rtc_livekit(cx: &mut App) {
     I18N_MANAGER.load_source(tr_load!());
 
@@ -113,6 +131,14 @@ pub struct LivekitCall {
     cancellation_source: CancellationTokenSource,
     started_at: Instant,
 
+    encryption: Option<CallEncryption>,
+    encryption_participants: HashSet<(OwnedUserId, OwnedDeviceId)>,
+
+    recorder: Option<CallRecorder>,
+    recording_task: Option<Task<()>>,
+    recording_participants: HashSet<(OwnedUserId, OwnedDeviceId)>,
+    focused_member: Option<(OwnedUserId, Option<OwnedDeviceId>)>,
+
     on_hold: bool,
 }
 
@@ -125,6 +151,9 @@ pub struct CallMember {
     screenshare_state: StreamState,
 
     mic_active: bool,
+
+    /// Whether this member has told everyone that they're recording the call
+    recording: bool,
 }
 
 #[derive(Clone, Copy, Hash, PartialEq, Eq, Debug)]
@@ -211,18 +240,30 @@ impl LivekitCall {
         let session_manager = cx.global::<SessionManager>();
         let client = session_manager.client().unwrap().read(cx).clone();
         let user_id = client.user_id().unwrap().to_owned();
-        let room = session_manager
+        let cached_room = session_manager
             .rooms()
             .read(cx)
             .room(&room_id)
             .unwrap()
-            .read(cx)
-            .inner
-            .clone();
+            .read(cx);
+        let is_direct = cached_room.is_direct();
+        let room = cached_room.inner.clone();
         let device_id = client.device_id().unwrap().to_owned();
 
         let rtc_foci = session_manager.rtc_foci().clone();
 
+        // Encrypted rooms get encrypted calls, with the keys shared through the room
+        let encryption = if room.encryption_state().is_encrypted() {
+            CallEncryption::new(room.clone(), &user_id, device_id.clone())
+                .inspect_err(|e| error!("Unable to set up call encryption: {e:?}"))
+                .ok()
+        } else {
+            None
+        };
+        let e2ee_options = encryption
+            .as_ref()
+            .map(|encryption| encryption.e2ee_options());
+
         let cancellation_source = CancellationTokenSource::new();
         let cancellation_token = cancellation_source.token();
 
@@ -341,6 +382,10 @@ impl LivekitCall {
                     return;
                 };
 
+                // If nobody else is here, we're the ones starting the call
+                let starting_call = room.active_room_call_participants().is_empty();
+                let notification_room = room.clone();
+
                 if let Err(e) = cx
                     .spawn_tokio(async move {
                         room.send_state_event_for_key(
@@ -378,11 +423,36 @@ impl LivekitCall {
                     return;
                 };
 
+                if starting_call {
+                    // Let the other members know about the call so that their clients ring
+                    let mut content = RtcNotificationEventContent::new(
+                        MilliSecondsSinceUnixEpoch::now(),
+                        CALL_NOTIFICATION_LIFETIME,
+                        if is_direct {
+                            NotificationType::Ring
+                        } else {
+                            NotificationType::Notification
+                        },
+                    );
+                    content.mentions = Some(Mentions::with_room_mention());
+
+                    cx.spawn(async move |cx: &mut AsyncApp| {
+                        if let Err(e) = cx
+                            .spawn_tokio(async move { notification_room.send(content).await })
+                            .await
+                        {
+                            error!("Unable to send call notification: {:?}", e);
+                        }
+                    })
+                    .detach();
+                }
+
                 let (livekit_room, mut room_events) = match cx
                     .spawn_tokio(async move {
                         let mut room_options = RoomOptions::default();
                         room_options.auto_subscribe = true;
                         room_options.adaptive_stream = true;
+                        room_options.e2ee = e2ee_options;
                         Room::connect(&livekit_jwt.url, &livekit_jwt.jwt, room_options).await
                     })
                     .await
@@ -399,6 +469,15 @@ impl LivekitCall {
                 };
 
                 let local_participant = livekit_room.local_participant();
+                let e2ee_manager = livekit_room.e2ee_manager().clone();
+                let remote_participants = livekit_room
+                    .remote_participants()
+                    .into_keys()
+                    .filter_map(|identity| {
+                        let identity: String = identity.into();
+                        decode_livekit_identity(&identity).ok()
+                    })
+                    .collect::<HashSet<_>>();
 
                 cx.spawn(async move |cx: &mut AsyncApp| {
                     cancellation_token.wait().await;
@@ -434,6 +513,11 @@ impl LivekitCall {
 
                                 if weak_this_clone
                                     .update(cx, |this, cx| {
+                                        this.update_participant_recording(
+                                            user_id.clone(),
+                                            device_id.clone(),
+                                            &participant.attributes(),
+                                        );
                                         this.subscribed_streams.push(SubscribedStream {
                                             stream_sid: track.sid(),
                                             user_id,
@@ -498,6 +582,66 @@ impl LivekitCall {
                                     return;
                                 }
                             }
+                            RoomEvent::ParticipantAttributesChanged { participant, .. } => {
+                                let identity: String = participant.identity().into();
+                                let Ok((user_id, device_id)) = decode_livekit_identity(&identity)
+                                else {
+                                    continue;
+                                };
+
+                                if weak_this_clone
+                                    .update(cx, |this, cx| {
+                                        this.update_participant_recording(
+                                            user_id,
+                                            device_id,
+                                            &participant.attributes(),
+                                        );
+                                        cx.notify();
+                                    })
+                                    .is_err()
+                                {
+                                    // TODO: End call?
+                                    return;
+                                }
+                            }
+                            RoomEvent::ParticipantConnected(participant) => {
+                                let identity: String = participant.identity().into();
+                                let Ok(participant) = decode_livekit_identity(&identity) else {
+                                    continue;
+                                };
+
+                                if weak_this_clone
+                                    .update(cx, |this, cx| {
+                                        let mut participants = this.encryption_participants.clone();
+                                        participants.insert(participant);
+                                        this.call_participants_changed(participants, cx);
+                                    })
+                                    .is_err()
+                                {
+                                    // TODO: End call?
+                                    return;
+                                }
+                            }
+                            RoomEvent::ParticipantDisconnected(participant) => {
+                                let identity: String = participant.identity().into();
+                                let Ok(participant) = decode_livekit_identity(&identity) else {
+                                    continue;
+                                };
+
+                                if weak_this_clone
+                                    .update(cx, |this, cx| {
+                                        this.recording_participants.remove(&participant);
+                                        let mut participants = this.encryption_participants.clone();
+                                        participants.remove(&participant);
+                                        this.call_participants_changed(participants, cx);
+                                        cx.notify();
+                                    })
+                                    .is_err()
+                                {
+                                    // TODO: End call?
+                                    return;
+                                }
+                            }
                             RoomEvent::ActiveSpeakersChanged { speakers } => {
                                 if weak_this_clone
                                     .update(cx, |this, cx| {
@@ -532,6 +676,12 @@ impl LivekitCall {
                     this.started_at = Instant::now();
                     cx.notify();
 
+                    if let Some(encryption) = &mut this.encryption {
+                        encryption.set_e2ee_manager(e2ee_manager);
+                        this.encryption_participants = remote_participants;
+                        this.send_encryption_key(cx);
+                    }
+
                     this.setup_local_mic(cx);
 
                     for (track_type, track_device) in initial_streams {
@@ -581,6 +731,12 @@ impl LivekitCall {
             state: CallState::Connecting,
             cancellation_source,
             started_at: Instant::now(),
+            encryption,
+            encryption_participants: HashSet::new(),
+            recorder: None,
+            recording_task: None,
+            recording_participants: HashSet::new(),
+            focused_member: None,
             our_track_sids: HashMap::new(),
             active_call_participants_state,
             subscribed_streams: Vec::new(),
@@ -715,6 +871,9 @@ impl LivekitCall {
                             call.our_track_sids.insert(track_type, sid.clone());
                             call.active_devices
                                 .insert(sid.clone(), device_entity_clone.clone());
+                            if let Some(encryption) = &call.encryption {
+                                encryption.use_own_key_index();
+                            }
                             cx.notify();
 
                             cx.observe(&device_entity_clone, move |call, device_entity, cx| {
@@ -829,6 +988,9 @@ impl LivekitCall {
                             call.our_track_sids.insert(track_type, sid.clone());
                             call.active_devices
                                 .insert(sid.clone(), device_entity_clone.clone());
+                            if let Some(encryption) = &call.encryption {
+                                encryption.use_own_key_index();
+                            }
                             cx.notify();
 
                             cx.observe(&device_entity_clone, move |call, device_entity, cx| {
@@ -845,6 +1007,16 @@ impl LivekitCall {
                                     return;
                                 };
 
+                                if matches!(track_type, TrackType::Mic) {
+                                    let muted = *cx.global::<LivekitCallManager>().mute().read(cx)
+                                        || call.on_hold;
+                                    if let Some(recorder) = &mut call.recorder
+                                        && !muted
+                                    {
+                                        recorder.push_mic(&samples, sample_rate, channels as u16);
+                                    }
+                                }
+
                                 let audio_frame = AudioFrame {
                                     num_channels: channels as u32,
                                     sample_rate,
@@ -921,6 +1093,7 @@ impl LivekitCall {
                     screenshare_state: StreamState::Unavailable,
                     camera_state: StreamState::Unavailable,
                     mic_active: false,
+                    recording: self.recording_participants.contains(tuple),
                 };
 
                 for stream in subscribed_streams {
@@ -953,9 +1126,9 @@ impl LivekitCall {
                 call_members.push(call_member);
                 devices.remove(&tuple);
             } else {
-                let (mic_state, camera_state, screenshare_state) = if !this_device_processed
-                    && participant.user_id() == this_user_id
-                {
+                let is_this_device =
+                    !this_device_processed && participant.user_id() == this_user_id;
+                let (mic_state, camera_state, screenshare_state) = if is_this_device {
                     this_device_processed = true;
                     (
                         if muted {
@@ -991,6 +1164,7 @@ impl LivekitCall {
                     camera_state,
                     screenshare_state,
                     mic_active: false,
+                    recording: is_this_device && self.recorder.is_some(),
                 });
             };
         }
@@ -1001,7 +1175,7 @@ impl LivekitCall {
         let call_manager = cx.global::<LivekitCallManager>();
         let track = call_manager.active_input_device().read(cx).clone();
 
-        let outbound_track = track.map(|device| open_mic(&device, cx));
+        let outbound_track = track.map(|device| open_call_mic(&device, cx));
         self.publish_track(TrackType::Mic, outbound_track, cx);
     }
 
@@ -1032,6 +1206,8 @@ impl LivekitCall {
         let call_manager = cx.global::<LivekitCallManager>();
         let call_manager_deaf = call_manager.deaf();
         let volumes = call_manager.volumes();
+        let echo_reference = call_manager.echo_reference();
+        let recording_mix = call_manager.recording_mix();
         let subscribed_stream = self
             .subscribed_streams
             .iter()
@@ -1064,6 +1240,8 @@ impl LivekitCall {
                 subscribed_stream.user_id.to_owned(),
                 subscribed_stream.device_id.to_owned(),
                 subscribed_stream.source,
+                echo_reference.writer(sample_rate.get(), channels.get()),
+                recording_mix.writer(sample_rate.get(), channels.get()),
                 cancellation_source.clone(),
                 cx,
             );
@@ -1189,9 +1367,12 @@ impl LivekitCall {
     }
 
     pub fn end_call(&mut self, cx: &mut Context<Self>) {
+        self.recorder = None;
+        self.recording_task = None;
         self.cancellation_source.cancel();
         self.our_track_sids.clear();
         self.active_devices.clear();
+        self.encryption = None;
 
         let session_manager = cx.global::<SessionManager>();
         let user_id = session_manager
@@ -1239,6 +1420,232 @@ impl LivekitCall {
         &self.state
     }
 
+    pub fn encryption_state(&self) -> CallEncryptionState {
+        if self.encryption.is_some() {
+            CallEncryptionState::Encrypted
+        } else {
+            CallEncryptionState::Unencrypted
+        }
+    }
+
+    fn send_encryption_key(&mut self, cx: &mut Context<Self>) {
+        let Some(encryption) = &self.encryption else {
+            return;
+        };
+
+        // Only the devices in the call get the key, rather than everyone in the room
+        let room = encryption.room().clone();
+        let content = encryption.key_event();
+        let recipients = self.encryption_participants.iter().cloned().collect();
+        cx.spawn(async move |_: WeakEntity<Self>, cx: &mut AsyncApp| {
+            if let Err(e) = cx
+                .spawn_tokio(async move { send_key_event(room, content, recipients).await })
+                .await
+            {
+                error!("Unable to send call encryption key: {:?}", e);
+            }
+        })
+        .detach();
+    }
+
+    /// Send or rotate the encryption key when devices join or leave the call.
+    ///
+    /// Participants are tracked per device, so that the key also rotates when someone leaves from
+    /// one of their devices but stays in the call on another.
+    fn call_participants_changed(
+        &mut self,
+        participants: HashSet<(OwnedUserId, OwnedDeviceId)>,
+        cx: &mut Context<Self>,
+    ) {
+        if self.encryption.is_none() || !matches!(self.state, CallState::Active { .. }) {
+            return;
+        }
+
+        let someone_left = self
+            .encryption_participants
+            .difference(&participants)
+            .next()
+            .is_some();
+        let someone_joined = participants
+            .difference(&self.encryption_participants)
+            .next()
+            .is_some();
+        self.encryption_participants = participants;
+
+        if someone_left {
+            // Anyone who left still has our old key, so start using a new one
+            self.rotate_encryption_key(cx);
+        } else if someone_joined {
+            self.send_encryption_key(cx);
+        }
+    }
+
+    fn rotate_encryption_key(&mut self, cx: &mut Context<Self>) {
+        let Some(encryption) = &mut self.encryption else {
+            return;
+        };
+
+        let key_index = match encryption.rotate_key() {
+            Ok(key_index) => key_index,
+            Err(e) => {
+                error!("Unable to rotate call encryption key: {e:?}");
+                return;
+            }
+        };
+        self.send_encryption_key(cx);
+
+        cx.spawn(
+            async move |weak_this: WeakEntity<Self>, cx: &mut AsyncApp| {
+                cx.background_executor().timer(KEY_USE_DELAY).await;
+                let _ = weak_this.update(cx, |this, cx| {
+                    if let Some(encryption) = &this.encryption
+                        && encryption.own_key_index() == key_index
+                    {
+                        encryption.use_own_key_index();
+                    }
+                });
+            },
+        )
+        .detach();
+    }
+
+    /// Start recording the call to a file on this device, and let everyone else know about it
+    pub fn start_recording(
+        &mut self,
+        path: PathBuf,
+        format: CallRecordingFormat,
+        records_video: bool,
+        cx: &mut Context<Self>,
+    ) -> anyhow::Result<()> {
+        if !matches!(self.state, CallState::Active { .. }) {
+            return Err(anyhow!("The call is not connected"));
+        }
+
+        self.recorder = Some(CallRecorder::start(path, format, records_video, cx)?);
+        self.recording_task = Some(cx.spawn(
+            async move |weak_this: WeakEntity<Self>, cx: &mut AsyncApp| loop {
+                cx.background_executor().timer(RECORDING_INTERVAL).await;
+                if weak_this.update(cx, |this, cx| this.record(cx)).is_err() {
+                    return;
+                }
+            },
+        ));
+        self.announce_recording(true, cx);
+        cx.notify();
+        Ok(())
+    }
+
+    pub fn stop_recording(&mut self, cx: &mut Context<Self>) {
+        if self.recorder.take().is_none() {
+            return;
+        }
+
+        self.recording_task = None;
+        self.announce_recording(false, cx);
+        cx.notify();
+    }
+
+    /// The file that the call is being recorded to, if we're recording it
+    pub fn recording_path(&self) -> Option<&Path> {
+        self.recorder.as_ref().map(|recorder| recorder.path())
+    }
+
+    /// Whether anybody in the call, including us, is recording it
+    pub fn is_being_recorded(&self) -> bool {
+        self.recorder.is_some() || !self.recording_participants.is_empty()
+    }
+
+    /// Set the member being focused on in the call, whose video is recorded
+    pub fn set_focused_member(&mut self, member: Option<(OwnedUserId, Option<OwnedDeviceId>)>) {
+        self.focused_member = member;
+    }
+
+    fn record(&mut self, cx: &mut Context<Self>) {
+        let video_frame = if self
+            .recorder
+            .as_ref()
+            .is_some_and(|recorder| recorder.records_video())
+        {
+            self.focused_video_frame(cx)
+        } else {
+            None
+        };
+
+        if let Some(recorder) = &mut self.recorder {
+            recorder.tick(video_frame);
+        }
+    }
+
+    /// The latest frame from the focused member's screen, or their camera if they aren't sharing
+    /// their screen
+    fn focused_video_frame(&self, cx: &App) -> Option<Arc<RenderImage>> {
+        let (user_id, device_id) = self.focused_member.as_ref()?;
+        let call_members = self.cached_call_members.read(cx);
+        let call_member = call_members.iter().find(|call_member| {
+            call_member.room_member.user_id() == user_id && &call_member.device_id == device_id
+        })?;
+
+        self.latest_frame(&call_member.screenshare_state, TrackType::Screenshare, cx)
+            .or_else(|| self.latest_frame(&call_member.camera_state, TrackType::Camera, cx))
+    }
+
+    fn latest_frame(
+        &self,
+        stream_state: &StreamState,
+        track_type: TrackType,
+        cx: &App,
+    ) -> Option<Arc<RenderImage>> {
+        let StreamState::On(track_sid) = stream_state else {
+            return None;
+        };
+
+        if self.our_track_sids.get(&track_type) == Some(track_sid) {
+            self.active_devices
+                .get(track_sid)
+                .and_then(|device| device.read(cx).latest_render_frame())
+        } else {
+            self.video_stream_images.get(track_sid).cloned()
+        }
+    }
+
+    fn announce_recording(&self, recording: bool, cx: &mut Context<Self>) {
+        let CallState::Active { local_participant } = &self.state else {
+            return;
+        };
+
+        // Setting an attribute to an empty value removes it
+        let local_participant = local_participant.clone();
+        let attributes = HashMap::from([(
+            RECORDING_ATTRIBUTE.to_string(),
+            if recording { "true" } else { "" }.to_string(),
+        )]);
+        cx.spawn(async move |_: WeakEntity<Self>, cx: &mut AsyncApp| {
+            if let Err(e) = cx
+                .spawn_tokio(async move { local_participant.set_attributes(attributes).await })
+                .await
+            {
+                error!("Unable to announce call recording: {:?}", e);
+            }
+        })
+        .detach();
+    }
+
+    fn update_participant_recording(
+        &mut self,
+        user_id: OwnedUserId,
+        device_id: OwnedDeviceId,
+        attributes: &HashMap<String, String>,
+    ) {
+        if attributes
+            .get(RECORDING_ATTRIBUTE)
+            .is_some_and(|value| value == "true")
+        {
+            self.recording_participants.insert((user_id, device_id));
+        } else {
+            self.recording_participants.remove(&(user_id, device_id));
+        }
+    }
+
     fn update_audio_track_mute_status(&mut self, track: LocalAudioTrack, cx: &mut Context<Self>) {
         let call_manager = cx.global::<LivekitCallManager>();
         let mute = call_manager.mute();
@@ -1,9 +1,24 @@
+use crate::audio_processing::{AudioProcessingControls, AudioProcessor};
 use cpal::traits::{DeviceTrait, StreamTrait};
 use gpui::{App, AppContext, AsyncApp, Entity};
 use log::error;
+use std::thread;
 use thegrid_common::outbound_track::OutboundTrack;
 
 pub fn open_mic(device: &cpal::Device, cx: &mut App) -> Entity<OutboundTrack> {
+    open_mic_with_processing(device, false, cx)
+}
+
+/// Open the mic for a call, cleaning up the audio according to the audio processing settings
+pub fn open_call_mic(device: &cpal::Device, cx: &mut App) -> Entity<OutboundTrack> {
+    open_mic_with_processing(device, true, cx)
+}
+
+fn open_mic_with_processing(
+    device: &cpal::Device,
+    process: bool,
+    cx: &mut App,
+) -> Entity<OutboundTrack> {
     let device = device.clone();
 
     let (tx, rx) = async_channel::unbounded();
@@ -37,6 +52,28 @@ pub fn open_mic(device: &cpal::Device, cx: &mut App) -> Entity<OutboundTrack> {
         )
         .unwrap();
 
+    // Audio processing is too much work for the main thread, so it happens on its own thread
+    let (rx, subscriptions) = if process {
+        let (controls, subscriptions) = AudioProcessingControls::new(cx);
+        let sample_rate = supported_config.sample_rate();
+        let channels = supported_config.channels();
+        let (processed_tx, processed_rx) = async_channel::unbounded();
+        thread::spawn(move || {
+            let mut audio_processor = AudioProcessor::new(sample_rate, channels, controls);
+            while let Ok(samples) = rx.recv_blocking() {
+                if processed_tx
+                    .send_blocking(audio_processor.process(&samples))
+                    .is_err()
+                {
+                    return;
+                }
+            }
+        });
+        (processed_rx, subscriptions)
+    } else {
+        (rx, Vec::new())
+    };
+
     let weak_outbound_track = outbound_track.downgrade();
     cx.spawn(async move |cx: &mut AsyncApp| {
         while let Ok(samples) = rx.recv().await {
@@ -54,6 +91,7 @@ pub fn open_mic(device: &cpal::Device, cx: &mut App) -> Entity<OutboundTrack> {
         }
 
         drop(input_stream);
+        drop(subscriptions);
     })
     .detach();
 
@@ -1,3 +1,4 @@
+use crate::audio_processing::PlaybackMixWriter;
 use crate::call_manager::VolumeKey;
 use async_ringbuf::producer::AsyncProducer;
 use async_ringbuf::traits::{Consumer, Observer, Split};
@@ -26,6 +27,8 @@ pub struct RtcAudioStreamSource {
     user_id: OwnedUserId,
     device_id: OwnedDeviceId,
     track_source: TrackSource,
+    echo_reference: PlaybackMixWriter,
+    recording_mix: PlaybackMixWriter,
 }
 
 impl RtcAudioStreamSource {
@@ -38,6 +41,8 @@ impl RtcAudioStreamSource {
         user_id: OwnedUserId,
         device_id: OwnedDeviceId,
         track_source: TrackSource,
+        echo_reference: PlaybackMixWriter,
+        recording_mix: PlaybackMixWriter,
         cancellation_token_source: CancellationTokenSource,
         cx: &mut App,
     ) -> Self {
@@ -109,6 +114,8 @@ impl RtcAudioStreamSource {
             user_id,
             device_id,
             track_source,
+            echo_reference,
+            recording_mix,
         }
     }
 }
@@ -118,20 +125,23 @@ impl Iterator for RtcAudioStreamSource {
 
     fn next(&mut self) -> Option<Self::Item> {
         if self.cancellation_token.is_canceled() {
-            None
-        } else if *self.deaf.read().unwrap() {
-            Some(0.)
+            return None;
+        }
+
+        // Keep taking audio out of the stream while deafened so that it can still be recorded
+        let received = self.consumer.try_pop().map(reformat).unwrap_or_default();
+        self.recording_mix.push(received);
+
+        let sample = if *self.deaf.read().unwrap() {
+            0.
         } else {
             let volume = *self.volume.read().unwrap();
-            Some(
-                self.consumer
-                    .try_pop()
-                    .map(reformat)
-                    .map(|v| v * volume)
-                    .unwrap_or_default()
-                    .clamp(-1., 1.),
-            )
-        }
+            (received * volume).clamp(-1., 1.)
+        };
+
+        // Let the echo canceller know what we're playing
+        self.echo_reference.push(sample);
+        Some(sample)
     }
 
     fn size_hint(&self) -> (usize, Option<usize>) {
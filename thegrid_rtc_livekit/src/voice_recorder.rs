@@ -0,0 +1,323 @@
+use crate::call_manager::LivekitCallManager;
+use crate::mic::open_mic;
+use audiopus::coder::Encoder;
+use audiopus::{Application, Channels, SampleRate};
+use cpal::traits::HostTrait;
+use gpui::http_client::anyhow;
+use gpui::private::anyhow;
+use gpui::{App, AppContext, Context, Entity, Subscription};
+use ogg::writing::{PacketWriteEndInfo, PacketWriter};
+use std::io::Write;
+use std::time::Duration;
+use thegrid_common::outbound_track::OutboundTrack;
+
+/// Opus always works at 48kHz internally, and granule positions are counted at this rate
+pub(crate) const OPUS_SAMPLE_RATE: u32 = 48000;
+
+/// The length of each encoded Opus packet
+const OPUS_FRAME_SAMPLES: usize = OPUS_SAMPLE_RATE as usize / 50;
+
+/// The number of samples summarised by each point of the waveform while recording
+const WAVEFORM_WINDOW_SAMPLES: usize = OPUS_SAMPLE_RATE as usize / 10;
+
+/// The number of points in the waveform attached to a voice message
+const WAVEFORM_POINTS: usize = 100;
+
+/// Records a voice message from the active input device.
+///
+/// Audio is downmixed to mono and resampled to 48kHz as it arrives, and encoded to Ogg/Opus once
+/// the recording is finished.
+pub struct VoiceRecorder {
+    mic: Option<(Entity<OutboundTrack>, Subscription)>,
+    resampler: Resampler,
+    samples: Vec<i16>,
+    waveform: Vec<f32>,
+}
+
+pub struct VoiceRecording {
+    pub data: Vec<u8>,
+    pub duration: Duration,
+
+    /// The loudness of the recording over time, each point between 0 and 1
+    pub waveform: Vec<f32>,
+}
+
+impl VoiceRecorder {
+    /// Start recording from the input device chosen for calls, or the default input device if
+    /// one hasn't been chosen.
+    pub fn start(cx: &mut App) -> anyhow::Result<Entity<Self>> {
+        let call_manager = cx.global::<LivekitCallManager>();
+        let device = call_manager
+            .active_input_device()
+            .read(cx)
+            .clone()
+            .or_else(|| cpal::default_host().default_input_device())
+            .ok_or_else(|| anyhow!("No microphone is available"))?;
+
+        Ok(cx.new(|cx| {
+            let mic = open_mic(&device, cx);
+            let subscription = cx.observe(&mic, |this: &mut Self, mic, cx| {
+                let (sample_rate, channels, samples) = mic.update(cx, |mic, _| {
+                    let samples: Vec<_> = mic.audio_sample_buffer().drain().collect();
+                    (mic.sample_rate(), mic.channels(), samples)
+                });
+                this.push_samples(&samples, sample_rate, channels);
+                cx.notify();
+            });
+
+            Self {
+                mic: Some((mic, subscription)),
+                resampler: Resampler::default(),
+                samples: Vec::new(),
+                waveform: Vec::new(),
+            }
+        }))
+    }
+
+    fn push_samples(&mut self, samples: &[i16], sample_rate: u32, channels: u16) {
+        let previous_len = self.samples.len();
+        self.samples
+            .extend(self.resampler.process(samples, sample_rate, channels));
+
+        // Update the live waveform with every complete window
+        let first_window = previous_len / WAVEFORM_WINDOW_SAMPLES;
+        let last_window = self.samples.len() / WAVEFORM_WINDOW_SAMPLES;
+        for window in first_window..last_window {
+            let window_samples = &self.samples
+                [window * WAVEFORM_WINDOW_SAMPLES..(window + 1) * WAVEFORM_WINDOW_SAMPLES];
+            self.waveform.push(loudness(window_samples));
+        }
+    }
+
+    pub fn duration(&self) -> Duration {
+        Duration::from_secs_f64(self.samples.len() as f64 / OPUS_SAMPLE_RATE as f64)
+    }
+
+    /// The loudness of the recording so far, one point per tenth of a second
+    pub fn waveform(&self) -> &[f32] {
+        &self.waveform
+    }
+
+    /// Stop recording, releasing the microphone.
+    ///
+    /// The returned closure encodes the recording. Encoding can take a while for long
+    /// recordings, so it should be run in the background.
+    pub fn finish(
+        &mut self,
+        cx: &mut Context<Self>,
+    ) -> impl FnOnce() -> anyhow::Result<VoiceRecording> + Send + 'static {
+        self.mic = None;
+        cx.notify();
+
+        let samples = std::mem::take(&mut self.samples);
+        move || encode_voice_recording(samples)
+    }
+}
+
+fn loudness(samples: &[i16]) -> f32 {
+    if samples.is_empty() {
+        return 0.;
+    }
+
+    let sum_of_squares: f64 = samples
+        .iter()
+        .map(|&sample| {
+            let sample = sample as f64 / i16::MAX as f64;
+            sample * sample
+        })
+        .sum();
+    let rms = (sum_of_squares / samples.len() as f64).sqrt() as f32;
+
+    // Speech rarely gets anywhere near full scale, so boost it to make the waveform readable
+    (rms * 3.).min(1.)
+}
+
+fn encode_voice_recording(samples: Vec<i16>) -> anyhow::Result<VoiceRecording> {
+    if samples.is_empty() {
+        return Err(anyhow!("Nothing was recorded"));
+    }
+
+    let duration = Duration::from_secs_f64(samples.len() as f64 / OPUS_SAMPLE_RATE as f64);
+
+    let chunk_size = samples.len().div_ceil(WAVEFORM_POINTS);
+    let waveform = samples.chunks(chunk_size).map(loudness).collect();
+
+    let mut writer = OggOpusWriter::new(Vec::new())?;
+    writer.write(&samples)?;
+    let data = writer.finish()?;
+
+    Ok(VoiceRecording {
+        data,
+        duration,
+        waveform,
+    })
+}
+
+/// Downmixes audio to mono and resamples it to 48kHz, ready to be encoded.
+///
+/// Audio arrives in chunks, so the resampler keeps its place between them. This way the chunks
+/// join up without gaps or clicks, and the output doesn't drift away from the input.
+#[derive(Default)]
+pub(crate) struct Resampler {
+    /// The sample rate and channel count of the input, so we can start afresh if it changes
+    format: Option<(u32, u16)>,
+
+    /// The last input sample of the previous chunk, which the next output sample may fall after
+    last_sample: Option<i16>,
+
+    /// Where the next output sample falls, counting from the last sample of the previous chunk
+    position: f64,
+}
+
+impl Resampler {
+    pub fn process(&mut self, samples: &[i16], sample_rate: u32, channels: u16) -> Vec<i16> {
+        if self.format != Some((sample_rate, channels)) {
+            *self = Self {
+                format: Some((sample_rate, channels)),
+                ..Self::default()
+            };
+        }
+
+        let channels = channels.max(1) as usize;
+        let mono = samples.chunks(channels).map(|frame| {
+            let sum = frame.iter().map(|&sample| sample as i32).sum::<i32>();
+            (sum / frame.len() as i32) as i16
+        });
+
+        if sample_rate == OPUS_SAMPLE_RATE {
+            return mono.collect();
+        }
+
+        let input = self.last_sample.into_iter().chain(mono).collect::<Vec<_>>();
+        let Some(&last_sample) = input.last() else {
+            return Vec::new();
+        };
+
+        // Voice doesn't need anything fancier than linear interpolation
+        let step = sample_rate as f64 / OPUS_SAMPLE_RATE as f64;
+        let mut output = Vec::new();
+        while (self.position as usize) + 1 < input.len() {
+            let index = self.position as usize;
+            let fraction = self.position - index as f64;
+            let current = input[index] as f64;
+            let next = input[index + 1] as f64;
+            output.push((current + (next - current) * fraction) as i16);
+            self.position += step;
+        }
+
+        // Carry on from the last sample of this chunk next time
+        self.position -= (input.len() - 1) as f64;
+        self.last_sample = Some(last_sample);
+        output
+    }
+}
+
+/// Encodes mono 48kHz audio to Ogg/Opus as it arrives
+pub(crate) struct OggOpusWriter<W: Write> {
+    writer: PacketWriter<'static, W>,
+    encoder: Encoder,
+    serial: u32,
+    pre_skip: u16,
+
+    /// Samples that don't fill a whole frame yet
+    pending: Vec<i16>,
+    encoded_samples: u64,
+
+    /// The most recent packet and its granule position. It is held back until we know whether
+    /// it is the last one, because the last packet has to end the stream.
+    held_packet: Option<(Vec<u8>, u64)>,
+}
+
+impl<W: Write> OggOpusWriter<W> {
+    pub fn new(output: W) -> anyhow::Result<Self> {
+        let encoder = Encoder::new(SampleRate::Hz48000, Channels::Mono, Application::Voip)?;
+        let pre_skip = encoder.lookahead()? as u16;
+
+        let mut writer = PacketWriter::new(output);
+
+        // Streams are told apart by their serial number, which should be random
+        let serial =
+            getrandom::u32().map_err(|e| anyhow!("Unable to generate Ogg stream serial: {e}"))?;
+
+        // Identification header, see RFC 7845 section 5.1
+        let mut opus_head = Vec::with_capacity(19);
+        opus_head.extend_from_slice(b"OpusHead");
+        opus_head.push(1);
+        opus_head.push(1);
+        opus_head.extend_from_slice(&pre_skip.to_le_bytes());
+        opus_head.extend_from_slice(&OPUS_SAMPLE_RATE.to_le_bytes());
+        opus_head.extend_from_slice(&0i16.to_le_bytes());
+        opus_head.push(0);
+        writer.write_packet(opus_head, serial, PacketWriteEndInfo::EndPage, 0)?;
+
+        // Comment header, see RFC 7845 section 5.2
+        let vendor = b"theGrid";
+        let mut opus_tags = Vec::new();
+        opus_tags.extend_from_slice(b"OpusTags");
+        opus_tags.extend_from_slice(&(vendor.len() as u32).to_le_bytes());
+        opus_tags.extend_from_slice(vendor);
+        opus_tags.extend_from_slice(&0u32.to_le_bytes());
+        writer.write_packet(opus_tags, serial, PacketWriteEndInfo::EndPage, 0)?;
+
+        Ok(Self {
+            writer,
+            encoder,
+            serial,
+            pre_skip,
+            pending: Vec::new(),
+            encoded_samples: 0,
+            held_packet: None,
+        })
+    }
+
+    pub fn write(&mut self, samples: &[i16]) -> anyhow::Result<()> {
+        self.pending.extend_from_slice(samples);
+        while self.pending.len() >= OPUS_FRAME_SAMPLES {
+            let frame = self.pending.drain(..OPUS_FRAME_SAMPLES).collect::<Vec<_>>();
+            self.encode_frame(&frame, OPUS_FRAME_SAMPLES)?;
+        }
+        Ok(())
+    }
+
+    fn encode_frame(&mut self, frame: &[i16], length: usize) -> anyhow::Result<()> {
+        let mut packet = vec![0; 4000];
+        let packet_length = self.encoder.encode(frame, &mut packet)?;
+        packet.truncate(packet_length);
+
+        self.encoded_samples += length as u64;
+        let granule_position = self.encoded_samples + self.pre_skip as u64;
+        if let Some((packet, granule_position)) =
+            self.held_packet.replace((packet, granule_position))
+        {
+            self.writer.write_packet(
+                packet,
+                self.serial,
+                PacketWriteEndInfo::NormalPacket,
+                granule_position,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Encode whatever is left and end the stream
+    pub fn finish(mut self) -> anyhow::Result<W> {
+        if !self.pending.is_empty() {
+            // The final frame has to be padded out to a full frame
+            let mut frame = std::mem::take(&mut self.pending);
+            let length = frame.len();
+            frame.resize(OPUS_FRAME_SAMPLES, 0);
+            self.encode_frame(&frame, length)?;
+        }
+
+        if let Some((packet, granule_position)) = self.held_packet.take() {
+            self.writer.write_packet(
+                packet,
+                self.serial,
+                PacketWriteEndInfo::EndStream,
+                granule_position,
+            )?;
+        }
+
+        Ok(self.writer.into_inner())
+    }
+}
@@ -0,0 +1,44 @@
+//! Support for composing messages in Markdown, so that they are styled while they are typed and
+//! sent the same way they are rendered by a [`TextView`](crate::TextView).
+
+use crate::format::{html, markdown};
+use contemporary::styling::theme::{Theme, VariableColor};
+use gpui::{App, HighlightStyle, Hsla};
+use std::ops::Range;
+
+/// Get the styles to draw Markdown source with while it is being typed.
+///
+/// Formatted text is styled the same way as it is in a `TextView`, and the Markdown syntax
+/// around it is dimmed.
+pub fn markdown_source_highlights(source: &str, cx: &App) -> Vec<(Range<usize>, HighlightStyle)> {
+    let theme = cx.global::<Theme>();
+    let (marks, dimmed) = markdown::source_marks(source);
+
+    let dimmed_style = HighlightStyle {
+        color: Some(Hsla::from(theme.foreground.disabled())),
+        ..Default::default()
+    };
+    marks
+        .iter()
+        .map(|(range, mark)| (range.clone(), mark.highlight_style(theme)))
+        .chain(dimmed.into_iter().map(|range| (range, dimmed_style)))
+        .collect()
+}
+
+/// Convert Markdown to HTML for the formatted body of a message.
+///
+/// Returns `None` if the Markdown doesn't contain any formatting, in which case the message
+/// should be sent as plain text.
+pub fn markdown_to_html(source: &str) -> Option<String> {
+    markdown::to_html(source)
+}
+
+/// Get the text of some HTML without any of its markup, for the plain body of a message.
+pub fn html_to_plain_text(source: &str) -> String {
+    html::to_plain_text(source)
+}
+
+/// Escape text so that it can be placed in the formatted body of a message.
+pub fn escape_html(text: &str) -> String {
+    markdown::escape_html(text)
+}
@@ -8,9 +8,11 @@ use std::rc::Rc;
 use crate::highlighter::HighlightTheme;
 use crate::node::{
     CodeBlock, ImageNode, InlineNode, LinkMark, NodeContext, Paragraph, Table, TableRow, TextMark,
+    replace_ranges,
 };
-use crate::{Events, TextViewStyle, node};
-use gpui::{DefiniteLength, SharedString, px, relative};
+use crate::utils::{subscript_char, superscript_char};
+use crate::{Events, TextViewStyle, math, node};
+use gpui::{DefiniteLength, Hsla, Rgba, SharedString, px, relative};
 use html5ever::tendril::TendrilSink;
 use html5ever::{LocalName, ParseOpts, QualName, local_name, parse_document};
 use markup5ever_rcdom::{Node, NodeData, RcDom};
@@ -83,6 +85,55 @@ pub(crate) fn parse(source: &str, cx: &mut NodeContext) -> Result<node::Node, Sh
     Ok(node)
 }
 
+/// Get the text of some HTML without any of its markup.
+///
+/// Block elements and line breaks are kept as new lines.
+pub(crate) fn to_plain_text(source: &str) -> String {
+    let mut cursor = std::io::Cursor::new(source.as_bytes());
+    let Ok(dom) = parse_document(RcDom::default(), ParseOpts::default())
+        .from_utf8()
+        .read_from(&mut cursor)
+    else {
+        return source.to_string();
+    };
+
+    let mut text = String::new();
+    write_plain_text(&dom.document, &mut text);
+    text.trim().to_string()
+}
+
+fn write_plain_text(node: &Rc<Node>, text: &mut String) {
+    let is_block = match &node.data {
+        NodeData::Text { contents } => {
+            text.push_str(&contents.borrow());
+            return;
+        }
+        NodeData::Element { name, .. } => match name.local {
+            local_name!("br") => {
+                text.push('\n');
+                return;
+            }
+            local_name!("head") | local_name!("style") | local_name!("script") => return,
+            ref name if IGNORED_ELEMENTS.contains(&name.as_ref()) => return,
+            ref name => {
+                BLOCK_ELEMENTS.contains(&name.as_ref())
+                    || matches!(*name, local_name!("li") | local_name!("tr"))
+            }
+        },
+        _ => false,
+    };
+
+    if is_block && !text.is_empty() && !text.ends_with('\n') {
+        text.push('\n');
+    }
+    for child in node.children.borrow().iter() {
+        write_plain_text(child, text);
+    }
+    if is_block && !text.ends_with('\n') {
+        text.push('\n');
+    }
+}
+
 fn cleanup_html(source: &str) -> Vec<u8> {
     let mut w = std::io::Cursor::new(vec![]);
     let mut r = std::io::Cursor::new(source);
@@ -105,6 +156,65 @@ fn attr_value(attrs: &RefCell<Vec<html5ever::Attribute>>, name: LocalName) -> Op
     })
 }
 
+/// Parse a `#rrggbb` colour from an attribute.
+fn value_to_color(value: &str) -> Option<Hsla> {
+    Rgba::try_from(value.trim()).ok().map(Into::into)
+}
+
+/// Get the mark for the colour and spoiler attributes of a `<span>` or `<font>`.
+fn span_mark(attrs: &RefCell<Vec<html5ever::Attribute>>) -> TextMark {
+    let mut mark = TextMark::default();
+
+    let color = attr_value(attrs, LocalName::from("data-mx-color"))
+        .or_else(|| attr_value(attrs, local_name!("color")));
+    if let Some(color) = color.as_deref().and_then(value_to_color) {
+        mark = mark.color(color);
+    }
+
+    let background_color = attr_value(attrs, LocalName::from("data-mx-bg-color"));
+    if let Some(background_color) = background_color.as_deref().and_then(value_to_color) {
+        mark = mark.background_color(background_color);
+    }
+
+    // The value of the attribute is the reason for the spoiler, which may be empty
+    if attr_value(attrs, LocalName::from("data-mx-spoiler")).is_some() {
+        mark = mark.spoiler();
+    }
+
+    mark
+}
+
+/// Check if a link is a matrix.to permalink to a user or room, rather than to an event.
+fn is_mention_url(url: &str) -> bool {
+    let Some(identifier) = url.strip_prefix("https://matrix.to/#/") else {
+        return false;
+    };
+    let identifier = identifier.split('?').next().unwrap_or_default();
+
+    !identifier.contains('/')
+        && ["@", "#", "!", "%40", "%23", "%21"]
+            .iter()
+            .any(|sigil| identifier.starts_with(sigil))
+}
+
+/// Convert text to superscript or subscript characters.
+///
+/// Text can only be drawn at one size in a line, so this is the closest we can get to raising or
+/// lowering it. Characters without a superscript or subscript form are left as they are.
+fn to_script(
+    text: &str,
+    marks: &[(Range<usize>, TextMark)],
+    script_char: fn(char) -> Option<char>,
+) -> (String, Vec<(Range<usize>, TextMark)>) {
+    let replacements: Vec<_> = text
+        .char_indices()
+        .filter_map(|(ix, c)| {
+            script_char(c).map(|script_c| (ix..ix + c.len_utf8(), script_c.to_string()))
+        })
+        .collect();
+    replace_ranges(text, marks, &replacements)
+}
+
 /// Get style properties to HashMap
 /// TODO: Use cssparser to parse style attribute.
 fn style_attrs(attrs: &RefCell<Vec<html5ever::Attribute>>) -> HashMap<String, String> {
@@ -256,7 +366,7 @@ fn parse_paragraph(
         let offset = text.len();
         text.push_str(new_text);
         for (range, style) in new_marks {
-            marks.push((range.start + offset..new_text.len() + offset, style.clone()));
+            marks.push((range.start + offset..range.end + offset, style.clone()));
         }
     }
 
@@ -300,6 +410,52 @@ fn parse_paragraph(
                 marks.push((0..text.len(), TextMark::default().strikethrough()));
                 paragraph.push(InlineNode::new(&text, events).marks(marks.clone()));
             }
+            local_name!("u") => {
+                let mut child_paragraph = Paragraph::default();
+                for child in node.children.borrow().iter() {
+                    let (child_text, child_marks) =
+                        parse_paragraph(&mut child_paragraph, &child, events);
+                    merge_child_text(&mut text, &mut marks, &child_text, &child_marks);
+                }
+                marks.push((0..text.len(), TextMark::default().underline()));
+                paragraph.push(InlineNode::new(&text, events).marks(marks.clone()));
+            }
+            local_name!("sup") | local_name!("sub") => {
+                let mut child_paragraph = Paragraph::default();
+                for child in node.children.borrow().iter() {
+                    let (child_text, child_marks) =
+                        parse_paragraph(&mut child_paragraph, &child, events);
+                    merge_child_text(&mut text, &mut marks, &child_text, &child_marks);
+                }
+                let script_char = if name.local == local_name!("sup") {
+                    superscript_char
+                } else {
+                    subscript_char
+                };
+                (text, marks) = to_script(&text, &marks, script_char);
+                paragraph.push(InlineNode::new(&text, events).marks(marks.clone()));
+            }
+            local_name!("span")
+                if attr_value(attrs, LocalName::from("data-mx-maths")).is_some() =>
+            {
+                // The children are a fallback for clients that can't render maths
+                let source = attr_value(attrs, LocalName::from("data-mx-maths")).unwrap();
+                (text, marks) = math::parse(&source).to_inline();
+                paragraph.push(InlineNode::new(&text, events).marks(marks.clone()));
+            }
+            local_name!("span") | local_name!("font") => {
+                let mut child_paragraph = Paragraph::default();
+                for child in node.children.borrow().iter() {
+                    let (child_text, child_marks) =
+                        parse_paragraph(&mut child_paragraph, &child, events);
+                    merge_child_text(&mut text, &mut marks, &child_text, &child_marks);
+                }
+                let mark = span_mark(attrs);
+                if mark != TextMark::default() {
+                    marks.push((0..text.len(), mark));
+                }
+                paragraph.push(InlineNode::new(&text, events).marks(marks.clone()));
+            }
             local_name!("code") => {
                 let mut child_paragraph = Paragraph::default();
                 for child in node.children.borrow().iter() {
@@ -318,16 +474,32 @@ fn parse_paragraph(
                     merge_child_text(&mut text, &mut marks, &child_text, &child_marks);
                 }
 
-                marks.push((
-                    0..text.len(),
-                    TextMark::default().link(LinkMark {
-                        url: attr_value(&attrs, local_name!("href"))
-                            .unwrap_or_default()
-                            .into(),
-                        title: attr_value(&attrs, local_name!("title")).map(Into::into),
-                        ..Default::default()
-                    }),
-                ));
+                let url = attr_value(&attrs, local_name!("href")).unwrap_or_default();
+                let is_mention = is_mention_url(&url);
+                let mut mark = TextMark::default().link(LinkMark {
+                    url: url.into(),
+                    title: attr_value(&attrs, local_name!("title")).map(Into::into),
+                    ..Default::default()
+                });
+                if is_mention {
+                    mark = mark.mention();
+                }
+                marks.push((0..text.len(), mark));
+                paragraph.push(InlineNode::new(&text, events).marks(marks.clone()));
+            }
+            local_name!("img")
+                if attr_value(attrs, LocalName::from("data-mx-emoticon")).is_some() =>
+            {
+                // Custom emoji flow with the text, and fall back to their shortcode
+                let Some(src) = attr_value(attrs, local_name!("src")) else {
+                    return (text, marks);
+                };
+
+                text = attr_value(attrs, local_name!("alt"))
+                    .or_else(|| attr_value(attrs, local_name!("title")))
+                    .filter(|alt| !alt.is_empty())
+                    .unwrap_or_else(|| ":emote:".to_string());
+                marks.push((0..text.len(), TextMark::default().emoticon(src)));
                 paragraph.push(InlineNode::new(&text, events).marks(marks.clone()));
             }
             local_name!("img") => {
@@ -433,6 +605,12 @@ fn parse_node(
                     Some(heading)
                 }
             }
+            local_name!("img")
+                if attr_value(attrs, LocalName::from("data-mx-emoticon")).is_some() =>
+            {
+                parse_paragraph(paragraph, node, &cx.events);
+                None
+            }
             local_name!("img") => {
                 let mut children = vec![];
                 consume_paragraph(&mut children, paragraph);
@@ -540,6 +718,22 @@ fn parse_node(
             local_name!("pre") if extract_codeblock(node, cx).is_some() => {
                 Some(node::Node::CodeBlock(extract_codeblock(node, cx).unwrap()))
             }
+            local_name!("div") if attr_value(attrs, LocalName::from("data-mx-maths")).is_some() => {
+                let mut children = vec![];
+                consume_paragraph(&mut children, paragraph);
+
+                let source = attr_value(attrs, LocalName::from("data-mx-maths")).unwrap();
+                let math = node::Node::Math {
+                    math: math::parse(&source),
+                    source: source.into(),
+                };
+                if children.len() > 0 {
+                    children.push(math);
+                    Some(node::Node::Root { children })
+                } else {
+                    Some(math)
+                }
+            }
             local_name!("style") | local_name!("script") => None,
             _ => {
                 if IGNORED_ELEMENTS.contains(&name.local.trim()) {
@@ -666,3 +860,155 @@ fn consume_paragraph(children: &mut Vec<node::Node>, paragraph: &mut Paragraph)
 
     children.push(node::Node::Paragraph(paragraph.take()));
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gpui::rgb;
+
+    /// Parse HTML and return the text and marks of each inline node.
+    fn parse_inline(source: &str) -> Vec<(String, Vec<(Range<usize>, TextMark)>)> {
+        fn collect(node: &node::Node, out: &mut Vec<(String, Vec<(Range<usize>, TextMark)>)>) {
+            match node {
+                node::Node::Root { children } => {
+                    for child in children {
+                        collect(child, out);
+                    }
+                }
+                node::Node::Paragraph(paragraph) => {
+                    for inline_node in &paragraph.children {
+                        out.push((inline_node.text.to_string(), inline_node.marks.clone()));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let node = parse(source, &mut NodeContext::default()).unwrap();
+        let mut out = vec![];
+        collect(&node, &mut out);
+        out
+    }
+
+    #[test]
+    fn test_to_plain_text() {
+        assert_eq!(to_plain_text("<b>hello</b> <i>world</i>"), "hello world");
+        assert_eq!(to_plain_text("a &amp; b"), "a & b");
+        assert_eq!(to_plain_text("one<br>two"), "one\ntwo");
+        assert_eq!(to_plain_text("<p>one</p><p>two</p>"), "one\ntwo");
+        assert_eq!(
+            to_plain_text("<ul><li>one</li><li>two</li></ul>"),
+            "one\ntwo"
+        );
+        assert_eq!(to_plain_text("<mx-reply>quoted</mx-reply>reply"), "reply");
+    }
+
+    #[test]
+    fn test_is_mention_url() {
+        assert!(is_mention_url("https://matrix.to/#/@alice:example.org"));
+        assert!(is_mention_url("https://matrix.to/#/%40alice%3Aexample.org"));
+        assert!(is_mention_url("https://matrix.to/#/#room:example.org"));
+        assert!(is_mention_url(
+            "https://matrix.to/#/!room:example.org?via=example.org"
+        ));
+        assert!(!is_mention_url(
+            "https://matrix.to/#/!room:example.org/$event?via=example.org"
+        ));
+        assert!(!is_mention_url("https://example.org/#/@alice:example.org"));
+    }
+
+    #[test]
+    fn test_to_script() {
+        let marks = vec![
+            (0..4, TextMark::default().bold()),
+            (1..2, TextMark::default().italic()),
+        ];
+        let (text, marks) = to_script("x2+y", &marks, superscript_char);
+        assert_eq!(text, "x²⁺y");
+        assert_eq!(
+            marks,
+            vec![
+                (0..text.len(), TextMark::default().bold()),
+                (1..3, TextMark::default().italic()),
+            ]
+        );
+
+        let (text, _) = to_script("H2O", &[], subscript_char);
+        assert_eq!(text, "H₂O");
+    }
+
+    #[test]
+    fn test_parse_span_marks() {
+        let nodes = parse_inline(
+            r##"<body><span data-mx-spoiler="plot">secret</span><font data-mx-color="#ff0000" data-mx-bg-color="#00ff00">red</font><u>under</u></body>"##,
+        );
+
+        assert_eq!(nodes[0].0, "secret");
+        assert_eq!(nodes[0].1, vec![(0..6, TextMark::default().spoiler())]);
+
+        assert_eq!(nodes[1].0, "red");
+        assert_eq!(
+            nodes[1].1,
+            vec![(
+                0..3,
+                TextMark::default()
+                    .color(rgb(0xff0000))
+                    .background_color(rgb(0x00ff00))
+            )]
+        );
+
+        assert_eq!(nodes[2].0, "under");
+        assert_eq!(nodes[2].1, vec![(0..5, TextMark::default().underline())]);
+    }
+
+    #[test]
+    fn test_parse_nested_marks() {
+        let nodes = parse_inline("<body><span>a<b>b</b>c</span></body>");
+        assert_eq!(nodes[0].0, "abc");
+        assert_eq!(nodes[0].1, vec![(1..2, TextMark::default().bold())]);
+    }
+
+    #[test]
+    fn test_parse_mention() {
+        let nodes = parse_inline(
+            r#"<body><a href="https://matrix.to/#/@alice:example.org">Alice</a></body>"#,
+        );
+        assert_eq!(nodes[0].0, "Alice");
+        assert!(nodes[0].1[0].1.mention);
+
+        let nodes = parse_inline(r#"<body><a href="https://example.org">Example</a></body>"#);
+        assert!(!nodes[0].1[0].1.mention);
+    }
+
+    #[test]
+    fn test_parse_emoticon() {
+        let nodes = parse_inline(
+            r#"<body>hi <img data-mx-emoticon src="mxc://example.org/blob" alt=":blob:" height="32"></body>"#,
+        );
+        assert_eq!(nodes[1].0, ":blob:");
+        assert_eq!(
+            nodes[1].1,
+            vec![(0..6, TextMark::default().emoticon("mxc://example.org/blob"))]
+        );
+
+        let nodes =
+            parse_inline(r#"<body><img src="mxc://example.org/photo" alt="photo"></body>"#);
+        assert!(nodes.iter().all(|(_, marks)| marks.is_empty()));
+    }
+
+    #[test]
+    fn test_parse_maths() {
+        let nodes =
+            parse_inline(r#"<body><span data-mx-maths="x^2"><code>x^2</code></span></body>"#);
+        assert_eq!(nodes[0].0, "x²");
+        assert_eq!(nodes[0].1, vec![(0..1, TextMark::default().italic())]);
+
+        let node = parse(
+            r#"<body><div data-mx-maths="\frac{1}{2}"><code>\frac{1}{2}</code></div></body>"#,
+            &mut NodeContext::default(),
+        )
+        .unwrap()
+        .compact();
+        assert!(matches!(node, node::Node::Math { .. }));
+    }
+}
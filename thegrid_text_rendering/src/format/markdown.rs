@@ -3,12 +3,27 @@ use crate::node::{
     CodeBlock, ImageNode, InlineNode, LinkMark, NodeContext, Paragraph, Span, Table, TableRow,
     TextMark,
 };
-use crate::{Events, TextViewStyle, node};
+use crate::{Events, TextViewStyle, math, node};
 use gpui::SharedString;
 use markdown::{
-    ParseOptions,
+    Constructs, ParseOptions,
     mdast::{self, Node},
 };
+use std::fmt::Write;
+use std::ops::Range;
+
+fn parse_options() -> ParseOptions {
+    ParseOptions {
+        constructs: Constructs {
+            math_text: true,
+            math_flow: true,
+            ..Constructs::gfm()
+        },
+        // Single dollars are far more often prices than maths
+        math_text_single_dollar: false,
+        ..ParseOptions::gfm()
+    }
+}
 
 /// Parse Markdown into a tree of nodes.
 pub(crate) fn parse(
@@ -17,7 +32,7 @@ pub(crate) fn parse(
     cx: &mut NodeContext,
     highlight_theme: &HighlightTheme,
 ) -> Result<node::Node, SharedString> {
-    markdown::to_mdast(&raw, &ParseOptions::gfm())
+    markdown::to_mdast(&raw, &parse_options())
         .map(|n| ast_to_node(n, style, cx, highlight_theme))
         .map_err(|e| e.to_string().into())
 }
@@ -147,11 +162,9 @@ fn parse_paragraph(paragraph: &mut Paragraph, node: &mdast::Node, cx: &mut NodeC
             );
         }
         Node::InlineMath(raw) => {
-            text = raw.value.clone();
-            paragraph.push(
-                InlineNode::new(&text, &cx.events)
-                    .marks(vec![(0..text.len(), TextMark::default().code())]),
-            );
+            let math = InlineNode::math(&raw.value, &cx.events);
+            text = math.text.to_string();
+            paragraph.push(math);
         }
         Node::MdxTextExpression(raw) => {
             text = raw.value.clone();
@@ -293,13 +306,10 @@ fn ast_to_node(
                 children: paragraph,
             }
         }
-        Node::Math(val) => node::Node::CodeBlock(CodeBlock::new(
-            val.value.into(),
-            None,
-            style,
-            highlight_theme,
-            &cx.events,
-        )),
+        Node::Math(val) => node::Node::Math {
+            math: math::parse(&val.value),
+            source: val.value.into(),
+        },
         Node::Html(val) => match super::html::parse(&val.value, cx) {
             Ok(el) => el,
             Err(err) => {
@@ -402,3 +412,314 @@ fn ast_to_node(
         }
     }
 }
+
+/// Find the formatting of Markdown source, as marks over the source text itself.
+///
+/// Also returns the ranges that should be dimmed, which are the Markdown syntax such as the `**`
+/// around bold text, and the contents of block quotes.
+pub(crate) fn source_marks(source: &str) -> (Vec<(Range<usize>, TextMark)>, Vec<Range<usize>>) {
+    let mut marks = vec![];
+    let mut dimmed = vec![];
+    if let Ok(root) = markdown::to_mdast(source, &parse_options()) {
+        collect_source_marks(source, &root, &mut marks, &mut dimmed);
+    }
+    dimmed.retain(|range| !range.is_empty());
+    (marks, dimmed)
+}
+
+fn node_range(node: &Node) -> Option<Range<usize>> {
+    node.position()
+        .map(|position| position.start.offset..position.end.offset)
+}
+
+/// Get the range from the start of the first child of a node to the end of its last child.
+fn children_range(node: &Node) -> Option<Range<usize>> {
+    let children = node.children()?;
+    let first = node_range(children.first()?)?;
+    let last = node_range(children.last()?)?;
+    Some(first.start..last.end)
+}
+
+fn collect_source_marks(
+    source: &str,
+    node: &Node,
+    marks: &mut Vec<(Range<usize>, TextMark)>,
+    dimmed: &mut Vec<Range<usize>>,
+) {
+    let Some(range) = node_range(node) else {
+        return;
+    };
+
+    match node {
+        Node::Strong(_) | Node::Heading(_) => {
+            marks.push((range.clone(), TextMark::default().bold()))
+        }
+        Node::Emphasis(_) => marks.push((range.clone(), TextMark::default().italic())),
+        Node::Delete(_) => marks.push((range.clone(), TextMark::default().strikethrough())),
+        Node::InlineCode(_) | Node::InlineMath(_) | Node::Code(_) | Node::Math(_) => {
+            marks.push((range.clone(), TextMark::default().code()))
+        }
+        Node::Link(link) => marks.push((
+            // Only the text of the link is styled as a link
+            children_range(node).unwrap_or(range.clone()),
+            TextMark {
+                link: Some(LinkMark {
+                    url: link.url.clone().into(),
+                    title: link.title.clone().map(Into::into),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+        )),
+        _ => {}
+    }
+
+    match node {
+        Node::Strong(_)
+        | Node::Emphasis(_)
+        | Node::Delete(_)
+        | Node::Link(_)
+        | Node::Heading(_)
+        | Node::ListItem(_) => match children_range(node) {
+            Some(children) => {
+                dimmed.push(range.start..children.start);
+                dimmed.push(children.end..range.end);
+            }
+            None => dimmed.push(range.clone()),
+        },
+        Node::InlineCode(_) | Node::InlineMath(_) => {
+            let fence = source[range.clone()]
+                .chars()
+                .take_while(|c| *c == '`' || *c == '$')
+                .count();
+            dimmed.push(range.start..range.start + fence);
+            dimmed.push(range.end - fence..range.end);
+        }
+        Node::Blockquote(_) => dimmed.push(range.clone()),
+        _ => {}
+    }
+
+    for child in node.children().into_iter().flatten() {
+        collect_source_marks(source, child, marks, dimmed);
+    }
+}
+
+/// Convert Markdown to the HTML used for the formatted body of a Matrix message.
+///
+/// Returns `None` if the Markdown doesn't contain any formatting, in which case the message
+/// should be sent as plain text.
+pub(crate) fn to_html(source: &str) -> Option<String> {
+    let root = markdown::to_mdast(source, &parse_options()).ok()?;
+    if !has_formatting(source, &root) {
+        return None;
+    }
+
+    let mut html = String::new();
+    match root.children().map(Vec::as_slice) {
+        // A message that is a single paragraph doesn't need to be wrapped in one
+        Some([paragraph @ Node::Paragraph(_)]) => write_html_children(paragraph, &mut html, true),
+        _ => write_html(&root, &mut html, false),
+    }
+    Some(html)
+}
+
+fn has_formatting(source: &str, node: &Node) -> bool {
+    match node {
+        Node::Root(_) | Node::Paragraph(_) => node
+            .children()
+            .is_some_and(|children| children.iter().any(|child| has_formatting(source, child))),
+        // Text that was escaped needs to be sent as HTML to lose the escapes
+        Node::Text(text) => {
+            node_range(node).is_none_or(|range| source.get(range) != Some(text.value.as_str()))
+        }
+        _ => true,
+    }
+}
+
+pub(crate) fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Write the children of a node as HTML.
+///
+/// When `tight` is set, paragraphs aren't wrapped in `<p>`, as in the items of a tight list.
+fn write_html_children(node: &Node, html: &mut String, tight: bool) {
+    for child in node.children().into_iter().flatten() {
+        write_html(child, html, tight);
+    }
+}
+
+fn write_html_element(tag: &str, node: &Node, html: &mut String, tight: bool) {
+    let _ = write!(html, "<{tag}>");
+    write_html_children(node, html, tight);
+    let _ = write!(html, "</{tag}>");
+}
+
+fn write_html(node: &Node, html: &mut String, tight: bool) {
+    match node {
+        Node::Paragraph(_) if tight => write_html_children(node, html, tight),
+        Node::Paragraph(_) => write_html_element("p", node, html, tight),
+        Node::Text(text) => html.push_str(&escape_html(&text.value).replace('\n', "<br>")),
+        Node::Break(_) => html.push_str("<br>"),
+        Node::Emphasis(_) => write_html_element("em", node, html, tight),
+        Node::Strong(_) => write_html_element("strong", node, html, tight),
+        Node::Delete(_) => write_html_element("del", node, html, tight),
+        Node::InlineCode(code) => {
+            let _ = write!(html, "<code>{}</code>", escape_html(&code.value));
+        }
+        Node::InlineMath(math) => {
+            let _ = write!(
+                html,
+                "<span data-mx-maths=\"{0}\"><code>{0}</code></span>",
+                escape_html(&math.value)
+            );
+        }
+        Node::Math(math) => {
+            let _ = write!(
+                html,
+                "<div data-mx-maths=\"{0}\"><code>{0}</code></div>",
+                escape_html(&math.value)
+            );
+        }
+        Node::Link(link) => {
+            let _ = write!(html, "<a href=\"{}\">", escape_html(&link.url));
+            write_html_children(node, html, tight);
+            html.push_str("</a>");
+        }
+        // Matrix clients only load images from the media repository
+        Node::Image(image) if image.url.starts_with("mxc://") => {
+            let _ = write!(
+                html,
+                "<img src=\"{}\" alt=\"{}\">",
+                escape_html(&image.url),
+                escape_html(&image.alt)
+            );
+        }
+        Node::Image(image) => {
+            let _ = write!(
+                html,
+                "<a href=\"{}\">{}</a>",
+                escape_html(&image.url),
+                escape_html(&image.alt)
+            );
+        }
+        Node::Heading(heading) => {
+            write_html_element(&format!("h{}", heading.depth), node, html, tight)
+        }
+        Node::Blockquote(_) => write_html_element("blockquote", node, html, false),
+        Node::List(list) if list.ordered => {
+            match list.start {
+                Some(start) if start != 1 => {
+                    let _ = write!(html, "<ol start=\"{start}\">");
+                }
+                _ => html.push_str("<ol>"),
+            }
+            write_html_children(node, html, false);
+            html.push_str("</ol>");
+        }
+        Node::List(_) => write_html_element("ul", node, html, false),
+        Node::ListItem(item) => {
+            html.push_str("<li>");
+            match item.checked {
+                Some(true) => html.push_str("☑ "),
+                Some(false) => html.push_str("☐ "),
+                None => {}
+            }
+            write_html_children(node, html, !item.spread);
+            html.push_str("</li>");
+        }
+        Node::Code(code) => {
+            match &code.lang {
+                Some(lang) => {
+                    let _ = write!(html, "<pre><code class=\"language-{}\">", escape_html(lang));
+                }
+                None => html.push_str("<pre><code>"),
+            }
+            html.push_str(&escape_html(&code.value));
+            html.push_str("</code></pre>");
+        }
+        Node::ThematicBreak(_) => html.push_str("<hr>"),
+        Node::Html(raw) => html.push_str(&raw.value),
+        Node::Table(table) => {
+            html.push_str("<table>");
+            for (ix, row) in table.children.iter().enumerate() {
+                let cell_tag = if ix == 0 { "th" } else { "td" };
+                if ix == 0 {
+                    html.push_str("<thead>");
+                } else if ix == 1 {
+                    html.push_str("<tbody>");
+                }
+                html.push_str("<tr>");
+                for cell in row.children().into_iter().flatten() {
+                    write_html_element(cell_tag, cell, html, true);
+                }
+                html.push_str("</tr>");
+                if ix == 0 {
+                    html.push_str("</thead>");
+                }
+            }
+            if table.children.len() > 1 {
+                html.push_str("</tbody>");
+            }
+            html.push_str("</table>");
+        }
+        Node::Definition(_) | Node::FootnoteDefinition(_) => {}
+        _ => write_html_children(node, html, tight),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_html() {
+        assert_eq!(to_html("hello world"), None);
+        assert_eq!(to_html("hello\n\nworld"), None);
+        assert_eq!(
+            to_html("**hello** _world_"),
+            Some("<strong>hello</strong> <em>world</em>".into())
+        );
+        assert_eq!(
+            to_html("[@alice:example.org](https://matrix.to/#/@alice:example.org) <3"),
+            Some(
+                "<a href=\"https://matrix.to/#/@alice:example.org\">@alice:example.org</a> &lt;3"
+                    .into()
+            )
+        );
+        assert_eq!(
+            to_html("- one\n- two"),
+            Some("<ul><li>one</li><li>two</li></ul>".into())
+        );
+        assert_eq!(
+            to_html("so $$x^2$$"),
+            Some("so <span data-mx-maths=\"x^2\"><code>x^2</code></span>".into())
+        );
+        assert_eq!(to_html("$x^2$"), None);
+        assert_eq!(to_html("it costs $5 or $10"), None);
+    }
+
+    #[test]
+    fn test_source_marks() {
+        let (marks, dimmed) = source_marks("a **b** `c`");
+        assert_eq!(
+            marks,
+            vec![
+                (2..7, TextMark::default().bold()),
+                (8..11, TextMark::default().code()),
+            ]
+        );
+        assert_eq!(dimmed, vec![2..4, 5..7, 8..9, 10..11]);
+    }
+}
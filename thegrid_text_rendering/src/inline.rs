@@ -1,15 +1,16 @@
 use crate::cursor::Selection;
 use crate::global_state::GlobalState;
-use crate::node::LinkMark;
+use crate::node::{LinkMark, PILL_AVATAR_SPACE};
 use crate::{Events, LinkClickedEvent};
 use contemporary::styling::theme::{Theme, ThemeStorage};
 use gpui::{
-    App, BorderStyle, Bounds, CursorStyle, Edges, Element, ElementId, GlobalElementId, Half,
-    HighlightStyle, Hitbox, HitboxBehavior, InspectorElementId, IntoElement, LayoutId,
-    MouseMoveEvent, MouseUpEvent, Pixels, Point, SharedString, StyledText, TextLayout, Window,
-    point, px, quad,
+    AnyElement, App, AvailableSpace, BorderStyle, Bounds, CursorStyle, Edges, Element, ElementId,
+    GlobalElementId, Half, HighlightStyle, Hitbox, HitboxBehavior, Hsla, InspectorElementId,
+    IntoElement, LayoutId, MouseMoveEvent, MouseUpEvent, Pixels, Point, SharedString, Size,
+    StyledText, TextLayout, Window, point, px, quad,
 };
 use std::{
+    collections::HashSet,
     ops::Range,
     rc::Rc,
     sync::{Arc, Mutex},
@@ -23,6 +24,9 @@ pub(super) struct Inline {
     text: SharedString,
     links: Rc<Vec<(Range<usize>, LinkMark)>>,
     highlights: Vec<(Range<usize>, HighlightStyle)>,
+    spoilers: Rc<Vec<Range<usize>>>,
+    /// Avatars of mention pills, drawn over the space left at each offset.
+    pills: Vec<(usize, AnyElement)>,
     styled_text: StyledText,
 
     events: Events,
@@ -37,6 +41,8 @@ pub(crate) struct InlineState {
     /// The text that actually rendering, matched with selection.
     pub(super) text: SharedString,
     pub(super) selection: Option<Selection>,
+    /// The start offsets of the spoilers that have been clicked on.
+    revealed_spoilers: HashSet<usize>,
 }
 
 impl InlineState {
@@ -59,6 +65,8 @@ impl Inline {
             id: id.into(),
             links: Rc::new(links),
             highlights,
+            spoilers: Rc::new(vec![]),
+            pills: vec![],
             text: text.clone(),
             styled_text: StyledText::new(text),
             state,
@@ -66,6 +74,32 @@ impl Inline {
         }
     }
 
+    /// Set the ranges of text that are hidden until clicked on.
+    pub(super) fn spoilers(mut self, spoilers: Vec<Range<usize>>) -> Self {
+        self.spoilers = Rc::new(spoilers);
+        self
+    }
+
+    /// Set the avatars of mention pills, keyed by the offset of the space left for them.
+    pub(super) fn pills(mut self, pills: Vec<(usize, AnyElement)>) -> Self {
+        self.pills = pills;
+        self
+    }
+
+    /// Get the hidden spoiler at given mouse position.
+    fn hidden_spoiler_for_position(
+        layout: &TextLayout,
+        spoilers: &Vec<Range<usize>>,
+        revealed_spoilers: &HashSet<usize>,
+        position: Point<Pixels>,
+    ) -> Option<Range<usize>> {
+        let offset = layout.index_for_position(position).ok()?;
+        spoilers
+            .iter()
+            .find(|range| range.contains(&offset) && !revealed_spoilers.contains(&range.start))
+            .cloned()
+    }
+
     /// Get link at given mouse position.
     fn link_for_position(
         layout: &TextLayout,
@@ -163,6 +197,26 @@ impl Inline {
         if end < start {
             std::mem::swap(&mut start, &mut end);
         }
+
+        Self::paint_range(
+            start..end,
+            theme.button_background.into(),
+            text_layout,
+            bounds,
+            window,
+        );
+    }
+
+    /// Fill the area covered by a range of text, following it across lines.
+    fn paint_range(
+        range: Range<usize>,
+        color: Hsla,
+        text_layout: &TextLayout,
+        bounds: &Bounds<Pixels>,
+        window: &mut Window,
+    ) {
+        let start = range.start;
+        let end = range.end;
         let Some(start_position) = text_layout.position_for_index(start) else {
             return;
         };
@@ -178,7 +232,7 @@ impl Inline {
                     point(end_position.x, end_position.y + line_height),
                 ),
                 px(0.),
-                theme.button_background,
+                color,
                 Edges::default(),
                 gpui::transparent_black(),
                 BorderStyle::default(),
@@ -190,7 +244,7 @@ impl Inline {
                     point(bounds.right(), start_position.y + line_height),
                 ),
                 px(0.),
-                theme.button_background,
+                color,
                 Edges::default(),
                 gpui::transparent_black(),
                 BorderStyle::default(),
@@ -203,7 +257,7 @@ impl Inline {
                         point(bounds.right(), end_position.y),
                     ),
                     px(0.),
-                    theme.button_background,
+                    color,
                     Edges::default(),
                     gpui::transparent_black(),
                     BorderStyle::default(),
@@ -216,7 +270,7 @@ impl Inline {
                     point(end_position.x, end_position.y + line_height),
                 ),
                 px(0.),
-                theme.button_background,
+                color,
                 Edges::default(),
                 gpui::transparent_black(),
                 BorderStyle::default(),
@@ -300,6 +354,38 @@ impl Element for Inline {
         self.styled_text
             .prepaint(id, inspector_id, bounds, &mut (), window, cx);
 
+        // Centre each avatar in the space left for it
+        let text_layout = self.styled_text.layout().clone();
+        let line_height = text_layout.line_height();
+        self.pills.retain_mut(|(offset, avatar)| {
+            let Some(position) = text_layout.position_for_index(*offset) else {
+                return false;
+            };
+
+            let size = avatar.layout_as_root(
+                Size {
+                    width: AvailableSpace::Definite(line_height),
+                    height: AvailableSpace::Definite(line_height),
+                },
+                window,
+                cx,
+            );
+            let space_width = text_layout
+                .position_for_index(*offset + PILL_AVATAR_SPACE.len())
+                .filter(|next_position| next_position.y == position.y)
+                .map(|next_position| next_position.x - position.x)
+                .unwrap_or(size.width);
+            avatar.prepaint_at(
+                point(
+                    position.x + (space_width - size.width).half(),
+                    position.y + (line_height - size.height).half(),
+                ),
+                window,
+                cx,
+            );
+            true
+        });
+
         let hitbox = window.insert_hitbox(bounds, HitboxBehavior::Normal);
         hitbox
     }
@@ -338,10 +424,39 @@ impl Element for Inline {
             window.set_cursor_style(CursorStyle::PointingHand, &hitbox);
         }
 
+        for (_, avatar) in self.pills.iter_mut() {
+            avatar.paint(window, cx);
+        }
+
         if let Some(selection) = &state.selection {
             Self::paint_selection(selection, &text_layout, &bounds, window, cx);
         }
 
+        // hide spoilers until they are clicked on
+        let theme = cx.global::<Theme>();
+        let spoiler_color: Hsla = theme.foreground.into();
+        for spoiler in self.spoilers.iter() {
+            if !state.revealed_spoilers.contains(&spoiler.start) {
+                Self::paint_range(
+                    spoiler.clone(),
+                    spoiler_color,
+                    &text_layout,
+                    &bounds,
+                    window,
+                );
+            }
+        }
+        if Self::hidden_spoiler_for_position(
+            &text_layout,
+            &self.spoilers,
+            &state.revealed_spoilers,
+            mouse_position,
+        )
+        .is_some()
+        {
+            window.set_cursor_style(CursorStyle::PointingHand, &hitbox);
+        }
+
         // mouse move, update hovered link
         window.on_mouse_event({
             let hitbox = hitbox.clone();
@@ -366,6 +481,8 @@ impl Element for Inline {
             // click to open link
             window.on_mouse_event({
                 let links = self.links.clone();
+                let spoilers = self.spoilers.clone();
+                let inline_state = self.state.clone();
                 let on_link_clicked = self.events.on_link_clicked.clone();
                 let text_layout = text_layout.clone();
 
@@ -374,6 +491,21 @@ impl Element for Inline {
                         return;
                     }
 
+                    // click to reveal spoiler, before following any link inside it
+                    let mut inline_state = inline_state.lock().unwrap();
+                    if let Some(spoiler) = Self::hidden_spoiler_for_position(
+                        &text_layout,
+                        &spoilers,
+                        &inline_state.revealed_spoilers,
+                        event.position,
+                    ) {
+                        cx.stop_propagation();
+                        inline_state.revealed_spoilers.insert(spoiler.start);
+                        cx.notify(current_view);
+                        return;
+                    }
+                    drop(inline_state);
+
                     if let Some(link) =
                         Self::link_for_position(&text_layout, &links, event.position)
                     {
@@ -1,13 +1,16 @@
+mod composer;
 mod cursor;
 mod format;
 mod global_state;
 mod highlighter;
 mod inline;
+mod math;
 mod node;
 mod style;
 mod text_view;
 mod utils;
 
+pub use composer::*;
 use gpui::App;
 pub use style::*;
 pub use text_view::*;
@@ -0,0 +1,1397 @@
+//! A small LaTeX maths typesetter.
+//!
+//! This covers the subset of LaTeX that people actually type into chat: fractions, roots,
+//! scripts, large operators, delimiters, matrices and the usual symbols. Display maths is laid
+//! out with nested flex boxes. Inline maths has to flow with the surrounding text, so it is
+//! written out on one line using Unicode instead.
+
+use crate::node::TextMark;
+use crate::utils::{subscript_char, superscript_char};
+use gpui::prelude::FluentBuilder;
+use gpui::{
+    AnyElement, Div, FontWeight, Hsla, IntoElement, ParentElement, Pixels, Styled, div, px,
+};
+use std::ops::Range;
+
+/// The size of scripts relative to the text they are attached to.
+const SCRIPT_SCALE: f32 = 0.7;
+
+/// The size of the numerator and denominator of a fraction relative to the surrounding text.
+const FRACTION_SCALE: f32 = 0.9;
+
+/// The size of large operators such as `\sum` relative to the surrounding text.
+const LARGE_OPERATOR_SCALE: f32 = 1.6;
+
+/// The smallest size in pixels that anything is drawn at, however deeply it is nested.
+const MIN_SIZE: f32 = 8.;
+
+/// How deeply an expression can be nested.
+///
+/// Parsing and laying out an expression both recurse through it, so anything nested more deeply
+/// than this is shown as it was written instead of overflowing the stack.
+const MAX_NESTING_DEPTH: usize = 32;
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum MathNode {
+    /// Atoms laid out left to right.
+    Row(Vec<MathNode>),
+    /// A variable, drawn in italics.
+    Identifier(String),
+    Number(String),
+    /// A binary operator or relation, drawn with space on either side.
+    Operator(String),
+    /// Punctuation, brackets and other symbols drawn without extra space.
+    Symbol(String),
+    /// Upright text, such as from `\text` or function names like `\sin`.
+    Text(String),
+    Fraction {
+        numerator: Box<MathNode>,
+        denominator: Box<MathNode>,
+        /// Whether the line between the numerator and denominator is drawn.
+        bar: bool,
+    },
+    Root {
+        index: Option<Box<MathNode>>,
+        radicand: Box<MathNode>,
+    },
+    Scripts {
+        base: Box<MathNode>,
+        subscript: Option<Box<MathNode>>,
+        superscript: Option<Box<MathNode>>,
+    },
+    /// An operator such as `\sum` or `\lim` that can take limits.
+    LargeOperator {
+        symbol: String,
+        /// Whether the symbol is drawn larger than the surrounding text.
+        large: bool,
+        /// Whether the limits are drawn above and below, rather than as scripts.
+        limits: bool,
+        lower: Option<Box<MathNode>>,
+        upper: Option<Box<MathNode>>,
+    },
+    Delimited {
+        left: String,
+        right: String,
+        body: Box<MathNode>,
+    },
+    Matrix {
+        rows: Vec<Vec<MathNode>>,
+        left: String,
+        right: String,
+        /// Whether the cells are aligned to the left, as in `cases`, rather than centred.
+        align_left: bool,
+    },
+    Accent {
+        /// The combining character used when written inline.
+        combining: char,
+        /// The character drawn above the body in display maths.
+        symbol: char,
+        body: Box<MathNode>,
+    },
+    Bold(Box<MathNode>),
+    /// Horizontal space, in ems.
+    Space(f32),
+    /// Something that couldn't be understood, shown as it was written.
+    Error(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Command(String),
+    Char(char),
+    Whitespace,
+    Open,
+    Close,
+    Superscript,
+    Subscript,
+    Ampersand,
+    NewRow,
+}
+
+fn tokenize(source: &str) -> Vec<Token> {
+    let mut tokens = vec![];
+    let mut chars = source.chars().peekable();
+    while let Some(c) = chars.next() {
+        tokens.push(match c {
+            '\\' => match chars.next() {
+                Some('\\') => Token::NewRow,
+                Some(c) if c.is_ascii_alphabetic() => {
+                    let mut name = c.to_string();
+                    while let Some(&c) = chars.peek()
+                        && c.is_ascii_alphabetic()
+                    {
+                        name.push(c);
+                        chars.next();
+                    }
+                    Token::Command(name)
+                }
+                Some(c) => Token::Command(c.to_string()),
+                None => Token::Char('\\'),
+            },
+            '{' => Token::Open,
+            '}' => Token::Close,
+            '^' => Token::Superscript,
+            '_' => Token::Subscript,
+            '&' => Token::Ampersand,
+            c if c.is_whitespace() => Token::Whitespace,
+            c => Token::Char(c),
+        });
+    }
+    tokens
+}
+
+/// Parse LaTeX maths.
+///
+/// This never fails; anything that can't be understood is kept as it was written. Expressions
+/// that are nested too deeply are kept as they were written in their entirety.
+pub(crate) fn parse(source: &str) -> MathNode {
+    let mut parser = Parser {
+        tokens: tokenize(source),
+        ix: 0,
+        depth: 0,
+        too_deep: false,
+    };
+
+    let mut children = vec![];
+    loop {
+        let row = parser.parse_row(&|_| false);
+        children.extend(row);
+        // Stray closing braces and alignment characters at the top level are ignored
+        if parser.too_deep || parser.next().is_none() {
+            break;
+        }
+    }
+
+    let row = MathNode::Row(children);
+    if parser.too_deep || row.is_too_deep() {
+        return MathNode::Row(vec![MathNode::Error(source.to_string())]);
+    }
+    row
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    ix: usize,
+
+    /// How many rows and atoms are currently being parsed
+    depth: usize,
+
+    /// Whether parsing was abandoned because the expression is nested too deeply
+    too_deep: bool,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.ix)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.ix).cloned();
+        self.ix += 1;
+        token
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek() == Some(&Token::Whitespace) {
+            self.ix += 1;
+        }
+    }
+
+    /// Run `parse` one level deeper, giving up on the whole expression if it is nested too
+    /// deeply.
+    ///
+    /// Each level of nesting takes both a row and an atom to parse, so twice the maximum depth is
+    /// allowed here. The depth of the finished expression is checked separately.
+    fn nested<T: Default>(&mut self, parse: impl FnOnce(&mut Self) -> T) -> T {
+        if self.too_deep || self.depth >= MAX_NESTING_DEPTH * 2 {
+            self.too_deep = true;
+            return T::default();
+        }
+
+        self.depth += 1;
+        let result = parse(self);
+        self.depth -= 1;
+        result
+    }
+
+    /// Parse atoms until the end of a group, or until `stop` matches the next token.
+    ///
+    /// The token that ended the row is not consumed.
+    fn parse_row(&mut self, stop: &dyn Fn(&Token) -> bool) -> Vec<MathNode> {
+        self.nested(|this| this.parse_row_contents(stop))
+    }
+
+    fn parse_row_contents(&mut self, stop: &dyn Fn(&Token) -> bool) -> Vec<MathNode> {
+        let mut children = vec![];
+        while !self.too_deep {
+            self.skip_whitespace();
+            match self.peek() {
+                None | Some(Token::Close | Token::Ampersand | Token::NewRow) => break,
+                Some(Token::Command(name)) if name == "right" || name == "end" => break,
+                Some(token) if stop(token) => break,
+                _ => {}
+            }
+
+            if let Some(atom) = self.parse_atom() {
+                children.push(atom);
+            }
+        }
+        children
+    }
+
+    /// Parse an atom along with any scripts attached to it.
+    fn parse_atom(&mut self) -> Option<MathNode> {
+        let mut atom = match self.peek()? {
+            Token::Superscript | Token::Subscript => MathNode::Row(vec![]),
+            _ => self.parse_nucleus()?,
+        };
+
+        loop {
+            self.skip_whitespace();
+            let is_superscript = match self.peek() {
+                Some(Token::Superscript) => true,
+                Some(Token::Subscript) => false,
+                Some(Token::Command(name)) if name == "limits" || name == "nolimits" => {
+                    if let MathNode::LargeOperator { limits, .. } = &mut atom {
+                        *limits = name == "limits";
+                    }
+                    self.ix += 1;
+                    continue;
+                }
+                _ => break,
+            };
+            self.ix += 1;
+            let script = Box::new(self.parse_argument());
+            let (new_subscript, new_superscript) = if is_superscript {
+                (None, Some(script))
+            } else {
+                (Some(script), None)
+            };
+
+            atom = match atom {
+                MathNode::LargeOperator {
+                    symbol,
+                    large,
+                    limits,
+                    lower,
+                    upper,
+                } => MathNode::LargeOperator {
+                    symbol,
+                    large,
+                    limits,
+                    lower: new_subscript.or(lower),
+                    upper: new_superscript.or(upper),
+                },
+                MathNode::Scripts {
+                    base,
+                    subscript,
+                    superscript,
+                } => MathNode::Scripts {
+                    base,
+                    subscript: new_subscript.or(subscript),
+                    superscript: new_superscript.or(superscript),
+                },
+                base => MathNode::Scripts {
+                    base: Box::new(base),
+                    subscript: new_subscript,
+                    superscript: new_superscript,
+                },
+            };
+        }
+
+        Some(atom)
+    }
+
+    /// Parse a group in braces, or a single atom without any scripts.
+    fn parse_argument(&mut self) -> MathNode {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(Token::Open) => {
+                self.ix += 1;
+                let children = self.parse_row(&|_| false);
+                if self.peek() == Some(&Token::Close) {
+                    self.ix += 1;
+                }
+                MathNode::Row(children)
+            }
+            Some(Token::Close) | None => MathNode::Row(vec![]),
+            _ => self.parse_nucleus().unwrap_or(MathNode::Row(vec![])),
+        }
+    }
+
+    /// Read the raw text of an argument, keeping spaces.
+    fn parse_text_argument(&mut self) -> String {
+        self.skip_whitespace();
+        let mut text = String::new();
+        if self.peek() != Some(&Token::Open) {
+            if let Some(Token::Char(c)) = self.next() {
+                text.push(c);
+            }
+            return text;
+        }
+
+        self.ix += 1;
+        let mut depth = 0;
+        while let Some(token) = self.next() {
+            match token {
+                Token::Open => depth += 1,
+                Token::Close if depth == 0 => break,
+                Token::Close => depth -= 1,
+                Token::Char(c) => text.push(c),
+                Token::Whitespace => text.push(' '),
+                Token::Command(name) => {
+                    if matches!(name.as_str(), "{" | "}" | "%" | "$" | "#" | "&" | "_") {
+                        text.push_str(&name);
+                    }
+                }
+                Token::Superscript => text.push('^'),
+                Token::Subscript => text.push('_'),
+                Token::Ampersand => text.push('&'),
+                Token::NewRow => {}
+            }
+        }
+        text
+    }
+
+    /// Read a delimiter following `\left`, `\right` or `\big`.
+    fn parse_delimiter(&mut self) -> String {
+        self.skip_whitespace();
+        match self.next() {
+            Some(Token::Char('.')) => String::new(),
+            Some(Token::Char(c)) => c.to_string(),
+            Some(Token::Command(name)) => match symbol(&name) {
+                Some(MathNode::Symbol(symbol) | MathNode::Operator(symbol)) => symbol,
+                _ => String::new(),
+            },
+            _ => String::new(),
+        }
+    }
+
+    fn parse_nucleus(&mut self) -> Option<MathNode> {
+        self.nested(|this| this.parse_nucleus_contents())
+    }
+
+    fn parse_nucleus_contents(&mut self) -> Option<MathNode> {
+        match self.next()? {
+            Token::Open => {
+                let children = self.parse_row(&|_| false);
+                if self.peek() == Some(&Token::Close) {
+                    self.ix += 1;
+                }
+                Some(MathNode::Row(children))
+            }
+            Token::Char(c) if c.is_ascii_digit() => {
+                let mut number = c.to_string();
+                while let Some(Token::Char(c)) = self.peek() {
+                    let c = *c;
+                    let continues_number = c.is_ascii_digit()
+                        || (c == '.'
+                            && matches!(
+                                self.tokens.get(self.ix + 1),
+                                Some(Token::Char(c)) if c.is_ascii_digit()
+                            ));
+                    if !continues_number {
+                        break;
+                    }
+                    number.push(c);
+                    self.ix += 1;
+                }
+                Some(MathNode::Number(number))
+            }
+            Token::Char(c) => Some(char_atom(c)),
+            Token::Command(name) => self.parse_command(name),
+            Token::Close
+            | Token::Whitespace
+            | Token::Superscript
+            | Token::Subscript
+            | Token::Ampersand
+            | Token::NewRow => None,
+        }
+    }
+
+    fn parse_command(&mut self, name: String) -> Option<MathNode> {
+        Some(match name.as_str() {
+            "frac" | "dfrac" | "tfrac" | "cfrac" => MathNode::Fraction {
+                numerator: Box::new(self.parse_argument()),
+                denominator: Box::new(self.parse_argument()),
+                bar: true,
+            },
+            "binom" | "dbinom" | "tbinom" => MathNode::Delimited {
+                left: "(".into(),
+                right: ")".into(),
+                body: Box::new(MathNode::Fraction {
+                    numerator: Box::new(self.parse_argument()),
+                    denominator: Box::new(self.parse_argument()),
+                    bar: false,
+                }),
+            },
+            "sqrt" => {
+                self.skip_whitespace();
+                let index = if self.peek() == Some(&Token::Char('[')) {
+                    self.ix += 1;
+                    let index = self.parse_row(&|token| *token == Token::Char(']'));
+                    if self.peek() == Some(&Token::Char(']')) {
+                        self.ix += 1;
+                    }
+                    Some(Box::new(MathNode::Row(index)))
+                } else {
+                    None
+                };
+                MathNode::Root {
+                    index,
+                    radicand: Box::new(self.parse_argument()),
+                }
+            }
+            "left" => {
+                let left = self.parse_delimiter();
+                let body = self.parse_row(&|_| false);
+                let right = if self.peek() == Some(&Token::Command("right".into())) {
+                    self.ix += 1;
+                    self.parse_delimiter()
+                } else {
+                    String::new()
+                };
+                MathNode::Delimited {
+                    left,
+                    right,
+                    body: Box::new(MathNode::Row(body)),
+                }
+            }
+            "big" | "Big" | "bigg" | "Bigg" | "bigl" | "Bigl" | "biggl" | "Biggl" | "bigr"
+            | "Bigr" | "biggr" | "Biggr" | "bigm" | "Bigm" => {
+                MathNode::Symbol(self.parse_delimiter())
+            }
+            "text" | "textrm" | "textnormal" | "textit" | "textbf" | "mbox" | "mathrm"
+            | "operatorname" => MathNode::Text(self.parse_text_argument()),
+            "mathbf" | "boldsymbol" | "bm" => MathNode::Bold(Box::new(self.parse_argument())),
+            "mathbb" => MathNode::Text(
+                self.parse_text_argument()
+                    .chars()
+                    .map(double_struck_char)
+                    .collect(),
+            ),
+            "mathit" | "mathcal" | "mathscr" | "mathfrak" | "mathsf" | "mathtt" => {
+                self.parse_argument()
+            }
+            "hat" | "widehat" => self.parse_accent('\u{302}', 'ˆ'),
+            "bar" | "overline" => self.parse_accent('\u{305}', '¯'),
+            "vec" | "overrightarrow" => self.parse_accent('\u{20D7}', '→'),
+            "dot" => self.parse_accent('\u{307}', '˙'),
+            "ddot" => self.parse_accent('\u{308}', '¨'),
+            "tilde" | "widetilde" => self.parse_accent('\u{303}', '˜'),
+            "begin" => self.parse_environment(),
+            "," | ">" => MathNode::Space(0.17),
+            ":" => MathNode::Space(0.22),
+            ";" => MathNode::Space(0.28),
+            " " => MathNode::Space(0.33),
+            "quad" => MathNode::Space(1.),
+            "qquad" => MathNode::Space(2.),
+            "!" | "displaystyle" | "textstyle" | "scriptstyle" | "nonumber" | "notag" => {
+                return None;
+            }
+            _ => symbol(&name).unwrap_or(MathNode::Error(format!("\\{name}"))),
+        })
+    }
+
+    fn parse_accent(&mut self, combining: char, symbol: char) -> MathNode {
+        MathNode::Accent {
+            combining,
+            symbol,
+            body: Box::new(self.parse_argument()),
+        }
+    }
+
+    /// Parse the contents of a `\begin{...}` and `\end{...}` pair.
+    fn parse_environment(&mut self) -> MathNode {
+        let environment = self.parse_text_argument();
+        let environment = environment.trim_end_matches('*');
+
+        let mut rows = vec![];
+        let mut row = vec![];
+        loop {
+            row.push(MathNode::Row(self.parse_row(&|_| false)));
+            match self.next() {
+                Some(Token::Ampersand) => {}
+                Some(Token::NewRow) => rows.push(std::mem::take(&mut row)),
+                Some(Token::Command(name)) if name == "end" => {
+                    self.parse_text_argument();
+                    break;
+                }
+                // Stray `}` or `\right` inside the environment
+                Some(_) => {}
+                None => break,
+            }
+        }
+        // A trailing `\\` leaves an empty row behind
+        if row.iter().any(|cell| *cell != MathNode::Row(vec![])) {
+            rows.push(row);
+        }
+
+        let (left, right) = match environment {
+            "pmatrix" => ("(", ")"),
+            "bmatrix" => ("[", "]"),
+            "Bmatrix" | "cases" => ("{", ""),
+            "vmatrix" => ("|", "|"),
+            "Vmatrix" => ("‖", "‖"),
+            _ => ("", ""),
+        };
+        let right = if environment == "Bmatrix" { "}" } else { right };
+
+        MathNode::Matrix {
+            rows,
+            left: left.into(),
+            right: right.into(),
+            align_left: matches!(environment, "cases" | "aligned" | "align" | "gathered"),
+        }
+    }
+}
+
+fn char_atom(c: char) -> MathNode {
+    match c {
+        '+' | '=' | '<' | '>' => MathNode::Operator(c.to_string()),
+        '-' => MathNode::Operator("−".into()),
+        '*' => MathNode::Operator("∗".into()),
+        '\'' => MathNode::Symbol("′".into()),
+        '~' => MathNode::Space(0.33),
+        c if c.is_alphabetic() => MathNode::Identifier(c.to_string()),
+        c => MathNode::Symbol(c.to_string()),
+    }
+}
+
+fn double_struck_char(c: char) -> char {
+    match c {
+        'C' => 'ℂ',
+        'H' => 'ℍ',
+        'N' => 'ℕ',
+        'P' => 'ℙ',
+        'Q' => 'ℚ',
+        'R' => 'ℝ',
+        'Z' => 'ℤ',
+        c => c,
+    }
+}
+
+/// Look up a command that stands for a single symbol.
+fn symbol(name: &str) -> Option<MathNode> {
+    let identifier = |s: &str| Some(MathNode::Identifier(s.into()));
+    let operator = |s: &str| Some(MathNode::Operator(s.into()));
+    let symbol = |s: &str| Some(MathNode::Symbol(s.into()));
+    let text = |s: &str| Some(MathNode::Text(s.into()));
+    let large_operator = |s: &str, large: bool, limits: bool| {
+        Some(MathNode::LargeOperator {
+            symbol: s.into(),
+            large,
+            limits,
+            lower: None,
+            upper: None,
+        })
+    };
+
+    match name {
+        // Greek letters
+        "alpha" => identifier("α"),
+        "beta" => identifier("β"),
+        "gamma" => identifier("γ"),
+        "delta" => identifier("δ"),
+        "epsilon" => identifier("ϵ"),
+        "varepsilon" => identifier("ε"),
+        "zeta" => identifier("ζ"),
+        "eta" => identifier("η"),
+        "theta" => identifier("θ"),
+        "vartheta" => identifier("ϑ"),
+        "iota" => identifier("ι"),
+        "kappa" => identifier("κ"),
+        "lambda" => identifier("λ"),
+        "mu" => identifier("μ"),
+        "nu" => identifier("ν"),
+        "xi" => identifier("ξ"),
+        "pi" => identifier("π"),
+        "varpi" => identifier("ϖ"),
+        "rho" => identifier("ρ"),
+        "varrho" => identifier("ϱ"),
+        "sigma" => identifier("σ"),
+        "varsigma" => identifier("ς"),
+        "tau" => identifier("τ"),
+        "upsilon" => identifier("υ"),
+        "phi" => identifier("ϕ"),
+        "varphi" => identifier("φ"),
+        "chi" => identifier("χ"),
+        "psi" => identifier("ψ"),
+        "omega" => identifier("ω"),
+        "Gamma" => text("Γ"),
+        "Delta" => text("Δ"),
+        "Theta" => text("Θ"),
+        "Lambda" => text("Λ"),
+        "Xi" => text("Ξ"),
+        "Pi" => text("Π"),
+        "Sigma" => text("Σ"),
+        "Upsilon" => text("Υ"),
+        "Phi" => text("Φ"),
+        "Psi" => text("Ψ"),
+        "Omega" => text("Ω"),
+
+        // Binary operators and relations
+        "pm" => operator("±"),
+        "mp" => operator("∓"),
+        "times" => operator("×"),
+        "div" => operator("÷"),
+        "cdot" => operator("⋅"),
+        "ast" => operator("∗"),
+        "star" => operator("⋆"),
+        "circ" => operator("∘"),
+        "bullet" => operator("∙"),
+        "oplus" => operator("⊕"),
+        "ominus" => operator("⊖"),
+        "otimes" => operator("⊗"),
+        "cup" => operator("∪"),
+        "cap" => operator("∩"),
+        "setminus" => operator("∖"),
+        "wedge" | "land" => operator("∧"),
+        "vee" | "lor" => operator("∨"),
+        "leq" | "le" => operator("≤"),
+        "geq" | "ge" => operator("≥"),
+        "neq" | "ne" => operator("≠"),
+        "ll" => operator("≪"),
+        "gg" => operator("≫"),
+        "approx" => operator("≈"),
+        "equiv" => operator("≡"),
+        "sim" => operator("∼"),
+        "simeq" => operator("≃"),
+        "cong" => operator("≅"),
+        "propto" => operator("∝"),
+        "in" => operator("∈"),
+        "notin" => operator("∉"),
+        "ni" => operator("∋"),
+        "subset" => operator("⊂"),
+        "subseteq" => operator("⊆"),
+        "supset" => operator("⊃"),
+        "supseteq" => operator("⊇"),
+        "perp" => operator("⊥"),
+        "parallel" => operator("∥"),
+        "mid" => operator("∣"),
+        "to" | "rightarrow" => operator("→"),
+        "gets" | "leftarrow" => operator("←"),
+        "leftrightarrow" => operator("↔"),
+        "Rightarrow" => operator("⇒"),
+        "Leftarrow" => operator("⇐"),
+        "Leftrightarrow" => operator("⇔"),
+        "implies" => operator("⟹"),
+        "iff" => operator("⟺"),
+        "mapsto" => operator("↦"),
+        "colon" => operator(":"),
+
+        // Other symbols
+        "neg" | "lnot" => symbol("¬"),
+        "forall" => symbol("∀"),
+        "exists" => symbol("∃"),
+        "partial" => symbol("∂"),
+        "nabla" => symbol("∇"),
+        "infty" => symbol("∞"),
+        "emptyset" | "varnothing" => symbol("∅"),
+        "hbar" => symbol("ℏ"),
+        "ell" => symbol("ℓ"),
+        "Re" => symbol("ℜ"),
+        "Im" => symbol("ℑ"),
+        "aleph" => symbol("ℵ"),
+        "angle" => symbol("∠"),
+        "degree" => symbol("°"),
+        "prime" => symbol("′"),
+        "ldots" | "dots" => symbol("…"),
+        "cdots" => symbol("⋯"),
+        "vdots" => symbol("⋮"),
+        "ddots" => symbol("⋱"),
+        "langle" => symbol("⟨"),
+        "rangle" => symbol("⟩"),
+        "lfloor" => symbol("⌊"),
+        "rfloor" => symbol("⌋"),
+        "lceil" => symbol("⌈"),
+        "rceil" => symbol("⌉"),
+        "lbrace" | "{" => symbol("{"),
+        "rbrace" | "}" => symbol("}"),
+        "vert" | "lvert" | "rvert" => symbol("|"),
+        "|" | "Vert" | "lVert" | "rVert" => symbol("‖"),
+        "%" | "$" | "#" | "&" | "_" => symbol(name),
+
+        // Large operators
+        "sum" => large_operator("∑", true, true),
+        "prod" => large_operator("∏", true, true),
+        "coprod" => large_operator("∐", true, true),
+        "bigcup" => large_operator("⋃", true, true),
+        "bigcap" => large_operator("⋂", true, true),
+        "bigoplus" => large_operator("⨁", true, true),
+        "bigotimes" => large_operator("⨂", true, true),
+        "int" => large_operator("∫", true, false),
+        "iint" => large_operator("∬", true, false),
+        "iiint" => large_operator("∭", true, false),
+        "oint" => large_operator("∮", true, false),
+        "lim" | "liminf" | "limsup" | "max" | "min" | "sup" | "inf" | "det" | "gcd" | "Pr"
+        | "argmax" | "argmin" => large_operator(
+            match name {
+                "liminf" => "lim inf",
+                "limsup" => "lim sup",
+                "argmax" => "arg max",
+                "argmin" => "arg min",
+                _ => name,
+            },
+            false,
+            true,
+        ),
+
+        // Functions
+        "sin" | "cos" | "tan" | "sec" | "csc" | "cot" | "arcsin" | "arccos" | "arctan" | "sinh"
+        | "cosh" | "tanh" | "coth" | "log" | "lg" | "ln" | "exp" | "dim" | "ker" | "deg"
+        | "hom" | "arg" | "mod" | "bmod" => text(name.trim_start_matches('b')),
+
+        _ => None,
+    }
+}
+
+impl MathNode {
+    /// Write the expression on a single line for inline maths.
+    ///
+    /// Returns the text, along with marks for the parts of it that should be drawn in italics.
+    pub(crate) fn to_inline(&self) -> (String, Vec<(Range<usize>, TextMark)>) {
+        let mut text = String::new();
+        let mut italics = vec![];
+        self.write_inline(&mut text, &mut italics);
+        let marks = italics
+            .into_iter()
+            .map(|range| (range, TextMark::default().italic()))
+            .collect();
+        (text, marks)
+    }
+
+    fn write_inline(&self, text: &mut String, italics: &mut Vec<Range<usize>>) {
+        match self {
+            MathNode::Row(children) => {
+                for (ix, child) in children.iter().enumerate() {
+                    match child {
+                        // A leading operator is a sign rather than an operation
+                        MathNode::Operator(operator) if ix == 0 => text.push_str(operator),
+                        child => child.write_inline(text, italics),
+                    }
+                }
+            }
+            MathNode::Identifier(identifier) => {
+                let start = text.len();
+                text.push_str(identifier);
+                italics.push(start..text.len());
+            }
+            MathNode::Number(s) | MathNode::Symbol(s) | MathNode::Text(s) => text.push_str(s),
+            MathNode::Error(s) => text.push_str(s),
+            MathNode::Operator(operator) => {
+                text.push(' ');
+                text.push_str(operator);
+                text.push(' ');
+            }
+            MathNode::Fraction {
+                numerator,
+                denominator,
+                bar,
+            } => {
+                numerator.write_inline_grouped(text, italics);
+                text.push_str(if *bar { "/" } else { " " });
+                denominator.write_inline_grouped(text, italics);
+            }
+            MathNode::Root { index, radicand } => {
+                if let Some(index) = index {
+                    write_inline_script(index, superscript_char, "", text, italics);
+                }
+                text.push('√');
+                radicand.write_inline_grouped(text, italics);
+            }
+            MathNode::Scripts {
+                base,
+                subscript,
+                superscript,
+            } => {
+                base.write_inline(text, italics);
+                if let Some(subscript) = subscript {
+                    write_inline_script(subscript, subscript_char, "_", text, italics);
+                }
+                if let Some(superscript) = superscript {
+                    write_inline_script(superscript, superscript_char, "^", text, italics);
+                }
+            }
+            MathNode::LargeOperator {
+                symbol,
+                lower,
+                upper,
+                ..
+            } => {
+                text.push_str(symbol);
+                if let Some(lower) = lower {
+                    write_inline_script(lower, subscript_char, "_", text, italics);
+                }
+                if let Some(upper) = upper {
+                    write_inline_script(upper, superscript_char, "^", text, italics);
+                }
+                text.push(' ');
+            }
+            MathNode::Delimited { left, right, body } => {
+                text.push_str(left);
+                body.write_inline(text, italics);
+                text.push_str(right);
+            }
+            MathNode::Matrix {
+                rows, left, right, ..
+            } => {
+                text.push_str(if left.is_empty() { "[" } else { left });
+                for (row_ix, row) in rows.iter().enumerate() {
+                    if row_ix > 0 {
+                        text.push_str("; ");
+                    }
+                    for (cell_ix, cell) in row.iter().enumerate() {
+                        if cell_ix > 0 {
+                            text.push_str(", ");
+                        }
+                        cell.write_inline(text, italics);
+                    }
+                }
+                text.push_str(match (left.is_empty(), right.is_empty()) {
+                    (true, _) => "]",
+                    (false, true) => "",
+                    (false, false) => right,
+                });
+            }
+            MathNode::Accent {
+                combining,
+                symbol,
+                body,
+            } => {
+                body.write_inline(text, italics);
+                if body.is_single_char() {
+                    text.push(*combining);
+                } else {
+                    text.push(*symbol);
+                }
+            }
+            MathNode::Bold(body) => body.write_inline(text, italics),
+            MathNode::Space(width) => {
+                if *width >= 0.5 {
+                    text.push(' ');
+                } else if *width > 0. {
+                    text.push('\u{2009}');
+                }
+            }
+        }
+    }
+
+    /// Write inline, with brackets around anything longer than a single atom.
+    fn write_inline_grouped(&self, text: &mut String, italics: &mut Vec<Range<usize>>) {
+        if self.is_atom() {
+            self.write_inline(text, italics);
+        } else {
+            text.push('(');
+            self.write_inline(text, italics);
+            text.push(')');
+        }
+    }
+
+    fn is_atom(&self) -> bool {
+        match self {
+            MathNode::Row(children) => children.len() == 1 && children[0].is_atom(),
+            MathNode::Identifier(_)
+            | MathNode::Number(_)
+            | MathNode::Symbol(_)
+            | MathNode::Text(_)
+            | MathNode::Delimited { .. }
+            | MathNode::Matrix { .. } => true,
+            MathNode::Accent { body, .. } | MathNode::Bold(body) => body.is_atom(),
+            MathNode::Scripts { base, .. } => base.is_atom(),
+            _ => false,
+        }
+    }
+
+    fn is_single_char(&self) -> bool {
+        match self {
+            MathNode::Row(children) => children.len() == 1 && children[0].is_single_char(),
+            MathNode::Identifier(s) | MathNode::Number(s) | MathNode::Symbol(s) => {
+                s.chars().count() == 1
+            }
+            _ => false,
+        }
+    }
+
+    /// Roughly how tall this is in ems, used to size delimiters and radical signs.
+    fn height(&self) -> f32 {
+        match self {
+            MathNode::Row(children) => children
+                .iter()
+                .map(|child| child.height())
+                .fold(1., f32::max),
+            MathNode::Fraction {
+                numerator,
+                denominator,
+                ..
+            } => (numerator.height() + denominator.height()) * FRACTION_SCALE + 0.2,
+            MathNode::Root { radicand, .. } => radicand.height() + 0.2,
+            MathNode::Scripts {
+                base,
+                subscript,
+                superscript,
+            } => {
+                base.height()
+                    + subscript
+                        .as_ref()
+                        .map_or(0., |script| script.height() * 0.35)
+                    + superscript
+                        .as_ref()
+                        .map_or(0., |script| script.height() * 0.35)
+            }
+            MathNode::LargeOperator {
+                large,
+                limits,
+                lower,
+                upper,
+                ..
+            } => {
+                let symbol_height = if *large { LARGE_OPERATOR_SCALE } else { 1. };
+                if *limits {
+                    symbol_height
+                        + lower
+                            .as_ref()
+                            .map_or(0., |script| script.height() * SCRIPT_SCALE)
+                        + upper
+                            .as_ref()
+                            .map_or(0., |script| script.height() * SCRIPT_SCALE)
+                } else {
+                    symbol_height
+                }
+            }
+            MathNode::Delimited { body, .. } => body.height(),
+            MathNode::Matrix { rows, .. } => rows
+                .iter()
+                .map(|row| row.iter().map(|cell| cell.height()).fold(1., f32::max) + 0.3)
+                .sum::<f32>()
+                .max(1.),
+            MathNode::Accent { body, .. } => body.height() + 0.3,
+            MathNode::Bold(body) => body.height(),
+            _ => 1.,
+        }
+    }
+
+    /// Whether the expression is nested too deeply to be laid out.
+    ///
+    /// Expressions returned by [`parse`] never are, but this should be checked before laying out
+    /// an expression that came from anywhere else.
+    pub(crate) fn is_too_deep(&self) -> bool {
+        self.exceeds_depth(MAX_NESTING_DEPTH)
+    }
+
+    fn exceeds_depth(&self, depth: usize) -> bool {
+        let Some(depth) = depth.checked_sub(1) else {
+            return true;
+        };
+
+        let exceeds = |node: &MathNode| node.exceeds_depth(depth);
+        let exceeds_optional = |node: &Option<Box<MathNode>>| node.as_deref().is_some_and(exceeds);
+        match self {
+            MathNode::Row(children) => children.iter().any(exceeds),
+            MathNode::Fraction {
+                numerator,
+                denominator,
+                ..
+            } => exceeds(numerator) || exceeds(denominator),
+            MathNode::Root { index, radicand } => exceeds_optional(index) || exceeds(radicand),
+            MathNode::Scripts {
+                base,
+                subscript,
+                superscript,
+            } => exceeds(base) || exceeds_optional(subscript) || exceeds_optional(superscript),
+            MathNode::LargeOperator { lower, upper, .. } => {
+                exceeds_optional(lower) || exceeds_optional(upper)
+            }
+            MathNode::Matrix { rows, .. } => rows.iter().flatten().any(exceeds),
+            MathNode::Delimited { body, .. }
+            | MathNode::Accent { body, .. }
+            | MathNode::Bold(body) => exceeds(body),
+            MathNode::Identifier(_)
+            | MathNode::Number(_)
+            | MathNode::Operator(_)
+            | MathNode::Symbol(_)
+            | MathNode::Text(_)
+            | MathNode::Space(_)
+            | MathNode::Error(_) => false,
+        }
+    }
+
+    /// Lay out the expression for display maths.
+    pub(crate) fn render(&self, size: Pixels, color: Hsla) -> AnyElement {
+        match self {
+            MathNode::Row(children) => div()
+                .flex()
+                .flex_row()
+                .items_center()
+                .children(children.iter().map(|child| child.render(size, color)))
+                .into_any_element(),
+            MathNode::Identifier(identifier) => {
+                glyphs(identifier, size).italic().into_any_element()
+            }
+            MathNode::Number(s) | MathNode::Symbol(s) | MathNode::Text(s) => {
+                glyphs(s, size).into_any_element()
+            }
+            MathNode::Error(s) => glyphs(s, size).into_any_element(),
+            MathNode::Operator(operator) => {
+                glyphs(operator, size).px(size * 0.22).into_any_element()
+            }
+            MathNode::Fraction {
+                numerator,
+                denominator,
+                bar,
+            } => {
+                let part_size = scaled(size, FRACTION_SCALE);
+                div()
+                    .flex()
+                    .flex_col()
+                    .items_center()
+                    .px(size * 0.1)
+                    .child(numerator.render(part_size, color))
+                    .child(
+                        div()
+                            .w_full()
+                            .h(px(1.))
+                            .my(size * 0.1)
+                            .when(*bar, |david| david.bg(color)),
+                    )
+                    .child(denominator.render(part_size, color))
+                    .into_any_element()
+            }
+            MathNode::Root { index, radicand } => {
+                let height = radicand.height();
+                div()
+                    .flex()
+                    .flex_row()
+                    .items_center()
+                    .when_some(index.as_ref(), |david, index| {
+                        david.child(
+                            div()
+                                .flex()
+                                .flex_col()
+                                .mr(size * -0.3)
+                                .child(index.render(scaled(size, SCRIPT_SCALE * 0.8), color))
+                                .child(div().h(size * height * 0.4)),
+                        )
+                    })
+                    .child(stretched_glyph("√", size, height + 0.2))
+                    .child(
+                        div()
+                            .border_t(px(1.))
+                            .border_color(color)
+                            .pl(size * 0.1)
+                            .child(radicand.render(size, color)),
+                    )
+                    .into_any_element()
+            }
+            MathNode::Scripts {
+                base,
+                subscript,
+                superscript,
+            } => div()
+                .flex()
+                .flex_row()
+                .items_center()
+                .child(base.render(size, color))
+                .child(scripts(
+                    subscript.as_deref(),
+                    superscript.as_deref(),
+                    size,
+                    color,
+                ))
+                .into_any_element(),
+            MathNode::LargeOperator {
+                symbol,
+                large,
+                limits,
+                lower,
+                upper,
+            } => {
+                let symbol_size = if *large {
+                    size * LARGE_OPERATOR_SCALE
+                } else {
+                    size
+                };
+                let symbol = glyphs(symbol, symbol_size).px(size * 0.1);
+                if *limits {
+                    let script_size = scaled(size, SCRIPT_SCALE);
+                    div()
+                        .flex()
+                        .flex_col()
+                        .items_center()
+                        .px(size * 0.1)
+                        .when_some(upper.as_ref(), |david, upper| {
+                            david.child(upper.render(script_size, color))
+                        })
+                        .child(symbol)
+                        .when_some(lower.as_ref(), |david, lower| {
+                            david.child(lower.render(script_size, color))
+                        })
+                        .into_any_element()
+                } else {
+                    div()
+                        .flex()
+                        .flex_row()
+                        .items_center()
+                        .pr(size * 0.1)
+                        .child(symbol)
+                        .child(scripts(lower.as_deref(), upper.as_deref(), size, color))
+                        .into_any_element()
+                }
+            }
+            MathNode::Delimited { left, right, body } => {
+                let height = body.height();
+                div()
+                    .flex()
+                    .flex_row()
+                    .items_center()
+                    .child(stretched_glyph(left, size, height))
+                    .child(body.render(size, color))
+                    .child(stretched_glyph(right, size, height))
+                    .into_any_element()
+            }
+            MathNode::Matrix {
+                rows,
+                left,
+                right,
+                align_left,
+            } => {
+                let columns = rows.iter().map(|row| row.len()).max().unwrap_or_default();
+                let row_heights: Vec<f32> = rows
+                    .iter()
+                    .map(|row| row.iter().map(|cell| cell.height()).fold(1., f32::max))
+                    .collect();
+                let height = self.height();
+
+                div()
+                    .flex()
+                    .flex_row()
+                    .items_center()
+                    .child(stretched_glyph(left, size, height))
+                    .child(
+                        div()
+                            .flex()
+                            .flex_row()
+                            .gap(size * 0.8)
+                            .px(size * 0.2)
+                            .children((0..columns).map(|column| {
+                                div()
+                                    .flex()
+                                    .flex_col()
+                                    .gap(size * 0.3)
+                                    .when_else(
+                                        *align_left,
+                                        |david| david.items_start(),
+                                        |david| david.items_center(),
+                                    )
+                                    .children(rows.iter().zip(&row_heights).map(
+                                        |(row, row_height)| {
+                                            div()
+                                                .flex()
+                                                .items_center()
+                                                .h(size * 1.25 * *row_height)
+                                                .when_some(row.get(column), |david, cell| {
+                                                    david.child(cell.render(size, color))
+                                                })
+                                        },
+                                    ))
+                            })),
+                    )
+                    .child(stretched_glyph(right, size, height))
+                    .into_any_element()
+            }
+            MathNode::Accent { symbol, body, .. } => div()
+                .flex()
+                .flex_col()
+                .items_center()
+                .child(
+                    glyphs(&symbol.to_string(), size)
+                        .line_height(size * 0.5)
+                        .h(size * 0.4),
+                )
+                .child(body.render(size, color))
+                .into_any_element(),
+            MathNode::Bold(body) => div()
+                .font_weight(FontWeight::BOLD)
+                .child(body.render(size, color))
+                .into_any_element(),
+            MathNode::Space(width) => div().w(size * *width).into_any_element(),
+        }
+    }
+}
+
+fn write_inline_script(
+    script: &MathNode,
+    script_char: fn(char) -> Option<char>,
+    fallback_prefix: &str,
+    text: &mut String,
+    italics: &mut Vec<Range<usize>>,
+) {
+    let mut script_text = String::new();
+    script.write_inline(&mut script_text, &mut vec![]);
+
+    // Use Unicode superscripts and subscripts when there are all the characters for them
+    let converted: Option<String> = script_text
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(script_char)
+        .collect();
+    match converted {
+        Some(converted) => text.push_str(&converted),
+        None => {
+            text.push_str(fallback_prefix);
+            script.write_inline_grouped(text, italics);
+        }
+    }
+}
+
+fn scaled(size: Pixels, scale: f32) -> Pixels {
+    let size = size * scale;
+    if size < px(MIN_SIZE) {
+        px(MIN_SIZE)
+    } else {
+        size
+    }
+}
+
+fn glyphs(text: &str, size: Pixels) -> Div {
+    div()
+        .flex_none()
+        .text_size(size)
+        .line_height(size * 1.25)
+        .child(text.to_string())
+}
+
+/// Draw a delimiter or radical sign tall enough to cover `height` ems.
+fn stretched_glyph(text: &str, size: Pixels, height: f32) -> Div {
+    let glyph_size = size * height.max(1.);
+    div()
+        .flex_none()
+        .text_size(glyph_size)
+        .line_height(glyph_size * 1.1)
+        .child(text.to_string())
+}
+
+fn scripts(
+    subscript: Option<&MathNode>,
+    superscript: Option<&MathNode>,
+    size: Pixels,
+    color: Hsla,
+) -> Div {
+    let script_size = scaled(size, SCRIPT_SCALE);
+    div()
+        .flex()
+        .flex_col()
+        .pl(size * 0.05)
+        .child(match superscript {
+            Some(superscript) => superscript.render(script_size, color),
+            None => div().h(size * 0.5).into_any_element(),
+        })
+        .child(match subscript {
+            Some(subscript) => subscript.render(script_size, color),
+            None => div().h(size * 0.5).into_any_element(),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn inline(source: &str) -> String {
+        parse(source).to_inline().0
+    }
+
+    #[test]
+    fn test_parse_scripts() {
+        assert_eq!(
+            parse("x^2_i"),
+            MathNode::Row(vec![MathNode::Scripts {
+                base: Box::new(MathNode::Identifier("x".into())),
+                subscript: Some(Box::new(MathNode::Identifier("i".into()))),
+                superscript: Some(Box::new(MathNode::Number("2".into()))),
+            }])
+        );
+    }
+
+    #[test]
+    fn test_parse_large_operator_limits() {
+        assert_eq!(
+            parse(r"\sum_{i=1}^n"),
+            MathNode::Row(vec![MathNode::LargeOperator {
+                symbol: "∑".into(),
+                large: true,
+                limits: true,
+                lower: Some(Box::new(MathNode::Row(vec![
+                    MathNode::Identifier("i".into()),
+                    MathNode::Operator("=".into()),
+                    MathNode::Number("1".into()),
+                ]))),
+                upper: Some(Box::new(MathNode::Identifier("n".into()))),
+            }])
+        );
+    }
+
+    #[test]
+    fn test_parse_environment() {
+        let MathNode::Row(children) = parse(r"\begin{pmatrix} a & b \\ c & d \end{pmatrix}") else {
+            panic!("Expected a row");
+        };
+        let MathNode::Matrix {
+            rows, left, right, ..
+        } = &children[0]
+        else {
+            panic!("Expected a matrix");
+        };
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[1].len(), 2);
+        assert_eq!(left, "(");
+        assert_eq!(right, ")");
+    }
+
+    #[test]
+    fn test_parse_unknown_command() {
+        assert_eq!(
+            parse(r"\foo"),
+            MathNode::Row(vec![MathNode::Error(r"\foo".into())])
+        );
+    }
+
+    #[test]
+    fn test_parse_deep_nesting() {
+        let source = format!("{}x{}", "{".repeat(100_000), "}".repeat(100_000));
+        assert_eq!(parse(&source), MathNode::Row(vec![MathNode::Error(source)]));
+
+        let source = r"\sqrt".repeat(100_000);
+        assert_eq!(parse(&source), MathNode::Row(vec![MathNode::Error(source)]));
+
+        let source = "x^{".repeat(100_000);
+        assert_eq!(parse(&source), MathNode::Row(vec![MathNode::Error(source)]));
+
+        let source = format!("{}x{}", "{".repeat(10), "}".repeat(10));
+        assert_eq!(inline(&source), "x");
+    }
+
+    #[test]
+    fn test_to_inline() {
+        assert_eq!(inline("E = mc^2"), "E = mc²");
+        assert_eq!(inline(r"\frac{a+b}{2}"), "(a + b)/2");
+        assert_eq!(inline(r"\sqrt{x}"), "√x");
+        assert_eq!(inline(r"x^{n+1}"), "xⁿ⁺¹");
+        assert_eq!(inline(r"x_{i+1}"), "x_(i + 1)");
+        assert_eq!(inline(r"\alpha \leq \beta"), "α ≤ β");
+        assert_eq!(inline(r"-x"), "−x");
+        assert_eq!(inline(r"\left( x \right)"), "(x)");
+    }
+
+    #[test]
+    fn test_to_inline_italics() {
+        let (text, marks) = parse("2x + y").to_inline();
+        assert_eq!(text, "2x + y");
+        assert_eq!(
+            marks,
+            vec![
+                (1..2, TextMark::default().italic()),
+                (5..6, TextMark::default().italic()),
+            ]
+        );
+    }
+}
@@ -1,10 +1,14 @@
-use super::{Events, LinkClickedEvent, TextViewStyle, utils::list_item_prefix};
+use super::{
+    EmoticonEvent, Events, LinkClickedEvent, MentionEvent, MentionPill, TextViewStyle,
+    utils::list_item_prefix,
+};
 use crate::highlighter::{HighlightTheme, SyntaxHighlighter};
 use crate::inline::{Inline, InlineState};
+use crate::math::{self, MathNode};
 use contemporary::styling::theme::{Theme, ThemeStorage, VariableColor};
 use gpui::{
     AnyElement, App, DefiniteLength, Div, Element, ElementId, FontStyle, FontWeight, Half,
-    HighlightStyle, InteractiveElement as _, IntoElement, Length, ListState, ObjectFit,
+    HighlightStyle, Hsla, InteractiveElement as _, IntoElement, Length, ListState, ObjectFit,
     ParentElement, Refineable, SharedString, SharedUri, StatefulInteractiveElement, Styled,
     StyledImage as _, Window, black, div, img, prelude::FluentBuilder as _, px, relative, rems,
     rgb,
@@ -19,6 +23,9 @@ use std::{
     sync::{Arc, Mutex},
 };
 
+/// Left blank at the start of a mention pill for its avatar to be drawn over.
+pub(crate) const PILL_AVATAR_SPACE: &str = "\u{2003}";
+
 #[allow(unused)]
 #[derive(Debug, Default, Clone, PartialEq)]
 pub struct LinkMark {
@@ -33,8 +40,17 @@ pub struct TextMark {
     pub bold: bool,
     pub italic: bool,
     pub strikethrough: bool,
+    pub underline: bool,
     pub code: bool,
     pub link: Option<LinkMark>,
+    pub color: Option<Hsla>,
+    pub background_color: Option<Hsla>,
+    /// Hidden until clicked.
+    pub spoiler: bool,
+    /// The link is a permalink to a user or room, and should be shown as a pill.
+    pub mention: bool,
+    /// The text is the shortcode of a custom emoji, drawn from this image URL instead.
+    pub emoticon: Option<SharedString>,
 }
 
 impl TextMark {
@@ -53,6 +69,11 @@ impl TextMark {
         self
     }
 
+    pub fn underline(mut self) -> Self {
+        self.underline = true;
+        self
+    }
+
     pub fn code(mut self) -> Self {
         self.code = true;
         self
@@ -62,6 +83,126 @@ impl TextMark {
         self.link = Some(link.into());
         self
     }
+
+    pub fn color(mut self, color: impl Into<Hsla>) -> Self {
+        self.color = Some(color.into());
+        self
+    }
+
+    pub fn background_color(mut self, color: impl Into<Hsla>) -> Self {
+        self.background_color = Some(color.into());
+        self
+    }
+
+    pub fn spoiler(mut self) -> Self {
+        self.spoiler = true;
+        self
+    }
+
+    pub fn mention(mut self) -> Self {
+        self.mention = true;
+        self
+    }
+
+    pub fn emoticon(mut self, url: impl Into<SharedString>) -> Self {
+        self.emoticon = Some(url.into());
+        self
+    }
+
+    /// Get the style that text with this mark is drawn with.
+    pub(crate) fn highlight_style(&self, theme: &Theme) -> HighlightStyle {
+        let mut highlight = HighlightStyle::default();
+        if self.bold {
+            highlight.font_weight = Some(FontWeight::BOLD);
+        }
+        if self.italic {
+            highlight.font_style = Some(FontStyle::Italic);
+        }
+        if self.strikethrough {
+            highlight.strikethrough = Some(gpui::StrikethroughStyle {
+                thickness: gpui::px(1.),
+                ..Default::default()
+            });
+        }
+        if self.underline {
+            highlight.underline = Some(gpui::UnderlineStyle {
+                thickness: gpui::px(1.),
+                ..Default::default()
+            });
+        }
+        if self.code {
+            highlight.background_color = Some(theme.layer_background.into());
+        }
+        if let Some(color) = self.color {
+            highlight.color = Some(color);
+        }
+        if let Some(background_color) = self.background_color {
+            highlight.background_color = Some(background_color);
+        }
+
+        if self.mention {
+            let pill_color: Hsla = theme.info_accent_color.into();
+            highlight.background_color = Some(pill_color.opacity(0.3));
+        } else if self.link.is_some() {
+            // TODO: Link colour
+            // highlight.color = Some(cx.theme().link);
+            highlight.underline = Some(gpui::UnderlineStyle {
+                thickness: gpui::px(1.),
+                ..Default::default()
+            });
+        }
+
+        highlight
+    }
+}
+
+/// Replace ranges of `text`, moving `marks` so they still cover the same content.
+///
+/// The replaced ranges must be sorted and must not overlap. A mark that starts or ends inside a
+/// replaced range is stretched to cover the whole replacement.
+pub(crate) fn replace_ranges(
+    text: &str,
+    marks: &[(Range<usize>, TextMark)],
+    replacements: &[(Range<usize>, String)],
+) -> (String, Vec<(Range<usize>, TextMark)>) {
+    let mut new_text = String::with_capacity(text.len());
+    // (old range, new range) of each replacement
+    let mut moved_ranges = Vec::with_capacity(replacements.len());
+    let mut ix = 0;
+    for (range, replacement) in replacements {
+        new_text.push_str(&text[ix..range.start]);
+        let new_start = new_text.len();
+        new_text.push_str(replacement);
+        moved_ranges.push((range.clone(), new_start..new_text.len()));
+        ix = range.end;
+    }
+    new_text.push_str(&text[ix..]);
+
+    let map_offset = |offset: usize, is_end: bool| {
+        let mut shift = 0isize;
+        for (old, new) in &moved_ranges {
+            if offset <= old.start {
+                break;
+            }
+            if offset < old.end {
+                return if is_end { new.end } else { new.start };
+            }
+            shift = new.end as isize - old.end as isize;
+        }
+        (offset as isize + shift) as usize
+    };
+
+    let marks = marks
+        .iter()
+        .map(|(range, mark)| {
+            (
+                map_offset(range.start, false)..map_offset(range.end, true),
+                mark.clone(),
+            )
+        })
+        .collect();
+
+    (new_text, marks)
 }
 
 #[derive(Debug, Default, Copy, Clone, PartialEq)]
@@ -143,10 +284,106 @@ impl InlineNode {
         this
     }
 
+    /// Inline maths, written out on one line so that it flows with the surrounding text.
+    pub(crate) fn math(source: &str, events: &Events) -> Self {
+        let (text, marks) = math::parse(source).to_inline();
+        Self::new(text, events).marks(marks)
+    }
+
     pub(crate) fn marks(mut self, marks: Vec<(Range<usize>, TextMark)>) -> Self {
         self.marks = marks;
         self
     }
+
+    /// Replace the text of each mention with the display name of what it mentions.
+    ///
+    /// Returns the text and marks to render, along with the avatars to draw and the offsets of
+    /// the space left for them. Mentions that don't resolve are left as regular links.
+    fn resolve_mentions(
+        &self,
+        window: &mut Window,
+        cx: &mut App,
+    ) -> (
+        String,
+        Vec<(Range<usize>, TextMark)>,
+        Vec<(usize, AnyElement)>,
+    ) {
+        let mut marks = self.marks.clone();
+        if !marks
+            .iter()
+            .any(|(_, mark)| mark.mention || mark.emoticon.is_some())
+        {
+            return (self.text.to_string(), marks, vec![]);
+        }
+
+        let font_size = window.text_style().font_size.to_pixels(window.rem_size());
+        let mut replacements = vec![];
+        for (range, mark) in marks.iter_mut() {
+            // Custom emoji are drawn the same way as pill avatars, in place of their shortcode
+            if let Some(url) = mark.emoticon.clone() {
+                let image = (self.events.resolve_emoticon)(
+                    &EmoticonEvent {
+                        url,
+                        alt: self.text[range.clone()].to_string().into(),
+                        font_size,
+                    },
+                    window,
+                    cx,
+                );
+                match image {
+                    Some(image) => replacements.push((
+                        range.clone(),
+                        MentionPill {
+                            display_name: SharedString::default(),
+                            avatar: Some(image),
+                        },
+                    )),
+                    None => mark.emoticon = None,
+                }
+                continue;
+            }
+
+            if !mark.mention {
+                continue;
+            }
+
+            let pill = mark.link.as_ref().and_then(|link| {
+                (self.events.resolve_mention)(
+                    &MentionEvent {
+                        url: link.url.clone(),
+                        font_size,
+                    },
+                    window,
+                    cx,
+                )
+            });
+            match pill {
+                Some(pill) => replacements.push((range.clone(), pill)),
+                None => mark.mention = false,
+            }
+        }
+        replacements.sort_by_key(|(range, _)| range.start);
+
+        let mut avatars = vec![];
+        let mut shift = 0isize;
+        let replacements: Vec<_> = replacements
+            .into_iter()
+            .map(|(range, pill)| {
+                let replacement = match pill.avatar {
+                    Some(avatar) => {
+                        avatars.push(((range.start as isize + shift) as usize, avatar));
+                        format!("{PILL_AVATAR_SPACE}{}", pill.display_name)
+                    }
+                    None => pill.display_name.to_string(),
+                };
+                shift += replacement.len() as isize - range.len() as isize;
+                (range, replacement)
+            })
+            .collect();
+
+        let (text, marks) = replace_ranges(&self.text, &marks, &replacements);
+        (text, marks, avatars)
+    }
 }
 
 /// The paragraph element, contains multiple text nodes.
@@ -449,6 +686,11 @@ pub(crate) enum Node {
     },
     CodeBlock(CodeBlock),
     Table(Table),
+    /// Display maths
+    Math {
+        source: SharedString,
+        math: MathNode,
+    },
     Break {
         html: bool,
     },
@@ -554,7 +796,11 @@ impl Node {
                     text.push('\n');
                 }
             }
-            Node::Definition { .. } | Node::Break { .. } | Node::Divider | Node::Unknown => {}
+            Node::Math { .. }
+            | Node::Definition { .. }
+            | Node::Break { .. }
+            | Node::Divider
+            | Node::Unknown => {}
         }
 
         text
@@ -562,12 +808,7 @@ impl Node {
 }
 
 impl Paragraph {
-    fn render(
-        &self,
-        node_cx: &NodeContext,
-        _window: &mut Window,
-        cx: &mut App,
-    ) -> impl IntoElement {
+    fn render(&self, node_cx: &NodeContext, window: &mut Window, cx: &mut App) -> impl IntoElement {
         let span = self.span;
         let children = &self.children;
 
@@ -576,14 +817,22 @@ impl Paragraph {
         let mut text = String::new();
         let mut highlights: Vec<(Range<usize>, HighlightStyle)> = vec![];
         let mut links: Vec<(Range<usize>, LinkMark)> = vec![];
+        let mut spoilers: Vec<Range<usize>> = vec![];
+        let mut pills: Vec<(usize, AnyElement)> = vec![];
         let mut offset = 0;
 
-        let theme = cx.global::<Theme>();
+        let theme = cx.global::<Theme>().clone();
 
         let mut ix = 0;
         for inline_node in children {
-            let text_len = inline_node.text.len();
-            text.push_str(&inline_node.text);
+            let (node_text, node_marks, node_pills) = inline_node.resolve_mentions(window, cx);
+            let text_len = node_text.len();
+            text.push_str(&node_text);
+            pills.extend(
+                node_pills
+                    .into_iter()
+                    .map(|(pill_offset, avatar)| (offset + pill_offset, avatar)),
+            );
 
             if let Some(image) = &inline_node.image {
                 if text.len() > 0 {
@@ -600,6 +849,8 @@ impl Paragraph {
                             highlights.clone(),
                             &inline_node.events,
                         )
+                        .spoilers(spoilers.clone())
+                        .pills(std::mem::take(&mut pills))
                         .into_any_element(),
                     );
                 }
@@ -626,37 +877,19 @@ impl Paragraph {
                 text.clear();
                 links.clear();
                 highlights.clear();
+                spoilers.clear();
                 offset = 0;
             } else {
                 let mut node_highlights = vec![];
-                for (range, style) in &inline_node.marks {
+                for (range, style) in &node_marks {
                     let inner_range = (offset + range.start)..(offset + range.end);
 
-                    let mut highlight = HighlightStyle::default();
-                    if style.bold {
-                        highlight.font_weight = Some(FontWeight::BOLD);
-                    }
-                    if style.italic {
-                        highlight.font_style = Some(FontStyle::Italic);
-                    }
-                    if style.strikethrough {
-                        highlight.strikethrough = Some(gpui::StrikethroughStyle {
-                            thickness: gpui::px(1.),
-                            ..Default::default()
-                        });
-                    }
-                    if style.code {
-                        highlight.background_color = Some(theme.layer_background.into());
+                    let highlight = style.highlight_style(&theme);
+                    if style.spoiler {
+                        spoilers.push(inner_range.clone());
                     }
 
                     if let Some(mut link_mark) = style.link.clone() {
-                        // TODO: Link colour
-                        // highlight.color = Some(cx.theme().link);
-                        highlight.underline = Some(gpui::UnderlineStyle {
-                            thickness: gpui::px(1.),
-                            ..Default::default()
-                        });
-
                         // convert link references, replace link
                         if let Some(identifier) = link_mark.identifier.as_ref() {
                             if let Some(mark) = node_cx.link_refs.get(identifier) {
@@ -681,6 +914,8 @@ impl Paragraph {
             self.state.lock().unwrap().set_text(text.into());
             child_nodes.push(
                 Inline::new(ix, self.state.clone(), links, highlights, &self.events)
+                    .spoilers(spoilers)
+                    .pills(pills)
                     .into_any_element(),
             );
         }
@@ -864,6 +1099,7 @@ impl Node {
                     "\n".to_string()
                 }
             }
+            Node::Math { source, .. } => format!("$$\n{}\n$$", source),
             Node::Divider => "---".to_string(),
             Node::Definition {
                 identifier,
@@ -1251,6 +1487,23 @@ impl Node {
                 .into_any_element(),
             Node::CodeBlock(code_block) => code_block.render(&options, node_cx, window, cx),
             Node::Table { .. } => Self::render_table(self, node_cx, window, cx).into_any_element(),
+            Node::Math { source, math } => {
+                let text_style = window.text_style();
+                let font_size = text_style.font_size.to_pixels(window.rem_size());
+
+                div()
+                    .id("math")
+                    .flex()
+                    .justify_center()
+                    .overflow_x_hidden()
+                    .pb(mb)
+                    .child(if math.is_too_deep() {
+                        source.clone().into_any_element()
+                    } else {
+                        math.render(font_size, text_style.color)
+                    })
+                    .into_any_element()
+            }
             Node::Divider => {
                 // let theme = cx.global::<Theme>();
                 // let border_color = theme.border_color;
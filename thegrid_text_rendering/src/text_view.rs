@@ -26,15 +26,44 @@ pub struct LinkClickedEvent {
     pub bounds: Bounds<Pixels>,
 }
 
+/// How a mention of a user or room is shown in the text.
+pub struct MentionPill {
+    pub display_name: SharedString,
+    /// Drawn at the start of the pill, at most one line high.
+    pub avatar: Option<AnyElement>,
+}
+
+pub struct MentionEvent {
+    /// The permalink of the mentioned user or room.
+    pub url: SharedString,
+    /// The font size of the surrounding text.
+    pub font_size: Pixels,
+}
+
+pub struct EmoticonEvent {
+    /// The image URL of the custom emoji.
+    pub url: SharedString,
+    /// The shortcode of the custom emoji, shown if it can't be drawn.
+    pub alt: SharedString,
+    /// The font size of the surrounding text.
+    pub font_size: Pixels,
+}
+
 #[derive(Clone)]
 pub struct Events {
     pub on_link_clicked: Arc<dyn Fn(&LinkClickedEvent, &mut Window, &mut App) + Send + Sync>,
+    pub resolve_mention:
+        Arc<dyn Fn(&MentionEvent, &mut Window, &mut App) -> Option<MentionPill> + Send + Sync>,
+    pub resolve_emoticon:
+        Arc<dyn Fn(&EmoticonEvent, &mut Window, &mut App) -> Option<AnyElement> + Send + Sync>,
 }
 
 impl Default for Events {
     fn default() -> Self {
         Self {
             on_link_clicked: Arc::new(|_, _, _| {}),
+            resolve_mention: Arc::new(|_, _, _| None),
+            resolve_emoticon: Arc::new(|_, _, _| None),
         }
     }
 }
@@ -48,6 +77,8 @@ impl Debug for Events {
 impl PartialEq for Events {
     fn eq(&self, other: &Self) -> bool {
         Arc::ptr_eq(&self.on_link_clicked, &other.on_link_clicked)
+            && Arc::ptr_eq(&self.resolve_mention, &other.resolve_mention)
+            && Arc::ptr_eq(&self.resolve_emoticon, &other.resolve_emoticon)
     }
 }
 
@@ -520,6 +551,34 @@ impl TextView {
         self.events.on_link_clicked = Arc::new(handler);
         self
     }
+
+    /// Set how mentions of users and rooms are shown.
+    ///
+    /// Mentions that resolve to `None` are shown as regular links.
+    pub fn on_resolve_mention(
+        mut self,
+        resolver: impl Fn(&MentionEvent, &mut Window, &mut App) -> Option<MentionPill>
+        + 'static
+        + Send
+        + Sync,
+    ) -> Self {
+        self.events.resolve_mention = Arc::new(resolver);
+        self
+    }
+
+    /// Set how custom emoji are drawn.
+    ///
+    /// Custom emoji that resolve to `None` are shown as their shortcode.
+    pub fn on_resolve_emoticon(
+        mut self,
+        resolver: impl Fn(&EmoticonEvent, &mut Window, &mut App) -> Option<AnyElement>
+        + 'static
+        + Send
+        + Sync,
+    ) -> Self {
+        self.events.resolve_emoticon = Arc::new(resolver);
+        self
+    }
 }
 
 impl IntoElement for TextView {